@@ -0,0 +1,403 @@
+//! The `firmware` module exports a [`Layout`] as keymap for keyboard firmwares (QMK's `keymap.c`
+//! and ZMK's `.keymap` devicetree files).
+//!
+//! Each layer of the layout becomes a firmware layer. Modifier keys of layers that are activated
+//! by a single modifier switch to that layer (momentarily or as one-shot). Layers that are
+//! activated by a combination of two such modifiers are realized as "tri-layers". The keys are
+//! arranged according to the `firmware_key_order` of the keyboard.
+//!
+//! Symbols are translated to keycodes assuming a US (ANSI) layout on the host. Symbols without
+//! such a keycode are sent as unicode characters (QMK) or left empty (ZMK).
+
+use crate::key::Hand;
+use crate::keyboard::KeyIndex;
+use crate::layout::{LayerKey, LayerKeyIndex, LayerModifierType, LayerModifiers, Layout};
+
+use ahash::AHashMap;
+use anyhow::Result;
+use std::fmt::Write;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum FirmwareError {
+    #[error(
+        "Layer {0} is activated by a long press, which is not supported for firmware keymaps."
+    )]
+    LongPressLayer(u8),
+    #[error("Layer {0} can not be activated by one modifier or a combination of two modifiers of other layers.")]
+    UnsupportedLayer(u8),
+}
+
+/// Keycodes (QMK, ZMK) of non-alphanumeric symbols (assuming a US layout on the host)
+const KEYCODES: [(char, &str, &str); 49] = [
+    (' ', "KC_SPC", "SPACE"),
+    ('\n', "KC_ENT", "RET"),
+    ('\t', "KC_TAB", "TAB"),
+    ('⇥', "KC_TAB", "TAB"),
+    ('\x1b', "KC_ESC", "ESC"),
+    ('⌫', "KC_BSPC", "BSPC"),
+    ('⌦', "KC_DEL", "DEL"),
+    ('⎀', "KC_INS", "INS"),
+    ('⇱', "KC_HOME", "HOME"),
+    ('⇲', "KC_END", "END"),
+    ('⇞', "KC_PGUP", "PG_UP"),
+    ('⇟', "KC_PGDN", "PG_DN"),
+    ('⇠', "KC_LEFT", "LEFT"),
+    ('⇢', "KC_RGHT", "RIGHT"),
+    ('⇡', "KC_UP", "UP"),
+    ('⇣', "KC_DOWN", "DOWN"),
+    ('↶', "KC_UNDO", "K_UNDO"),
+    ('-', "KC_MINS", "MINUS"),
+    ('=', "KC_EQL", "EQUAL"),
+    ('[', "KC_LBRC", "LBKT"),
+    (']', "KC_RBRC", "RBKT"),
+    ('\\', "KC_BSLS", "BSLH"),
+    (';', "KC_SCLN", "SEMI"),
+    ('\'', "KC_QUOT", "SQT"),
+    ('`', "KC_GRV", "GRAVE"),
+    (',', "KC_COMM", "COMMA"),
+    ('.', "KC_DOT", "DOT"),
+    ('/', "KC_SLSH", "SLASH"),
+    ('!', "KC_EXLM", "EXCL"),
+    ('@', "KC_AT", "AT"),
+    ('#', "KC_HASH", "HASH"),
+    ('$', "KC_DLR", "DLLR"),
+    ('%', "KC_PERC", "PRCNT"),
+    ('^', "KC_CIRC", "CARET"),
+    ('&', "KC_AMPR", "AMPS"),
+    ('*', "KC_ASTR", "STAR"),
+    ('(', "KC_LPRN", "LPAR"),
+    (')', "KC_RPRN", "RPAR"),
+    ('_', "KC_UNDS", "UNDER"),
+    ('+', "KC_PLUS", "PLUS"),
+    ('{', "KC_LCBR", "LBRC"),
+    ('}', "KC_RCBR", "RBRC"),
+    ('|', "KC_PIPE", "PIPE"),
+    (':', "KC_COLN", "COLON"),
+    ('"', "KC_DQUO", "DQT"),
+    ('~', "KC_TILD", "TILDE"),
+    ('<', "KC_LABK", "LT"),
+    ('>', "KC_RABK", "GT"),
+    ('?', "KC_QUES", "QMARK"),
+];
+
+/// The firmwares for which keymaps can be generated
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Firmware {
+    Qmk,
+    Zmk,
+}
+
+impl Firmware {
+    /// Get the keycode for a symbol
+    fn keycode(&self, c: char) -> Option<String> {
+        if let Some((_, qmk, zmk)) = KEYCODES.iter().find(|(kc, _, _)| *kc == c) {
+            return Some(match self {
+                Self::Qmk => qmk.to_string(),
+                Self::Zmk => zmk.to_string(),
+            });
+        }
+
+        match (self, c) {
+            (Self::Qmk, 'a'..='z' | '0'..='9') => Some(format!("KC_{}", c.to_ascii_uppercase())),
+            (Self::Qmk, 'A'..='Z') => Some(format!("S(KC_{})", c)),
+            (Self::Qmk, c) if !c.is_control() => Some(format!("UC(0x{:04X})", c as u32)),
+            (Self::Zmk, 'a'..='z') => Some(c.to_ascii_uppercase().to_string()),
+            (Self::Zmk, 'A'..='Z') => Some(format!("LS({})", c)),
+            (Self::Zmk, '0'..='9') => Some(format!("N{}", c)),
+            _ => None,
+        }
+    }
+
+    /// Get the binding for a symbol (or an empty key)
+    fn binding(&self, c: Option<char>) -> String {
+        match (self, c.and_then(|c| self.keycode(c))) {
+            (Self::Qmk, Some(kc)) => kc,
+            (Self::Qmk, None) => "KC_NO".to_string(),
+            (Self::Zmk, Some(kc)) => format!("&kp {}", kc),
+            (Self::Zmk, None) => "&none".to_string(),
+        }
+    }
+
+    /// Get the binding for a key switching to a layer
+    fn layer_switch(&self, layer: u8, modifier_type: &LayerModifierType) -> String {
+        match (self, modifier_type.is_one_shot()) {
+            (Self::Qmk, false) => format!("MO({})", layer),
+            (Self::Qmk, true) => format!("OSL({})", layer),
+            (Self::Zmk, false) => format!("&mo {}", layer),
+            (Self::Zmk, true) => format!("&sl {}", layer),
+        }
+    }
+
+    /// Get the binding for keys that fall through to lower layers
+    fn transparent(&self) -> &'static str {
+        match self {
+            Self::Qmk => "KC_TRNS",
+            Self::Zmk => "&trans",
+        }
+    }
+}
+
+/// Layer switching keys and tri-layers (two layers that activate a third one) of a layout
+struct LayerSwitches<'a> {
+    keys: AHashMap<KeyIndex, (u8, &'a LayerKey)>,
+    tri_layers: Vec<(u8, u8, u8)>,
+}
+
+impl<'a> LayerSwitches<'a> {
+    fn from_layout(layout: &'a Layout) -> Result<Self> {
+        let mut modifier_layers: AHashMap<LayerKeyIndex, u8> = AHashMap::default();
+        let mut combined_layers = Vec::new();
+
+        for layer in 1..layout.n_layers() as u8 {
+            let mods_per_hand = match layout.get_layer_modifiers(layer) {
+                Some(mods_per_hand) => mods_per_hand,
+                None => continue,
+            };
+
+            for mods in [Hand::Left, Hand::Right]
+                .iter()
+                .filter_map(|hand| mods_per_hand.get(hand))
+            {
+                match mods {
+                    LayerModifiers::LongPress => {
+                        return Err(FirmwareError::LongPressLayer(layer).into())
+                    }
+                    mods => match mods.layerkey_indices() {
+                        [m] => {
+                            modifier_layers.entry(*m).or_insert(layer);
+                        }
+                        [m1, m2] => combined_layers.push((*m1, *m2, layer)),
+                        [] => {}
+                        _ => return Err(FirmwareError::UnsupportedLayer(layer).into()),
+                    },
+                }
+            }
+        }
+
+        let mut tri_layers = Vec::new();
+        for (m1, m2, layer) in combined_layers {
+            match (modifier_layers.get(&m1), modifier_layers.get(&m2)) {
+                (Some(l1), Some(l2)) if l1 != l2 => {
+                    let tri_layer = (*l1.min(l2), *l1.max(l2), layer);
+                    if !tri_layers.contains(&tri_layer) {
+                        tri_layers.push(tri_layer);
+                    }
+                }
+                _ => return Err(FirmwareError::UnsupportedLayer(layer).into()),
+            }
+        }
+
+        let keys = modifier_layers
+            .iter()
+            .filter_map(|(m, layer)| {
+                let lk = layout.get_layerkey(m);
+                layout
+                    .keyboard
+                    .keys
+                    .iter()
+                    .position(|k| k.matrix_position == lk.key.matrix_position)
+                    .map(|key_index| (key_index as KeyIndex, (*layer, lk)))
+            })
+            .collect();
+
+        Ok(Self { keys, tri_layers })
+    }
+}
+
+/// Generate the bindings of all layers in the firmware's key order (rows of bindings per layer)
+fn layer_bindings(
+    layout: &Layout,
+    switches: &LayerSwitches,
+    firmware: Firmware,
+) -> Vec<Vec<Vec<String>>> {
+    (0..layout.n_layers())
+        .map(|layer| {
+            let layerkeys = layout.get_layer_layerkeys(layer);
+            layout
+                .keyboard
+                .firmware_key_order
+                .iter()
+                .map(|row| {
+                    row.iter()
+                        .map(|key_index| {
+                            let key_index = match key_index {
+                                Some(key_index) => *key_index,
+                                None => return firmware.binding(None),
+                            };
+
+                            if let Some((switch_layer, lk)) = switches.keys.get(&key_index) {
+                                return match layer {
+                                    0 => firmware.layer_switch(*switch_layer, &lk.is_modifier),
+                                    _ => firmware.transparent().to_string(),
+                                };
+                            }
+
+                            match layerkeys[key_index as usize] {
+                                Some(lk) if layout.macro_keys().output(&lk.symbol).is_some() => {
+                                    log::warn!(
+                                        "Macro '{}' can not be exported to firmware keymap",
+                                        layout.symbol_output(lk.symbol)
+                                    );
+                                    firmware.binding(None)
+                                }
+                                Some(lk) => firmware.binding(Some(lk.symbol)),
+                                None => firmware.binding(None),
+                            }
+                        })
+                        .collect()
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Generate a QMK `keymap.c` for a layout using given `LAYOUT` macro of the keyboard.
+///
+/// Chords and dead keys are not exported. Symbols without keycode are sent using QMK's unicode
+/// feature (`UC()`), which needs to be enabled in the firmware.
+pub fn qmk_keymap(layout: &Layout, layout_macro: &str, description: &str) -> Result<String> {
+    let switches = LayerSwitches::from_layout(layout)?;
+    let bindings = layer_bindings(layout, &switches, Firmware::Qmk);
+
+    let mut s = String::new();
+    writeln!(s, "// {}", description)?;
+    writeln!(s, "#include QMK_KEYBOARD_H")?;
+    writeln!(s)?;
+    writeln!(
+        s,
+        "const uint16_t PROGMEM keymaps[][MATRIX_ROWS][MATRIX_COLS] = {{"
+    )?;
+    for (layer, rows) in bindings.iter().enumerate() {
+        writeln!(s, "    [{}] = {}(", layer, layout_macro)?;
+        let rows: Vec<String> = rows
+            .iter()
+            .filter(|row| !row.is_empty())
+            .map(|row| format!("        {}", row.join(", ")))
+            .collect();
+        writeln!(s, "{}", rows.join(",\n"))?;
+        writeln!(s, "    ),")?;
+    }
+    writeln!(s, "}};")?;
+
+    if !switches.tri_layers.is_empty() {
+        writeln!(s)?;
+        writeln!(
+            s,
+            "layer_state_t layer_state_set_user(layer_state_t state) {{"
+        )?;
+        for (l1, l2, l3) in switches.tri_layers.iter() {
+            writeln!(
+                s,
+                "    state = update_tri_layer_state(state, {}, {}, {});",
+                l1, l2, l3
+            )?;
+        }
+        writeln!(s, "    return state;")?;
+        writeln!(s, "}}")?;
+    }
+
+    Ok(s)
+}
+
+/// Generate a ZMK `.keymap` devicetree file for a layout.
+///
+/// Chords and dead keys are not exported. Symbols without keycode are left empty (`&none`).
+pub fn zmk_keymap(layout: &Layout, description: &str) -> Result<String> {
+    let switches = LayerSwitches::from_layout(layout)?;
+    let bindings = layer_bindings(layout, &switches, Firmware::Zmk);
+
+    let mut s = String::new();
+    writeln!(s, "/* {} */", description)?;
+    writeln!(s)?;
+    writeln!(s, "#include <behaviors.dtsi>")?;
+    writeln!(s, "#include <dt-bindings/zmk/keys.h>")?;
+    writeln!(s)?;
+    writeln!(s, "/ {{")?;
+
+    if !switches.tri_layers.is_empty() {
+        writeln!(s, "    conditional_layers {{")?;
+        writeln!(s, "        compatible = \"zmk,conditional-layers\";")?;
+        for (l1, l2, l3) in switches.tri_layers.iter() {
+            writeln!(s, "        tri_layer_{} {{", l3)?;
+            writeln!(s, "            if-layers = <{} {}>;", l1, l2)?;
+            writeln!(s, "            then-layer = <{}>;", l3)?;
+            writeln!(s, "        }};")?;
+        }
+        writeln!(s, "    }};")?;
+        writeln!(s)?;
+    }
+
+    writeln!(s, "    keymap {{")?;
+    writeln!(s, "        compatible = \"zmk,keymap\";")?;
+    for (layer, rows) in bindings.iter().enumerate() {
+        writeln!(s)?;
+        writeln!(s, "        layer_{} {{", layer)?;
+        writeln!(s, "            bindings = <")?;
+        for row in rows.iter().filter(|row| !row.is_empty()) {
+            writeln!(s, "                {}", row.join(" "))?;
+        }
+        writeln!(s, "            >;")?;
+        writeln!(s, "        }};")?;
+    }
+    writeln!(s, "    }};")?;
+    writeln!(s, "}};")?;
+
+    Ok(s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::LayoutConfig, keyboard::Keyboard, layout::LayerModifierLocations,
+        layout_generator::LayoutGenerator, neo_layout_generator::NeoLayoutGenerator,
+    };
+
+    use std::sync::Arc;
+
+    const LAYOUT_CONFIG: &str =
+        concat!(env!("CARGO_MANIFEST_DIR"), "/../config/keyboard/crkbd.yml");
+
+    /// A layout for the crkbd whose layer 2 is activated by one-shot modifiers (without the last
+    /// layer, whose modifiers combine those of layers 2 and 3).
+    fn layout() -> Layout {
+        let mut layout_config = LayoutConfig::from_yaml(LAYOUT_CONFIG).unwrap();
+        let modifiers = &mut layout_config.base_layout.modifiers;
+        modifiers.truncate(4);
+        for mods in modifiers[1].values_mut() {
+            *mods = LayerModifierLocations::OneShot(mods.iter().cloned().collect());
+        }
+        let keyboard = Arc::new(Keyboard::from_yaml_object(layout_config.keyboard));
+        NeoLayoutGenerator::from_object(layout_config.base_layout, keyboard)
+            .generate("xvlcwkhgfqyßuiaeosnrtdüöäpzbm,.j")
+            .unwrap()
+    }
+
+    #[test]
+    fn qmk_layer_switches() {
+        let keymap = qmk_keymap(&layout(), "LAYOUT_split_3x6_3", "test").unwrap();
+        let lines: Vec<&str> = keymap.lines().map(|l| l.trim()).collect();
+
+        assert!(lines.contains(&"[0] = LAYOUT_split_3x6_3("));
+        assert!(lines.contains(
+            &"OSL(2), KC_U, KC_I, KC_A, KC_E, KC_O, KC_S, KC_N, KC_R, KC_T, KC_D, OSL(2),"
+        ));
+        assert!(lines.contains(&"MO(3), MO(1), KC_TAB, KC_ENT, KC_SPC, MO(3)"));
+        // layer 4 is activated by the modifiers of layers 1 and 3
+        assert!(lines.contains(&"state = update_tri_layer_state(state, 1, 3, 4);"));
+    }
+
+    #[test]
+    fn zmk_layer_switches() {
+        let keymap = zmk_keymap(&layout(), "test").unwrap();
+        let lines: Vec<&str> = keymap.lines().map(|l| l.trim()).collect();
+
+        assert!(lines
+            .contains(&"&sl 2 &kp U &kp I &kp A &kp E &kp O &kp S &kp N &kp R &kp T &kp D &sl 2"));
+        assert!(lines.contains(&"&mo 3 &mo 1 &kp TAB &kp RET &kp SPACE &mo 3"));
+        // layer 4 is activated by the modifiers of layers 1 and 3
+        assert!(lines.contains(&"if-layers = <1 3>;"));
+        assert!(lines.contains(&"then-layer = <4>;"));
+    }
+}
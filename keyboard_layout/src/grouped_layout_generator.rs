@@ -1,7 +1,8 @@
 use crate::key::Hand;
 use crate::keyboard::Keyboard;
-use crate::layout::{LayerModifierLocations, Layout};
+use crate::layout::{Chord, DeadKey, LayerModifierLocations, Layout};
 use crate::layout_generator::LayoutGenerator;
+use crate::macro_keys::MacroKeys;
 use crate::neo_layout_generator::BaseLayoutYAML;
 
 use ahash::{AHashMap, AHashSet};
@@ -26,17 +27,26 @@ pub struct GroupedLayoutGenerator {
     permutable_key_map: AHashMap<char, (u8, u8)>,
     grouped_layers: u8,
     modifiers: Vec<AHashMap<Hand, LayerModifierLocations>>,
+    chords: Vec<Chord>,
+    dead_keys: Vec<DeadKey>,
+    macro_keys: Arc<MacroKeys>,
     keyboard: Arc<Keyboard>,
 }
 
 impl GroupedLayoutGenerator {
     /// Generate a [`GroupedLayoutGenerator`] from a [`BaseLayoutYAML`] object
     pub fn from_object(base: BaseLayoutYAML, keyboard: Arc<Keyboard>) -> Self {
+        let mut macro_keys = MacroKeys::default();
         let base_layout_symbols: Vec<Vec<char>> = base
             .keys
             .iter()
             .flatten()
-            .map(|layers| layers.iter().filter_map(|l| l.chars().next()).collect())
+            .map(|layers| {
+                layers
+                    .iter()
+                    .filter_map(|l| macro_keys.symbol_for(l))
+                    .collect()
+            })
             .collect();
         let fixed_keys: Vec<bool> = base.fixed_keys.iter().flatten().cloned().collect();
 
@@ -66,6 +76,9 @@ impl GroupedLayoutGenerator {
             permutable_key_map,
             grouped_layers: base.grouped_layers,
             modifiers: base.modifiers,
+            chords: base.chords,
+            dead_keys: base.dead_keys,
+            macro_keys: Arc::new(macro_keys),
             keyboard,
         }
     }
@@ -87,7 +100,11 @@ impl GroupedLayoutGenerator {
     /// Does not check whether the given string is valid (sufficient, correct and unique characters).
     /// This is useful for plotting unfinished or invalid layouts.
     pub fn generate_unchecked(&self, layout_keys: &str) -> Result<Layout> {
-        let chars: Vec<char> = layout_keys.chars().collect();
+        let chars: Vec<char> = self
+            .macro_keys
+            .parse_layout_str(layout_keys)?
+            .chars()
+            .collect();
 
         // assemble a Vec<Vec<char>> representation of the layer for the given layout string
         let n_fixed = self.fixed_keys.iter().filter(|fixed| !**fixed).count();
@@ -150,6 +167,9 @@ impl GroupedLayoutGenerator {
             self.fixed_keys.clone(),
             self.keyboard.clone(),
             self.modifiers.clone(),
+            self.chords.clone(),
+            self.dead_keys.clone(),
+            self.macro_keys.clone(),
         )
     }
 }
@@ -157,7 +177,11 @@ impl GroupedLayoutGenerator {
 impl LayoutGenerator for GroupedLayoutGenerator {
     /// Generate a Neo variant [`Layout`] from a given string representation of its base layer (only non-fixed keys)
     fn generate(&self, layout_keys: &str) -> Result<Layout> {
-        let chars: Vec<char> = layout_keys.chars().collect();
+        let chars: Vec<char> = self
+            .macro_keys
+            .parse_layout_str(layout_keys)?
+            .chars()
+            .collect();
 
         let n_fixed = self.fixed_keys.iter().filter(|fixed| !**fixed).count();
         if chars.len() % n_fixed != 0 {
@@ -175,7 +199,11 @@ impl LayoutGenerator for GroupedLayoutGenerator {
         // missing_chars.sort_unstable();
 
         if !unsupported_chars.is_empty() {
-            return Err(LayoutError::UnsupportedChars(unsupported_chars.iter().collect()).into());
+            let unsupported_chars: String = unsupported_chars.iter().collect();
+            return Err(LayoutError::UnsupportedChars(
+                self.macro_keys.format_layout_str(&unsupported_chars),
+            )
+            .into());
         }
         // if !missing_chars.is_empty() {
         //     return Err(LayoutError::MissingChars(missing_chars.iter().collect()).into());
@@ -183,4 +211,17 @@ impl LayoutGenerator for GroupedLayoutGenerator {
 
         self.generate_unchecked(layout_keys)
     }
+
+    fn macro_keys(&self) -> &MacroKeys {
+        &self.macro_keys
+    }
+
+    fn permutable_symbols(&self) -> Vec<char> {
+        self.base_layout_symbols
+            .iter()
+            .zip(self.fixed_keys.iter())
+            .filter(|(key_layers, fixed)| !**fixed && !key_layers.is_empty())
+            .map(|(key_layers, _fixed)| key_layers[0])
+            .collect()
+    }
 }
@@ -1,12 +1,14 @@
 //! This module provides structs for representing physical properties of keys in a keyboard
 
+use crate::keyboard::KeyIndex;
+
 use ahash::AHashMap;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::fmt;
 use std::slice;
 
 /// Row and columnar location on the keyboard
-#[derive(Clone, Copy, Default, Deserialize, PartialEq, Eq, Hash, Debug)]
+#[derive(Clone, Copy, Default, Deserialize, Serialize, PartialEq, Eq, Hash, Debug)]
 pub struct MatrixPosition(
     /// Index of column
     pub u8,
@@ -310,4 +312,7 @@ pub struct Key {
 
     /// How strongly does the hand need to move away from the home row (start position) horizontally and vertically
     pub unbalancing: Position,
+
+    /// Index of the key in the keyboard's list of keys
+    pub index: KeyIndex,
 }
@@ -16,6 +16,10 @@ pub enum KeyboardError {
     DuplicateMatrixPositions,
     #[error("Invalid keyboard: Duplicate `positions`.")]
     DuplicatePositions,
+    #[error("Invalid keyboard: Not the same number of keys in `xkb_keycodes` as in the other keyboard lists.")]
+    WrongXkbKeycodeNumber,
+    #[error("Invalid keyboard: Unknown or duplicate `matrix_positions` in `firmware_key_order`.")]
+    InvalidFirmwareKeyOrder,
 }
 
 /// The index of a [`Key`] in the `keys` vec of a [`Keyboard`]
@@ -27,6 +31,11 @@ pub struct Keyboard {
     /// The keys of the keyboard
     pub keys: Vec<Key>,
     pub finger_resting_positions: HandFingerMap<Position>,
+    /// XKB keycode names (e.g. "AD01") of the keys (empty if not configured)
+    pub xkb_keycodes: Vec<Option<String>>,
+    /// Rows of keys in the order of the firmware's keymap (`None` for firmware keys that are not
+    /// part of the keyboard)
+    pub firmware_key_order: Vec<Vec<Option<KeyIndex>>>,
     plot_template: String,
     plot_template_short: String,
 }
@@ -36,16 +45,20 @@ pub struct Keyboard {
 /// Corresponds to (parts of) a YAML configuration file.
 #[derive(Deserialize, Debug)]
 pub struct KeyboardYAML {
-    matrix_positions: Vec<Vec<MatrixPosition>>,
-    positions: Vec<Vec<Position>>,
-    hands: Vec<Vec<Hand>>,
-    fingers: Vec<Vec<Finger>>,
-    key_costs: Vec<Vec<f64>>,
-    symmetries: Vec<Vec<u8>>,
-    unbalancing_positions: Vec<Vec<Position>>,
-    finger_resting_positions: AHashMap<Hand, AHashMap<Finger, Position>>,
-    plot_template: String,
-    plot_template_short: String,
+    pub(crate) matrix_positions: Vec<Vec<MatrixPosition>>,
+    pub(crate) positions: Vec<Vec<Position>>,
+    pub(crate) hands: Vec<Vec<Hand>>,
+    pub(crate) fingers: Vec<Vec<Finger>>,
+    pub(crate) key_costs: Vec<Vec<f64>>,
+    pub(crate) symmetries: Vec<Vec<u8>>,
+    pub(crate) unbalancing_positions: Vec<Vec<Position>>,
+    pub(crate) finger_resting_positions: AHashMap<Hand, AHashMap<Finger, Position>>,
+    #[serde(default)]
+    pub(crate) xkb_keycodes: Vec<Vec<Option<String>>>,
+    #[serde(default)]
+    pub(crate) firmware_key_order: Vec<Vec<Option<MatrixPosition>>>,
+    pub(crate) plot_template: String,
+    pub(crate) plot_template_short: String,
 }
 
 /// Takes a slice of some iterable and checks whether that iterable contains
@@ -81,6 +94,27 @@ impl KeyboardYAML {
             return Err(KeyboardError::WrongKeyNumber.into());
         }
 
+        // The XKB keycodes are optional, but if they are given, there has to be one for each key.
+        let n_xkb_keycodes = self.xkb_keycodes.concat().len();
+        if n_xkb_keycodes > 0 && n_xkb_keycodes != flat_matrix_positions.len() {
+            return Err(KeyboardError::WrongXkbKeycodeNumber.into());
+        }
+
+        // Make sure that the firmware key order only contains known keys (each only once).
+        let flat_firmware_key_order: Vec<MatrixPosition> = self
+            .firmware_key_order
+            .iter()
+            .flatten()
+            .filter_map(|mp| *mp)
+            .collect();
+        if contains_duplicates(&flat_firmware_key_order)
+            || flat_firmware_key_order
+                .iter()
+                .any(|mp| !flat_matrix_positions.contains(mp))
+        {
+            return Err(KeyboardError::InvalidFirmwareKeyOrder.into());
+        }
+
         // Make sure there are no duplicates in `matrix_positions`.
         if contains_duplicates(&flat_matrix_positions) {
             return Err(KeyboardError::DuplicateMatrixPositions.into());
@@ -93,12 +127,110 @@ impl KeyboardYAML {
 
         Ok(())
     }
+
+    /// Write the [`KeyboardYAML`] as `keyboard` section of a YAML configuration file
+    /// (only the keyboard lists, the finger resting positions, and the plot templates).
+    pub fn to_yaml_string(&self) -> String {
+        fn write_rows<T>(s: &mut String, name: &str, rows: &[Vec<T>], fmt: impl Fn(&T) -> String) {
+            s.push_str(&format!("  {}:\n", name));
+            for row in rows {
+                let items: Vec<String> = row.iter().map(&fmt).collect();
+                s.push_str(&format!("    - [{}]\n", items.join(", ")));
+            }
+            s.push('\n');
+        }
+
+        fn write_template(s: &mut String, name: &str, template: &str) {
+            s.push_str(&format!("  {}: |2\n", name));
+            for line in template.lines() {
+                s.push_str(&format!("    {}\n", line));
+            }
+            s.push('\n');
+        }
+
+        let mut s = "keyboard:\n".to_string();
+        write_rows(&mut s, "matrix_positions", &self.matrix_positions, |mp| {
+            format!("[{},{}]", mp.0, mp.1)
+        });
+        write_rows(&mut s, "positions", &self.positions, |p| {
+            format!("[{:.1},{:.1}]", p.0, p.1)
+        });
+        write_rows(&mut s, "hands", &self.hands, |h| format!("{:?}", h));
+        write_rows(&mut s, "fingers", &self.fingers, |f| format!("{:?}", f));
+        write_rows(&mut s, "key_costs", &self.key_costs, |c| format!("{}", c));
+        write_rows(
+            &mut s,
+            "unbalancing_positions",
+            &self.unbalancing_positions,
+            |p| format!("[{},{}]", p.0, p.1),
+        );
+        write_rows(&mut s, "symmetries", &self.symmetries, |i| format!("{}", i));
+
+        s.push_str("  finger_resting_positions:\n");
+        for hand in [Hand::Left, Hand::Right] {
+            let positions = match self.finger_resting_positions.get(&hand) {
+                Some(positions) => positions,
+                None => continue,
+            };
+            s.push_str(&format!("    {:?}:\n", hand));
+            for finger in [
+                Finger::Pinky,
+                Finger::Ring,
+                Finger::Middle,
+                Finger::Index,
+                Finger::Thumb,
+            ] {
+                if let Some(p) = positions.get(&finger) {
+                    s.push_str(&format!("      {:?}: [{:.1}, {:.1}]\n", finger, p.0, p.1));
+                }
+            }
+        }
+        s.push('\n');
+
+        write_template(&mut s, "plot_template", &self.plot_template);
+        write_template(&mut s, "plot_template_short", &self.plot_template_short);
+
+        s
+    }
 }
 
 impl Keyboard {
     /// Generate a [`Keyboard`] from a [`KeyboardYAML`] object
     pub fn from_yaml_object(k: KeyboardYAML) -> Self {
-        let keys = k
+        let flat_matrix_positions = k.matrix_positions.concat();
+        let firmware_key_order = if k.firmware_key_order.is_empty() {
+            // use the order of the keys in the configuration
+            let mut key_index = 0;
+            k.matrix_positions
+                .iter()
+                .map(|row| {
+                    row.iter()
+                        .map(|_| {
+                            key_index += 1;
+                            Some((key_index - 1) as KeyIndex)
+                        })
+                        .collect()
+                })
+                .collect()
+        } else {
+            k.firmware_key_order
+                .iter()
+                .map(|row| {
+                    row.iter()
+                        .map(|mp| {
+                            mp.and_then(|mp| {
+                                flat_matrix_positions
+                                    .iter()
+                                    .position(|kmp| *kmp == mp)
+                                    .map(|idx| idx as KeyIndex)
+                            })
+                        })
+                        .collect()
+                })
+                .collect()
+        };
+
+        let mut keys: Vec<Key> = k
             .hands
             .into_iter()
             .flatten()
@@ -120,9 +252,13 @@ impl Keyboard {
                     symmetry_index,
                     cost,
                     unbalancing,
+                    index: 0,
                 },
             )
             .collect();
+        keys.iter_mut()
+            .enumerate()
+            .for_each(|(i, key)| key.index = i as KeyIndex);
 
         Keyboard {
             keys,
@@ -130,6 +266,8 @@ impl Keyboard {
                 &k.finger_resting_positions,
                 Position::default(),
             ),
+            xkb_keycodes: k.xkb_keycodes.into_iter().flatten().collect(),
+            firmware_key_order,
             plot_template: k.plot_template,
             plot_template_short: k.plot_template_short,
         }
@@ -0,0 +1,498 @@
+//! The `kle` module imports physical keyboards from the raw data of
+//! [keyboard-layout-editor.com](http://www.keyboard-layout-editor.com) (KLE).
+//! Both the "Raw data" as shown in the editor (rows without enclosing brackets and with unquoted
+//! property names) and the downloaded JSON are supported.
+//!
+//! The result is a skeleton [`KeyboardYAML`] with the key positions (including KLE's rotations).
+//! Matrix positions, hands, fingers, symmetries, and finger resting positions are derived from
+//! simple heuristics based on the key's rows and columns and need to be reviewed. Key costs and
+//! unbalancing positions are left at zero.
+
+use crate::key::{Finger, Hand, MatrixPosition, Position};
+use crate::keyboard::KeyboardYAML;
+
+use ahash::AHashMap;
+use anyhow::Result;
+use serde_json::Value;
+use thiserror::Error;
+
+/// Size of a key (one KLE unit) in the coordinates of the keyboard's `positions`
+const KEY_SIZE: f64 = 50.0;
+
+/// Maximal horizontal distance (in KLE units) of thumb keys in the bottom row from the keyboard's center
+const MAX_THUMB_DISTANCE: f64 = 4.5;
+
+#[derive(Error, Debug)]
+pub enum KleError {
+    #[error("Invalid KLE data: {0}")]
+    InvalidFormat(String),
+    #[error("Invalid KLE data: No keys found.")]
+    NoKeys,
+}
+
+/// Current state while walking through KLE's rows (in KLE units)
+#[derive(Debug)]
+struct Cursor {
+    x: f64,
+    y: f64,
+    w: f64,
+    h: f64,
+    r: f64,
+    rx: f64,
+    ry: f64,
+}
+
+impl Default for Cursor {
+    fn default() -> Self {
+        Self {
+            x: 0.0,
+            y: 0.0,
+            w: 1.0,
+            h: 1.0,
+            r: 0.0,
+            rx: 0.0,
+            ry: 0.0,
+        }
+    }
+}
+
+impl Cursor {
+    /// Center of the key at the cursor (rotated around the rotation origin)
+    fn key_center(&self) -> Position {
+        let cx = self.x + self.w / 2.0 - self.rx;
+        let cy = self.y + self.h / 2.0 - self.ry;
+        let (sin, cos) = self.r.to_radians().sin_cos();
+
+        Position(self.rx + cx * cos - cy * sin, self.ry + cx * sin + cy * cos)
+    }
+}
+
+/// Quote the unquoted property names (e.g. `{a:7}`) in KLE's raw data, resulting in valid JSON
+fn quote_property_names(raw: &str) -> String {
+    let mut quoted = String::with_capacity(raw.len());
+    let mut chars = raw.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                // copy strings as they are
+                quoted.push(c);
+                while let Some(c) = chars.next() {
+                    quoted.push(c);
+                    match c {
+                        '\\' => quoted.extend(chars.next()),
+                        '"' => break,
+                        _ => {}
+                    }
+                }
+            }
+            c if c.is_ascii_digit() || c == '-' => {
+                // numbers may contain letters (exponents)
+                quoted.push(c);
+                while let Some(c) =
+                    chars.next_if(|c| c.is_ascii_alphanumeric() || "+-.".contains(*c))
+                {
+                    quoted.push(c);
+                }
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let mut name = c.to_string();
+                while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_') {
+                    name.push(c);
+                }
+                match name.as_str() {
+                    "true" | "false" | "null" => quoted.push_str(&name),
+                    _ => {
+                        quoted.push('"');
+                        quoted.push_str(&name);
+                        quoted.push('"');
+                    }
+                }
+            }
+            c => quoted.push(c),
+        }
+    }
+
+    quoted
+}
+
+/// Read the rows of KLE's raw data or downloaded JSON
+fn parse_rows(data: &str) -> Result<Vec<Value>> {
+    // raw data lacks the enclosing brackets
+    let wrapped = format!("[{}]", quote_property_names(data));
+    let rows: Vec<Value> =
+        serde_json::from_str(&wrapped).map_err(|e| KleError::InvalidFormat(e.to_string()))?;
+
+    // downloaded JSON has the enclosing brackets, resulting in a single row containing rows
+    match rows.as_slice() {
+        [Value::Array(inner)]
+            if inner
+                .iter()
+                .all(|row| matches!(row, Value::Array(_) | Value::Object(_))) =>
+        {
+            Ok(inner.clone())
+        }
+        _ => Ok(rows),
+    }
+}
+
+/// Read the centers (in KLE units) of all keys from KLE's raw data
+fn parse_key_centers(data: &str) -> Result<Vec<Position>> {
+    let rows = parse_rows(data)?;
+
+    let mut centers = Vec::new();
+    let mut cursor = Cursor::default();
+    for row in rows.iter() {
+        let items = match row {
+            Value::Array(items) => items,
+            // keyboard metadata
+            Value::Object(_) => continue,
+            _ => return Err(KleError::InvalidFormat(format!("Unexpected row '{}'", row)).into()),
+        };
+
+        for item in items.iter() {
+            match item {
+                Value::Object(props) => {
+                    let get = |name: &str| props.get(name).and_then(|v| v.as_f64());
+                    if let Some(r) = get("r") {
+                        cursor.r = r;
+                    }
+                    if let Some(rx) = get("rx") {
+                        cursor.rx = rx;
+                        cursor.x = cursor.rx;
+                        cursor.y = cursor.ry;
+                    }
+                    if let Some(ry) = get("ry") {
+                        cursor.ry = ry;
+                        cursor.x = cursor.rx;
+                        cursor.y = cursor.ry;
+                    }
+                    cursor.x += get("x").unwrap_or(0.0);
+                    cursor.y += get("y").unwrap_or(0.0);
+                    cursor.w = get("w").unwrap_or(cursor.w);
+                    cursor.h = get("h").unwrap_or(cursor.h);
+                }
+                Value::String(_) => {
+                    centers.push(cursor.key_center());
+                    cursor.x += cursor.w;
+                    cursor.w = 1.0;
+                    cursor.h = 1.0;
+                }
+                _ => {
+                    return Err(
+                        KleError::InvalidFormat(format!("Unexpected key '{}'", item)).into(),
+                    )
+                }
+            }
+        }
+
+        cursor.y += 1.0;
+        cursor.x = cursor.rx;
+    }
+
+    if centers.is_empty() {
+        return Err(KleError::NoKeys.into());
+    }
+
+    Ok(centers)
+}
+
+/// Properties of a key derived from its position
+#[derive(Debug)]
+struct DerivedKey {
+    center: Position,
+    matrix_position: MatrixPosition,
+    hand: Hand,
+    finger: Finger,
+    /// Number of columns from the innermost column of the hand (in the same row)
+    column_offset: u8,
+    symmetry_index: u8,
+}
+
+/// Default finger for a key given its column's distance from the hand's innermost column
+fn finger_for_column_offset(column_offset: u8) -> Finger {
+    match column_offset {
+        0 | 1 => Finger::Index,
+        2 => Finger::Middle,
+        3 => Finger::Ring,
+        _ => Finger::Pinky,
+    }
+}
+
+/// Column offset of the key the finger rests on
+fn resting_column_offset(finger: Finger) -> u8 {
+    match finger {
+        Finger::Thumb => 0,
+        Finger::Index => 1,
+        Finger::Middle => 2,
+        Finger::Ring => 3,
+        Finger::Pinky => 4,
+    }
+}
+
+/// Derive matrix positions, hands, and fingers of the keys from their centers
+fn derive_keys(centers: &[Position]) -> Vec<DerivedKey> {
+    let min_x = centers.iter().map(|c| c.0).fold(f64::INFINITY, f64::min);
+    let max_x = centers
+        .iter()
+        .map(|c| c.0)
+        .fold(f64::NEG_INFINITY, f64::max);
+    let min_y = centers.iter().map(|c| c.1).fold(f64::INFINITY, f64::min);
+    let center_x = (min_x + max_x) / 2.0;
+
+    // rows and columns are derived from the key centers (collisions are moved to the next free column)
+    let mut occupied: Vec<MatrixPosition> = Vec::new();
+    let mut keys: Vec<DerivedKey> = centers
+        .iter()
+        .map(|c| {
+            let row = (c.1 - min_y + 0.5).floor().max(0.0) as u8;
+            let mut column = (c.0 - min_x + 0.5).floor().max(0.0) as u8;
+            while occupied.contains(&MatrixPosition(column, row)) {
+                column += 1;
+            }
+            occupied.push(MatrixPosition(column, row));
+
+            DerivedKey {
+                center: *c,
+                matrix_position: MatrixPosition(column, row),
+                hand: if c.0 < center_x {
+                    Hand::Left
+                } else {
+                    Hand::Right
+                },
+                finger: Finger::Index,
+                column_offset: 0,
+                symmetry_index: 0,
+            }
+        })
+        .collect();
+
+    // keys in rows below the last "full" row (with at least half as many keys as the longest row)
+    // are pressed with the thumbs; if there are no such rows, the keys in the bottom row close to
+    // the center are
+    let mut row_lengths: AHashMap<u8, usize> = AHashMap::default();
+    keys.iter()
+        .for_each(|k| *row_lengths.entry(k.matrix_position.1).or_insert(0) += 1);
+    let max_row_length = row_lengths.values().max().cloned().unwrap_or(0);
+    let last_full_row = row_lengths
+        .iter()
+        .filter(|(_, n)| 2 * **n >= max_row_length)
+        .map(|(row, _)| *row)
+        .max()
+        .unwrap_or(0);
+    let bottom_row = row_lengths.keys().max().cloned().unwrap_or(0);
+    keys.iter_mut()
+        .filter(|k| {
+            k.matrix_position.1 > last_full_row
+                || (last_full_row == bottom_row
+                    && k.matrix_position.1 == bottom_row
+                    && (k.center.0 - center_x).abs() < MAX_THUMB_DISTANCE)
+        })
+        .for_each(|k| k.finger = Finger::Thumb);
+
+    // the other fingers are assigned according to the columns' distances from the hand's innermost column
+    let mut inner_columns: AHashMap<(Hand, u8), u8> = AHashMap::default();
+    keys.iter()
+        .filter(|k| k.finger != Finger::Thumb)
+        .for_each(|k| {
+            let MatrixPosition(column, row) = k.matrix_position;
+            let inner = inner_columns.entry((k.hand, row)).or_insert(column);
+            *inner = match k.hand {
+                Hand::Left => (*inner).max(column),
+                Hand::Right => (*inner).min(column),
+            };
+        });
+    keys.iter_mut()
+        .filter(|k| k.finger != Finger::Thumb)
+        .for_each(|k| {
+            let MatrixPosition(column, row) = k.matrix_position;
+            let inner = inner_columns[&(k.hand, row)];
+            k.column_offset = column.abs_diff(inner);
+            k.finger = finger_for_column_offset(k.column_offset);
+        });
+
+    // thumb keys are ordered by their distance from the center
+    let mut thumb_keys: Vec<&mut DerivedKey> = keys
+        .iter_mut()
+        .filter(|k| k.finger == Finger::Thumb)
+        .collect();
+    thumb_keys.sort_by(|k1, k2| {
+        (k1.center.0 - center_x)
+            .abs()
+            .partial_cmp(&(k2.center.0 - center_x).abs())
+            .unwrap()
+    });
+    let mut n_thumb_keys: AHashMap<Hand, u8> = AHashMap::default();
+    thumb_keys.into_iter().for_each(|k| {
+        let n = n_thumb_keys.entry(k.hand).or_insert(0);
+        k.column_offset = *n;
+        *n += 1;
+    });
+
+    keys
+}
+
+/// Generate a skeleton [`KeyboardYAML`] from KLE's raw data or downloaded JSON
+pub fn keyboard_yaml_from_kle(data: &str) -> Result<KeyboardYAML> {
+    let centers = parse_key_centers(data)?;
+    let mut keys = derive_keys(&centers);
+    keys.sort_by_key(|k| (k.matrix_position.1, k.matrix_position.0));
+
+    // mirrored keys (same row, finger, and column offset) are symmetrical
+    let mut symmetry_indices: AHashMap<(u8, Finger, u8), u8> = AHashMap::default();
+    for k in keys.iter_mut() {
+        let n = symmetry_indices.len() as u8;
+        k.symmetry_index = *symmetry_indices
+            .entry((k.matrix_position.1, k.finger, k.column_offset))
+            .or_insert(n + 1);
+    }
+
+    // the home row is the second to last row without thumb keys
+    let thumb_rows: Vec<u8> = keys
+        .iter()
+        .filter(|k| k.finger == Finger::Thumb)
+        .map(|k| k.matrix_position.1)
+        .collect();
+    let max_finger_row = keys
+        .iter()
+        .map(|k| k.matrix_position.1)
+        .filter(|row| !thumb_rows.contains(row))
+        .max()
+        .unwrap_or(0);
+    let home_row = max_finger_row.saturating_sub(1);
+    let mut finger_resting_positions: AHashMap<Hand, AHashMap<Finger, Position>> =
+        AHashMap::default();
+    for k in keys.iter() {
+        let is_resting_key = match k.finger {
+            Finger::Thumb => k.column_offset == 0,
+            finger => {
+                k.matrix_position.1 == home_row && k.column_offset == resting_column_offset(finger)
+            }
+        };
+        if is_resting_key {
+            finger_resting_positions
+                .entry(k.hand)
+                .or_default()
+                .insert(k.finger, scale(&k.center));
+        }
+    }
+
+    // group keys into rows
+    let mut rows: Vec<Vec<&DerivedKey>> = Vec::new();
+    for k in keys.iter() {
+        match rows.last_mut() {
+            Some(row) if row[0].matrix_position.1 == k.matrix_position.1 => row.push(k),
+            _ => rows.push(vec![k]),
+        }
+    }
+
+    let mut key_index = 0;
+    let plot_template: String = rows
+        .iter()
+        .map(|row| {
+            let labels: Vec<String> = row
+                .iter()
+                .map(|_| {
+                    key_index += 1;
+                    format!("{{{{{}}}}}", key_index - 1)
+                })
+                .collect();
+            format!("{}\n", labels.join(" "))
+        })
+        .collect();
+
+    Ok(KeyboardYAML {
+        matrix_positions: map_rows(&rows, |k| k.matrix_position),
+        positions: map_rows(&rows, |k| scale(&k.center)),
+        hands: map_rows(&rows, |k| k.hand),
+        fingers: map_rows(&rows, |k| k.finger),
+        key_costs: map_rows(&rows, |_| 0.0),
+        symmetries: map_rows(&rows, |k| k.symmetry_index),
+        unbalancing_positions: map_rows(&rows, |_| Position::default()),
+        finger_resting_positions,
+        xkb_keycodes: Vec::new(),
+        firmware_key_order: Vec::new(),
+        plot_template: plot_template.clone(),
+        plot_template_short: plot_template,
+    })
+}
+
+/// Map each key of the rows to a value
+fn map_rows<T>(rows: &[Vec<&DerivedKey>], f: impl Fn(&DerivedKey) -> T) -> Vec<Vec<T>> {
+    rows.iter()
+        .map(|row| row.iter().map(|k| f(k)).collect())
+        .collect()
+}
+
+/// Convert KLE units into the coordinates of the keyboard's `positions`
+fn scale(p: &Position) -> Position {
+    Position(p.0 * KEY_SIZE, p.1 * KEY_SIZE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_centers(data: &str, expected: &[(f64, f64)]) {
+        let centers = parse_key_centers(data).unwrap();
+        assert_eq!(centers.len(), expected.len(), "{:?}", centers);
+        for (center, (x, y)) in centers.iter().zip(expected.iter()) {
+            assert!(
+                (center.0 - x).abs() < 1e-9 && (center.1 - y).abs() < 1e-9,
+                "{:?} != ({}, {})",
+                center,
+                x,
+                y
+            );
+        }
+    }
+
+    #[test]
+    fn raw_data_and_downloaded_json() {
+        let expected = [(0.5, 0.5), (1.5, 0.5), (0.5, 1.5), (1.5, 1.5)];
+        assert_centers("[\"Q\",\"W\"],\n[{a:7},\"A\",\"S\"]", &expected);
+        assert_centers(
+            "[{\"name\":\"kb\"},[\"Q\",\"W\"],[{\"a\":7},\"A\",\"S\"]]",
+            &expected,
+        );
+        assert_centers("[\"Q\",\"W\"]", &expected[..2]);
+        assert_centers("[[\"Q\",\"W\"]]", &expected[..2]);
+    }
+
+    #[test]
+    fn raw_data_with_escaped_strings() {
+        assert_centers(
+            r#"[{f:3},"\"\n'","x:1",{x:-0.5e0},"\\"]"#,
+            &[(0.5, 0.5), (1.5, 0.5), (2.0, 0.5)],
+        );
+    }
+
+    #[test]
+    fn offsets_and_sizes() {
+        // x/y offsets are relative to the cursor, w/h only apply to the next key
+        assert_centers(
+            "[\"A\",{x:0.5},\"B\"],\n[{w:2},\"C\",\"D\"],\n[{y:0.5,h:2},\"E\",\"F\"]",
+            &[
+                (0.5, 0.5),
+                (2.0, 0.5),
+                (1.0, 1.5),
+                (2.5, 1.5),
+                (0.5, 3.5),
+                (1.5, 3.0),
+            ],
+        );
+    }
+
+    #[test]
+    fn rotations() {
+        // keys are rotated around (rx, ry), which also resets the cursor to it
+        assert_centers(
+            "[{r:90,rx:1,ry:1},\"A\",\"B\"],\n[\"C\"]",
+            &[(0.5, 1.5), (0.5, 2.5), (-0.5, 1.5)],
+        );
+        // a new rotation origin resets the cursor, x/y are relative to it
+        assert_centers(
+            "[\"A\"],\n[{r:180,rx:2,ry:0,x:1,y:1},\"B\"]",
+            &[(0.5, 0.5), (0.5, -1.5)],
+        );
+    }
+}
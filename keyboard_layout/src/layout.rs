@@ -4,12 +4,13 @@
 
 use crate::key::{Hand, Key, MatrixPosition};
 use crate::keyboard::{KeyIndex, Keyboard};
+use crate::macro_keys::MacroKeys;
 
 use ahash::AHashMap;
 use anyhow::Result;
 use colored::Colorize;
 use core::slice;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use smallmap::Map;
 use std::{fmt, sync::Arc};
 
@@ -99,13 +100,62 @@ impl LayerModifierLocations {
     }
 }
 
+/// A symbol that is generated by simultaneously pressing several keys (a "chord" or "combo").
+///
+/// The keys are given as their positions on the keyboard. The first key is considered the
+/// chord's main key, the others are pressed together with it.
+#[derive(Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct Chord {
+    pub symbol: char,
+    pub keys: Vec<MatrixPosition>,
+}
+
+/// A symbol that is generated by typing a sequence of other symbols (e.g. a dead key followed
+/// by a base letter like "´" and "e" for "é" or a compose sequence).
+///
+/// The symbols of the sequence are looked up in the layout, i.e. they move with their keys.
+#[derive(Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct DeadKey {
+    pub symbol: char,
+    pub sequence: String,
+}
+
+/// A base-layer symbol that is located at different keys in two layouts (see [`Layout::swap_cycles`])
+#[derive(Serialize, Clone, PartialEq, Eq, Debug)]
+pub struct MovedSymbol {
+    pub symbol: char,
+    pub from: MatrixPosition,
+    pub to: MatrixPosition,
+}
+
+impl fmt::Display for MovedSymbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({},{}) → ({},{})",
+            self.symbol.escape_debug(),
+            self.from.0,
+            self.from.1,
+            self.to.0,
+            self.to.1
+        )
+    }
+}
+
 /// Enumeration describing the various modifier types (e.g. whether the modifier has to be held or tapped
 /// for activating a layer)
+///
+/// Chords are represented as a special kind of modifiers: The other keys of the chord need to be
+/// pressed simultaneously with the main key.
+/// Similarly, symbols generated by dead keys list the [`LayerKeyIndex`]es of their whole
+/// sequence (including the final key).
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub enum LayerModifiers {
     Hold(Vec<LayerKeyIndex>),
     OneShot(Vec<LayerKeyIndex>),
     LongPress,
+    Chord(Vec<LayerKeyIndex>),
+    DeadKey(Vec<LayerKeyIndex>),
 }
 
 impl LayerModifiers {
@@ -114,6 +164,9 @@ impl LayerModifiers {
             Self::Hold(v) => v,
             Self::OneShot(v) => v,
             Self::LongPress => &[],
+            Self::Chord(v) => v,
+            // the last element of the sequence is the key itself
+            Self::DeadKey(v) => &v[..v.len().saturating_sub(1)],
         }
     }
 }
@@ -197,6 +250,22 @@ pub struct Layout {
     key_layers: Vec<Vec<LayerKeyIndex>>,
     /// Map for retrieving the [`LayerKey`] for the symbol it generates
     key_map: Map<char, LayerKeyIndex>,
+    /// All [`LayerKey`]s generating a symbol (only for symbols that can be generated by more than one)
+    alternative_layerkeys: AHashMap<char, Vec<LayerKeyIndex>>,
+    /// Modifiers (per hand pressing them) required for activating each layer above the base layer
+    layer_modifiers: Vec<AHashMap<Hand, LayerModifiers>>,
+    /// If at least one layer is configured as hold layer
+    has_hold_layers: bool,
+    /// If at least one layer is configured as one-shot layer
+    has_one_shot_layers: bool,
+    /// If at least one chord is configured
+    has_chords: bool,
+    /// If at least one dead key is configured
+    has_dead_keys: bool,
+    /// For each [`Key`] of the [`Keyboard`] if it also serves as a hold modifier (e.g. home-row mods)
+    hold_modifier_keys: Vec<bool>,
+    /// Multi-character outputs of keys represented by placeholder symbols
+    macro_keys: Arc<MacroKeys>,
 }
 
 impl fmt::Display for Layout {
@@ -211,6 +280,9 @@ impl Layout {
         fixed_keys: Vec<bool>,
         keyboard: Arc<Keyboard>,
         modifiers: Vec<AHashMap<Hand, LayerModifierLocations>>,
+        chords: Vec<Chord>,
+        dead_keys: Vec<DeadKey>,
+        macro_keys: Arc<MacroKeys>,
     ) -> Result<Self> {
         // generate layer keys
         let mut layerkeys = Vec::new();
@@ -355,7 +427,117 @@ impl Layout {
             k.modifiers = mods;
         });
 
-        let key_map = Self::gen_key_map(&layerkeys);
+        // add chords as layerkeys belonging to the chord's main key
+        for chord in chords.iter() {
+            let chord_keys = chord
+                .keys
+                .iter()
+                .map(|mp| {
+                    pos2layerkey_index
+                        .get(mp)
+                        .cloned()
+                        .ok_or(format!(
+                            "Chord position '{:?}' for symbol '{}' not found",
+                            mp, chord.symbol
+                        ))
+                        .map_err(anyhow::Error::msg)
+                })
+                .collect::<Result<Vec<LayerKeyIndex>>>()?;
+
+            let (main_key_idx, other_keys) = match chord_keys.split_first() {
+                Some((main_key_idx, other_keys)) if !other_keys.is_empty() => {
+                    (*main_key_idx, other_keys.to_vec())
+                }
+                _ => {
+                    return Err(anyhow::Error::msg(format!(
+                        "Chord for symbol '{}' requires at least two keys",
+                        chord.symbol
+                    )))
+                }
+            };
+
+            let main_layerkey = &layerkeys[main_key_idx as usize];
+            layerkeys.push(LayerKey::new(
+                0,
+                main_layerkey.key.clone(),
+                chord.symbol,
+                LayerModifiers::Chord(other_keys),
+                true,
+                LayerModifierType::None,
+            ));
+            layerkey_to_key_index.push(layerkey_to_key_index[main_key_idx as usize]);
+        }
+
+        let mut key_map = Self::gen_key_map(&layerkeys);
+
+        // add dead keys as layerkeys belonging to the final key of their sequence
+        for dead_key in dead_keys.iter() {
+            let sequence = dead_key
+                .sequence
+                .chars()
+                .map(|c| {
+                    key_map
+                        .get(&c)
+                        .cloned()
+                        .ok_or(format!(
+                            "Dead key symbol '{}' for symbol '{}' not found",
+                            c, dead_key.symbol
+                        ))
+                        .map_err(anyhow::Error::msg)
+                })
+                .collect::<Result<Vec<LayerKeyIndex>>>()?;
+
+            let final_key_idx = *sequence
+                .last()
+                .ok_or(format!(
+                    "Dead key sequence for symbol '{}' is empty",
+                    dead_key.symbol
+                ))
+                .map_err(anyhow::Error::msg)?;
+
+            let final_layerkey = &layerkeys[final_key_idx as usize];
+            layerkeys.push(LayerKey::new(
+                final_layerkey.layer,
+                final_layerkey.key.clone(),
+                dead_key.symbol,
+                LayerModifiers::DeadKey(sequence),
+                final_layerkey.is_fixed,
+                LayerModifierType::None,
+            ));
+            layerkey_to_key_index.push(layerkey_to_key_index[final_key_idx as usize]);
+
+            // symbols that the layout generates directly take precedence
+            key_map
+                .entry(dead_key.symbol)
+                .or_insert((layerkeys.len() - 1) as LayerKeyIndex);
+        }
+
+        let has_hold_layers = layerkeys
+            .iter()
+            .any(|lk| std::matches!(lk.modifiers, LayerModifiers::Hold(_)));
+        let has_one_shot_layers = layerkeys
+            .iter()
+            .any(|lk| std::matches!(lk.modifiers, LayerModifiers::OneShot(_)));
+        let has_chords = !chords.is_empty();
+        let has_dead_keys = !dead_keys.is_empty();
+        let mut alternative_layerkeys: AHashMap<char, Vec<LayerKeyIndex>> = AHashMap::default();
+        layerkeys
+            .iter()
+            .enumerate()
+            .filter(|(_, lk)| lk.is_modifier.is_none())
+            .for_each(|(idx, lk)| {
+                alternative_layerkeys
+                    .entry(lk.symbol)
+                    .or_default()
+                    .push(idx as LayerKeyIndex)
+            });
+        alternative_layerkeys.retain(|_, indices| indices.len() > 1);
+        let mut hold_modifier_keys = vec![false; keyboard.keys.len()];
+        layerkeys
+            .iter()
+            .zip(layerkey_to_key_index.iter())
+            .filter(|(lk, _)| lk.is_modifier.is_hold())
+            .for_each(|(_, idx)| hold_modifier_keys[*idx as usize] = true);
 
         Ok(Self {
             layerkeys,
@@ -363,6 +545,14 @@ impl Layout {
             keyboard,
             layerkey_to_key_index,
             key_map,
+            alternative_layerkeys,
+            layer_modifiers: mod_map,
+            has_hold_layers,
+            has_one_shot_layers,
+            has_chords,
+            has_dead_keys,
+            hold_modifier_keys,
+            macro_keys,
         })
     }
 
@@ -431,6 +621,25 @@ impl Layout {
         self.key_map.get(c).cloned()
     }
 
+    /// Get the indices of all [`LayerKey`]s that generate the same symbol as the given one (including
+    /// itself). The slice is empty if there are no alternatives.
+    #[inline(always)]
+    pub fn get_alternative_layerkey_indices(
+        &self,
+        layerkey_index: &LayerKeyIndex,
+    ) -> &[LayerKeyIndex] {
+        self.alternative_layerkeys
+            .get(&self.get_layerkey(layerkey_index).symbol)
+            .map(|indices| indices.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// If at least one symbol can be generated by more than one [`LayerKey`]
+    #[inline(always)]
+    pub fn has_alternative_layerkeys(&self) -> bool {
+        !self.alternative_layerkeys.is_empty()
+    }
+
     /// Get the index of the "base" symbol (the one on the base layer, e.g. "A" -> "a") for a given [`LayerKeyIndex`]
     #[inline(always)]
     pub fn get_base_layerkey_index(&self, layerkey_index: &LayerKeyIndex) -> LayerKeyIndex {
@@ -453,52 +662,221 @@ impl Layout {
         (base, mods)
     }
 
-    /// If the layout has at least one layer configured as hold layer
-    pub fn has_hold_layers(&self) -> bool {
+    /// Get the indices of all (non-modifier) [`LayerKey`]s that are generated with a given key
+    #[inline(always)]
+    pub fn get_layerkey_indices_for_key(&self, key_index: KeyIndex) -> &[LayerKeyIndex] {
+        &self.key_layers[key_index as usize]
+    }
+
+    /// If a modifier is located at a given key
+    pub fn has_modifier_at_key(&self, key_index: KeyIndex) -> bool {
         self.layerkeys
             .iter()
-            .any(|lk| std::matches!(lk.modifiers, LayerModifiers::Hold(_)))
+            .zip(self.layerkey_to_key_index.iter())
+            .any(|(lk, idx)| lk.is_modifier.is_some() && *idx == key_index)
+    }
+
+    /// If a hold modifier is located at a given key (i.e. the key is a "hold-tap" key)
+    #[inline(always)]
+    pub fn is_hold_modifier_key(&self, key_index: KeyIndex) -> bool {
+        self.hold_modifier_keys[key_index as usize]
+    }
+
+    /// Get the modifiers (per hand pressing them) that activate a given layer. Returns `None` for
+    /// the base layer and layers without configured modifiers.
+    pub fn get_layer_modifiers(&self, layer: u8) -> Option<&AHashMap<Hand, LayerModifiers>> {
+        (layer as usize)
+            .checked_sub(1)
+            .and_then(|idx| self.layer_modifiers.get(idx))
+    }
+
+    /// Get the number of layers that can be activated (including the base layer)
+    pub fn n_layers(&self) -> usize {
+        self.layer_modifiers.len() + 1
+    }
+
+    /// Get all keys that generate different symbols than in another layout (for the same keyboard)
+    pub fn differing_keys(&self, other: &Layout) -> Vec<KeyIndex> {
+        self.key_layers
+            .iter()
+            .zip(other.key_layers.iter())
+            .enumerate()
+            .filter(|(_, (layers, other_layers))| {
+                layers.len() != other_layers.len()
+                    || layers
+                        .iter()
+                        .zip(other_layers.iter())
+                        .any(|(lk, other_lk)| {
+                            self.get_layerkey(lk).symbol != other.get_layerkey(other_lk).symbol
+                        })
+            })
+            .map(|(key_index, _)| key_index as KeyIndex)
+            .collect()
+    }
+
+    /// Get the base-layer symbols that are located at different keys in another layout (for the same
+    /// keyboard), grouped into cycles of swaps: Each symbol of a cycle moves to the key of the following
+    /// one and the last one to the key of the first one. If the layouts do not generate the same symbols,
+    /// a "cycle" may end with a symbol that moves to a key without counterpart.
+    pub fn swap_cycles(&self, other: &Layout) -> Vec<Vec<MovedSymbol>> {
+        let base_symbol = |layout: &Layout, key_index: usize| {
+            layout.key_layers[key_index]
+                .first()
+                .map(|lk| layout.get_layerkey(lk).symbol)
+        };
+        let other_keys: AHashMap<char, usize> = (0..other.key_layers.len())
+            .filter_map(|key_index| Some((base_symbol(other, key_index)?, key_index)))
+            .collect();
+
+        // the key in the other layout that each key's symbol moved to
+        let targets: Vec<Option<usize>> = (0..self.key_layers.len())
+            .map(|key_index| {
+                let target = *other_keys.get(&base_symbol(self, key_index)?)?;
+                (target != key_index).then(|| target)
+            })
+            .collect();
+
+        let position = |key_index: usize| self.keyboard.keys[key_index].matrix_position;
+        let mut visited = vec![false; targets.len()];
+        let mut cycles = Vec::new();
+        for start in 0..targets.len() {
+            let mut cycle = Vec::new();
+            let mut key_index = start;
+            while let (false, Some(target)) = (visited[key_index], targets[key_index]) {
+                visited[key_index] = true;
+                cycle.push(MovedSymbol {
+                    symbol: base_symbol(self, key_index).unwrap(),
+                    from: position(key_index),
+                    to: position(target),
+                });
+                key_index = target;
+            }
+
+            if !cycle.is_empty() {
+                cycles.push(cycle);
+            }
+        }
+
+        cycles
+    }
+
+    /// If the layout has at least one layer configured as hold layer
+    #[inline(always)]
+    pub fn has_hold_layers(&self) -> bool {
+        self.has_hold_layers
     }
 
     /// If the layout has at least one layer configured as one-shot layer
+    #[inline(always)]
     pub fn has_one_shot_layers(&self) -> bool {
+        self.has_one_shot_layers
+    }
+
+    /// If the layout has at least one chord
+    #[inline(always)]
+    pub fn has_chords(&self) -> bool {
+        self.has_chords
+    }
+
+    /// If the layout has at least one dead key
+    #[inline(always)]
+    pub fn has_dead_keys(&self) -> bool {
+        self.has_dead_keys
+    }
+
+    /// Get the indices of all chord and dead key [`LayerKey`]s that involve a given key
+    pub fn get_composite_layerkey_indices_for_key(
+        &self,
+        key_index: KeyIndex,
+    ) -> Vec<LayerKeyIndex> {
+        if !self.has_chords && !self.has_dead_keys {
+            return Vec::new();
+        }
+
         self.layerkeys
             .iter()
-            .any(|lk| std::matches!(lk.modifiers, LayerModifiers::OneShot(_)))
+            .enumerate()
+            .filter(|(idx, lk)| match &lk.modifiers {
+                LayerModifiers::Chord(keys) | LayerModifiers::DeadKey(keys) => {
+                    self.layerkey_to_key_index[*idx] == key_index
+                        || keys
+                            .iter()
+                            .any(|k| self.layerkey_to_key_index[*k as usize] == key_index)
+                }
+                _ => false,
+            })
+            .map(|(idx, _)| idx as LayerKeyIndex)
+            .collect()
     }
 
-    /// Plot a graphical representation of a layer
-    pub fn plot_layer(&self, layer: usize) -> String {
-        let fmt_char = |c: char| -> char {
-            match c {
-                ' ' => '␣',
-                '\n' => '\u{23ce}',
-                '\t' => '\u{21e5}',
-                '' => '\u{2327}',
-                normal_char => normal_char,
-            }
-        };
-        let key_chars: Vec<String> = self
-            .key_layers
+    /// Get the multi-character outputs of keys that are represented by placeholder symbols
+    #[inline(always)]
+    pub fn macro_keys(&self) -> &MacroKeys {
+        &self.macro_keys
+    }
+
+    /// Get the text that a symbol of the layout generates (differs from the symbol only for
+    /// placeholders of macros)
+    pub fn symbol_output(&self, c: char) -> String {
+        match self.macro_keys.output(&c) {
+            Some(output) => output.to_string(),
+            None => c.to_string(),
+        }
+    }
+
+    /// Get a label for a [`LayerKey`] (as its [`fmt::Display`] implementation, but with the
+    /// outputs of macros instead of their placeholders)
+    pub fn layerkey_label(&self, k: &LayerKey) -> String {
+        match self.macro_keys.output(&k.symbol) {
+            Some(output) if k.is_modifier.is_some() => format!("[{}]", output.escape_debug()),
+            Some(output) => output.escape_debug().to_string(),
+            None => k.to_string(),
+        }
+    }
+
+    /// Get the [`LayerKey`] of each key in a given layer (`None` if the key has no symbol in that
+    /// layer). Fixed keys without symbol in the layer show the symbol of their highest layer.
+    pub fn get_layer_layerkeys(&self, layer: usize) -> Vec<Option<&LayerKey>> {
+        self.key_layers
             .iter()
             .map(|layers| {
-                if layers.is_empty() {
-                    return " ".to_string();
-                }
                 // layers may have less items than given "layer"
-                let k = self.get_layerkey(&layers[layer.min(layers.len() - 1)]);
-
+                let k = self.get_layerkey(layers.get(layer.min(layers.len().checked_sub(1)?))?);
                 if layer >= layers.len() && !k.is_fixed {
-                    // for non-fixed, show empty field if no symbol is in layers
-                    " ".to_string()
+                    None
                 } else {
-                    // if no symbol is in layers, show last layers value if it is fixed
-                    let mut s = fmt_char(k.symbol).to_string();
+                    Some(k)
+                }
+            })
+            .collect()
+    }
+
+    /// Get a printable label for a symbol (whitespace and control characters are replaced by
+    /// visible symbols, macros by their output)
+    pub fn symbol_label(&self, c: char) -> String {
+        match c {
+            ' ' => '␣'.to_string(),
+            '\n' => '\u{23ce}'.to_string(),
+            '\t' => '\u{21e5}'.to_string(),
+            '' => '\u{2327}'.to_string(),
+            normal_char => self.symbol_output(normal_char),
+        }
+    }
+
+    /// Plot a graphical representation of a layer
+    pub fn plot_layer(&self, layer: usize) -> String {
+        let key_chars: Vec<String> = self
+            .get_layer_layerkeys(layer)
+            .into_iter()
+            .map(|k| match k {
+                Some(k) => {
+                    let mut s = self.symbol_label(k.symbol);
                     if !k.is_fixed {
                         s = s.yellow().bold().to_string();
                     }
                     s
                 }
+                None => " ".to_string(),
             })
             .collect();
 
@@ -517,18 +895,84 @@ impl Layout {
             .iter()
             .filter_map(|layerkeys| layerkeys.first().map(|lk| self.get_layerkey(lk)))
             .filter(|k| !k.is_fixed)
-            .map(|k| k.symbol.to_string())
+            .map(|k| self.symbol_output(k.symbol))
             .collect();
         self.keyboard.plot_compact(&key_chars)
     }
 
-    /// Concatenate all non-fixed keys into a string without any whitespace
+    /// Concatenate all non-fixed keys into a layout string without any whitespace
     pub fn as_text(&self) -> String {
         self.key_layers
             .iter()
             .filter_map(|layerkeys| layerkeys.first().map(|lk| self.get_layerkey(lk)))
             .filter(|k| !k.is_fixed)
-            .map(|k| k.symbol.to_string())
+            .map(|k| self.macro_keys.format_symbol(k.symbol))
             .collect()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        config::LayoutConfig, layout_generator::LayoutGenerator,
+        neo_layout_generator::NeoLayoutGenerator,
+    };
+
+    const LAYOUT_CONFIG: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/../config/keyboard/standard.yml"
+    );
+
+    fn layout_generator() -> NeoLayoutGenerator {
+        let layout_config = LayoutConfig::from_yaml(LAYOUT_CONFIG).unwrap();
+        let keyboard =
+            std::sync::Arc::new(super::Keyboard::from_yaml_object(layout_config.keyboard));
+        NeoLayoutGenerator::from_object(layout_config.base_layout, keyboard)
+    }
+
+    #[test]
+    fn swap_cycles() {
+        let generator = layout_generator();
+        let layout = generator
+            .generate("xvlcwkhgfqyßuiaeosnrtdüöäpzbm,.j")
+            .unwrap();
+
+        assert!(layout.swap_cycles(&layout).is_empty());
+
+        // a single swap (x <-> v) and a cycle of three symbols (u -> a -> e -> u)
+        let other = generator
+            .generate("vxlcwkhgfqyßeiuaosnrtdüöäpzbm,.j")
+            .unwrap();
+        let cycles = layout.swap_cycles(&other);
+        assert_eq!(cycles.len(), 2);
+
+        // the cycles' symbols (rotated such that each starts with its smallest symbol)
+        let mut symbols: Vec<String> = cycles
+            .iter()
+            .map(|cycle| {
+                let mut symbols: Vec<char> = cycle.iter().map(|m| m.symbol).collect();
+                let start = (0..symbols.len()).min_by_key(|i| symbols[*i]).unwrap();
+                symbols.rotate_left(start);
+                symbols.into_iter().collect()
+            })
+            .collect();
+        symbols.sort();
+        assert_eq!(symbols, vec!["aeu", "vx"]);
+
+        for cycle in cycles.iter() {
+            for (i, moved) in cycle.iter().enumerate() {
+                let next = &cycle[(i + 1) % cycle.len()];
+                // each symbol moves to the key of the following one
+                assert_eq!(moved.to, next.from);
+                assert_eq!(
+                    other
+                        .get_layerkey_for_symbol(&moved.symbol)
+                        .unwrap()
+                        .key
+                        .matrix_position,
+                    moved.to
+                );
+            }
+        }
+    }
+}
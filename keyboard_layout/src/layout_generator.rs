@@ -4,10 +4,18 @@
 use core::fmt;
 
 use crate::layout::Layout;
+use crate::macro_keys::MacroKeys;
 use anyhow::Result;
 
 pub trait LayoutGenerator: Send + Sync + LayoutGeneratorClone + fmt::Debug {
     fn generate(&self, layout_keys: &str) -> Result<Layout>;
+
+    /// The multi-character outputs of keys that are represented by placeholder symbols
+    fn macro_keys(&self) -> &MacroKeys;
+
+    /// The base-layer symbols of the non-fixed keys (in the order of the base layout). Any
+    /// permutation of them is a valid layout string.
+    fn permutable_symbols(&self) -> Vec<char>;
 }
 
 impl Clone for Box<dyn LayoutGenerator> {
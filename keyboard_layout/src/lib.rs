@@ -7,12 +7,17 @@
 //! and other associated properties.
 
 pub mod config;
+pub mod firmware;
 pub mod grouped_layout_generator;
 pub mod key;
 pub mod keyboard;
+pub mod kle;
 pub mod layout;
 pub mod layout_generator;
+pub mod macro_keys;
 pub mod neo_layout_generator;
+pub mod svg;
+pub mod xkb;
 
 #[cfg(test)]
 mod tests {
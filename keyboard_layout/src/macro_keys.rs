@@ -0,0 +1,221 @@
+//! The `macro_keys` module provides a mapping between multi-character outputs of keys
+//! ("macros", e.g. "qu" or "sch") and the symbols that represent them.
+//!
+//! Layouts, ngrams, and metrics work with single `char` symbols. Therefore, each macro output
+//! is represented by a placeholder symbol from Unicode's private use area. Texts (e.g. corpora)
+//! need to be tokenized with [`MacroKeys::tokenize`] before they are used.
+//!
+//! In layout strings, macros are given explicitly by their output enclosed in braces (e.g.
+//! `{qu}`), so that keys for `q` and `u` next to each other are not mistaken for a macro. The
+//! braces themselves are given as `{{}` and `{}}`. Layout strings are parsed with
+//! [`MacroKeys::parse_layout_str`] and generated with [`MacroKeys::format_layout_str`].
+
+use thiserror::Error;
+
+/// First placeholder symbol (start of Unicode's private use area)
+const PLACEHOLDER_START: u32 = 0xE000;
+
+/// Delimiters enclosing the output of a macro in layout strings
+const OUTPUT_START: char = '{';
+const OUTPUT_END: char = '}';
+
+#[derive(Error, Debug)]
+pub enum MacroKeysError {
+    #[error("Invalid keyboard layout: Unclosed '{{' in provided layout '{0}'")]
+    UnclosedOutput(String),
+    #[error("Invalid keyboard layout: Unknown macro '{{{0}}}' in provided layout")]
+    UnknownMacro(String),
+}
+
+/// Collection of multi-character outputs of keys and their placeholder symbols.
+#[derive(Clone, Default, Debug)]
+pub struct MacroKeys {
+    outputs: Vec<String>,
+}
+
+impl MacroKeys {
+    /// Get the symbol representing a given key output. Single-character outputs are represented
+    /// by themselves, multi-character ones by a placeholder (that is generated if required).
+    /// Returns `None` for empty outputs.
+    pub fn symbol_for(&mut self, output: &str) -> Option<char> {
+        let mut chars = output.chars();
+        let c = chars.next()?;
+        if chars.next().is_none() {
+            return Some(c);
+        }
+
+        let idx = match self.outputs.iter().position(|o| o == output) {
+            Some(idx) => idx,
+            None => {
+                self.outputs.push(output.to_string());
+                self.outputs.len() - 1
+            }
+        };
+
+        char::from_u32(PLACEHOLDER_START + idx as u32)
+    }
+
+    /// Get the symbol representing a given key output if it is known, i.e. the output consists of
+    /// a single character or is a configured macro.
+    pub fn lookup_symbol(&self, output: &str) -> Option<char> {
+        let mut chars = output.chars();
+        let c = chars.next()?;
+        if chars.next().is_none() {
+            return Some(c);
+        }
+
+        let idx = self.outputs.iter().position(|o| o == output)?;
+        char::from_u32(PLACEHOLDER_START + idx as u32)
+    }
+
+    /// Get the output of a macro for a given placeholder symbol
+    pub fn output(&self, symbol: &char) -> Option<&str> {
+        let idx = (*symbol as u32).checked_sub(PLACEHOLDER_START)?;
+        self.outputs.get(idx as usize).map(|o| o.as_str())
+    }
+
+    /// If no macros are configured
+    pub fn is_empty(&self) -> bool {
+        self.outputs.is_empty()
+    }
+
+    /// Replace all occurrences of macro outputs in a text with their placeholders. At each position
+    /// of the text, the longest matching output is used (greedy tokenization). This is meant for
+    /// texts that are typed with the layout, not for layout strings (see
+    /// [`MacroKeys::parse_layout_str`]).
+    pub fn tokenize(&self, text: &str) -> String {
+        if self.outputs.is_empty() {
+            return text.to_string();
+        }
+
+        let mut tokenized = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(c) = rest.chars().next() {
+            let longest_match = self
+                .outputs
+                .iter()
+                .enumerate()
+                .filter(|(_, output)| rest.starts_with(output.as_str()))
+                .max_by_key(|(_, output)| output.len());
+
+            match longest_match {
+                Some((idx, output)) => {
+                    tokenized.push(char::from_u32(PLACEHOLDER_START + idx as u32).unwrap());
+                    rest = &rest[output.len()..];
+                }
+                None => {
+                    tokenized.push(c);
+                    rest = &rest[c.len_utf8()..];
+                }
+            }
+        }
+
+        tokenized
+    }
+
+    /// Parse a layout string into its symbols, replacing the outputs of macros given in braces
+    /// (e.g. `{qu}`) with their placeholders. All other characters are symbols themselves.
+    pub fn parse_layout_str(&self, layout_str: &str) -> Result<String, MacroKeysError> {
+        let mut symbols = String::with_capacity(layout_str.len());
+        let mut chars = layout_str.chars();
+        while let Some(c) = chars.next() {
+            if c != OUTPUT_START {
+                symbols.push(c);
+                continue;
+            }
+
+            // the first character always belongs to the output (allowing for "{{}" and "{}}")
+            let mut output = String::new();
+            loop {
+                match chars.next() {
+                    Some(OUTPUT_END) if !output.is_empty() => break,
+                    Some(c) => output.push(c),
+                    None => return Err(MacroKeysError::UnclosedOutput(layout_str.to_string())),
+                }
+            }
+
+            let symbol = self
+                .lookup_symbol(&output)
+                .ok_or(MacroKeysError::UnknownMacro(output))?;
+            symbols.push(symbol);
+        }
+
+        Ok(symbols)
+    }
+
+    /// Format a symbol for a layout string (macros and braces are enclosed in braces)
+    pub fn format_symbol(&self, symbol: char) -> String {
+        match self.output(&symbol) {
+            Some(output) => format!("{}{}{}", OUTPUT_START, output, OUTPUT_END),
+            None if symbol == OUTPUT_START || symbol == OUTPUT_END => {
+                format!("{}{}{}", OUTPUT_START, symbol, OUTPUT_END)
+            }
+            None => symbol.to_string(),
+        }
+    }
+
+    /// Format a string of symbols as a layout string, i.e. the inverse of
+    /// [`MacroKeys::parse_layout_str`]
+    pub fn format_layout_str(&self, symbols: &str) -> String {
+        symbols.chars().map(|c| self.format_symbol(c)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn macro_keys() -> MacroKeys {
+        let mut macro_keys = MacroKeys::default();
+        macro_keys.symbol_for("qu");
+        macro_keys.symbol_for("sch");
+        macro_keys
+    }
+
+    #[test]
+    fn layout_str_with_macro_and_its_characters() {
+        let macro_keys = macro_keys();
+        let qu = macro_keys.lookup_symbol("qu").unwrap();
+
+        let symbols = macro_keys.parse_layout_str("aqu{qu}b").unwrap();
+        assert_eq!(symbols, format!("aqu{}b", qu));
+        assert_eq!(macro_keys.format_layout_str(&symbols), "aqu{qu}b");
+    }
+
+    #[test]
+    fn layout_str_with_braces() {
+        let macro_keys = macro_keys();
+
+        let symbols = macro_keys.parse_layout_str("a{{}{}}b").unwrap();
+        assert_eq!(symbols, "a{}b");
+        assert_eq!(macro_keys.format_layout_str(&symbols), "a{{}{}}b");
+        assert_eq!(macro_keys.parse_layout_str("{a}").unwrap(), "a");
+    }
+
+    #[test]
+    fn invalid_layout_str() {
+        let macro_keys = macro_keys();
+
+        assert!(matches!(
+            macro_keys.parse_layout_str("ab{qu"),
+            Err(MacroKeysError::UnclosedOutput(_))
+        ));
+        assert!(matches!(
+            macro_keys.parse_layout_str("a{}"),
+            Err(MacroKeysError::UnclosedOutput(_))
+        ));
+        assert!(matches!(
+            macro_keys.parse_layout_str("a{xy}"),
+            Err(MacroKeysError::UnknownMacro(o)) if o == "xy"
+        ));
+    }
+
+    #[test]
+    fn tokenize_text() {
+        let macro_keys = macro_keys();
+        let qu = macro_keys.lookup_symbol("qu").unwrap();
+        let sch = macro_keys.lookup_symbol("sch").unwrap();
+
+        assert_eq!(macro_keys.tokenize("quasch q"), format!("{}a{} q", qu, sch));
+    }
+}
@@ -1,7 +1,8 @@
 use crate::key::Hand;
 use crate::keyboard::Keyboard;
-use crate::layout::{LayerModifierLocations, Layout};
+use crate::layout::{Chord, DeadKey, LayerModifierLocations, Layout};
 use crate::layout_generator::LayoutGenerator;
+use crate::macro_keys::MacroKeys;
 
 use ahash::{AHashMap, AHashSet};
 use anyhow::Result;
@@ -21,6 +22,10 @@ pub enum LayoutError {
         "Invalid base layout: Not the same number of `keys` ({0}) as entries in `fixed_keys` ({1})"
     )]
     WrongKeyNumber(usize, usize),
+    #[error("Invalid base layout: Chord for symbol '{0}' does not consist of at least two distinct keys")]
+    InvalidChord(char),
+    #[error("Invalid base layout: Dead key sequence for symbol '{0}' is empty or contains the symbol itself")]
+    InvalidDeadKey(char),
 }
 
 /// A collection of data (configuration) regarding the Neo layout (and its family)
@@ -34,6 +39,10 @@ pub struct BaseLayoutYAML {
     pub fixed_layers: Vec<u8>,
     pub modifiers: Vec<AHashMap<Hand, LayerModifierLocations>>,
     pub grouped_layers: u8,
+    #[serde(default)]
+    pub chords: Vec<Chord>,
+    #[serde(default)]
+    pub dead_keys: Vec<DeadKey>,
 }
 
 impl BaseLayoutYAML {
@@ -48,6 +57,21 @@ impl BaseLayoutYAML {
             return Err(LayoutError::WrongKeyNumber(flat_keys.len(), flat_fixed_keys.len()).into());
         }
 
+        // Make sure that each chord requires multiple keys.
+        for chord in self.chords.iter() {
+            let distinct_keys: AHashSet<_> = chord.keys.iter().collect();
+            if chord.keys.len() < 2 || distinct_keys.len() != chord.keys.len() {
+                return Err(LayoutError::InvalidChord(chord.symbol).into());
+            }
+        }
+
+        // Make sure that each dead key is generated by a sequence of other symbols.
+        for dead_key in self.dead_keys.iter() {
+            if dead_key.sequence.is_empty() || dead_key.sequence.contains(dead_key.symbol) {
+                return Err(LayoutError::InvalidDeadKey(dead_key.symbol).into());
+            }
+        }
+
         Ok(())
     }
 }
@@ -60,17 +84,26 @@ pub struct NeoLayoutGenerator {
     permutable_key_map: AHashMap<char, u8>,
     fixed_layers: Vec<u8>,
     modifiers: Vec<AHashMap<Hand, LayerModifierLocations>>,
+    chords: Vec<Chord>,
+    dead_keys: Vec<DeadKey>,
+    macro_keys: Arc<MacroKeys>,
     keyboard: Arc<Keyboard>,
 }
 
 impl NeoLayoutGenerator {
     /// Generate a [`NeoLayoutGenerator`] from a [`BaseLayoutYAML`] object
     pub fn from_object(base: BaseLayoutYAML, keyboard: Arc<Keyboard>) -> Self {
+        let mut macro_keys = MacroKeys::default();
         let base_layout_symbols: Vec<Vec<char>> = base
             .keys
             .iter()
             .flatten()
-            .map(|layers| layers.iter().filter_map(|l| l.chars().next()).collect())
+            .map(|layers| {
+                layers
+                    .iter()
+                    .filter_map(|l| macro_keys.symbol_for(l))
+                    .collect()
+            })
             .collect();
         let fixed_keys: Vec<bool> = base.fixed_keys.iter().flatten().cloned().collect();
 
@@ -92,6 +125,9 @@ impl NeoLayoutGenerator {
             permutable_key_map,
             fixed_layers: base.fixed_layers,
             modifiers: base.modifiers,
+            chords: base.chords,
+            dead_keys: base.dead_keys,
+            macro_keys: Arc::new(macro_keys),
             keyboard,
         }
     }
@@ -113,7 +149,11 @@ impl NeoLayoutGenerator {
     /// Does not check whether the given string is valid (sufficient, correct and unique charactors).
     /// This is useful for plotting unfinished or invalid layouts.
     pub fn generate_unchecked(&self, layout_keys: &str) -> Result<Layout> {
-        let chars: Vec<char> = layout_keys.chars().collect();
+        let chars: Vec<char> = self
+            .macro_keys
+            .parse_layout_str(layout_keys)?
+            .chars()
+            .collect();
 
         // assemble a Vec<Vec<char>> representation of the layer for the given layout string
         let mut given_chars = chars.iter();
@@ -156,6 +196,9 @@ impl NeoLayoutGenerator {
             self.fixed_keys.clone(),
             self.keyboard.clone(),
             self.modifiers.clone(),
+            self.chords.clone(),
+            self.dead_keys.clone(),
+            self.macro_keys.clone(),
         )
     }
 
@@ -168,7 +211,11 @@ impl NeoLayoutGenerator {
 impl LayoutGenerator for NeoLayoutGenerator {
     /// Generate a Neo variant [`Layout`] from a given string representation of its base layer (only non-fixed keys)
     fn generate(&self, layout_keys: &str) -> Result<Layout> {
-        let chars: Vec<char> = layout_keys.chars().collect();
+        let chars: Vec<char> = self
+            .macro_keys
+            .parse_layout_str(layout_keys)?
+            .chars()
+            .collect();
 
         let char_set: AHashSet<char> = AHashSet::from_iter(chars.clone());
         let layout_set: AHashSet<char> =
@@ -199,12 +246,33 @@ impl LayoutGenerator for NeoLayoutGenerator {
         missing_chars.sort_unstable();
 
         if !unsupported_chars.is_empty() {
-            return Err(LayoutError::UnsupportedChars(unsupported_chars.iter().collect()).into());
+            let unsupported_chars: String = unsupported_chars.iter().collect();
+            return Err(LayoutError::UnsupportedChars(
+                self.macro_keys.format_layout_str(&unsupported_chars),
+            )
+            .into());
         }
         if !missing_chars.is_empty() {
-            return Err(LayoutError::MissingChars(missing_chars.iter().collect()).into());
+            let missing_chars: String = missing_chars.iter().collect();
+            return Err(LayoutError::MissingChars(
+                self.macro_keys.format_layout_str(&missing_chars),
+            )
+            .into());
         }
 
         self.generate_unchecked(layout_keys)
     }
+
+    fn macro_keys(&self) -> &MacroKeys {
+        &self.macro_keys
+    }
+
+    fn permutable_symbols(&self) -> Vec<char> {
+        self.base_layout_symbols
+            .iter()
+            .zip(self.fixed_keys.iter())
+            .filter(|(key_layers, fixed)| !**fixed && !key_layers.is_empty())
+            .map(|(key_layers, _fixed)| key_layers[0])
+            .collect()
+    }
 }
@@ -0,0 +1,157 @@
+//! The `svg` module renders a [`Layout`] as SVG image based on the keys' positions.
+//!
+//! Each key is drawn with the legends of all its layers: The base layer in the center and the
+//! higher layers in rows above and below it. Optionally, the keys are colored according to a
+//! value per key (e.g. its load or the cost that a metric attributes to it).
+
+use crate::keyboard::KeyIndex;
+use crate::layout::Layout;
+
+use std::fmt::Write;
+
+/// Margin around the keyboard (relative to the key size)
+const MARGIN: f64 = 0.25;
+
+/// Number of higher-layer legends in each row of a key
+const LEGENDS_PER_ROW: usize = 3;
+
+/// Color of keys with the highest value of a heatmap
+const HEAT_COLOR: (f64, f64, f64) = (215.0, 48.0, 31.0);
+
+/// Escape characters with special meaning in XML
+fn escape_xml(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            '&' => "&amp;".to_string(),
+            '<' => "&lt;".to_string(),
+            '>' => "&gt;".to_string(),
+            '"' => "&quot;".to_string(),
+            '\'' => "&apos;".to_string(),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+/// Color of a key with given fraction of the heatmap's maximal value
+fn heat_color(fraction: f64) -> String {
+    let fraction = fraction.clamp(0.0, 1.0);
+    let mix = |c: f64| (255.0 - (255.0 - c) * fraction).round() as u8;
+    format!(
+        "#{:02x}{:02x}{:02x}",
+        mix(HEAT_COLOR.0),
+        mix(HEAT_COLOR.1),
+        mix(HEAT_COLOR.2)
+    )
+}
+
+/// Render a layout as SVG image. If `key_values` (one value per key of the keyboard) are given,
+/// the keys are colored as heatmap of these values.
+pub fn layout_svg(layout: &Layout, key_values: Option<&[f64]>) -> String {
+    let keys = &layout.keyboard.keys;
+
+    // the key size is derived from the smallest distance between two keys
+    let key_size = keys
+        .iter()
+        .enumerate()
+        .flat_map(|(i, k1)| {
+            keys[i + 1..]
+                .iter()
+                .map(move |k2| k1.position.distance(&k2.position))
+        })
+        .filter(|d| *d > 0.0)
+        .fold(f64::INFINITY, f64::min);
+    let key_size = if key_size.is_finite() { key_size } else { 50.0 };
+    let key_box = 0.95 * key_size;
+    let margin = MARGIN * key_size + key_size / 2.0;
+
+    let min_x = keys
+        .iter()
+        .map(|k| k.position.0)
+        .fold(f64::INFINITY, f64::min);
+    let min_y = keys
+        .iter()
+        .map(|k| k.position.1)
+        .fold(f64::INFINITY, f64::min);
+    let max_x = keys
+        .iter()
+        .map(|k| k.position.0)
+        .fold(f64::NEG_INFINITY, f64::max);
+    let max_y = keys
+        .iter()
+        .map(|k| k.position.1)
+        .fold(f64::NEG_INFINITY, f64::max);
+    let width = max_x - min_x + 2.0 * margin;
+    let height = max_y - min_y + 2.0 * margin;
+
+    let max_value = key_values
+        .map(|values| values.iter().cloned().fold(0.0, f64::max))
+        .unwrap_or(0.0);
+
+    let mut s = String::new();
+    writeln!(
+        s,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{:.1}\" height=\"{:.1}\" viewBox=\"0 0 {:.1} {:.1}\" font-family=\"sans-serif\">",
+        width, height, width, height
+    )
+    .unwrap();
+
+    for (key_index, key) in keys.iter().enumerate() {
+        let cx = key.position.0 - min_x + margin;
+        let cy = key.position.1 - min_y + margin;
+
+        let value = key_values.and_then(|values| values.get(key_index).cloned());
+        let fill = match value {
+            Some(v) if max_value > 0.0 => heat_color(v / max_value),
+            _ => "#ffffff".to_string(),
+        };
+
+        writeln!(s, "  <g>").unwrap();
+        if let Some(v) = value {
+            writeln!(s, "    <title>{:.4}</title>", v).unwrap();
+        }
+        writeln!(
+            s,
+            "    <rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" rx=\"{:.1}\" fill=\"{}\" stroke=\"#444444\"/>",
+            cx - key_box / 2.0,
+            cy - key_box / 2.0,
+            key_box,
+            key_box,
+            key_box / 10.0,
+            fill
+        )
+        .unwrap();
+
+        for layerkey_index in layout.get_layerkey_indices_for_key(key_index as KeyIndex) {
+            let lk = layout.get_layerkey(layerkey_index);
+            let label = escape_xml(&layout.symbol_label(lk.symbol));
+            let color = if lk.is_fixed { "#888888" } else { "#000000" };
+
+            let (x, y, font_size) = if lk.layer == 0 {
+                (cx, cy + key_box * 0.1, key_box * 0.3)
+            } else {
+                // higher layers are arranged in rows above (odd rows) and below (even rows) the base layer
+                let slot = lk.layer as usize - 1;
+                let column = slot % LEGENDS_PER_ROW;
+                let row = slot / LEGENDS_PER_ROW;
+                let dx = (column as f64 - 1.0) * key_box / (LEGENDS_PER_ROW as f64);
+                let dy = match row % 2 {
+                    0 => -0.3,
+                    _ => 0.38,
+                } * key_box;
+                (cx + dx, cy + dy, key_box * 0.17)
+            };
+
+            writeln!(
+                s,
+                "    <text x=\"{:.1}\" y=\"{:.1}\" font-size=\"{:.1}\" text-anchor=\"middle\" fill=\"{}\">{}</text>",
+                x, y, font_size, color, label
+            )
+            .unwrap();
+        }
+        writeln!(s, "  </g>").unwrap();
+    }
+
+    writeln!(s, "</svg>").unwrap();
+
+    s
+}
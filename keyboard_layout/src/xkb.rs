@@ -0,0 +1,285 @@
+//! The `xkb` module exports a [`Layout`] as an XKB `xkb_symbols` section that can be installed
+//! as keyboard layout (e.g. in `/usr/share/X11/xkb/symbols/`).
+//!
+//! Layers are mapped to XKB levels by means of their modifiers: The modifiers of the first three
+//! layers that are activated by a single modifier (per hand) become `Shift`, `ISO_Level3`, and
+//! `ISO_Level5` (in that order). All other layers need to be activated by combinations of these.
+//! Keys are identified by the `xkb_keycodes` of the keyboard. Keys without keycode are omitted.
+
+use crate::key::Hand;
+use crate::keyboard::KeyIndex;
+use crate::layout::{LayerKeyIndex, LayerModifierType, LayerModifiers, Layout};
+
+use ahash::AHashMap;
+use anyhow::Result;
+use std::fmt::Write;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum XkbError {
+    #[error("The keyboard does not provide `xkb_keycodes`.")]
+    MissingKeycodes,
+    #[error("Layer {0} is activated by a long press, which can not be represented in XKB.")]
+    LongPressLayer(u8),
+    #[error(
+        "The layers require more than three distinct modifiers (Shift, ISO_Level3, ISO_Level5)."
+    )]
+    TooManyModifiers,
+    #[error("Layer {0} can not be mapped to a combination of XKB modifiers.")]
+    UnresolvableLayer(u8),
+    #[error("Layers {0} and {1} are mapped to the same XKB level.")]
+    DuplicateLevel(u8, u8),
+}
+
+/// The modifiers that XKB uses for selecting a key's level
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum XkbModifier {
+    Shift,
+    Level3,
+    Level5,
+}
+
+impl XkbModifier {
+    const ALL: [XkbModifier; 3] = [Self::Shift, Self::Level3, Self::Level5];
+
+    /// Offset of the level when the modifier is active
+    fn level_offset(&self) -> usize {
+        match self {
+            Self::Shift => 1,
+            Self::Level3 => 2,
+            Self::Level5 => 4,
+        }
+    }
+
+    /// Keysym to place on a key that acts as this modifier
+    fn keysym(&self, hand: &Hand, modifier_type: &LayerModifierType) -> &'static str {
+        match (self, modifier_type.is_one_shot()) {
+            (Self::Shift, true) => "ISO_Level2_Latch",
+            (Self::Shift, false) => match hand {
+                Hand::Left => "Shift_L",
+                Hand::Right => "Shift_R",
+            },
+            (Self::Level3, true) => "ISO_Level3_Latch",
+            (Self::Level3, false) => "ISO_Level3_Shift",
+            (Self::Level5, true) => "ISO_Level5_Latch",
+            (Self::Level5, false) => "ISO_Level5_Shift",
+        }
+    }
+}
+
+/// Get the keysym name for a symbol
+fn keysym(c: char) -> String {
+    let name = match c {
+        ' ' => "space",
+        '\n' => "Return",
+        '\t' | '⇥' => "Tab",
+        '\x1b' => "Escape",
+        '⌫' => "BackSpace",
+        '⌦' => "Delete",
+        '⎀' => "Insert",
+        '⇱' => "Home",
+        '⇲' => "End",
+        '⇞' => "Prior",
+        '⇟' => "Next",
+        '⇠' => "Left",
+        '⇢' => "Right",
+        '⇡' => "Up",
+        '⇣' => "Down",
+        '↶' => "Undo",
+        c if c.is_control() => "NoSymbol",
+        c => return format!("U{:04X}", c as u32),
+    };
+
+    name.to_string()
+}
+
+/// Escape a string for use in a quoted XKB string (which is UTF-8 apart from these escapes)
+fn escape_string(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Get the modifier LayerKeys of each layer (per hand) in a fixed order
+fn layer_modifier_indices(layout: &Layout, layer: u8) -> Result<Vec<&[LayerKeyIndex]>> {
+    let mods_per_hand = match layout.get_layer_modifiers(layer) {
+        Some(mods_per_hand) => mods_per_hand,
+        None => return Ok(Vec::new()),
+    };
+
+    [Hand::Left, Hand::Right]
+        .iter()
+        .filter_map(|hand| mods_per_hand.get(hand))
+        .map(|mods| match mods {
+            LayerModifiers::LongPress => Err(XkbError::LongPressLayer(layer).into()),
+            mods => Ok(mods.layerkey_indices()),
+        })
+        .collect()
+}
+
+/// Assign an [`XkbModifier`] to each modifier LayerKey
+fn modifier_roles(layout: &Layout) -> Result<AHashMap<LayerKeyIndex, XkbModifier>> {
+    let mut roles = AHashMap::default();
+    let mut available_roles = XkbModifier::ALL.iter();
+
+    for layer in 1..layout.n_layers() as u8 {
+        let mods = layer_modifier_indices(layout, layer)?;
+
+        let is_single_modifier_layer = !mods.is_empty() && mods.iter().all(|m| m.len() == 1);
+        if !is_single_modifier_layer || mods.iter().all(|m| roles.contains_key(&m[0])) {
+            continue;
+        }
+
+        let role = *available_roles.next().ok_or(XkbError::TooManyModifiers)?;
+        mods.iter().for_each(|m| {
+            roles.entry(m[0]).or_insert(role);
+        });
+    }
+
+    Ok(roles)
+}
+
+/// Determine the (zero-based) XKB level for each layer
+fn layer_levels(
+    layout: &Layout,
+    roles: &AHashMap<LayerKeyIndex, XkbModifier>,
+) -> Result<Vec<usize>> {
+    let mut levels = vec![0];
+
+    for layer in 1..layout.n_layers() as u8 {
+        let mut layer_level = None;
+        for mods in layer_modifier_indices(layout, layer)? {
+            let mut mod_roles = Vec::new();
+            for m in mods.iter() {
+                let role = roles.get(m).ok_or(XkbError::UnresolvableLayer(layer))?;
+                if !mod_roles.contains(role) {
+                    mod_roles.push(*role);
+                }
+            }
+            let level: usize = mod_roles.iter().map(|r| r.level_offset()).sum();
+
+            // the modifiers of both hands need to lead to the same level
+            if *layer_level.get_or_insert(level) != level {
+                return Err(XkbError::UnresolvableLayer(layer).into());
+            }
+        }
+
+        let level = layer_level.ok_or(XkbError::UnresolvableLayer(layer))?;
+        if let Some(other_layer) = levels.iter().position(|l| *l == level) {
+            return Err(XkbError::DuplicateLevel(other_layer as u8, layer).into());
+        }
+        levels.push(level);
+    }
+
+    Ok(levels)
+}
+
+/// Get the name of the XKB key type providing a given number of levels
+fn key_type(n_levels: usize) -> &'static str {
+    match n_levels {
+        0 | 1 => "ONE_LEVEL",
+        2 => "TWO_LEVEL",
+        3 | 4 => "FOUR_LEVEL",
+        _ => "EIGHT_LEVEL",
+    }
+}
+
+/// Generate an XKB `xkb_symbols` section with given name and description for a layout.
+///
+/// Chords and dead keys (which are no symbols of the layout's keys) are not exported.
+/// Multi-character outputs (macros) can not be represented in XKB and are omitted as well.
+pub fn xkb_symbols(layout: &Layout, name: &str, description: &str) -> Result<String> {
+    let keyboard = &layout.keyboard;
+    if keyboard.xkb_keycodes.len() != keyboard.keys.len() {
+        return Err(XkbError::MissingKeycodes.into());
+    }
+
+    let roles = modifier_roles(layout)?;
+    let levels = layer_levels(layout, &roles)?;
+
+    let mut s = String::new();
+    writeln!(s, "default partial alphanumeric_keys modifier_keys")?;
+    writeln!(s, "xkb_symbols \"{}\" {{", escape_string(name))?;
+    writeln!(s, "    name[Group1] = \"{}\";", escape_string(description))?;
+    writeln!(s)?;
+    if roles.values().any(|r| *r == XkbModifier::Level3) {
+        writeln!(s, "    include \"level3(modifier_mapping)\"")?;
+    }
+    if roles.values().any(|r| *r == XkbModifier::Level5) {
+        writeln!(s, "    include \"level5(modifier_mapping)\"")?;
+    }
+
+    for (key_index, (key, keycode)) in keyboard
+        .keys
+        .iter()
+        .zip(keyboard.xkb_keycodes.iter())
+        .enumerate()
+    {
+        let keycode = match keycode {
+            Some(keycode) => keycode,
+            None => continue,
+        };
+
+        // keys holding a modifier only act as that modifier
+        let modifier = layout
+            .layerkeys
+            .iter()
+            .enumerate()
+            .filter(|(_, lk)| {
+                lk.is_modifier.is_some() && lk.key.matrix_position == key.matrix_position
+            })
+            .find_map(|(idx, lk)| roles.get(&(idx as LayerKeyIndex)).map(|r| (r, lk)));
+
+        let keysyms: Vec<String> = match modifier {
+            Some((role, lk)) => vec![role.keysym(&key.hand, &lk.is_modifier).to_string()],
+            None => {
+                let mut keysyms = Vec::new();
+                for layerkey_index in layout.get_layerkey_indices_for_key(key_index as KeyIndex) {
+                    let lk = layout.get_layerkey(layerkey_index);
+                    let level = levels[lk.layer as usize];
+                    if keysyms.len() <= level {
+                        keysyms.resize(level + 1, "NoSymbol".to_string());
+                    }
+                    keysyms[level] = if layout.macro_keys().output(&lk.symbol).is_some() {
+                        log::warn!(
+                            "Macro '{}' can not be exported to XKB",
+                            layout.symbol_output(lk.symbol)
+                        );
+                        "NoSymbol".to_string()
+                    } else {
+                        keysym(lk.symbol)
+                    };
+                }
+                keysyms
+            }
+        };
+
+        if keysyms.is_empty() {
+            continue;
+        }
+
+        writeln!(
+            s,
+            "    key <{}> {{ type[Group1] = \"{}\", [ {} ] }};",
+            keycode,
+            key_type(keysyms.len()),
+            keysyms.join(", ")
+        )?;
+    }
+
+    writeln!(s, "}};")?;
+
+    Ok(s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_string_keeps_utf8() {
+        assert_eq!(escape_string("Neo mit ß und ü"), "Neo mit ß und ü");
+        assert_eq!(
+            escape_string(r#"Mein "Layout" C:\xkb"#),
+            r#"Mein \"Layout\" C:\\xkb"#
+        );
+    }
+}
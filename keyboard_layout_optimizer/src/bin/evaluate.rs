@@ -1,13 +1,17 @@
-use keyboard_layout::layout::Layout;
+use keyboard_layout::{key::MatrixPosition, layout::Layout, layout_generator::LayoutGenerator};
 use keyboard_layout_optimizer::common;
-use layout_evaluation::{cache::Cache, results::EvaluationResult};
+use layout_evaluation::{
+    cache::Cache,
+    evaluation::{EvaluationOptions, Evaluator},
+    results::{EvaluationResult, NgramCost},
+};
 
-use clap::Parser;
+use clap::{ArgEnum, Parser};
 use rayon::prelude::*;
 use serde::Serialize;
 use std::{
     fs::File,
-    io::{BufRead, BufReader},
+    io::{BufRead, BufReader, BufWriter},
 };
 
 #[derive(Serialize)]
@@ -26,6 +30,104 @@ impl From<EvaluationResult> for LayoutEvaluation {
     }
 }
 
+/// A row of the per-ngram cost breakdown of a metric for a layout (JSON format)
+#[derive(Serialize)]
+struct BreakdownRow<'a> {
+    layout: &'a str,
+    metric_type: String,
+    metric: &'a str,
+    symbols: &'a [String],
+    keys: &'a [MatrixPosition],
+    weight: f64,
+    cost: f64,
+}
+
+/// A row of the per-ngram cost breakdown of a metric for a layout (CSV format)
+#[derive(Serialize)]
+struct CsvBreakdownRow<'a> {
+    layout: &'a str,
+    metric_type: String,
+    metric: &'a str,
+    ngram: String,
+    keys: String,
+    weight: f64,
+    cost: f64,
+}
+
+impl<'a> From<BreakdownRow<'a>> for CsvBreakdownRow<'a> {
+    fn from(row: BreakdownRow<'a>) -> Self {
+        Self {
+            layout: row.layout,
+            metric_type: row.metric_type,
+            metric: row.metric,
+            ngram: row.symbols.concat(),
+            keys: row
+                .keys
+                .iter()
+                .map(|MatrixPosition(c, r)| format!("({},{})", c, r))
+                .collect::<Vec<String>>()
+                .join(" "),
+            weight: row.weight,
+            cost: row.cost,
+        }
+    }
+}
+
+#[derive(ArgEnum, Clone, Copy, Debug)]
+enum BreakdownFormat {
+    Csv,
+    Json,
+}
+
+/// Collect the rows of the per-ngram cost breakdowns of all metrics for all layouts.
+fn breakdown_rows(results: &[(String, Layout, EvaluationResult)]) -> Vec<BreakdownRow<'_>> {
+    results
+        .iter()
+        .flat_map(|(layout_str, _, evaluation_result)| {
+            evaluation_result.iter().flat_map(move |metric_results| {
+                metric_results.metric_costs.iter().flat_map(move |mc| {
+                    mc.core
+                        .breakdown
+                        .iter()
+                        .flatten()
+                        .map(move |ngram_cost: &NgramCost| BreakdownRow {
+                            layout: layout_str,
+                            metric_type: format!("{:?}", metric_results.metric_type),
+                            metric: &mc.core.name,
+                            symbols: &ngram_cost.symbols,
+                            keys: &ngram_cost.keys,
+                            weight: ngram_cost.weight,
+                            cost: ngram_cost.cost,
+                        })
+                })
+            })
+        })
+        .collect()
+}
+
+/// Write the per-ngram cost breakdowns of all metrics for all layouts to a file.
+fn write_breakdown(
+    results: &[(String, Layout, EvaluationResult)],
+    filename: &str,
+    format: BreakdownFormat,
+) -> anyhow::Result<()> {
+    let rows = breakdown_rows(results);
+    let file = BufWriter::new(File::create(filename)?);
+
+    match format {
+        BreakdownFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(file);
+            for row in rows {
+                writer.serialize(CsvBreakdownRow::from(row))?;
+            }
+            writer.flush()?;
+        }
+        BreakdownFormat::Json => serde_json::to_writer(file, &rows)?,
+    }
+
+    Ok(())
+}
+
 #[derive(Parser, Debug)]
 #[clap(name = "Keyboard layout evaluation")]
 struct Options {
@@ -55,6 +157,23 @@ struct Options {
     /// Sort results by total costs
     #[clap(long)]
     sort: bool,
+
+    /// Number of ngrams with the highest costs (or the largest cost changes with "--compare") to list
+    /// for each metric
+    #[clap(long, default_value = "3")]
+    n_worst: usize,
+
+    /// Compare two layouts: list the moved symbols and the changes of the metrics' costs
+    #[clap(long, conflicts_with_all = &["breakdown", "sort", "only-total-costs"])]
+    compare: bool,
+
+    /// Write the costs of all individual ngrams for each metric and layout to given file
+    #[clap(long)]
+    breakdown: Option<String>,
+
+    /// Format of the per-ngram cost breakdown
+    #[clap(long, arg_enum, default_value = "csv", requires = "breakdown")]
+    breakdown_format: BreakdownFormat,
 }
 
 fn main() {
@@ -72,7 +191,8 @@ fn main() {
     if let Some(filename) = &options.from_file {
         match File::open(filename) {
             Ok(file) => {
-                layout_strings.append(&mut BufReader::new(file).lines().flatten().collect());
+                layout_strings
+                    .append(&mut BufReader::new(file).lines().map_while(Result::ok).collect());
             }
             Err(e) => {
                 log::error!("Error reading layouts file {}: {:?}", filename, e);
@@ -81,25 +201,31 @@ fn main() {
         }
     }
 
+    if options.compare {
+        compare(
+            &layout_strings,
+            &options,
+            layout_generator.as_ref(),
+            &evaluator,
+        );
+        return;
+    }
+
+    let evaluation_options = EvaluationOptions {
+        n_worst: options.n_worst,
+        breakdown: options.breakdown.is_some(),
+    };
     let result_cache: Cache<EvaluationResult> = Cache::new();
 
     // evaluate layouts
     let mut results: Vec<(String, Layout, EvaluationResult)> = layout_strings
         .par_iter()
         .map(|layout_str| {
-            let layout_str: String = layout_str
-                .chars()
-                .filter(|c| options.do_not_remove_whitespace || !c.is_whitespace())
-                .collect();
-            let layout = match layout_generator.generate(&layout_str) {
-                Ok(layout) => layout,
-                Err(e) => {
-                    log::error!("Error in generating layout: {:?}", e);
-                    panic!("{:?}", e);
-                }
-            };
-            let evaluation_result =
-                result_cache.get_or_insert_with(&layout_str, || evaluator.evaluate_layout(&layout));
+            let (layout_str, layout) =
+                generate_layout(layout_str, &options, layout_generator.as_ref());
+            let evaluation_result = result_cache.get_or_insert_with(&layout_str, || {
+                evaluator.evaluate_layout(&layout, &evaluation_options)
+            });
             (layout_str, layout, evaluation_result)
         })
         .collect();
@@ -111,6 +237,13 @@ fn main() {
         });
     }
 
+    if let Some(filename) = &options.breakdown {
+        if let Err(e) = write_breakdown(&results, filename, options.breakdown_format) {
+            log::error!("Error writing breakdown to {}: {:?}", filename, e);
+            panic!("{:?}", e);
+        }
+    }
+
     // print results
     if options.json {
         let results: Vec<LayoutEvaluation> =
@@ -128,3 +261,49 @@ fn main() {
         }
     }
 }
+
+/// Generate a layout from its string representation (removing whitespace if required).
+fn generate_layout(
+    layout_str: &str,
+    options: &Options,
+    layout_generator: &dyn LayoutGenerator,
+) -> (String, Layout) {
+    let layout_str: String = layout_str
+        .chars()
+        .filter(|c| options.do_not_remove_whitespace || !c.is_whitespace())
+        .collect();
+    let layout = match layout_generator.generate(&layout_str) {
+        Ok(layout) => layout,
+        Err(e) => {
+            log::error!("Error in generating layout: {:?}", e);
+            panic!("{:?}", e);
+        }
+    };
+    (layout_str, layout)
+}
+
+/// Compare two layouts and print the differences of their evaluations.
+fn compare(
+    layout_strings: &[String],
+    options: &Options,
+    layout_generator: &dyn LayoutGenerator,
+    evaluator: &Evaluator,
+) {
+    if layout_strings.len() != 2 {
+        log::error!(
+            "Comparing layouts requires exactly two layouts, got {}",
+            layout_strings.len()
+        );
+        panic!("Comparing layouts requires exactly two layouts");
+    }
+
+    let (_, layout_a) = generate_layout(&layout_strings[0], options, layout_generator);
+    let (_, layout_b) = generate_layout(&layout_strings[1], options, layout_generator);
+    let comparison = evaluator.compare_layouts(&layout_a, &layout_b, options.n_worst);
+
+    if options.json {
+        println!("{}", serde_json::to_string(&comparison).unwrap());
+    } else {
+        println!("{}", comparison);
+    }
+}
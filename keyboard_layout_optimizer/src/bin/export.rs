@@ -0,0 +1,82 @@
+use clap::Parser;
+use std::fs;
+
+use keyboard_layout::{firmware, xkb};
+use keyboard_layout_optimizer::common;
+
+#[derive(Parser, Debug)]
+#[clap(name = "Keyboard layout export")]
+struct Options {
+    /// Layout keys from left to right, top to bottom
+    layout_str: String,
+
+    /// Do not remove whitespace from layout strings
+    #[clap(long)]
+    do_not_remove_whitespace: bool,
+
+    /// Filename of layout configuration file to use
+    #[clap(short, long, default_value = "config/keyboard/standard.yml")]
+    layout_config: String,
+
+    /// Interpred given layout string using the "grouped" logic
+    #[clap(long)]
+    pub grouped_layout_generator: bool,
+
+    /// Format to export the layout to
+    #[clap(short, long, default_value = "xkb", possible_values = &["xkb", "qmk", "zmk"])]
+    format: String,
+
+    /// Name of the XKB symbols section
+    #[clap(long, default_value = "basic")]
+    name: String,
+
+    /// Name of the keyboard's `LAYOUT` macro used in QMK keymaps
+    #[clap(long, default_value = "LAYOUT")]
+    qmk_layout_macro: String,
+
+    /// Description of the layout (defaults to the layout string)
+    #[clap(long)]
+    description: Option<String>,
+
+    /// Write the exported layout to given file instead of stdout
+    #[clap(short, long)]
+    output: Option<String>,
+}
+
+fn main() {
+    dotenv::dotenv().ok();
+    env_logger::init();
+    let options = Options::parse();
+
+    let layout_str: String = options
+        .layout_str
+        .chars()
+        .filter(|c| options.do_not_remove_whitespace || !c.is_whitespace())
+        .collect();
+    let layout_generator =
+        common::init_layout_generator(&options.layout_config, options.grouped_layout_generator);
+
+    let layout = match layout_generator.generate(&layout_str) {
+        Ok(layout) => layout,
+        Err(e) => {
+            log::error!("{:?}", e);
+            panic!("{:?}", e);
+        }
+    };
+
+    let description = options
+        .description
+        .unwrap_or_else(|| format!("Layout {}", layout_str));
+    let exported = match options.format.as_str() {
+        "qmk" => firmware::qmk_keymap(&layout, &options.qmk_layout_macro, &description),
+        "zmk" => firmware::zmk_keymap(&layout, &description),
+        _ => xkb::xkb_symbols(&layout, &options.name, &description),
+    }
+    .unwrap_or_else(|e| panic!("Could not export layout '{}': {}", layout_str, e));
+
+    match options.output {
+        Some(filename) => fs::write(&filename, exported)
+            .unwrap_or_else(|e| panic!("Could not write file {}: {}", filename, e)),
+        None => print!("{}", exported),
+    }
+}
@@ -0,0 +1,36 @@
+use clap::Parser;
+use std::fs;
+
+use keyboard_layout::kle;
+
+#[derive(Parser, Debug)]
+#[clap(name = "Keyboard import from keyboard-layout-editor.com")]
+struct Options {
+    /// Filename of the KLE raw data (as shown in the editor or downloaded JSON)
+    kle_file: String,
+
+    /// Write the keyboard configuration to given file instead of stdout
+    #[clap(short, long)]
+    output: Option<String>,
+}
+
+fn main() {
+    dotenv::dotenv().ok();
+    env_logger::init();
+    let options = Options::parse();
+
+    let data = fs::read_to_string(&options.kle_file)
+        .unwrap_or_else(|e| panic!("Could not read file {}: {}", options.kle_file, e));
+    let keyboard = kle::keyboard_yaml_from_kle(&data)
+        .unwrap_or_else(|e| panic!("Could not import file {}: {}", options.kle_file, e));
+    keyboard
+        .validate()
+        .unwrap_or_else(|e| panic!("Imported keyboard is invalid: {}", e));
+
+    let yaml = keyboard.to_yaml_string();
+    match options.output {
+        Some(filename) => fs::write(&filename, yaml)
+            .unwrap_or_else(|e| panic!("Could not write file {}: {}", filename, e)),
+        None => print!("{}", yaml),
+    }
+}
@@ -2,7 +2,7 @@ use ahash::AHashMap;
 use clap::Parser;
 use std::{hash::Hash, path::Path, str::FromStr};
 
-use layout_evaluation::ngrams::{Bigrams, Trigrams, Unigrams};
+use layout_evaluation::ngrams::{Bigrams, Quadgrams, Skipgrams, Trigrams, Unigrams};
 
 #[derive(Debug)]
 struct WeightedComponent(f64, String);
@@ -29,6 +29,12 @@ struct Options {
 
     /// Pairs of weight and ngram frequency directory in the form path:weight
     components: Vec<WeightedComponent>,
+
+    /// Only keep the most common merged quadgrams up to the given fraction (of their total weight).
+    /// The input quadgrams are usually reduced by `ngrams` already, so any value below one
+    /// reduces them again.
+    #[clap(long, default_value = "1.0")]
+    quadgram_tops: f64,
 }
 
 fn add<T: Clone + Eq + Hash>(weight: f64, res: &mut AHashMap<T, f64>, ngrams: &AHashMap<T, f64>) {
@@ -48,10 +54,15 @@ fn main() {
     let mut res_unigrams = AHashMap::default();
     let mut res_bigrams = AHashMap::default();
     let mut res_trigrams = AHashMap::default();
+    let mut res_quadgrams = AHashMap::default();
+    // one entry per skip distance (starting at one)
+    let mut res_skipgrams: Vec<AHashMap<(char, char), f64>> = Vec::new();
 
     let mut target_unigrams_total: Option<f64> = None;
     let mut target_bigrams_total: Option<f64> = None;
     let mut target_trigrams_total: Option<f64> = None;
+    let mut target_quadgrams_total: Option<f64> = None;
+    let mut target_skipgrams_totals: Vec<f64> = Vec::new();
 
     for component in options.components {
         log::info!("Processing {}...", component.1);
@@ -97,6 +108,49 @@ fn main() {
             &mut res_trigrams,
             &trigrams.grams,
         );
+
+        // skipgram files are optional
+        let skipgrams = Skipgrams::from_dir(&component.1)
+            .unwrap_or_else(|_| panic!("Could not read skipgram files from '{}'.", &component.1));
+        if skipgrams.is_empty() {
+            log::warn!(
+                "No skipgram files found in '{}'. Skipping them.",
+                &component.1
+            );
+        }
+        for (i, skipgrams) in skipgrams.iter().enumerate() {
+            let skipgrams_total = skipgrams.total_weight();
+
+            // first ngram file determines "absolute level"
+            if target_skipgrams_totals.len() <= i {
+                target_skipgrams_totals.push(skipgrams_total);
+                res_skipgrams.push(AHashMap::default());
+            }
+            add(
+                component.0 * target_skipgrams_totals[i] / skipgrams_total,
+                &mut res_skipgrams[i],
+                &skipgrams.bigrams.grams,
+            );
+        }
+
+        // quadgram files are optional
+        let p = Path::new(&component.1).join("4-grams.txt");
+        if !p.exists() {
+            log::warn!("No 4-gramme file found at '{:?}'. Skipping it.", &p);
+            continue;
+        }
+        let quadgrams = Quadgrams::from_file(p.to_str().unwrap())
+            .unwrap_or_else(|_| panic!("Could not read 4-gramme file from '{:?}'.", &p));
+
+        let quadgrams_total = quadgrams.total_weight();
+
+        // first ngram file determines "absolute level"
+        target_quadgrams_total = target_quadgrams_total.or(Some(quadgrams_total));
+        add(
+            component.0 * target_quadgrams_total.unwrap() / quadgrams_total,
+            &mut res_quadgrams,
+            &quadgrams.grams,
+        );
     }
 
     log::info!("Writing result to {}...", options.out);
@@ -114,4 +168,22 @@ fn main() {
     }
     .save_frequencies(out.join("3-grams.txt"))
     .unwrap();
+    if !res_quadgrams.is_empty() {
+        let mut quadgrams = Quadgrams {
+            grams: res_quadgrams,
+        };
+        // `tops` may drop the rarest quadgrams due to rounding even for a fraction of one
+        if options.quadgram_tops < 1.0 {
+            quadgrams = quadgrams.tops(options.quadgram_tops);
+        }
+        quadgrams.save_frequencies(out.join("4-grams.txt")).unwrap();
+    }
+    for (i, grams) in res_skipgrams.into_iter().enumerate() {
+        Skipgrams {
+            skip: i + 1,
+            bigrams: Bigrams { grams },
+        }
+        .save_frequencies(out)
+        .unwrap();
+    }
 }
@@ -1,7 +1,9 @@
 use clap::Parser;
 use std::{fs, path::Path};
 
-use layout_evaluation::ngrams::{Bigrams, Trigrams, Unigrams};
+use layout_evaluation::ngrams::{
+    Bigrams, Quadgrams, Skipgrams, Trigrams, Unigrams, DEFAULT_MAX_SKIP,
+};
 
 #[derive(Parser, Debug)]
 #[clap(name = "Ngram frequency generator")]
@@ -12,6 +14,14 @@ struct Options {
 
     /// Name for resulting ngram frequencies (a directory at that path will be generated)
     out: String,
+
+    /// Only keep the most common quadgrams up to the given fraction (of their total weight)
+    #[clap(long, default_value = "0.9")]
+    quadgram_tops: f64,
+
+    /// Generate skipgrams with up to this many chars in between their two chars
+    #[clap(long, default_value_t = DEFAULT_MAX_SKIP)]
+    max_skip: usize,
 }
 
 fn main() {
@@ -35,4 +45,16 @@ fn main() {
     let trigrams = Trigrams::from_text(&text).expect("Could not generate trigrams from text.");
     let p = d.join("3-grams.txt");
     trigrams.save_frequencies(p).unwrap();
+
+    let quadgrams = Quadgrams::from_text(&text)
+        .expect("Could not generate quadgrams from text.")
+        .tops(options.quadgram_tops);
+    let p = d.join("4-grams.txt");
+    quadgrams.save_frequencies(p).unwrap();
+
+    for skip in 1..=options.max_skip {
+        let skipgrams =
+            Skipgrams::from_text(&text, skip).expect("Could not generate skipgrams from text.");
+        skipgrams.save_frequencies(d).unwrap();
+    }
 }
@@ -1,9 +1,9 @@
 use keyboard_layout_optimizer::common;
-use layout_evaluation::cache::Cache;
+use layout_evaluation::{cache::Cache, evaluation::EvaluationOptions};
 use layout_optimization_genetic::optimization;
 
 use clap::Parser;
-use std::{env, process};
+use std::process;
 
 #[derive(Parser, Debug)]
 #[clap(name = "Keyboard layout optimization - Genetic Algorithm")]
@@ -57,11 +57,6 @@ fn main() {
     dotenv::dotenv().ok();
     env_logger::init();
 
-    // Disable storing worst ngrams for speed boost
-    if env::var("SHOW_WORST").is_err() {
-        env::set_var("SHOW_WORST", "false");
-    };
-
     let final_results: Cache<f64> = Cache::new();
 
     // Handle Ctrl+C
@@ -111,12 +106,12 @@ fn main() {
             &optimization_params,
             &evaluator,
             &fix_from,
-            &layout_generator,
+            layout_generator.as_ref(),
             &options.fix.clone().unwrap_or_default(),
             start_layout.is_some(),
             !options.no_cache_results,
         );
-        let evaluation_result = evaluator.evaluate_layout(&layout);
+        let evaluation_result = evaluator.evaluate_layout(&layout, &EvaluationOptions::summary());
         let cost = evaluation_result.total_cost();
         let _ = final_results.get_or_insert_with(&layout_str, || cost);
 
@@ -133,13 +128,16 @@ fn main() {
 
         // Publish to webservice.
         let o = &options.publishing_options;
-        if o.publish_as.is_some() && cost < o.publish_if_cost_below.unwrap_or(f64::INFINITY) {
-            common::publish_to_webservice(
-                &layout_str,
-                o.publish_as.as_ref().unwrap(),
-                &o.publish_to,
-                &o.publish_layout_config,
-            );
+        match &o.publish_as {
+            Some(publish_as) if cost < o.publish_if_cost_below.unwrap_or(f64::INFINITY) => {
+                common::publish_to_webservice(
+                    &layout_str,
+                    publish_as,
+                    &o.publish_to,
+                    &o.publish_layout_config,
+                );
+            }
+            _ => (),
         }
 
         if !options.run_forever {
@@ -0,0 +1,123 @@
+use keyboard_layout_optimizer::common;
+use layout_optimization_nsga::optimization::{self, ParetoArchive};
+
+use clap::Parser;
+use std::fs::File;
+
+#[derive(Parser, Debug)]
+#[clap(name = "Keyboard layout optimization - Multi-objective (NSGA-II)")]
+struct Options {
+    /// Evaluation parameters
+    #[clap(flatten)]
+    evaluation_parameters: common::Options,
+
+    /// Do not optimize those keys (wrt. --start-layout or --fix-from)
+    #[clap(short, long)]
+    fix: Option<String>,
+
+    /// Fix the keys from this layout (will be overwritten by --start-layout)
+    #[clap(long, default_value = "xvlcwkhgfqyßuiaeosnrtdüöäpzbm,.j")]
+    fix_from: String,
+
+    /// Filename of optimization configuration file
+    #[clap(short, long, default_value = "config/optimization/nsga.yml")]
+    optimization_parameters: String,
+
+    /// Start optimization from this layout (keys from left to right, top to bottom)
+    #[clap(short, long)]
+    start_layout: Option<String>,
+
+    /// Do not remove whitespace from layout strings
+    #[clap(long)]
+    do_not_remove_whitespace: bool,
+
+    /// Do not cache intermediate results
+    #[clap(long)]
+    no_cache_results: bool,
+
+    /// Maximum number of generations
+    #[clap(long)]
+    generation_limit: Option<u64>,
+
+    /// Write the non-dominated layouts and their objective values to this CSV file
+    #[clap(long)]
+    pareto_front: Option<String>,
+}
+
+/// Write the layouts of the archive and their objective values to a CSV file.
+fn write_pareto_front(archive: &ParetoArchive, filename: &str) -> anyhow::Result<()> {
+    let mut writer = csv::Writer::from_writer(File::create(filename)?);
+
+    let mut header = vec!["layout".to_string()];
+    header.extend(archive.objectives.iter().cloned());
+    writer.write_record(&header)?;
+
+    for l in archive.layouts.iter() {
+        let mut record = vec![l.layout.clone()];
+        record.extend(l.objectives.iter().map(|o| o.to_string()));
+        writer.write_record(&record)?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+fn main() {
+    dotenv::dotenv().ok();
+    env_logger::init();
+
+    let options = Options::parse();
+
+    let fix_from: String = options
+        .fix_from
+        .chars()
+        .filter(|c| options.do_not_remove_whitespace || !c.is_whitespace())
+        .collect();
+
+    let start_layout = options.start_layout.as_ref().map(|s| {
+        s.chars()
+            .filter(|c| options.do_not_remove_whitespace || !c.is_whitespace())
+            .collect::<String>()
+    });
+
+    let (layout_generator, evaluator) = common::init(&options.evaluation_parameters);
+
+    let mut optimization_params =
+        optimization::Parameters::from_yaml(&options.optimization_parameters).unwrap_or_else(|e| {
+            panic!(
+                "Could not read optimization parameters from {}: {:?}",
+                &options.optimization_parameters, e
+            )
+        });
+
+    if let Some(generation_limit) = options.generation_limit {
+        optimization_params.generation_limit = generation_limit
+    }
+
+    let fix_from = start_layout.as_ref().unwrap_or(&fix_from).to_string();
+
+    let archive = match optimization::optimize(
+        &optimization_params,
+        &evaluator,
+        &fix_from,
+        layout_generator.as_ref(),
+        &options.fix.clone().unwrap_or_default(),
+        start_layout.is_some(),
+        !options.no_cache_results,
+    ) {
+        Ok(archive) => archive,
+        Err(e) => {
+            log::error!("Error in optimization: {:?}", e);
+            panic!("{:?}", e);
+        }
+    };
+
+    println!("{}", archive);
+
+    if let Some(filename) = &options.pareto_front {
+        if let Err(e) = write_pareto_front(&archive, filename) {
+            log::error!("Error writing Pareto front to {}: {:?}", filename, e);
+            panic!("{:?}", e);
+        }
+    }
+}
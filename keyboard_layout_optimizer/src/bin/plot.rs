@@ -1,5 +1,7 @@
 use clap::Parser;
+use std::fs;
 
+use keyboard_layout::svg;
 use keyboard_layout_optimizer::common;
 
 #[derive(Parser, Debug)]
@@ -19,6 +21,53 @@ struct Options {
     /// Interpred given layout string using the "grouped" logic
     #[clap(long)]
     pub grouped_layout_generator: bool,
+
+    /// Write an SVG image of the layout to given file
+    #[clap(long)]
+    svg: Option<String>,
+
+    /// Color the keys of the SVG image by their unigram load ("unigram_load") or by the cost
+    /// attributed to them by the (unigram, bigram, or trigram) metric with given name
+    #[clap(long, requires = "svg")]
+    heatmap: Option<String>,
+
+    /// Path to ngram files used for the heatmap
+    #[clap(
+        long,
+        requires = "heatmap",
+        default_value = "ngrams/deu_mixed_wiki_web_0.6_eng_news_typical_wiki_web_0.4"
+    )]
+    ngrams: String,
+
+    /// Filename of corpus file to use for the heatmap instead of ngram files
+    #[clap(long, requires = "heatmap")]
+    corpus: Option<String>,
+
+    /// Filename of evaluation configuration file to use for the heatmap
+    #[clap(
+        long,
+        requires = "heatmap",
+        default_value = "config/evaluation/default.yml"
+    )]
+    eval_parameters: String,
+}
+
+impl Options {
+    /// Options for evaluating ngrams for the heatmap
+    fn evaluation_options(&self) -> common::Options {
+        common::Options {
+            ngrams: self.ngrams.clone(),
+            eval_parameters: self.eval_parameters.clone(),
+            layout_config: self.layout_config.clone(),
+            corpus: self.corpus.clone(),
+            text: None,
+            tops: None,
+            exclude_chars: None,
+            no_split_modifiers: false,
+            no_increase_common_ngrams: false,
+            grouped_layout_generator: self.grouped_layout_generator,
+        }
+    }
 }
 
 fn main() {
@@ -52,4 +101,26 @@ fn main() {
     }
     println!("Layout compact: \n{}", layout.plot_compact());
     println!("Layout as text: \n{}", layout);
+
+    if let Some(filename) = &options.svg {
+        let key_values = options.heatmap.as_ref().map(|heatmap| {
+            let evaluator =
+                common::init_evaluator(&options.evaluation_options(), layout_generator.as_ref());
+            match heatmap.as_str() {
+                "unigram_load" => evaluator.key_loads(&layout),
+                metric_name => evaluator
+                    .key_costs(&layout, metric_name)
+                    .unwrap_or_else(|| {
+                        panic!(
+                            "Unknown unigram, bigram, or trigram metric '{}'",
+                            metric_name
+                        )
+                    }),
+            }
+        });
+
+        let image = svg::layout_svg(&layout, key_values.as_deref());
+        fs::write(filename, image)
+            .unwrap_or_else(|e| panic!("Could not write file {}: {}", filename, e));
+    }
 }
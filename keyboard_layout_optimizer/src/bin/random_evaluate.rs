@@ -2,6 +2,7 @@ use clap::Parser;
 use rand::{self, seq::SliceRandom};
 
 use keyboard_layout_optimizer::common;
+use layout_evaluation::{calibration::Calibration, evaluation::EvaluationOptions};
 
 #[derive(Parser, Debug)]
 #[clap(name = "Random keyboard layout evaluation")]
@@ -10,10 +11,16 @@ struct Options {
     #[clap(default_value = "1000")]
     number_of_samples: usize,
 
+    /// Write the distributions of the metrics' weighted costs to this calibration file
+    /// (for z-score normalizations)
+    #[clap(long)]
+    calibration: Option<String>,
+
     /// Evaluation parameters
     #[clap(flatten)]
     evaluation_parameters: common::Options,
 }
+
 fn main() {
     dotenv::dotenv().ok();
     env_logger::init();
@@ -21,13 +28,14 @@ fn main() {
 
     let (layout_generator, evaluator) = common::init(&options.evaluation_parameters);
 
-    let layout_str = "abcdefghijklmnopqrstuvwxyzäöüß,.";
+    let permutable_symbols = layout_generator.permutable_symbols();
     let mut best_cost: Option<f64> = None;
     let mut best_layout: String = "".into();
+    let mut results = Vec::new();
 
     for _ in 0..options.number_of_samples {
         let mut rng = rand::thread_rng();
-        let mut s: Vec<char> = layout_str.chars().collect();
+        let mut s: Vec<char> = permutable_symbols.clone();
         s.shuffle(&mut rng);
         let s: String = s.iter().collect();
 
@@ -39,7 +47,7 @@ fn main() {
             }
         };
 
-        let evaluation_result = evaluator.evaluate_layout(&layout);
+        let evaluation_result = evaluator.evaluate_layout(&layout, &EvaluationOptions::default());
 
         let cost = evaluation_result.total_cost();
         best_cost = Some(best_cost.unwrap_or(cost));
@@ -50,8 +58,28 @@ fn main() {
         };
 
         log::info!("Evaluated {}: {}", s, cost);
+
+        if options.calibration.is_some() {
+            results.push(evaluation_result);
+        }
     }
     log::info!("Best: {}: {}", best_layout, best_cost.unwrap_or(0.0));
+
+    if let Some(filename) = &options.calibration {
+        let calibration = Calibration::from_results(&results);
+        for (name, metric) in calibration.metrics.iter() {
+            log::info!(
+                "{:<35} mean: {:>8.2}, std: {:>8.2}",
+                name,
+                metric.mean,
+                metric.std
+            );
+        }
+        if let Err(e) = calibration.to_yaml(filename) {
+            log::error!("Error writing calibration to {}: {:?}", filename, e);
+            panic!("{:?}", e);
+        }
+    }
     // for layout_str in options.layout_str.iter() {
     //     let layout = match layout_generator.generate(layout_str) {
     //         Ok(layout) => layout,
@@ -61,7 +89,7 @@ fn main() {
     //         }
     //     };
     //     println!("Layout (layer 1):\n{}", layout.plot_layer(0));
-    //     let metric_costs = evaluator.evaluate_layout(&layout);
+    //     let metric_costs = evaluator.evaluate_layout(&layout, &EvaluationOptions::default());
     //     let mut cost = 0.0;
     //     for mc in metric_costs.iter().filter(|mc| mc.metric_costs.len() > 0) {
     //         cost += mc.total_cost();
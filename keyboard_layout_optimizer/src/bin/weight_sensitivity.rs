@@ -0,0 +1,160 @@
+use keyboard_layout_optimizer::common;
+use layout_evaluation::{
+    evaluation::EvaluationOptions,
+    results::EvaluationResult,
+    sensitivity::{MetricCostTable, WeightSensitivity},
+};
+
+use clap::Parser;
+use rand::Rng;
+use rayon::prelude::*;
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+};
+
+#[derive(Parser, Debug)]
+#[clap(name = "Metric weight sensitivity analysis")]
+struct Options {
+    /// List of Layout keys from left to right, top to bottom
+    layout_str: Vec<String>,
+
+    /// Do not remove whitespace from layout strings
+    #[clap(long)]
+    do_not_remove_whitespace: bool,
+
+    /// Read layouts from file and append to command line layouts
+    #[clap(long)]
+    from_file: Option<String>,
+
+    /// General parameters
+    #[clap(flatten)]
+    general_parameters: common::Options,
+
+    /// Number of random weight perturbations
+    #[clap(long, default_value = "1000", validator = at_least_one)]
+    samples: usize,
+
+    /// Maximal relative change of the metrics' weights (between 0 and 1)
+    #[clap(long, default_value = "0.5", validator = valid_spread)]
+    spread: f64,
+
+    /// Instead of random perturbations, change each metric's weight individually along a grid of
+    /// given number of steps
+    #[clap(long, validator = at_least_one)]
+    grid: Option<usize>,
+
+    /// Number of best layouts whose order is analysed for weight changes reversing it
+    #[clap(long, default_value = "3")]
+    top: usize,
+
+    /// If to only output the results as JSON to stdout
+    #[clap(long)]
+    json: bool,
+}
+
+/// Without perturbations, the rank distributions and win probabilities are undefined.
+fn at_least_one(s: &str) -> Result<(), String> {
+    match s.parse::<usize>() {
+        Ok(n) if n >= 1 => Ok(()),
+        _ => Err("must be a positive integer".to_string()),
+    }
+}
+
+/// A spread above one would lead to negative weights.
+fn valid_spread(s: &str) -> Result<(), String> {
+    match s.parse::<f64>() {
+        Ok(spread) if (0.0..=1.0).contains(&spread) => Ok(()),
+        _ => Err("must be a number between 0 and 1".to_string()),
+    }
+}
+
+/// Weight factors changing the weight of each metric individually along a grid.
+fn grid_factors(n_metrics: usize, steps: usize, spread: f64) -> Vec<Vec<f64>> {
+    (0..n_metrics)
+        .flat_map(|m| {
+            (0..steps).map(move |step| {
+                let mut factors = vec![1.0; n_metrics];
+                if steps > 1 {
+                    factors[m] = 1.0 - spread + 2.0 * spread * step as f64 / (steps - 1) as f64;
+                }
+                factors
+            })
+        })
+        .collect()
+}
+
+/// Weight factors changing the weights of all metrics randomly.
+fn random_factors(n_metrics: usize, samples: usize, spread: f64) -> Vec<Vec<f64>> {
+    let mut rng = rand::thread_rng();
+    (0..samples)
+        .map(|_| {
+            (0..n_metrics)
+                .map(|_| rng.gen_range((1.0 - spread)..=(1.0 + spread)))
+                .collect()
+        })
+        .collect()
+}
+
+fn main() {
+    dotenv::dotenv().ok();
+    let options = Options::parse();
+    if !options.json {
+        // if the "json" option is set, we do not want any other log messages
+        env_logger::init();
+    }
+
+    let (layout_generator, evaluator) = common::init(&options.general_parameters);
+
+    // collect layout strings to a vec
+    let mut layout_strings = options.layout_str.to_vec();
+    if let Some(filename) = &options.from_file {
+        match File::open(filename) {
+            Ok(file) => {
+                layout_strings
+                    .append(&mut BufReader::new(file).lines().map_while(Result::ok).collect());
+            }
+            Err(e) => {
+                log::error!("Error reading layouts file {}: {:?}", filename, e);
+                panic!("{:?}", e);
+            }
+        }
+    }
+    let layout_strings: Vec<String> = layout_strings
+        .iter()
+        .map(|layout_str| {
+            layout_str
+                .chars()
+                .filter(|c| options.do_not_remove_whitespace || !c.is_whitespace())
+                .collect()
+        })
+        .collect();
+
+    // evaluate each layout once
+    let results: Vec<EvaluationResult> = layout_strings
+        .par_iter()
+        .map(|layout_str| {
+            let layout = match layout_generator.generate(layout_str) {
+                Ok(layout) => layout,
+                Err(e) => {
+                    log::error!("Error in generating layout: {:?}", e);
+                    panic!("{:?}", e);
+                }
+            };
+            evaluator.evaluate_layout(&layout, &EvaluationOptions::default())
+        })
+        .collect();
+
+    let table = MetricCostTable::new(layout_strings, &results);
+    let weight_factors = match options.grid {
+        Some(steps) => grid_factors(table.metrics.len(), steps, options.spread),
+        None => random_factors(table.metrics.len(), options.samples, options.spread),
+    };
+    let sensitivity = WeightSensitivity::new(&table, &weight_factors, options.top);
+
+    if options.json {
+        println!("{}", serde_json::to_string(&sensitivity).unwrap());
+    } else {
+        println!("{}", sensitivity);
+    }
+}
@@ -6,7 +6,7 @@ use layout_evaluation::{
     config::EvaluationParameters,
     evaluation::Evaluator,
     ngram_mapper::on_demand_ngram_mapper::OnDemandNgramMapper,
-    ngrams::{Bigrams, Trigrams, Unigrams},
+    ngrams::{Bigrams, Quadgrams, Skipgrams, Trigrams, Unigrams, DEFAULT_MAX_SKIP},
 };
 
 use ahash::AHashMap;
@@ -89,10 +89,11 @@ pub struct PublishingOptions {
 }
 
 pub fn init(options: &Options) -> (Box<dyn LayoutGenerator>, Evaluator) {
-    (
-        init_layout_generator(&options.layout_config, options.grouped_layout_generator),
-        init_evaluator(options),
-    )
+    let layout_generator =
+        init_layout_generator(&options.layout_config, options.grouped_layout_generator);
+    let evaluator = init_evaluator(options, layout_generator.as_ref());
+
+    (layout_generator, evaluator)
 }
 
 pub fn init_layout_generator(
@@ -133,7 +134,8 @@ pub fn init_layout_generator(
     }
 }
 
-pub fn init_evaluator(options: &Options) -> Evaluator {
+pub fn init_evaluator(options: &Options, layout_generator: &dyn LayoutGenerator) -> Evaluator {
+    let macro_keys = layout_generator.macro_keys();
     let eval_params =
         EvaluationParameters::from_yaml(&options.eval_parameters).unwrap_or_else(|e| {
             panic!(
@@ -159,17 +161,32 @@ pub fn init_evaluator(options: &Options) -> Evaluator {
         ngrams_config.increase_common_ngrams.enabled = false;
     }
 
-    let (mut unigrams, mut bigrams, mut trigrams) = match text {
+    let (mut unigrams, mut bigrams, mut trigrams, mut quadgrams, mut skipgrams) = match text {
         Some(txt) => {
+            // represent multi-character outputs of keys by their placeholders
+            let txt = macro_keys.tokenize(&txt);
             let unigrams =
                 Unigrams::from_text(&txt).expect("Could not generate unigrams from text.");
             let bigrams = Bigrams::from_text(&txt).expect("Could not generate bigrams from text.");
             let trigrams =
                 Trigrams::from_text(&txt).expect("Could not generate trigrams from text.");
-
-            (unigrams, bigrams, trigrams)
+            let quadgrams =
+                Quadgrams::from_text(&txt).expect("Could not generate quadgrams from text.");
+            let skipgrams: Vec<Skipgrams> = (1..=DEFAULT_MAX_SKIP)
+                .map(|skip| {
+                    Skipgrams::from_text(&txt, skip)
+                        .expect("Could not generate skipgrams from text.")
+                })
+                .collect();
+
+            (unigrams, bigrams, trigrams, quadgrams, skipgrams)
         }
         None => {
+            // ngram files are character-based and can not be mapped to macros reliably
+            if !macro_keys.is_empty() {
+                panic!("The layout contains macro keys. These can only be evaluated with a corpus or text (--corpus/--text), not with ngram files.");
+            }
+
             let p = Path::new(&options.ngrams).join("1-grams.txt");
             log::info!("Reading unigram file: '{:?}'", p);
             let unigrams = Unigrams::from_file(p.to_str().unwrap())
@@ -182,8 +199,25 @@ pub fn init_evaluator(options: &Options) -> Evaluator {
             log::info!("Reading trigram file: '{:?}'", p);
             let trigrams = Trigrams::from_file(p.to_str().unwrap())
                 .unwrap_or_else(|_| panic!("Could not read 3-gramme file from '{:?}'.", &p));
-
-            (unigrams, bigrams, trigrams)
+            // quadgram files are optional (they are only required for quadgram metrics)
+            let p = Path::new(&options.ngrams).join("4-grams.txt");
+            let quadgrams = if p.exists() {
+                log::info!("Reading quadgram file: '{:?}'", p);
+                Quadgrams::from_file(p.to_str().unwrap())
+                    .unwrap_or_else(|_| panic!("Could not read 4-gramme file from '{:?}'.", &p))
+            } else {
+                log::info!("No quadgram file found at '{:?}'", p);
+                Quadgrams::default()
+            };
+            // skipgram files are optional as well (they are only required for skipgram metrics)
+            let skipgrams = Skipgrams::from_dir(&options.ngrams).unwrap_or_else(|_| {
+                panic!("Could not read skipgram files from '{}'.", &options.ngrams)
+            });
+            if skipgrams.is_empty() {
+                log::info!("No skipgram files found in '{}'", &options.ngrams);
+            }
+
+            (unigrams, bigrams, trigrams, quadgrams, skipgrams)
         }
     };
 
@@ -192,6 +226,11 @@ pub fn init_evaluator(options: &Options) -> Evaluator {
             unigrams = unigrams.exclude_char(&exclude_char);
             bigrams = bigrams.exclude_char(&exclude_char);
             trigrams = trigrams.exclude_char(&exclude_char);
+            quadgrams = quadgrams.exclude_char(&exclude_char);
+            skipgrams = skipgrams
+                .iter()
+                .map(|s| s.exclude_char(&exclude_char))
+                .collect();
         }
     }
 
@@ -199,18 +238,39 @@ pub fn init_evaluator(options: &Options) -> Evaluator {
         unigrams = unigrams.increase_common(&ngrams_config.increase_common_ngrams);
         bigrams = bigrams.increase_common(&ngrams_config.increase_common_ngrams);
         trigrams = trigrams.increase_common(&ngrams_config.increase_common_ngrams);
+        quadgrams = quadgrams.increase_common(&ngrams_config.increase_common_ngrams);
+        skipgrams = skipgrams
+            .iter()
+            .map(|s| s.increase_common(&ngrams_config.increase_common_ngrams))
+            .collect();
     }
 
     if let Some(tops) = options.tops {
         unigrams = unigrams.tops(tops);
         bigrams = bigrams.tops(tops);
         trigrams = trigrams.tops(tops);
+        quadgrams = quadgrams.tops(tops);
+        skipgrams = skipgrams.iter().map(|s| s.tops(tops)).collect();
     }
 
-    let ngram_provider =
-        OnDemandNgramMapper::with_ngrams(unigrams, bigrams, trigrams, ngram_mapper_config);
+    let ngram_provider = OnDemandNgramMapper::with_ngrams(
+        unigrams,
+        bigrams,
+        trigrams,
+        quadgrams,
+        skipgrams,
+        ngram_mapper_config,
+    );
 
-    Evaluator::default(Box::new(ngram_provider)).default_metrics(&eval_params.metrics)
+    Evaluator::default(Box::new(ngram_provider))
+        .with_layout_generator(layout_generator.clone_box())
+        .default_metrics(&eval_params.metrics)
+        .unwrap_or_else(|e| {
+            panic!(
+                "Invalid metrics in evaluation yaml file {}: {}",
+                options.eval_parameters, e
+            )
+        })
 }
 
 /// Appends a layout-string to a file.
@@ -4,9 +4,9 @@ use keyboard_layout::{
 };
 use layout_evaluation::{
     config::EvaluationParameters,
-    evaluation::Evaluator,
+    evaluation::{EvaluationOptions, Evaluator},
     ngram_mapper::on_demand_ngram_mapper::OnDemandNgramMapper,
-    ngrams::{Bigrams, Trigrams, Unigrams},
+    ngrams::{Bigrams, Quadgrams, Trigrams, Unigrams},
 };
 
 use criterion::{criterion_group, criterion_main, Criterion};
@@ -60,11 +60,18 @@ pub fn evaluate_bench(c: &mut Criterion) {
         trigrams = trigrams.increase_common(&ngrams_config.increase_common_ngrams);
     }
 
-    let ngram_provider =
-        OnDemandNgramMapper::with_ngrams(unigrams, bigrams, trigrams, ngram_mapper_config);
+    let ngram_provider = OnDemandNgramMapper::with_ngrams(
+        unigrams,
+        bigrams,
+        trigrams,
+        Quadgrams::default(),
+        Vec::new(),
+        ngram_mapper_config,
+    );
 
-    let evaluator =
-        Evaluator::default(Box::new(ngram_provider)).default_metrics(&eval_params.metrics);
+    let evaluator = Evaluator::default(Box::new(ngram_provider))
+        .default_metrics(&eval_params.metrics)
+        .expect("Could not initialize metrics");
 
     let layout = match layout_generator.generate("jduaxphlmwqßctieobnrsgfvüäöyz,.k") {
         Ok(layout) => layout,
@@ -74,7 +81,7 @@ pub fn evaluate_bench(c: &mut Criterion) {
         }
     };
     c.bench_function("evaluate", |b| {
-        b.iter(|| evaluator.evaluate_layout(&layout));
+        b.iter(|| evaluator.evaluate_layout(&layout, &EvaluationOptions::default()));
     });
 }
 
@@ -0,0 +1,117 @@
+//! Compares evaluating key-based metrics directly with looking up their costs in tables over all
+//! pairs (bigrams) or triples (trigrams) of keys (see the `tabulated` module).
+
+use keyboard_layout::{
+    config::LayoutConfig, keyboard::Keyboard, layout_generator::LayoutGenerator,
+    neo_layout_generator::NeoLayoutGenerator,
+};
+use layout_evaluation::{
+    config::EvaluationParameters,
+    metrics::{
+        bigram_metrics::{finger_repeats, movement_pattern, BigramMetric},
+        tabulated::{TabulatedBigramMetric, TabulatedTrigramMetric},
+        trigram_metrics::{
+            no_handswitch_in_trigram, trigram_finger_repeats, trigram_rolls, TrigramMetric,
+        },
+    },
+    ngram_mapper::{on_demand_ngram_mapper::OnDemandNgramMapper, NgramMapper},
+    ngrams::{Bigrams, Quadgrams, Trigrams, Unigrams},
+};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use serde::de::DeserializeOwned;
+use std::{path::Path, sync::Arc};
+
+const NGRAMS: &str = "../ngrams/deu_mixed_wiki_web_0.6_eng_news_typical_wiki_web_0.4";
+const LAYOUT_CONFIG: &str = "../config/keyboard/standard.yml";
+const EVALUATION_PARAMETERS: &str = "../config/evaluation/default.yml";
+
+fn params<P: DeserializeOwned>(eval_params: &EvaluationParameters, name: &str) -> P {
+    let params = &eval_params
+        .metrics
+        .0
+        .get(name)
+        .unwrap_or_else(|| panic!("Metric '{}' not in evaluation parameters", name))
+        .params;
+    serde_yaml::to_string(params)
+        .and_then(|s| serde_yaml::from_str(&s))
+        .unwrap_or_else(|e| panic!("Invalid parameters for '{}': {}", name, e))
+}
+
+pub fn tabulation_bench(c: &mut Criterion) {
+    let layout_config = LayoutConfig::from_yaml(LAYOUT_CONFIG)
+        .unwrap_or_else(|e| panic!("Could not load config file '/keyboard/standard.yml': {}", e));
+    let keyboard = Arc::new(Keyboard::from_yaml_object(layout_config.keyboard));
+    let layout_generator = NeoLayoutGenerator::from_object(layout_config.base_layout, keyboard);
+    let layout = layout_generator
+        .generate("jduaxphlmwqßctieobnrsgfvüäöyz,.k")
+        .unwrap();
+
+    let eval_params = EvaluationParameters::from_yaml(EVALUATION_PARAMETERS).unwrap_or_else(|_| {
+        panic!("Could not read evaluation yaml file '/evaluation/default.yml'")
+    });
+
+    let p = Path::new(NGRAMS);
+    let ngram_mapper = OnDemandNgramMapper::with_ngrams(
+        Unigrams::from_file(p.join("1-grams.txt").to_str().unwrap()).unwrap(),
+        Bigrams::from_file(p.join("2-grams.txt").to_str().unwrap()).unwrap(),
+        Trigrams::from_file(p.join("3-grams.txt").to_str().unwrap()).unwrap(),
+        Quadgrams::default(),
+        Vec::new(),
+        eval_params.ngram_mapper.clone(),
+    );
+
+    let bigrams = ngram_mapper.map_bigrams(&layout);
+    let bigram_metrics: Vec<Box<dyn BigramMetric>> = vec![
+        Box::new(finger_repeats::FingerRepeats::new(&params(
+            &eval_params,
+            "finger_repeats",
+        ))),
+        Box::new(movement_pattern::MovementPattern::new(&params(
+            &eval_params,
+            "movement_pattern",
+        ))),
+    ];
+
+    let mut group = c.benchmark_group("bigram_tabulation");
+    for metric in bigram_metrics {
+        let tabulated = TabulatedBigramMetric::new(metric.clone());
+        group.bench_function(BenchmarkId::new("direct", metric.name()), |b| {
+            b.iter(|| metric.total_cost(&bigrams.grams, None, &layout, 0))
+        });
+        group.bench_function(BenchmarkId::new("tabulated", metric.name()), |b| {
+            b.iter(|| tabulated.total_cost(&bigrams.grams, None, &layout, 0))
+        });
+    }
+    group.finish();
+
+    let trigrams = ngram_mapper.map_trigrams(&layout);
+    let trigram_metrics: Vec<Box<dyn TrigramMetric>> = vec![
+        Box::new(no_handswitch_in_trigram::NoHandswitchInTrigram::new(
+            &params(&eval_params, "no_handswitch_in_trigram"),
+        )),
+        Box::new(trigram_finger_repeats::TrigramFingerRepeats::new(&params(
+            &eval_params,
+            "trigram_finger_repeats",
+        ))),
+        Box::new(trigram_rolls::TrigramRolls::new(&params(
+            &eval_params,
+            "trigram_rolls",
+        ))),
+    ];
+
+    let mut group = c.benchmark_group("trigram_tabulation");
+    for metric in trigram_metrics {
+        let tabulated = TabulatedTrigramMetric::new(metric.clone());
+        group.bench_function(BenchmarkId::new("direct", metric.name()), |b| {
+            b.iter(|| metric.total_cost(&trigrams.grams, None, &layout, 0))
+        });
+        group.bench_function(BenchmarkId::new("tabulated", metric.name()), |b| {
+            b.iter(|| tabulated.total_cost(&trigrams.grams, None, &layout, 0))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, tabulation_bench);
+criterion_main!(benches);
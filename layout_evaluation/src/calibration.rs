@@ -0,0 +1,157 @@
+//! The `calibration` module provides the distributions of the metrics' costs for random layouts.
+//!
+//! The metrics live on very different scales, which makes their weights hard to reason about. A
+//! [`Calibration`] records the mean and standard deviation of each metric's weighted cost over
+//! a sample of random layouts. The [`NormalizationType::ZScore`] normalization then expresses a
+//! metric's cost as z-score relative to these random layouts.
+
+use crate::results::{EvaluationResult, NormalizationType};
+
+use ahash::AHashMap;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs::File};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum CalibrationError {
+    #[error("No calibration for metric '{0}' in calibration file {1}")]
+    MissingMetric(String, String),
+    #[error("Calibration of metric '{0}' in calibration file {1} uses a z-score normalization")]
+    NestedZScore(String, String),
+}
+
+/// Distribution of a metric's weighted cost for random layouts.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct MetricCalibration {
+    /// The weight of the metric used for the calibration.
+    pub weight: f64,
+    /// The normalization of the metric used for the calibration.
+    pub normalization: NormalizationType,
+    /// Mean of the weighted cost.
+    pub mean: f64,
+    /// Standard deviation of the weighted cost.
+    pub std: f64,
+}
+
+impl MetricCalibration {
+    /// The z-score of a weighted cost (weighted and normalized as in the calibration).
+    pub fn z_score(&self, weighted_cost: f64) -> f64 {
+        if self.std > 0.0 {
+            (weighted_cost - self.mean) / self.std
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Distributions of the metrics' weighted costs for random layouts (by metric name).
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Calibration {
+    /// Number of random layouts the calibration is based on.
+    pub samples: usize,
+    /// Distribution of each metric's weighted cost.
+    pub metrics: BTreeMap<String, MetricCalibration>,
+}
+
+impl Calibration {
+    /// Compute the distributions of the metrics' weighted costs from the evaluation results of
+    /// random layouts. For metrics with a z-score normalization, the weighted costs of the
+    /// normalization's calibration are used.
+    pub fn from_results<'a>(results: impl IntoIterator<Item = &'a EvaluationResult>) -> Self {
+        let mut samples = 0;
+        let mut costs: BTreeMap<String, (f64, NormalizationType, Vec<f64>)> = BTreeMap::new();
+        for result in results {
+            samples += 1;
+            for metric_results in result.iter() {
+                for metric_cost in metric_results.metric_costs.iter() {
+                    let (weight, normalization) = metric_cost.core.base_weighting();
+                    costs
+                        .entry(metric_cost.core.name.clone())
+                        .or_insert_with(|| (weight, normalization.clone(), Vec::new()))
+                        .2
+                        .push(metric_results.base_weighted_cost(&metric_cost.core));
+                }
+            }
+        }
+
+        let metrics = costs
+            .into_iter()
+            .map(|(name, (weight, normalization, costs))| {
+                let n = costs.len() as f64;
+                let mean = costs.iter().sum::<f64>() / n;
+                let variance = if costs.len() > 1 {
+                    costs.iter().map(|c| (c - mean).powi(2)).sum::<f64>() / (n - 1.0)
+                } else {
+                    0.0
+                };
+
+                let calibration = MetricCalibration {
+                    weight,
+                    normalization,
+                    mean,
+                    std: variance.sqrt(),
+                };
+
+                (name, calibration)
+            })
+            .collect();
+
+        Self { samples, metrics }
+    }
+
+    pub fn from_yaml(filename: &str) -> Result<Self> {
+        let f = File::open(filename)?;
+        let c: Calibration = serde_yaml::from_reader(f)?;
+
+        Ok(c)
+    }
+
+    pub fn to_yaml(&self, filename: &str) -> Result<()> {
+        let f = File::create(filename)?;
+        serde_yaml::to_writer(f, self)?;
+
+        Ok(())
+    }
+}
+
+/// Resolves the z-score normalizations of metrics by reading their calibration files
+/// (each file only once).
+#[derive(Debug, Clone, Default)]
+pub struct CalibrationLoader {
+    calibrations: AHashMap<String, Calibration>,
+}
+
+impl CalibrationLoader {
+    /// Load the distribution of the metric's cost if given normalization is a z-score normalization.
+    pub fn resolve(
+        &mut self,
+        mut normalization: NormalizationType,
+        metric_name: &str,
+    ) -> Result<NormalizationType> {
+        if let NormalizationType::ZScore(z) = &mut normalization {
+            if !self.calibrations.contains_key(&z.calibration) {
+                let calibration = Calibration::from_yaml(&z.calibration)?;
+                self.calibrations.insert(z.calibration.clone(), calibration);
+            }
+
+            let distribution = self.calibrations[&z.calibration]
+                .metrics
+                .get(metric_name)
+                .ok_or_else(|| {
+                    CalibrationError::MissingMetric(metric_name.to_string(), z.calibration.clone())
+                })?;
+            if let NormalizationType::ZScore(_) = distribution.normalization {
+                return Err(CalibrationError::NestedZScore(
+                    metric_name.to_string(),
+                    z.calibration.clone(),
+                )
+                .into());
+            }
+
+            z.distribution = Some(Box::new(distribution.clone()));
+        }
+
+        Ok(normalization)
+    }
+}
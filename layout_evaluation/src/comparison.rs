@@ -0,0 +1,194 @@
+//! The `comparison` module provides structs describing the differences between the evaluations
+//! of two layouts: the symbols that moved, the changes of each metric's weighted cost, and the
+//! ngrams whose (raw) costs changed the most.
+
+use crate::results::{EvaluationResult, MetricType};
+
+use keyboard_layout::layout::{Layout, MovedSymbol};
+
+use ahash::AHashMap;
+use colored::Colorize;
+use ordered_float::OrderedFloat;
+use serde::Serialize;
+use std::{cmp::Reverse, fmt};
+
+/// Change of the cost of an individual ngram in a metric's breakdown. The costs are the ngram's raw
+/// (not weighted or normalized) costs, as the total cost of some metrics is not the sum of the
+/// individual costs (e.g. irregularity).
+#[derive(Debug, Clone, Serialize)]
+pub struct NgramDelta {
+    /// Labels of the symbols (or modifiers) of the ngram.
+    pub symbols: Vec<String>,
+    /// Raw cost in the first layout.
+    pub cost_a: f64,
+    /// Raw cost in the second layout.
+    pub cost_b: f64,
+}
+
+impl NgramDelta {
+    /// Change of the raw cost from the first to the second layout.
+    pub fn delta(&self) -> f64 {
+        self.cost_b - self.cost_a
+    }
+}
+
+/// Change of the weighted (and normalized) cost of a metric.
+#[derive(Debug, Clone, Serialize)]
+pub struct MetricDelta {
+    /// Type of the metric.
+    pub metric_type: MetricType,
+    /// Name of the metric.
+    pub name: String,
+    /// Weighted cost in the first layout.
+    pub cost_a: f64,
+    /// Weighted cost in the second layout.
+    pub cost_b: f64,
+    /// The ngrams with the largest (absolute) changes of their raw costs.
+    pub ngram_deltas: Vec<NgramDelta>,
+}
+
+impl MetricDelta {
+    /// Change of the weighted cost from the first to the second layout.
+    pub fn delta(&self) -> f64 {
+        self.cost_b - self.cost_a
+    }
+}
+
+/// Describes the differences between the evaluations of two layouts.
+#[derive(Debug, Clone, Serialize)]
+pub struct LayoutComparison {
+    /// The first layout (as text).
+    pub layout_a: String,
+    /// The second layout (as text).
+    pub layout_b: String,
+    /// The base-layer symbols that moved from the first to the second layout, grouped into swap cycles
+    /// (see [`Layout::swap_cycles`]).
+    pub moved_symbols: Vec<Vec<MovedSymbol>>,
+    /// Total cost of the first layout.
+    pub total_cost_a: f64,
+    /// Total cost of the second layout.
+    pub total_cost_b: f64,
+    /// Changes of the metrics' weighted costs (in the order of the evaluation results).
+    pub metric_deltas: Vec<MetricDelta>,
+}
+
+impl LayoutComparison {
+    /// Compare the evaluation results of two layouts that were evaluated with the same evaluator.
+    /// For each metric, the `n_ngrams` ngrams with the largest changes of their raw costs are
+    /// listed (this requires results with per-ngram breakdowns).
+    pub fn new(
+        layout_a: &Layout,
+        result_a: &EvaluationResult,
+        layout_b: &Layout,
+        result_b: &EvaluationResult,
+        n_ngrams: usize,
+    ) -> Self {
+        let metric_deltas = result_a
+            .iter()
+            .zip(result_b.iter())
+            .flat_map(|(results_a, results_b)| {
+                results_a
+                    .metric_costs
+                    .iter()
+                    .zip(results_b.metric_costs.iter())
+                    .map(move |(mc_a, mc_b)| {
+                        // raw costs of ngrams (identified by their symbols) in both layouts
+                        let mut ngram_costs: AHashMap<&[String], (f64, f64)> = AHashMap::default();
+                        for c in mc_a.core.breakdown.iter().flatten() {
+                            ngram_costs.entry(&c.symbols).or_default().0 += c.cost;
+                        }
+                        for c in mc_b.core.breakdown.iter().flatten() {
+                            ngram_costs.entry(&c.symbols).or_default().1 += c.cost;
+                        }
+
+                        let mut ngram_deltas: Vec<NgramDelta> = ngram_costs
+                            .into_iter()
+                            .filter(|(_, (cost_a, cost_b))| cost_a != cost_b)
+                            .map(|(symbols, (cost_a, cost_b))| NgramDelta {
+                                symbols: symbols.to_vec(),
+                                cost_a,
+                                cost_b,
+                            })
+                            .collect();
+                        ngram_deltas.sort_by_key(|d| Reverse(OrderedFloat(d.delta().abs())));
+                        ngram_deltas.truncate(n_ngrams);
+
+                        MetricDelta {
+                            metric_type: results_a.metric_type.clone(),
+                            name: mc_a.core.name.clone(),
+                            cost_a: mc_a.weighted_cost,
+                            cost_b: mc_b.weighted_cost,
+                            ngram_deltas,
+                        }
+                    })
+            })
+            .collect();
+
+        Self {
+            layout_a: layout_a.as_text(),
+            layout_b: layout_b.as_text(),
+            moved_symbols: layout_a.swap_cycles(layout_b),
+            total_cost_a: result_a.total_cost(),
+            total_cost_b: result_b.total_cost(),
+            metric_deltas,
+        }
+    }
+}
+
+/// Format a change of costs (colored by whether it is an improvement).
+fn format_delta(delta: f64, width: usize) -> String {
+    let s = format!("{:>+width$.2}", delta, width = width);
+    if delta < 0.0 {
+        s.green().to_string()
+    } else if delta > 0.0 {
+        s.red().to_string()
+    } else {
+        s
+    }
+}
+
+impl fmt::Display for LayoutComparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} {}", "Layout A:".bold(), self.layout_a)?;
+        writeln!(f, "{} {}", "Layout B:".bold(), self.layout_b)?;
+
+        writeln!(f, "{}", "Moved symbols:".bold())?;
+        for cycle in self.moved_symbols.iter() {
+            let moves: Vec<String> = cycle.iter().map(|m| m.to_string()).collect();
+            writeln!(f, "  {}", moves.join(", "))?;
+        }
+
+        let mut metric_type = None;
+        for md in self.metric_deltas.iter() {
+            if metric_type != Some(&md.metric_type) {
+                metric_type = Some(&md.metric_type);
+                writeln!(f, "{}", format!("{:?} metrics:", md.metric_type).bold())?;
+            }
+
+            let ngram_deltas: Vec<String> = md
+                .ngram_deltas
+                .iter()
+                .map(|nd| format!("{} ({:+.2e})", nd.symbols.concat(), nd.delta()))
+                .collect();
+            writeln!(
+                f,
+                "  {} {} {:>7.2} → {:>7.2} | {}",
+                format_delta(md.delta(), 8),
+                format!("{:<35}", md.name).bold(),
+                md.cost_a,
+                md.cost_b,
+                ngram_deltas.join(", "),
+            )?;
+        }
+
+        writeln!(
+            f,
+            "Cost: {:.2} → {:.2} ({})",
+            self.total_cost_a,
+            self.total_cost_b,
+            format_delta(self.total_cost_b - self.total_cost_a, 0),
+        )?;
+
+        Ok(())
+    }
+}
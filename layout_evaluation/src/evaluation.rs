@@ -2,22 +2,44 @@
 //! layouts with respect to a list of metrics and ngram data.
 //!
 //! It can hold multiple metrics operating on the layout itself, unigrams, bigrams,
-//! or trigrams. These are required to implement the corresponding trait from the `metrics` module.
+//! trigrams, quadgrams, or skipgrams. These are required to implement the corresponding trait from the `metrics` module.
 //!
 //! The ngram mapper is responsible for mapping char-based ngrams (as read from input data)
-//! to singles, pairs, and triplets of [`LayerKey`]s that can then be analysed by the individual metrics.
+//! to singles, pairs, triplets, and quadruplets of [`LayerKey`]s that can then be analysed by the individual metrics.
+//!
+//! For optimizations that only change a few keys at a time, a [`DeltaEvaluationState`] caches the costs
+//! of each individual ngram such that only those ngrams involving changed keys need to be reevaluated.
 
 use crate::results::{
-    EvaluationResult, MetricResult, MetricResults, MetricType, NormalizationType,
+    EvaluationResult, MetricResult, MetricResults, MetricType, NgramCost, NormalizationType,
 };
 use crate::{
-    metrics::{bigram_metrics::*, layout_metrics::*, trigram_metrics::*, unigram_metrics::*},
-    ngram_mapper::NgramMapper,
+    calibration::CalibrationLoader,
+    comparison::LayoutComparison,
+    metrics::{
+        bigram_metrics::BigramMetric,
+        layout_metrics::LayoutMetric,
+        quadgram_metrics::QuadgramMetric,
+        registry::{Metric, MetricRegistry, RegistryError},
+        skipgram_metrics::SkipgramMetric,
+        tabulated::{TabulatedBigramMetric, TabulatedTrigramMetric},
+        trigram_metrics::TrigramMetric,
+        unigram_metrics::UnigramMetric,
+    },
+    ngram_mapper::{LayerKeyQuadgram, LayerKeySkipgram, NgramMapper},
 };
 
-use keyboard_layout::layout::{LayerKey, Layout};
+use keyboard_layout::{
+    keyboard::KeyIndex,
+    layout::{LayerKey, Layout},
+    layout_generator::LayoutGenerator,
+};
 
+use ahash::{AHashMap, AHashSet};
+use anyhow::Result;
+use ordered_float::OrderedFloat;
 use serde::Deserialize;
+use std::{cmp::Reverse, collections::BTreeMap};
 
 /// A wrapper around individuals metric's parameters (`T`) specifying
 /// additional generic attributes. This mostly facilitates configuration of
@@ -32,54 +54,221 @@ pub struct WeightedParams<T> {
     pub normalization: NormalizationType,
     /// The metric's individual parameters.
     pub params: T,
+    /// The registered metric to use (defaults to the name of the config entry). This allows
+    /// configuring the same metric multiple times with different parameters.
+    #[serde(default)]
+    pub metric: Option<String>,
 }
 
-/// Compiles configuration parameters for all "default" metrics available.
-/// This is usually read from a config file.
+/// Compiles configuration parameters for all configured metrics (by the name under which they are
+/// registered in a [`MetricRegistry`]). This is usually read from a config file.
 #[derive(Clone, Deserialize, Debug)]
-pub struct MetricParameters {
-    pub shortcut_keys: Option<WeightedParams<shortcut_keys::Parameters>>,
-    pub similar_letters: Option<WeightedParams<similar_letters::Parameters>>,
-    pub similar_letter_groups: Option<WeightedParams<similar_letter_groups::Parameters>>,
-
-    pub finger_balance: Option<WeightedParams<finger_balance::Parameters>>,
-    pub hand_disbalance: Option<WeightedParams<hand_disbalance::Parameters>>,
-    pub row_loads: Option<WeightedParams<row_loads::Parameters>>,
-    pub key_costs: Option<WeightedParams<key_costs::Parameters>>,
-    pub modifier_usage: Option<WeightedParams<modifier_usage::Parameters>>,
-
-    pub symmetric_handswitches: Option<WeightedParams<symmetric_handswitches::Parameters>>,
-    pub finger_repeats: Option<WeightedParams<finger_repeats::Parameters>>,
-    pub manual_bigram_penalty: Option<WeightedParams<manual_bigram_penalty::Parameters>>,
-    pub movement_pattern: Option<WeightedParams<movement_pattern::Parameters>>,
-    pub no_handswitch_after_unbalancing_key:
-        Option<WeightedParams<no_handswitch_after_unbalancing_key::Parameters>>,
-
-    pub irregularity: Option<WeightedParams<irregularity::Parameters>>,
-    pub no_handswitch_in_trigram: Option<WeightedParams<no_handswitch_in_trigram::Parameters>>,
-    pub secondary_bigrams: Option<WeightedParams<secondary_bigrams::Parameters>>,
-    pub trigram_finger_repeats: Option<WeightedParams<trigram_finger_repeats::Parameters>>,
-    pub trigram_rolls: Option<WeightedParams<trigram_rolls::Parameters>>,
-
-    pub kla_home_key_words: Option<WeightedParams<kla_home_key_words::Parameters>>,
-    pub kla_same_finger_words: Option<WeightedParams<kla_same_finger_words::Parameters>>,
-    pub kla_distance: Option<WeightedParams<kla_distance::Parameters>>,
-    pub kla_finger_usage: Option<WeightedParams<kla_finger_usage::Parameters>>,
-    pub kla_same_finger: Option<WeightedParams<kla_same_finger::Parameters>>,
-    pub kla_same_hand: Option<WeightedParams<kla_same_hand::Parameters>>,
-
-    pub oxey_combined_trigram: Option<WeightedParams<oxey_combined_trigram::Parameters>>,
-
-    pub oxey_sfbs: Option<WeightedParams<oxey_sfbs::Parameters>>,
-    pub oxey_lsbs: Option<WeightedParams<oxey_lsbs::Parameters>>,
-    pub oxey_dsfbs: Option<WeightedParams<oxey_dsfbs::Parameters>>,
-    pub oxey_inward_rolls: Option<WeightedParams<oxey_inward_rolls::Parameters>>,
-    pub oxey_outward_rolls: Option<WeightedParams<oxey_outward_rolls::Parameters>>,
-    pub oxey_onehands: Option<WeightedParams<oxey_onehands::Parameters>>,
-    pub oxey_alternates: Option<WeightedParams<oxey_alternates::Parameters>>,
-    pub oxey_alternates_sfs: Option<WeightedParams<oxey_alternates_sfs::Parameters>>,
-    pub oxey_redirects: Option<WeightedParams<oxey_redirects::Parameters>>,
-    pub oxey_bad_redirects: Option<WeightedParams<oxey_bad_redirects::Parameters>>,
+#[serde(transparent)]
+pub struct MetricParameters(pub BTreeMap<String, WeightedParams<serde_yaml::Value>>);
+
+/// Options for the level of detail of the results of [`Evaluator::evaluate_layout`]. The default
+/// options only compute the metrics' costs (as required for optimizations).
+#[derive(Clone, Debug, Default)]
+pub struct EvaluationOptions {
+    /// Number of ngrams with the highest costs to list in the metrics' messages (no messages if zero).
+    pub n_worst: usize,
+    /// Whether to provide the costs of all individual ngrams for each metric (see [`MetricResult::breakdown`]).
+    pub breakdown: bool,
+}
+
+impl EvaluationOptions {
+    /// Options for a summary of an evaluation listing the three worst ngrams of each metric.
+    pub fn summary() -> Self {
+        Self {
+            n_worst: 3,
+            breakdown: false,
+        }
+    }
+}
+
+/// Generate the [`NgramCost`] of an ngram of given [`LayerKey`]s.
+fn ngram_cost(keys: &[&LayerKey], weight: f64, cost: f64, layout: &Layout) -> NgramCost {
+    NgramCost {
+        symbols: keys.iter().map(|k| layout.layerkey_label(k)).collect(),
+        keys: keys.iter().map(|k| k.key.matrix_position).collect(),
+        weight,
+        cost,
+    }
+}
+
+/// Sort the ngram costs by descending absolute cost (NaN costs first).
+fn sort_breakdown(mut breakdown: Vec<NgramCost>) -> Vec<NgramCost> {
+    breakdown.sort_by_key(|c| Reverse(OrderedFloat(c.cost.abs())));
+    breakdown
+}
+
+/// Cached costs of each individual char-based ngram (of one type) for all metrics of the corresponding type.
+#[derive(Clone, Debug)]
+struct NgramCosts<T> {
+    /// The char-based ngrams and their weights
+    ngrams: Vec<(T, f64)>,
+    /// Indices (in `ngrams`) of all ngrams containing a given symbol
+    ngrams_with_symbol: AHashMap<char, Vec<usize>>,
+    /// Total weight of the mapped ngrams resulting from each ngram (`None` if it can not be generated by the layout)
+    mapped_weights: Vec<Option<f64>>,
+    /// The cost of each ngram (inner Vec) for each metric (outer Vec)
+    costs: Vec<Vec<f64>>,
+    /// The total cost for each metric
+    total_costs: Vec<f64>,
+    /// The total weight of all mapped ngrams
+    total_mapped_weight: f64,
+    /// The total weight of all ngrams
+    total_weight: f64,
+    /// The total weight of all ngrams that can not be generated by the layout
+    weight_not_found: f64,
+}
+
+impl<T> NgramCosts<T> {
+    /// Generate an [`NgramCosts`] object with all costs set to zero.
+    fn new(ngrams: Vec<(T, f64)>, symbols: impl Fn(&T) -> Vec<char>, n_metrics: usize) -> Self {
+        let mut ngrams_with_symbol: AHashMap<char, Vec<usize>> = AHashMap::default();
+        ngrams.iter().enumerate().for_each(|(i, (ngram, _))| {
+            let mut ngram_symbols = symbols(ngram);
+            ngram_symbols.sort_unstable();
+            ngram_symbols.dedup();
+            ngram_symbols
+                .into_iter()
+                .for_each(|c| ngrams_with_symbol.entry(c).or_default().push(i));
+        });
+
+        let n_ngrams = ngrams.len();
+        let total_weight = ngrams.iter().map(|(_, w)| w).sum();
+
+        Self {
+            ngrams,
+            ngrams_with_symbol,
+            mapped_weights: vec![Some(0.0); n_ngrams],
+            costs: vec![vec![0.0; n_ngrams]; n_metrics],
+            total_costs: vec![0.0; n_metrics],
+            total_mapped_weight: 0.0,
+            total_weight,
+            weight_not_found: 0.0,
+        }
+    }
+
+    /// Indices of all ngrams that contain at least one of the given symbols.
+    fn ngrams_with_symbols(&self, symbols: &AHashSet<char>) -> Vec<usize> {
+        let mut indices: Vec<usize> = symbols
+            .iter()
+            .filter_map(|c| self.ngrams_with_symbol.get(c))
+            .flatten()
+            .cloned()
+            .collect();
+        indices.sort_unstable();
+        indices.dedup();
+
+        indices
+    }
+
+    /// Reevaluate the ngrams with given indices. The closure `map` maps an ngram to the layout's
+    /// keys (returning `false` if the ngram can not be generated by the layout). Once all ngrams are
+    /// mapped, the closure `eval` writes the cost of an ngram's mapped ngrams for each metric into
+    /// the provided slice, given the total weight of all mapped ngrams (as in a full evaluation).
+    fn update<K, M, E>(&mut self, indices: &[usize], mut map: M, mut eval: E)
+    where
+        M: FnMut(&T, f64, &mut Vec<(K, f64)>) -> bool,
+        E: FnMut(&[(K, f64)], f64, &mut [f64]),
+    {
+        let mapped: Vec<Option<Vec<(K, f64)>>> = indices
+            .iter()
+            .map(|i| {
+                let (ngram, weight) = &self.ngrams[*i];
+                let mut grams = Vec::new();
+                let mapped_grams = if map(ngram, *weight, &mut grams) {
+                    Some(grams)
+                } else {
+                    None
+                };
+                let mapped_weight = mapped_grams
+                    .as_ref()
+                    .map(|grams| grams.iter().map(|(_, w)| w).sum());
+
+                match self.mapped_weights[*i] {
+                    Some(w) => self.total_mapped_weight -= w,
+                    None => self.weight_not_found -= weight,
+                }
+                match mapped_weight {
+                    Some(w) => self.total_mapped_weight += w,
+                    None => self.weight_not_found += weight,
+                }
+                self.mapped_weights[*i] = mapped_weight;
+
+                mapped_grams
+            })
+            .collect();
+
+        let total_mapped_weight = self.total_mapped_weight;
+        let mut costs = vec![0.0; self.total_costs.len()];
+        indices.iter().zip(mapped.iter()).for_each(|(i, grams)| {
+            costs.iter_mut().for_each(|c| *c = 0.0);
+            if let Some(grams) = grams {
+                eval(grams, total_mapped_weight, &mut costs);
+            }
+
+            self.costs
+                .iter_mut()
+                .zip(self.total_costs.iter_mut())
+                .zip(costs.iter())
+                .for_each(|((metric_costs, total_cost), cost)| {
+                    *total_cost += cost - metric_costs[*i];
+                    metric_costs[*i] = *cost;
+                });
+        });
+    }
+
+    /// Generate [`MetricResults`] from the cached total costs.
+    fn metric_results<'a>(
+        &self,
+        metric_type: MetricType,
+        metrics: impl Iterator<Item = (f64, &'a NormalizationType, &'a str)>,
+    ) -> MetricResults {
+        let mut results = MetricResults::new(
+            metric_type,
+            self.total_weight - self.weight_not_found,
+            self.weight_not_found,
+        );
+        metrics
+            .zip(self.total_costs.iter())
+            .for_each(|((weight, normalization, name), cost)| {
+                results.add_result(MetricResult {
+                    name: name.to_string(),
+                    cost: *cost,
+                    weight,
+                    normalization: normalization.clone(),
+                    message: None,
+                    breakdown: None,
+                })
+            });
+
+        results
+    }
+}
+
+/// Cached per-ngram costs of an evaluated layout. These allow for fast reevaluations of layouts that differ
+/// from it in only a few keys (see [`Evaluator::evaluate_layout_delta`]).
+///
+/// Per-ngram costs are only cached for ngram types whose metrics are all additive (see e.g.
+/// `BigramMetric::is_additive`). All other metrics are evaluated in full for each layout.
+#[derive(Clone, Debug)]
+pub struct DeltaEvaluationState {
+    layout: Layout,
+    unigrams: Option<NgramCosts<char>>,
+    bigrams: Option<NgramCosts<(char, char)>>,
+    trigrams: Option<NgramCosts<(char, char, char)>>,
+    quadgrams: Option<NgramCosts<(char, char, char, char)>>,
+    skipgrams: Option<NgramCosts<(char, char, usize)>>,
+}
+
+impl DeltaEvaluationState {
+    /// The layout that the cached costs correspond to.
+    pub fn layout(&self) -> &Layout {
+        &self.layout
+    }
 }
 
 /// The [`Evaluator`] object is responsible for evaluating multiple metrics with respect to given ngram data.
@@ -90,7 +279,11 @@ pub struct Evaluator {
     unigram_metrics: Vec<(f64, NormalizationType, Box<dyn UnigramMetric>)>,
     bigram_metrics: Vec<(f64, NormalizationType, Box<dyn BigramMetric>)>,
     trigram_metrics: Vec<(f64, NormalizationType, Box<dyn TrigramMetric>)>,
+    quadgram_metrics: Vec<(f64, NormalizationType, Box<dyn QuadgramMetric>)>,
+    skipgram_metrics: Vec<(f64, NormalizationType, Box<dyn SkipgramMetric>)>,
     ngram_mapper: Box<dyn NgramMapper>,
+    /// Generator for interpreting layout strings in metric parameters (e.g. reference layouts)
+    layout_generator: Option<Box<dyn LayoutGenerator>>,
 }
 
 impl Evaluator {
@@ -101,106 +294,81 @@ impl Evaluator {
             unigram_metrics: Vec::new(),
             bigram_metrics: Vec::new(),
             trigram_metrics: Vec::new(),
+            quadgram_metrics: Vec::new(),
+            skipgram_metrics: Vec::new(),
             ngram_mapper,
+            layout_generator: None,
         }
     }
 
-    /// Add all "default" metrics to the evaluator.
-    pub fn default_metrics(mut self, params: &MetricParameters) -> Self {
-        macro_rules! add_metric {
-            ($metric_type:ident, $metric_name:ident, $metric_struct:ident) => {
-                if let Some(p) = &params.$metric_name {
-                    if p.enabled {
-                        self.$metric_type(
-                            Box::new($metric_name::$metric_struct::new(&p.params)),
-                            p.weight,
-                            p.normalization.clone(),
-                        );
-                    }
-                }
-            };
-            ($metric_type:ident, $metric_name:ident, $metric_struct:ident, "add_bigram_metrics") => {
-                if let Some(p) = &params.$metric_name {
-                    if p.enabled {
-                        self.$metric_type(
-                            Box::new($metric_name::$metric_struct::new(
-                                self.bigram_metrics.clone(),
-                                &p.params,
-                            )),
-                            p.weight,
-                            p.normalization.clone(),
-                        );
-                    }
-                }
-            };
-        }
+    /// Set the layout generator that metrics use for interpreting layout strings in their
+    /// parameters. It needs to be set before adding such metrics.
+    pub fn with_layout_generator(mut self, layout_generator: Box<dyn LayoutGenerator>) -> Self {
+        self.layout_generator = Some(layout_generator);
+        self
+    }
 
-        // layout metrics
-        add_metric!(layout_metric, shortcut_keys, ShortcutKeys);
-        add_metric!(layout_metric, similar_letters, SimilarLetters);
-        add_metric!(layout_metric, similar_letter_groups, SimilarLetterGroups);
-
-        // unigram metrics
-        add_metric!(unigram_metric, finger_balance, FingerBalance);
-        add_metric!(unigram_metric, hand_disbalance, HandDisbalance);
-        add_metric!(unigram_metric, row_loads, RowLoads);
-        add_metric!(unigram_metric, modifier_usage, ModifierUsage);
-        add_metric!(unigram_metric, key_costs, KeyCost);
-
-        // bigram metrics
-        add_metric!(bigram_metric, finger_repeats, FingerRepeats);
-        add_metric!(bigram_metric, manual_bigram_penalty, ManualBigramPenalty);
-        add_metric!(bigram_metric, movement_pattern, MovementPattern);
-        add_metric!(
-            bigram_metric,
-            no_handswitch_after_unbalancing_key,
-            NoHandSwitchAfterUnbalancingKey
-        );
-        add_metric!(bigram_metric, symmetric_handswitches, SymmetricHandswitches);
+    /// The layout generator (if one has been set with [`Self::with_layout_generator`]).
+    pub fn layout_generator(&self) -> Option<&dyn LayoutGenerator> {
+        self.layout_generator.as_deref()
+    }
 
-        // trigram_metrics
-        add_metric!(
-            trigram_metric,
-            no_handswitch_in_trigram,
-            NoHandswitchInTrigram
-        );
-        add_metric!(trigram_metric, trigram_finger_repeats, TrigramFingerRepeats);
-        add_metric!(trigram_metric, trigram_rolls, TrigramRolls);
-        add_metric!(
-            trigram_metric,
-            irregularity,
-            Irregularity,
-            "add_bigram_metrics"
-        );
-        add_metric!(
-            trigram_metric,
-            secondary_bigrams,
-            SecondaryBigrams,
-            "add_bigram_metrics"
-        );
+    /// The ngram mapper providing the ngrams that the metrics are evaluated on.
+    pub fn ngram_mapper(&self) -> &dyn NgramMapper {
+        self.ngram_mapper.as_ref()
+    }
+
+    /// Add all configured metrics of this crate to the evaluator.
+    pub fn default_metrics(self, params: &MetricParameters) -> Result<Self> {
+        self.registered_metrics(&MetricRegistry::default(), params)
+    }
 
-        add_metric!(layout_metric, kla_same_finger_words, KLASameFingerWords);
-        add_metric!(layout_metric, kla_home_key_words, KLAHomeKeyWords);
+    /// Add all configured metrics to the evaluator using the constructors of given registry.
+    /// The metrics are added in the order of their registration. The calibration files of z-score
+    /// normalizations are read when adding the corresponding metrics.
+    pub fn registered_metrics(
+        mut self,
+        registry: &MetricRegistry,
+        params: &MetricParameters,
+    ) -> Result<Self> {
+        // the registered metric used by each config entry
+        let entries: Vec<(&str, &WeightedParams<serde_yaml::Value>)> = params
+            .0
+            .iter()
+            .map(|(name, p)| (p.metric.as_deref().unwrap_or(name), p))
+            .collect();
 
-        add_metric!(bigram_metric, kla_distance, KLADistance);
-        add_metric!(bigram_metric, kla_finger_usage, KLAFingerUsage);
-        add_metric!(bigram_metric, kla_same_finger, KLASameFinger);
-        add_metric!(bigram_metric, kla_same_hand, KLASameHand);
+        if let Some((name, _)) = entries.iter().find(|(name, _)| !registry.contains(name)) {
+            return Err(RegistryError::UnknownMetric(
+                name.to_string(),
+                registry.names().collect::<Vec<_>>().join(", "),
+            )
+            .into());
+        }
 
-        add_metric!(trigram_metric, oxey_combined_trigram, OxeyCombinedTrigram);
+        let mut calibrations = CalibrationLoader::default();
+        for (name, constructor) in registry.constructors() {
+            for (_, p) in entries.iter().filter(|(n, p)| n == name && p.enabled) {
+                let metric = constructor(&p.params, &self)?;
+                let weight = p.weight;
+                let normalization = calibrations.resolve(p.normalization.clone(), metric.name())?;
+                match metric {
+                    Metric::Layout(m) => self.layout_metric(m, weight, normalization),
+                    Metric::Unigram(m) => self.unigram_metric(m, weight, normalization),
+                    Metric::Bigram(m) => self.bigram_metric(m, weight, normalization),
+                    Metric::Trigram(m) => self.trigram_metric(m, weight, normalization),
+                    Metric::Quadgram(m) => self.quadgram_metric(m, weight, normalization),
+                    Metric::Skipgram(m) => self.skipgram_metric(m, weight, normalization),
+                }
+            }
+        }
 
-        add_metric!(bigram_metric, oxey_sfbs, OxeySfbs);
-        add_metric!(bigram_metric, oxey_lsbs, OxeyLsbs);
-        add_metric!(trigram_metric, oxey_dsfbs, OxeyDsfbs);
-        add_metric!(trigram_metric, oxey_inward_rolls, OxeyInwardRolls);
-        add_metric!(trigram_metric, oxey_outward_rolls, OxeyOutwardRolls);
-        add_metric!(trigram_metric, oxey_onehands, OxeyOnehands);
-        add_metric!(trigram_metric, oxey_alternates, OxeyAlternates);
-        add_metric!(trigram_metric, oxey_alternates_sfs, OxeyAlternatesSfs);
-        add_metric!(trigram_metric, oxey_redirects, OxeyRedirects);
-        add_metric!(trigram_metric, oxey_bad_redirects, OxeyBadRedirects);
+        Ok(self)
+    }
 
-        self
+    /// The bigram metrics that have been added to the evaluator.
+    pub fn bigram_metrics(&self) -> &[(f64, NormalizationType, Box<dyn BigramMetric>)] {
+        &self.bigram_metrics
     }
 
     /// Add a metric that operates only on the layout itself ("layout metric").
@@ -224,25 +392,57 @@ impl Evaluator {
     }
 
     /// Add a metric that operates on the bigram data ("bigram metric").
+    /// Key-based metrics are wrapped such that their costs are looked up in a precomputed table.
     pub fn bigram_metric(
         &mut self,
         metric: Box<dyn BigramMetric>,
         weight: f64,
         normalization: NormalizationType,
     ) {
+        let metric: Box<dyn BigramMetric> = if metric.is_key_based() {
+            Box::new(TabulatedBigramMetric::new(metric))
+        } else {
+            metric
+        };
         self.bigram_metrics.push((weight, normalization, metric));
     }
 
     /// Add a metric that operates on the trigram data ("trigram metric").
+    /// Key-based metrics are wrapped such that their costs are looked up in a precomputed table.
     pub fn trigram_metric(
         &mut self,
         metric: Box<dyn TrigramMetric>,
         weight: f64,
         normalization: NormalizationType,
     ) {
+        let metric: Box<dyn TrigramMetric> = if metric.is_key_based() {
+            Box::new(TabulatedTrigramMetric::new(metric))
+        } else {
+            metric
+        };
         self.trigram_metrics.push((weight, normalization, metric));
     }
 
+    /// Add a metric that operates on the quadgram data ("quadgram metric").
+    pub fn quadgram_metric(
+        &mut self,
+        metric: Box<dyn QuadgramMetric>,
+        weight: f64,
+        normalization: NormalizationType,
+    ) {
+        self.quadgram_metrics.push((weight, normalization, metric));
+    }
+
+    /// Add a metric that operates on the skipgram data ("skipgram metric").
+    pub fn skipgram_metric(
+        &mut self,
+        metric: Box<dyn SkipgramMetric>,
+        weight: f64,
+        normalization: NormalizationType,
+    ) {
+        self.skipgram_metrics.push((weight, normalization, metric));
+    }
+
     /// Evaluate all layout metrics for a layout.
     fn evaluate_layout_metrics(&self, layout: &Layout) -> Vec<MetricResult> {
         if self.layout_metrics.is_empty() {
@@ -260,6 +460,7 @@ impl Evaluator {
                     weight: *weight,
                     normalization: normalization.clone(),
                     message,
+                    breakdown: None,
                 }
             })
             .collect();
@@ -272,6 +473,7 @@ impl Evaluator {
         &self,
         layout: &Layout,
         keys: &[(&LayerKey, f64)],
+        options: &EvaluationOptions,
     ) -> Vec<MetricResult> {
         if self.unigram_metrics.is_empty() {
             return Vec::new();
@@ -282,13 +484,25 @@ impl Evaluator {
             .unigram_metrics
             .iter()
             .map(|(weight, normalization, metric)| {
-                let (cost, message) = metric.total_cost(keys, Some(total_weight), layout);
+                let (cost, message) =
+                    metric.total_cost(keys, Some(total_weight), layout, options.n_worst);
+                let breakdown = options.breakdown.then(|| {
+                    sort_breakdown(
+                        keys.iter()
+                            .filter_map(|(k, w)| {
+                                let cost = metric.individual_cost(k, *w, total_weight, layout)?;
+                                Some(ngram_cost(&[k], *w, cost, layout))
+                            })
+                            .collect(),
+                    )
+                });
                 MetricResult {
                     name: metric.name().to_string(),
                     cost,
                     weight: *weight,
                     normalization: normalization.clone(),
                     message,
+                    breakdown,
                 }
             })
             .collect();
@@ -301,6 +515,7 @@ impl Evaluator {
         &self,
         layout: &Layout,
         keys: &[((&LayerKey, &LayerKey), f64)],
+        options: &EvaluationOptions,
     ) -> Vec<MetricResult> {
         if self.bigram_metrics.is_empty() {
             return Vec::new();
@@ -311,13 +526,26 @@ impl Evaluator {
             .bigram_metrics
             .iter()
             .map(|(weight, normalization, metric)| {
-                let (cost, message) = metric.total_cost(keys, Some(total_weight), layout);
+                let (cost, message) =
+                    metric.total_cost(keys, Some(total_weight), layout, options.n_worst);
+                let breakdown = options.breakdown.then(|| {
+                    sort_breakdown(
+                        keys.iter()
+                            .filter_map(|((k1, k2), w)| {
+                                let cost =
+                                    metric.individual_cost(k1, k2, *w, total_weight, layout)?;
+                                Some(ngram_cost(&[k1, k2], *w, cost, layout))
+                            })
+                            .collect(),
+                    )
+                });
                 MetricResult {
                     name: metric.name().to_string(),
                     cost,
                     weight: *weight,
                     normalization: normalization.clone(),
                     message,
+                    breakdown,
                 }
             })
             .collect();
@@ -330,6 +558,7 @@ impl Evaluator {
         &self,
         layout: &Layout,
         keys: &[((&LayerKey, &LayerKey, &LayerKey), f64)],
+        options: &EvaluationOptions,
     ) -> Vec<MetricResult> {
         if self.trigram_metrics.is_empty() {
             return Vec::new();
@@ -340,13 +569,125 @@ impl Evaluator {
             .trigram_metrics
             .iter()
             .map(|(weight, normalization, metric)| {
-                let (cost, message) = metric.total_cost(keys, Some(total_weight), layout);
+                let (cost, message) =
+                    metric.total_cost(keys, Some(total_weight), layout, options.n_worst);
+                let breakdown = options.breakdown.then(|| {
+                    sort_breakdown(
+                        keys.iter()
+                            .filter_map(|((k1, k2, k3), w)| {
+                                let cost =
+                                    metric.individual_cost(k1, k2, k3, *w, total_weight, layout)?;
+                                Some(ngram_cost(&[k1, k2, k3], *w, cost, layout))
+                            })
+                            .collect(),
+                    )
+                });
+                MetricResult {
+                    name: metric.name().to_string(),
+                    cost,
+                    weight: *weight,
+                    normalization: normalization.clone(),
+                    message,
+                    breakdown,
+                }
+            })
+            .collect();
+
+        metric_costs
+    }
+
+    /// Evaluate all quadgram metrics for a layout.
+    fn evaluate_quadgram_metrics(
+        &self,
+        layout: &Layout,
+        keys: &[(LayerKeyQuadgram, f64)],
+        options: &EvaluationOptions,
+    ) -> Vec<MetricResult> {
+        if self.quadgram_metrics.is_empty() {
+            return Vec::new();
+        }
+
+        let total_weight = keys.iter().map(|(_, w)| w).sum();
+        let metric_costs: Vec<MetricResult> = self
+            .quadgram_metrics
+            .iter()
+            .map(|(weight, normalization, metric)| {
+                let (cost, message) =
+                    metric.total_cost(keys, Some(total_weight), layout, options.n_worst);
+                let breakdown = options.breakdown.then(|| {
+                    sort_breakdown(
+                        keys.iter()
+                            .filter_map(|((k1, k2, k3, k4), w)| {
+                                let cost = metric.individual_cost(
+                                    k1,
+                                    k2,
+                                    k3,
+                                    k4,
+                                    *w,
+                                    total_weight,
+                                    layout,
+                                )?;
+                                Some(ngram_cost(&[k1, k2, k3, k4], *w, cost, layout))
+                            })
+                            .collect(),
+                    )
+                });
+                MetricResult {
+                    name: metric.name().to_string(),
+                    cost,
+                    weight: *weight,
+                    normalization: normalization.clone(),
+                    message,
+                    breakdown,
+                }
+            })
+            .collect();
+
+        metric_costs
+    }
+
+    /// Evaluate all skipgram metrics for a layout.
+    fn evaluate_skipgram_metrics(
+        &self,
+        layout: &Layout,
+        keys: &[(LayerKeySkipgram, f64)],
+        options: &EvaluationOptions,
+    ) -> Vec<MetricResult> {
+        if self.skipgram_metrics.is_empty() {
+            return Vec::new();
+        }
+
+        let total_weight = keys.iter().map(|(_, w)| w).sum();
+        let metric_costs: Vec<MetricResult> = self
+            .skipgram_metrics
+            .iter()
+            .map(|(weight, normalization, metric)| {
+                let (cost, message) =
+                    metric.total_cost(keys, Some(total_weight), layout, options.n_worst);
+                let breakdown = options.breakdown.then(|| {
+                    sort_breakdown(
+                        keys.iter()
+                            .filter_map(|((k1, k2, skip), w)| {
+                                let cost = metric.individual_cost(
+                                    k1,
+                                    k2,
+                                    *skip,
+                                    *w,
+                                    total_weight,
+                                    layout,
+                                )?;
+                                Some(ngram_cost(&[k1, k2], *w, cost, layout))
+                            })
+                            .collect(),
+                    )
+                });
                 MetricResult {
                     name: metric.name().to_string(),
                     cost,
                     weight: *weight,
                     normalization: normalization.clone(),
                     message,
+                    breakdown,
                 }
             })
             .collect();
@@ -354,68 +695,661 @@ impl Evaluator {
         metric_costs
     }
 
-    /// Evaluate all metrics for a layout.
-    pub fn evaluate_layout(&self, layout: &Layout) -> EvaluationResult {
+    /// Evaluate all unigram metrics for a layout, mapping all unigrams.
+    fn unigram_results(&self, layout: &Layout, options: &EvaluationOptions) -> MetricResults {
+        let mapped_unigrams = self.ngram_mapper.map_unigrams(layout);
+        let metric_costs = self.evaluate_unigram_metrics(layout, &mapped_unigrams.grams, options);
+        let mut unigram_costs = MetricResults::new(
+            MetricType::Unigram,
+            mapped_unigrams.weight_found,
+            mapped_unigrams.weight_not_found,
+        );
+        metric_costs
+            .into_iter()
+            .for_each(|mc| unigram_costs.add_result(mc));
+
+        unigram_costs
+    }
+
+    /// Evaluate all bigram metrics for a layout, mapping all bigrams.
+    fn bigram_results(&self, layout: &Layout, options: &EvaluationOptions) -> MetricResults {
+        let mapped_bigrams = self.ngram_mapper.map_bigrams(layout);
+        let metric_costs = self.evaluate_bigram_metrics(layout, &mapped_bigrams.grams, options);
+        let mut bigram_costs = MetricResults::new(
+            MetricType::Bigram,
+            mapped_bigrams.weight_found,
+            mapped_bigrams.weight_not_found,
+        );
+        metric_costs
+            .into_iter()
+            .for_each(|mc| bigram_costs.add_result(mc));
+
+        bigram_costs
+    }
+
+    /// Evaluate all trigram metrics for a layout, mapping all trigrams.
+    fn trigram_results(&self, layout: &Layout, options: &EvaluationOptions) -> MetricResults {
+        let mapped_trigrams = self.ngram_mapper.map_trigrams(layout);
+        let metric_costs = self.evaluate_trigram_metrics(layout, &mapped_trigrams.grams, options);
+        let mut trigram_costs = MetricResults::new(
+            MetricType::Trigram,
+            mapped_trigrams.weight_found,
+            mapped_trigrams.weight_not_found,
+        );
+        metric_costs
+            .into_iter()
+            .for_each(|mc| trigram_costs.add_result(mc));
+
+        trigram_costs
+    }
+
+    /// Evaluate all quadgram metrics for a layout, mapping all quadgrams.
+    fn quadgram_results(&self, layout: &Layout, options: &EvaluationOptions) -> MetricResults {
+        let mapped_quadgrams = self.ngram_mapper.map_quadgrams(layout);
+        let metric_costs = self.evaluate_quadgram_metrics(layout, &mapped_quadgrams.grams, options);
+        let mut quadgram_costs = MetricResults::new(
+            MetricType::Quadgram,
+            mapped_quadgrams.weight_found,
+            mapped_quadgrams.weight_not_found,
+        );
+        metric_costs
+            .into_iter()
+            .for_each(|mc| quadgram_costs.add_result(mc));
+
+        quadgram_costs
+    }
+
+    /// Evaluate all skipgram metrics for a layout, mapping all skipgrams.
+    fn skipgram_results(&self, layout: &Layout, options: &EvaluationOptions) -> MetricResults {
+        let mapped_skipgrams = self.ngram_mapper.map_skipgrams(layout);
+        let metric_costs = self.evaluate_skipgram_metrics(layout, &mapped_skipgrams.grams, options);
+        let mut skipgram_costs = MetricResults::new(
+            MetricType::Skipgram,
+            mapped_skipgrams.weight_found,
+            mapped_skipgrams.weight_not_found,
+        );
+        metric_costs
+            .into_iter()
+            .for_each(|mc| skipgram_costs.add_result(mc));
+
+        skipgram_costs
+    }
+
+    /// Evaluate all layout metrics for a layout.
+    fn layout_results(&self, layout: &Layout) -> MetricResults {
+        let metric_costs = self.evaluate_layout_metrics(layout);
+        let mut layout_costs = MetricResults::new(MetricType::Layout, 1.0, 0.0);
+        metric_costs
+            .into_iter()
+            .for_each(|mc| layout_costs.add_result(mc));
+
+        layout_costs
+    }
+
+    /// Evaluate all metrics for a layout. The `options` determine the level of detail of the results.
+    pub fn evaluate_layout(
+        &self,
+        layout: &Layout,
+        options: &EvaluationOptions,
+    ) -> EvaluationResult {
         let mut results: Vec<MetricResults> = Vec::new();
 
         // Layout metrics
         if !self.layout_metrics.is_empty() {
-            let metric_costs = self.evaluate_layout_metrics(layout);
-            let mut layout_costs = MetricResults::new(MetricType::Layout, 1.0, 0.0);
-            metric_costs
-                .into_iter()
-                .for_each(|mc| layout_costs.add_result(mc));
-            results.push(layout_costs);
+            results.push(self.layout_results(layout));
         }
 
         // Unigram metrics
         if !self.unigram_metrics.is_empty() {
-            let mapped_unigrams = self.ngram_mapper.map_unigrams(layout);
-            let metric_costs = self.evaluate_unigram_metrics(layout, &mapped_unigrams.grams);
-            let mut unigram_costs = MetricResults::new(
-                MetricType::Unigram,
-                mapped_unigrams.weight_found,
-                mapped_unigrams.weight_not_found,
-            );
-            metric_costs
-                .into_iter()
-                .for_each(|mc| unigram_costs.add_result(mc));
-
-            results.push(unigram_costs);
+            results.push(self.unigram_results(layout, options));
         }
 
         // Bigram metrics
         if !self.bigram_metrics.is_empty() {
-            let mapped_bigrams = self.ngram_mapper.map_bigrams(layout);
-            let metric_costs = self.evaluate_bigram_metrics(layout, &mapped_bigrams.grams);
-            let mut bigram_costs = MetricResults::new(
-                MetricType::Bigram,
-                mapped_bigrams.weight_found,
-                mapped_bigrams.weight_not_found,
+            results.push(self.bigram_results(layout, options));
+        }
+
+        // Trigram metrics
+        if !self.trigram_metrics.is_empty() {
+            results.push(self.trigram_results(layout, options));
+        }
+
+        // Quadgram metrics
+        if !self.quadgram_metrics.is_empty() {
+            results.push(self.quadgram_results(layout, options));
+        }
+
+        // Skipgram metrics
+        if !self.skipgram_metrics.is_empty() {
+            results.push(self.skipgram_results(layout, options));
+        }
+
+        EvaluationResult::new(layout.as_text(), results)
+    }
+
+    /// Evaluate two layouts and compare the results (see [`LayoutComparison`]), listing the `n_ngrams`
+    /// ngrams with the largest changes of their raw costs for each metric.
+    pub fn compare_layouts(
+        &self,
+        layout_a: &Layout,
+        layout_b: &Layout,
+        n_ngrams: usize,
+    ) -> LayoutComparison {
+        let options = EvaluationOptions {
+            n_worst: 0,
+            breakdown: true,
+        };
+        let result_a = self.evaluate_layout(layout_a, &options);
+        let result_b = self.evaluate_layout(layout_b, &options);
+
+        LayoutComparison::new(layout_a, &result_a, layout_b, &result_b, n_ngrams)
+    }
+
+    /// Compute the relative load (fraction of all mapped unigrams) of each key of the keyboard.
+    /// All loads are zero if no unigrams could be mapped.
+    pub fn key_loads(&self, layout: &Layout) -> Vec<f64> {
+        let mapped_unigrams = self.ngram_mapper.map_unigrams(layout);
+        let total_weight: f64 = mapped_unigrams.grams.iter().map(|(_, w)| w).sum();
+
+        let mut loads = vec![0.0; layout.keyboard.keys.len()];
+        if total_weight <= 0.0 {
+            return loads;
+        }
+
+        mapped_unigrams.grams.iter().for_each(|(k, w)| {
+            loads[k.key.index as usize] += w / total_weight;
+        });
+
+        loads
+    }
+
+    /// Attribute the cost of the unigram, bigram, trigram, quadgram, or skipgram metric with given name to
+    /// the keys of the keyboard. The cost of a bigram (trigram, quadgram, skipgram) is split equally among
+    /// its keys. Only
+    /// metrics providing individual costs for each ngram are supported. Returns `None` if no such
+    /// metric is found.
+    pub fn key_costs(&self, layout: &Layout, metric_name: &str) -> Option<Vec<f64>> {
+        let mut costs = vec![0.0; layout.keyboard.keys.len()];
+        let mut add_cost = |keys: &[&LayerKey], cost: f64| {
+            keys.iter().for_each(|k| {
+                costs[k.key.index as usize] += cost / keys.len() as f64;
+            })
+        };
+
+        if let Some((_, _, metric)) = self
+            .unigram_metrics
+            .iter()
+            .find(|(_, _, m)| m.name() == metric_name)
+        {
+            let grams = self.ngram_mapper.map_unigrams(layout).grams;
+            let total_weight = grams.iter().map(|(_, w)| w).sum();
+            grams.iter().for_each(|(k, w)| {
+                if let Some(cost) = metric.individual_cost(k, *w, total_weight, layout) {
+                    add_cost(&[k], cost);
+                }
+            });
+        } else if let Some((_, _, metric)) = self
+            .bigram_metrics
+            .iter()
+            .find(|(_, _, m)| m.name() == metric_name)
+        {
+            let grams = self.ngram_mapper.map_bigrams(layout).grams;
+            let total_weight = grams.iter().map(|(_, w)| w).sum();
+            grams.iter().for_each(|((k1, k2), w)| {
+                if let Some(cost) = metric.individual_cost(k1, k2, *w, total_weight, layout) {
+                    add_cost(&[k1, k2], cost);
+                }
+            });
+        } else if let Some((_, _, metric)) = self
+            .trigram_metrics
+            .iter()
+            .find(|(_, _, m)| m.name() == metric_name)
+        {
+            let grams = self.ngram_mapper.map_trigrams(layout).grams;
+            let total_weight = grams.iter().map(|(_, w)| w).sum();
+            grams.iter().for_each(|((k1, k2, k3), w)| {
+                if let Some(cost) = metric.individual_cost(k1, k2, k3, *w, total_weight, layout) {
+                    add_cost(&[k1, k2, k3], cost);
+                }
+            });
+        } else if let Some((_, _, metric)) = self
+            .quadgram_metrics
+            .iter()
+            .find(|(_, _, m)| m.name() == metric_name)
+        {
+            let grams = self.ngram_mapper.map_quadgrams(layout).grams;
+            let total_weight = grams.iter().map(|(_, w)| w).sum();
+            grams.iter().for_each(|((k1, k2, k3, k4), w)| {
+                if let Some(cost) = metric.individual_cost(k1, k2, k3, k4, *w, total_weight, layout)
+                {
+                    add_cost(&[k1, k2, k3, k4], cost);
+                }
+            });
+        } else if let Some((_, _, metric)) = self
+            .skipgram_metrics
+            .iter()
+            .find(|(_, _, m)| m.name() == metric_name)
+        {
+            let grams = self.ngram_mapper.map_skipgrams(layout).grams;
+            let total_weight = grams.iter().map(|(_, w)| w).sum();
+            grams.iter().for_each(|((k1, k2, skip), w)| {
+                if let Some(cost) = metric.individual_cost(k1, k2, *skip, *w, total_weight, layout)
+                {
+                    add_cost(&[k1, k2], cost);
+                }
+            });
+        } else {
+            return None;
+        }
+
+        Some(costs)
+    }
+
+    /// Reevaluate the unigrams with given indices for all unigram metrics.
+    fn update_unigram_costs(
+        &self,
+        unigram_costs: &mut NgramCosts<char>,
+        layout: &Layout,
+        indices: &[usize],
+    ) {
+        unigram_costs.update(
+            indices,
+            |unigram, weight, grams| {
+                self.ngram_mapper
+                    .map_single_unigram(unigram, weight, layout, grams)
+            },
+            |grams, total_weight, costs| {
+                self.unigram_metrics.iter().zip(costs.iter_mut()).for_each(
+                    |((_, _, metric), cost)| {
+                        *cost = grams
+                            .iter()
+                            .filter_map(|(k, w)| {
+                                metric.individual_cost(k, *w, total_weight, layout)
+                            })
+                            .sum();
+                    },
+                );
+            },
+        );
+    }
+
+    /// Reevaluate the bigrams with given indices for all bigram metrics.
+    fn update_bigram_costs(
+        &self,
+        bigram_costs: &mut NgramCosts<(char, char)>,
+        layout: &Layout,
+        indices: &[usize],
+    ) {
+        bigram_costs.update(
+            indices,
+            |bigram, weight, grams| {
+                self.ngram_mapper
+                    .map_single_bigram(bigram, weight, layout, grams)
+            },
+            |grams, total_weight, costs| {
+                self.bigram_metrics.iter().zip(costs.iter_mut()).for_each(
+                    |((_, _, metric), cost)| {
+                        *cost = grams
+                            .iter()
+                            .filter_map(|((k1, k2), w)| {
+                                metric.individual_cost(k1, k2, *w, total_weight, layout)
+                            })
+                            .sum();
+                    },
+                );
+            },
+        );
+    }
+
+    /// Reevaluate the trigrams with given indices for all trigram metrics.
+    fn update_trigram_costs(
+        &self,
+        trigram_costs: &mut NgramCosts<(char, char, char)>,
+        layout: &Layout,
+        indices: &[usize],
+    ) {
+        trigram_costs.update(
+            indices,
+            |trigram, weight, grams| {
+                self.ngram_mapper
+                    .map_single_trigram(trigram, weight, layout, grams)
+            },
+            |grams, total_weight, costs| {
+                self.trigram_metrics.iter().zip(costs.iter_mut()).for_each(
+                    |((_, _, metric), cost)| {
+                        *cost = grams
+                            .iter()
+                            .filter_map(|((k1, k2, k3), w)| {
+                                metric.individual_cost(k1, k2, k3, *w, total_weight, layout)
+                            })
+                            .sum();
+                    },
+                );
+            },
+        );
+    }
+
+    /// Reevaluate the quadgrams with given indices for all quadgram metrics.
+    fn update_quadgram_costs(
+        &self,
+        quadgram_costs: &mut NgramCosts<(char, char, char, char)>,
+        layout: &Layout,
+        indices: &[usize],
+    ) {
+        quadgram_costs.update(
+            indices,
+            |quadgram, weight, grams| {
+                self.ngram_mapper
+                    .map_single_quadgram(quadgram, weight, layout, grams)
+            },
+            |grams, total_weight, costs| {
+                self.quadgram_metrics.iter().zip(costs.iter_mut()).for_each(
+                    |((_, _, metric), cost)| {
+                        *cost = grams
+                            .iter()
+                            .filter_map(|((k1, k2, k3, k4), w)| {
+                                metric.individual_cost(k1, k2, k3, k4, *w, total_weight, layout)
+                            })
+                            .sum();
+                    },
+                );
+            },
+        );
+    }
+
+    /// Reevaluate the skipgrams with given indices for all skipgram metrics.
+    fn update_skipgram_costs(
+        &self,
+        skipgram_costs: &mut NgramCosts<(char, char, usize)>,
+        layout: &Layout,
+        indices: &[usize],
+    ) {
+        skipgram_costs.update(
+            indices,
+            |(c1, c2, skip), weight, grams| {
+                self.ngram_mapper
+                    .map_single_skipgram(&(*c1, *c2), *skip, weight, layout, grams)
+            },
+            |grams, total_weight, costs| {
+                self.skipgram_metrics.iter().zip(costs.iter_mut()).for_each(
+                    |((_, _, metric), cost)| {
+                        *cost = grams
+                            .iter()
+                            .filter_map(|((k1, k2, skip), w)| {
+                                metric.individual_cost(k1, k2, *skip, *w, total_weight, layout)
+                            })
+                            .sum();
+                    },
+                );
+            },
+        );
+    }
+
+    /// Evaluate a layout, caching the costs of each individual ngram for subsequent calls of
+    /// [`Self::evaluate_layout_delta`]. This takes longer than [`Self::evaluate_layout`].
+    pub fn delta_evaluation_state(&self, layout: &Layout) -> DeltaEvaluationState {
+        let unigrams = (!self.unigram_metrics.is_empty()
+            && self.unigram_metrics.iter().all(|(_, _, m)| m.is_additive()))
+        .then(|| {
+            let ngrams = self.ngram_mapper.unigrams().grams.clone().into_iter();
+            let mut unigram_costs =
+                NgramCosts::new(ngrams.collect(), |c| vec![*c], self.unigram_metrics.len());
+            let indices: Vec<usize> = (0..unigram_costs.ngrams.len()).collect();
+            self.update_unigram_costs(&mut unigram_costs, layout, &indices);
+
+            unigram_costs
+        });
+
+        let bigrams = (!self.bigram_metrics.is_empty()
+            && self.bigram_metrics.iter().all(|(_, _, m)| m.is_additive()))
+        .then(|| {
+            let ngrams = self.ngram_mapper.bigrams().grams.clone().into_iter();
+            let mut bigram_costs = NgramCosts::new(
+                ngrams.collect(),
+                |(c1, c2)| vec![*c1, *c2],
+                self.bigram_metrics.len(),
             );
-            metric_costs
-                .into_iter()
-                .for_each(|mc| bigram_costs.add_result(mc));
+            let indices: Vec<usize> = (0..bigram_costs.ngrams.len()).collect();
+            self.update_bigram_costs(&mut bigram_costs, layout, &indices);
+
+            bigram_costs
+        });
+
+        let trigrams = (!self.trigram_metrics.is_empty()
+            && self.trigram_metrics.iter().all(|(_, _, m)| m.is_additive()))
+        .then(|| {
+            let ngrams = self.ngram_mapper.trigrams().grams.clone().into_iter();
+            let mut trigram_costs = NgramCosts::new(
+                ngrams.collect(),
+                |(c1, c2, c3)| vec![*c1, *c2, *c3],
+                self.trigram_metrics.len(),
+            );
+            let indices: Vec<usize> = (0..trigram_costs.ngrams.len()).collect();
+            self.update_trigram_costs(&mut trigram_costs, layout, &indices);
+
+            trigram_costs
+        });
+
+        let quadgrams = (!self.quadgram_metrics.is_empty()
+            && self
+                .quadgram_metrics
+                .iter()
+                .all(|(_, _, m)| m.is_additive()))
+        .then(|| {
+            let ngrams = self.ngram_mapper.quadgrams().grams.clone().into_iter();
+            let mut quadgram_costs = NgramCosts::new(
+                ngrams.collect(),
+                |(c1, c2, c3, c4)| vec![*c1, *c2, *c3, *c4],
+                self.quadgram_metrics.len(),
+            );
+            let indices: Vec<usize> = (0..quadgram_costs.ngrams.len()).collect();
+            self.update_quadgram_costs(&mut quadgram_costs, layout, &indices);
+
+            quadgram_costs
+        });
+
+        let skipgrams = (!self.skipgram_metrics.is_empty()
+            && self
+                .skipgram_metrics
+                .iter()
+                .all(|(_, _, m)| m.is_additive()))
+        .then(|| {
+            let ngrams = self.ngram_mapper.skipgrams().iter().flat_map(|s| {
+                s.bigrams
+                    .grams
+                    .iter()
+                    .map(move |((c1, c2), w)| ((*c1, *c2, s.skip), *w))
+            });
+            let mut skipgram_costs = NgramCosts::new(
+                ngrams.collect(),
+                |(c1, c2, _)| vec![*c1, *c2],
+                self.skipgram_metrics.len(),
+            );
+            let indices: Vec<usize> = (0..skipgram_costs.ngrams.len()).collect();
+            self.update_skipgram_costs(&mut skipgram_costs, layout, &indices);
+
+            skipgram_costs
+        });
+
+        DeltaEvaluationState {
+            layout: layout.clone(),
+            unigrams,
+            bigrams,
+            trigrams,
+            quadgrams,
+            skipgrams,
+        }
+    }
+
+    /// Evaluate a layout that differs from the `state`'s layout only in the given keys (e.g. after swapping them).
+    /// For ngram types with only additive metrics, only those ngrams that involve symbols of these keys are
+    /// reevaluated. All other metrics are evaluated as in [`Self::evaluate_layout`]. Afterwards, the `state`
+    /// corresponds to the new layout.
+    ///
+    /// The `state` needs to be generated with [`Self::delta_evaluation_state`] of the same evaluator.
+    /// Note that the resulting metric costs do not contain messages about individual ngrams.
+    pub fn evaluate_layout_delta(
+        &self,
+        state: &mut DeltaEvaluationState,
+        layout: &Layout,
+        changed_keys: &[KeyIndex],
+    ) -> EvaluationResult {
+        if changed_keys
+            .iter()
+            .any(|k| state.layout.has_modifier_at_key(*k) || layout.has_modifier_at_key(*k))
+        {
+            // moving a modifier affects all symbols of the corresponding layer -> reevaluate all ngrams
+            *state = self.delta_evaluation_state(layout);
+        } else if !changed_keys.is_empty() {
+            // all symbols of a changed key are affected (even those on unchanged layers), because
+            // higher-layer symbols are resolved to the key's base-layer symbol
+            // (the same holds for chords and dead keys involving the key)
+            let symbols: AHashSet<char> = changed_keys
+                .iter()
+                .flat_map(|k| {
+                    let old_layerkeys = state.layout.get_layerkey_indices_for_key(*k).iter();
+                    let new_layerkeys = layout.get_layerkey_indices_for_key(*k).iter();
+                    let composite_layerkeys = layout
+                        .get_composite_layerkey_indices_for_key(*k)
+                        .into_iter()
+                        .chain(state.layout.get_composite_layerkey_indices_for_key(*k));
+                    old_layerkeys
+                        .map(|idx| state.layout.get_layerkey(idx).symbol)
+                        .chain(new_layerkeys.map(|idx| layout.get_layerkey(idx).symbol))
+                        .chain(composite_layerkeys.map(|idx| layout.get_layerkey(&idx).symbol))
+                })
+                .collect();
+
+            if let Some(unigram_costs) = &mut state.unigrams {
+                let indices = unigram_costs.ngrams_with_symbols(&symbols);
+                self.update_unigram_costs(unigram_costs, layout, &indices);
+            }
+
+            if let Some(bigram_costs) = &mut state.bigrams {
+                let indices = bigram_costs.ngrams_with_symbols(&symbols);
+                self.update_bigram_costs(bigram_costs, layout, &indices);
+            }
+
+            if let Some(trigram_costs) = &mut state.trigrams {
+                let indices = trigram_costs.ngrams_with_symbols(&symbols);
+                self.update_trigram_costs(trigram_costs, layout, &indices);
+            }
+
+            if let Some(quadgram_costs) = &mut state.quadgrams {
+                let indices = quadgram_costs.ngrams_with_symbols(&symbols);
+                self.update_quadgram_costs(quadgram_costs, layout, &indices);
+            }
+
+            if let Some(skipgram_costs) = &mut state.skipgrams {
+                let indices = skipgram_costs.ngrams_with_symbols(&symbols);
+                self.update_skipgram_costs(skipgram_costs, layout, &indices);
+            }
+
+            state.layout = layout.clone();
+        }
+
+        let mut results: Vec<MetricResults> = Vec::new();
+
+        // Layout metrics
+        if !self.layout_metrics.is_empty() {
+            results.push(self.layout_results(layout));
+        }
+
+        // Unigram metrics
+        if !self.unigram_metrics.is_empty() {
+            results.push(match &state.unigrams {
+                Some(unigram_costs) => unigram_costs.metric_results(
+                    MetricType::Unigram,
+                    self.unigram_metrics
+                        .iter()
+                        .map(|(w, n, m)| (*w, n, m.name())),
+                ),
+                None => self.unigram_results(layout, &EvaluationOptions::default()),
+            });
+        }
 
-            results.push(bigram_costs);
+        // Bigram metrics
+        if !self.bigram_metrics.is_empty() {
+            results.push(match &state.bigrams {
+                Some(bigram_costs) => bigram_costs.metric_results(
+                    MetricType::Bigram,
+                    self.bigram_metrics
+                        .iter()
+                        .map(|(w, n, m)| (*w, n, m.name())),
+                ),
+                None => self.bigram_results(layout, &EvaluationOptions::default()),
+            });
         }
 
         // Trigram metrics
         if !self.trigram_metrics.is_empty() {
-            let mapped_trigrams = self.ngram_mapper.map_trigrams(layout);
-            let metric_costs = self.evaluate_trigram_metrics(layout, &mapped_trigrams.grams);
-            let mut trigram_costs = MetricResults::new(
-                MetricType::Trigram,
-                mapped_trigrams.weight_found,
-                mapped_trigrams.weight_not_found,
-            );
-            metric_costs
-                .into_iter()
-                .for_each(|mc| trigram_costs.add_result(mc));
+            results.push(match &state.trigrams {
+                Some(trigram_costs) => trigram_costs.metric_results(
+                    MetricType::Trigram,
+                    self.trigram_metrics
+                        .iter()
+                        .map(|(w, n, m)| (*w, n, m.name())),
+                ),
+                None => self.trigram_results(layout, &EvaluationOptions::default()),
+            });
+        }
+
+        // Quadgram metrics
+        if !self.quadgram_metrics.is_empty() {
+            results.push(match &state.quadgrams {
+                Some(quadgram_costs) => quadgram_costs.metric_results(
+                    MetricType::Quadgram,
+                    self.quadgram_metrics
+                        .iter()
+                        .map(|(w, n, m)| (*w, n, m.name())),
+                ),
+                None => self.quadgram_results(layout, &EvaluationOptions::default()),
+            });
+        }
 
-            results.push(trigram_costs);
+        // Skipgram metrics
+        if !self.skipgram_metrics.is_empty() {
+            results.push(match &state.skipgrams {
+                Some(skipgram_costs) => skipgram_costs.metric_results(
+                    MetricType::Skipgram,
+                    self.skipgram_metrics
+                        .iter()
+                        .map(|(w, n, m)| (*w, n, m.name())),
+                ),
+                None => self.skipgram_results(layout, &EvaluationOptions::default()),
+            });
         }
 
         EvaluationResult::new(layout.as_text(), results)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn breakdown_entry(symbols: &str, cost: f64) -> NgramCost {
+        NgramCost {
+            symbols: symbols.chars().map(|c| c.to_string()).collect(),
+            keys: Vec::new(),
+            weight: 1.0,
+            cost,
+        }
+    }
+
+    #[test]
+    fn sort_breakdown_by_absolute_cost_with_nan() {
+        let breakdown = vec![
+            breakdown_entry("ab", 1.0),
+            breakdown_entry("cd", f64::NAN),
+            breakdown_entry("ef", -3.0),
+            breakdown_entry("gh", 2.0),
+        ];
+
+        let symbols: Vec<String> = sort_breakdown(breakdown)
+            .iter()
+            .map(|c| c.symbols.concat())
+            .collect();
+        assert_eq!(symbols, vec!["cd", "ef", "gh", "ab"]);
+    }
+}
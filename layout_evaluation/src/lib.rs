@@ -1,10 +1,17 @@
 pub mod cache;
+pub mod calibration;
+pub mod comparison;
 pub mod config;
 pub mod evaluation;
 pub mod metrics;
 pub mod ngram_mapper;
 pub mod ngrams;
+pub mod objectives;
 pub mod results;
+pub mod sensitivity;
+
+#[cfg(test)]
+mod test_utils;
 
 #[cfg(test)]
 mod tests {
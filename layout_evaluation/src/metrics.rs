@@ -1,6 +1,12 @@
-//! The `metrics` module provides traits for layout, unigram, bigram, and trigram metrics.
+//! The `metrics` module provides traits for layout, unigram, bigram, trigram, quadgram, and skipgram
+//! metrics.
 
 pub mod bigram_metrics;
+pub mod expression;
 pub mod layout_metrics;
+pub mod quadgram_metrics;
+pub mod registry;
+pub mod skipgram_metrics;
+pub mod tabulated;
 pub mod trigram_metrics;
 pub mod unigram_metrics;
@@ -3,9 +3,10 @@ use keyboard_layout::layout::{LayerKey, Layout};
 
 use ordered_float::OrderedFloat;
 use priority_queue::DoublePriorityQueue;
-use std::{env, fmt};
+use std::fmt;
 
 pub mod finger_repeats;
+pub mod hold_tap_misfires;
 pub mod kla_distance;
 pub mod kla_finger_usage;
 pub mod kla_same_finger;
@@ -15,7 +16,10 @@ pub mod movement_pattern;
 pub mod no_handswitch_after_unbalancing_key;
 pub mod oxey_lsbs;
 pub mod oxey_sfbs;
+pub mod scissors;
+pub mod scripted_bigram;
 pub mod symmetric_handswitches;
+pub mod typing_time;
 
 /// BigramMetric is a trait for metrics that iterates over weighted bigrams.
 pub trait BigramMetric: Send + Sync + BigramMetricClone + fmt::Debug {
@@ -35,23 +39,32 @@ pub trait BigramMetric: Send + Sync + BigramMetricClone + fmt::Debug {
         None
     }
 
-    /// Compute the total cost for the metric.
+    /// Whether the total cost is the sum of the individual costs of all bigrams and each individual cost
+    /// is proportional to the bigram's weight (independently of all other bigrams). Only such metrics
+    /// can be evaluated incrementally for small changes of a layout (see `Evaluator::evaluate_layout_delta`).
+    fn is_additive(&self) -> bool {
+        false
+    }
+
+    /// Whether the cost of a bigram of non-modifier keys from the base layer only depends on the keys
+    /// (not on the symbols or the total weight) and is proportional to the bigram's weight. The costs
+    /// of such metrics are precomputed for all combinations of keys by the `Evaluator`, such that
+    /// evaluating a bigram is a table lookup (see the `tabulated` module). This requires the default
+    /// implementation of `total_cost`.
+    fn is_key_based(&self) -> bool {
+        false
+    }
+
+    /// Compute the total cost for the metric. The optional message lists the `n_worst` bigrams with the
+    /// highest costs (no message is generated if `n_worst` is zero).
     fn total_cost(
         &self,
         bigrams: &[((&LayerKey, &LayerKey), f64)],
         // total_weight is optional for performance reasons (it can be computed from bigrams).
         total_weight: Option<f64>,
         layout: &Layout,
+        n_worst: usize,
     ) -> (f64, Option<String>) {
-        let show_worst: bool = env::var("SHOW_WORST")
-            .ok()
-            .and_then(|s| s.parse().ok())
-            .unwrap_or(true);
-        let n_worst: usize = env::var("N_WORST")
-            .ok()
-            .and_then(|s| s.parse().ok())
-            .unwrap_or(3);
-
         let total_weight = total_weight.unwrap_or_else(|| bigrams.iter().map(|(_, w)| w).sum());
         let cost_iter = bigrams
             .iter()
@@ -63,7 +76,7 @@ pub trait BigramMetric: Send + Sync + BigramMetricClone + fmt::Debug {
                 cost_option.map(|cost| (i, bigram, cost))
             });
 
-        let (total_cost, msg) = if show_worst {
+        let (total_cost, msg) = if n_worst > 0 {
             let (total_cost, worst, worst_nonfixed) = cost_iter.fold(
                 (0.0, DoublePriorityQueue::new(), DoublePriorityQueue::new()),
                 |(mut total_cost, mut worst, mut worst_nonfixed), (i, bigram, cost)| {
@@ -94,8 +107,8 @@ pub trait BigramMetric: Send + Sync + BigramMetricClone + fmt::Debug {
                         let (gram, _) = bigrams[i];
                         format!(
                             "{}{} ({:>5.2}%)",
-                            gram.0,
-                            gram.1,
+                            layout.layerkey_label(gram.0),
+                            layout.layerkey_label(gram.1),
                             100.0 * cost.into_inner() / total_cost,
                         )
                     })
@@ -3,6 +3,9 @@
 //! with a configurable factor (usually lessening the cost).
 //!
 //! *Note:* In contrast to ArneBab's version of the metric, thumbs are excluded.
+//!
+//! *Note:* Chorded symbols are split into bigrams of their constituent keys by the ngram mapper.
+//! Therefore, a chord that requires the same finger for two of its keys incurs a finger repeat.
 
 use super::BigramMetric;
 
@@ -49,6 +52,14 @@ impl BigramMetric for FingerRepeats {
         "Finger Repeats"
     }
 
+    fn is_additive(&self) -> bool {
+        true
+    }
+
+    fn is_key_based(&self) -> bool {
+        true
+    }
+
     #[inline(always)]
     fn individual_cost(
         &self,
@@ -0,0 +1,83 @@
+//! The bigram metric [`HoldTapMisfires`] estimates the risk of "misfiring" hold-tap keys (e.g.
+//! home-row mods): If a key that also serves as a hold modifier is tapped and quickly followed
+//! by another key of the same hand, the first key may still be held down when the second one is
+//! pressed. The firmware then interprets the key as a modifier instead of its symbol.
+//!
+//! The cost of such a bigram is its weight multiplied with a factor for the pair of fingers
+//! involved, which expresses how fast the roll between the fingers is typically typed (the faster
+//! the roll, the higher the risk of overlapping keystrokes). Same-finger bigrams are excluded,
+//! because the finger has to release the first key before pressing the second one.
+
+use super::BigramMetric;
+
+use keyboard_layout::{
+    key::Finger,
+    layout::{LayerKey, Layout},
+};
+
+use serde::Deserialize;
+
+#[derive(Copy, Clone, Deserialize, Debug)]
+pub struct FingerPairFactor {
+    /// The pair of fingers (in any order)
+    pub fingers: (Finger, Finger),
+    pub factor: f64,
+}
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct Parameters {
+    /// "Roll speed" factor for specific pairs of fingers
+    pub roll_speeds: Vec<FingerPairFactor>,
+    /// "Roll speed" factor for pairs of fingers that are not listed in `roll_speeds`
+    pub default_roll_speed: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct HoldTapMisfires {
+    /// Factor for each pair of fingers (indexed by the fingers' numbers)
+    roll_speeds: [[f64; 5]; 5],
+}
+
+impl HoldTapMisfires {
+    pub fn new(params: &Parameters) -> Self {
+        let mut roll_speeds = [[params.default_roll_speed; 5]; 5];
+        for fpf in params.roll_speeds.iter() {
+            let (f1, f2) = fpf.fingers;
+            roll_speeds[f1 as usize][f2 as usize] = fpf.factor;
+            roll_speeds[f2 as usize][f1 as usize] = fpf.factor;
+        }
+
+        Self { roll_speeds }
+    }
+}
+
+impl BigramMetric for HoldTapMisfires {
+    fn name(&self) -> &str {
+        "Hold-Tap Misfires"
+    }
+
+    fn is_additive(&self) -> bool {
+        true
+    }
+
+    #[inline(always)]
+    fn individual_cost(
+        &self,
+        k1: &LayerKey,
+        k2: &LayerKey,
+        weight: f64,
+        _total_weight: f64,
+        layout: &Layout,
+    ) -> Option<f64> {
+        // only taps of hold-tap keys can misfire (holding the modifier is intended)
+        if k1.is_modifier.is_some() || !layout.is_hold_modifier_key(k1.key.index) {
+            return Some(0.0);
+        }
+
+        if k1.key.hand != k2.key.hand || k1.key.finger == k2.key.finger {
+            return Some(0.0);
+        }
+
+        Some(weight * self.roll_speeds[k1.key.finger as usize][k2.key.finger as usize])
+    }
+}
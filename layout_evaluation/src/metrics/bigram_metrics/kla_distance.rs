@@ -66,7 +66,7 @@ impl<'a> FingerStates<'a> {
     }
 
     #[inline(always)]
-    pub fn iter(&self) -> slice::Iter<'_, KeyUsage> {
+    pub fn iter(&self) -> slice::Iter<'_, KeyUsage<'_>> {
         self.0.iter()
     }
 }
@@ -81,6 +81,7 @@ impl BigramMetric for KLADistance {
         bigrams: &[((&LayerKey, &LayerKey), f64)],
         _total_weight: Option<f64>,
         layout: &Layout,
+        _n_worst: usize,
     ) -> (f64, Option<String>) {
         let mut finger_values: HandFingerMap<f64> = HandFingerMap::with_default(0.0);
 
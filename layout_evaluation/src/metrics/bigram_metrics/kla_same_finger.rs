@@ -42,6 +42,7 @@ impl BigramMetric for KLASameFinger {
         bigrams: &[((&LayerKey, &LayerKey), f64)],
         _total_weight: Option<f64>,
         layout: &Layout,
+        _n_worst: usize,
     ) -> (f64, Option<String>) {
         let mut finger_values: HandFingerMap<f64> = HandFingerMap::with_default(0.0);
 
@@ -42,6 +42,7 @@ impl BigramMetric for KLASameHand {
         bigrams: &[((&LayerKey, &LayerKey), f64)],
         _total_weight: Option<f64>,
         layout: &Layout,
+        _n_worst: usize,
     ) -> (f64, Option<String>) {
         let mut hand_values: HandMap<f64> = HandMap::with_default(0.0);
 
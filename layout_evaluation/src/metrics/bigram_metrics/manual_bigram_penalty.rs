@@ -45,6 +45,14 @@ impl BigramMetric for ManualBigramPenalty {
         "Manual Bigram Penalty"
     }
 
+    fn is_additive(&self) -> bool {
+        true
+    }
+
+    fn is_key_based(&self) -> bool {
+        true
+    }
+
     #[inline(always)]
     fn individual_cost(
         &self,
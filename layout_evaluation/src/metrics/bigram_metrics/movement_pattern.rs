@@ -67,6 +67,14 @@ impl BigramMetric for MovementPattern {
         "Movement Pattern"
     }
 
+    fn is_additive(&self) -> bool {
+        true
+    }
+
+    fn is_key_based(&self) -> bool {
+        true
+    }
+
     #[inline(always)]
     fn individual_cost(
         &self,
@@ -29,6 +29,14 @@ impl BigramMetric for NoHandSwitchAfterUnbalancingKey {
         "No Handswitch After Unbalancing Key"
     }
 
+    fn is_additive(&self) -> bool {
+        true
+    }
+
+    fn is_key_based(&self) -> bool {
+        true
+    }
+
     #[inline(always)]
     fn individual_cost(
         &self,
@@ -37,6 +37,15 @@ impl BigramMetric for OxeyLsbs {
         "Lsbs"
     }
 
+    fn is_additive(&self) -> bool {
+        true
+    }
+
+    fn is_key_based(&self) -> bool {
+        // the costs depend on the symbols if some of them are excluded
+        self.exclude_chars.is_empty()
+    }
+
     #[inline(always)]
     fn individual_cost(
         &self,
@@ -0,0 +1,148 @@
+//! The bigram metric [`Scissors`] puts cost on "scissors": bigrams on adjacent fingers of the same
+//! hand where one finger reaches up and the other one down. A "full scissor" spans (at least) two
+//! rows, e.g. from the top to the bottom row, a "half scissor" spans one row. The cost depends on
+//! the pair of fingers involved.
+//!
+//! By default, rows are given by the keys' matrix positions. Optionally, the vertical distance of
+//! the keys' (physical) positions can be used instead, which accounts for the column stagger of
+//! boards like the Corne (`crkbd.yml`) or the Moonlander.
+
+use super::BigramMetric;
+
+use keyboard_layout::{
+    key::{Finger, FingerMap},
+    layout::{LayerKey, Layout},
+};
+
+use anyhow::{bail, Result};
+use serde::Deserialize;
+
+#[derive(Copy, Clone, Deserialize, Debug)]
+pub struct FingerPairCost {
+    /// The pair of adjacent fingers (in any order)
+    pub fingers: (Finger, Finger),
+    pub cost: f64,
+}
+
+#[derive(Copy, Clone, Deserialize, Debug)]
+pub struct GeometryParameters {
+    /// Height of a row in units of the keyboard's key positions
+    pub row_height: f64,
+    /// Minimal vertical distance (in rows) of a half scissor
+    pub half_scissor_threshold: f64,
+    /// Minimal vertical distance (in rows) of a full scissor
+    pub full_scissor_threshold: f64,
+}
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct Parameters {
+    /// Cost for scissors on each pair of adjacent fingers (pairs not listed incur no cost)
+    pub finger_pair_costs: Vec<FingerPairCost>,
+    pub full_scissor_factor: f64,
+    pub half_scissor_factor: f64,
+    /// If given, use the vertical distance of the keys' positions instead of their matrix rows
+    pub geometry: Option<GeometryParameters>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Scissors {
+    /// Cost for each pair of adjacent fingers, indexed by the finger closer to the thumb
+    finger_pair_costs: FingerMap<f64>,
+    full_scissor_factor: f64,
+    half_scissor_factor: f64,
+    geometry: Option<GeometryParameters>,
+}
+
+impl Scissors {
+    pub fn new(params: &Parameters) -> Result<Self> {
+        let mut finger_pair_costs = FingerMap::with_default(0.0);
+        for fpc in params.finger_pair_costs.iter() {
+            let (f1, f2) = fpc.fingers;
+            if f1.distance(&f2) != 1 {
+                bail!("Fingers {:?} and {:?} are not adjacent", f1, f2);
+            }
+            finger_pair_costs.set(&Self::inner_finger(f1, f2), fpc.cost);
+        }
+
+        Ok(Self {
+            finger_pair_costs,
+            full_scissor_factor: params.full_scissor_factor,
+            half_scissor_factor: params.half_scissor_factor,
+            geometry: params.geometry,
+        })
+    }
+
+    /// The finger of the pair that is closer to the thumb.
+    #[inline(always)]
+    fn inner_finger(f1: Finger, f2: Finger) -> Finger {
+        if (f1 as u8) < (f2 as u8) {
+            f1
+        } else {
+            f2
+        }
+    }
+
+    /// The factor for the scissor (full or half) formed by the keys (zero if it is none).
+    #[inline(always)]
+    fn scissor_factor(&self, k1: &LayerKey, k2: &LayerKey) -> f64 {
+        let (is_full, is_half) = match &self.geometry {
+            Some(geometry) => {
+                let rows = (k1.key.position.1 - k2.key.position.1).abs() / geometry.row_height;
+                (
+                    rows >= geometry.full_scissor_threshold,
+                    rows >= geometry.half_scissor_threshold,
+                )
+            }
+            None => {
+                let rows = k1.key.matrix_position.1.abs_diff(k2.key.matrix_position.1);
+                (rows >= 2, rows == 1)
+            }
+        };
+
+        if is_full {
+            self.full_scissor_factor
+        } else if is_half {
+            self.half_scissor_factor
+        } else {
+            0.0
+        }
+    }
+}
+
+impl BigramMetric for Scissors {
+    fn name(&self) -> &str {
+        "Scissors"
+    }
+
+    fn is_additive(&self) -> bool {
+        true
+    }
+
+    fn is_key_based(&self) -> bool {
+        true
+    }
+
+    #[inline(always)]
+    fn individual_cost(
+        &self,
+        k1: &LayerKey,
+        k2: &LayerKey,
+        weight: f64,
+        _total_weight: f64,
+        _layout: &Layout,
+    ) -> Option<f64> {
+        let f1 = k1.key.finger;
+        let f2 = k2.key.finger;
+
+        if k1.key.hand != k2.key.hand || f1.distance(&f2) != 1 {
+            return Some(0.0);
+        }
+
+        let cost = self.finger_pair_costs.get(&Self::inner_finger(f1, f2));
+        if *cost == 0.0 {
+            return Some(0.0);
+        }
+
+        Some(weight * cost * self.scissor_factor(k1, k2))
+    }
+}
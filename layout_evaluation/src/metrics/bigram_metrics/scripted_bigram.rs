@@ -0,0 +1,68 @@
+//! The bigram metric [`ScriptedBigram`] computes the cost of each bigram with an expression
+//! given in the evaluation config (see the [`expression`](crate::metrics::expression) module).
+//! This allows trying new cost ideas without implementing a new metric.
+//! The keys of the bigram are available as `k1` and `k2` and the resulting cost is multiplied
+//! with the bigram's weight.
+//!
+//! *Note:* The metric can be configured multiple times (with `metric: scripted_bigram` in the
+//! config entry).
+
+use super::BigramMetric;
+use crate::metrics::expression::Expression;
+
+use keyboard_layout::layout::{LayerKey, Layout};
+
+use anyhow::Result;
+use serde::Deserialize;
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct Parameters {
+    /// Name of the metric
+    pub name: String,
+    /// Expression for the cost of a bigram
+    pub expression: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct ScriptedBigram {
+    name: String,
+    expression: Expression,
+}
+
+impl ScriptedBigram {
+    pub fn new(params: &Parameters) -> Result<Self> {
+        let expression: Expression = params.expression.parse()?;
+        expression.check_n_keys(2)?;
+
+        Ok(Self {
+            name: params.name.clone(),
+            expression,
+        })
+    }
+}
+
+impl BigramMetric for ScriptedBigram {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn is_additive(&self) -> bool {
+        true
+    }
+
+    fn is_key_based(&self) -> bool {
+        true
+    }
+
+    #[inline(always)]
+    fn individual_cost(
+        &self,
+        k1: &LayerKey,
+        k2: &LayerKey,
+        weight: f64,
+        _total_weight: f64,
+        _layout: &Layout,
+    ) -> Option<f64> {
+        Some(weight * self.expression.eval(&[k1, k2]))
+    }
+}
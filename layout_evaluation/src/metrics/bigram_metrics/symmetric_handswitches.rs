@@ -30,6 +30,14 @@ impl BigramMetric for SymmetricHandswitches {
         "Symmetric Handswitches"
     }
 
+    fn is_additive(&self) -> bool {
+        true
+    }
+
+    fn is_key_based(&self) -> bool {
+        true
+    }
+
     #[inline(always)]
     fn individual_cost(
         &self,
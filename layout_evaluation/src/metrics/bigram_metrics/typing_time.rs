@@ -0,0 +1,181 @@
+//! The bigram metric [`TypingTime`] estimates the time (in milliseconds) between two consecutive
+//! keystrokes with a Fitts-style model: The interval consists of a base time depending on the
+//! kind of transition (same key, same finger, same hand, or hand alternation) and a movement time
+//! that grows logarithmically with the distance the finger of the second key has to travel
+//! (scaled by a finger-individual speed constant). For a same-finger bigram, the finger travels
+//! from the first key, otherwise it travels from its resting position. When alternating hands,
+//! only a fraction of the movement time counts, because the other hand moves in parallel.
+//!
+//! The message reports the estimated typing speed in words per minute (assuming a fixed number of
+//! keystrokes per word) and the share of the total time that each finger is busy.
+//!
+//! *Note:* With modifier splitting, the keystrokes of modifiers are part of the bigrams, i.e. the
+//! estimated speed accounts for the time of pressing modifiers.
+
+use super::BigramMetric;
+
+use ahash::AHashMap;
+use anyhow::{bail, Result};
+use keyboard_layout::{
+    key::{Finger, FingerMap, Hand, HandFingerMap},
+    layout::{LayerKey, Layout},
+};
+
+use serde::Deserialize;
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct Parameters {
+    /// Movement time (in ms) per unit of difficulty (log2(1 + distance / key_width)) for each finger
+    /// (all five fingers are required)
+    pub finger_speeds: AHashMap<Finger, f64>,
+    /// Base interval (in ms) for repeating the same key
+    pub same_key_interval: f64,
+    /// Base interval (in ms) for different keys hit by the same finger
+    pub same_finger_interval: f64,
+    /// Base interval (in ms) for keys hit by different fingers of the same hand
+    pub same_hand_interval: f64,
+    /// Base interval (in ms) for keys hit by different hands
+    pub alternating_interval: f64,
+    /// Fraction of the movement time that counts when alternating hands
+    pub alternating_overlap: f64,
+    /// Width of a key in units of the keyboard's key positions (e.g. 50 for the keyboards of this repository)
+    pub key_width: f64,
+    /// Number of keystrokes per word for computing the words per minute
+    pub chars_per_word: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct TypingTime {
+    finger_speeds: FingerMap<f64>,
+    same_key_interval: f64,
+    same_finger_interval: f64,
+    same_hand_interval: f64,
+    alternating_interval: f64,
+    alternating_overlap: f64,
+    key_width: f64,
+    chars_per_word: f64,
+}
+
+impl TypingTime {
+    pub fn new(params: &Parameters) -> Result<Self> {
+        let missing: Vec<Finger> = FingerMap::<f64>::keys()
+            .iter()
+            .copied()
+            .filter(|f| !params.finger_speeds.contains_key(f))
+            .collect();
+        if !missing.is_empty() {
+            bail!("No finger speeds given for {:?}", missing);
+        }
+
+        Ok(Self {
+            finger_speeds: FingerMap::with_hashmap(&params.finger_speeds, 0.0),
+            same_key_interval: params.same_key_interval,
+            same_finger_interval: params.same_finger_interval,
+            same_hand_interval: params.same_hand_interval,
+            alternating_interval: params.alternating_interval,
+            alternating_overlap: params.alternating_overlap,
+            key_width: params.key_width,
+            chars_per_word: params.chars_per_word,
+        })
+    }
+
+    /// Estimated interval (in ms) between pressing `k1` and `k2`.
+    #[inline(always)]
+    fn interval(&self, k1: &LayerKey, k2: &LayerKey, layout: &Layout) -> f64 {
+        let key1 = &k1.key;
+        let key2 = &k2.key;
+
+        if key1.index == key2.index {
+            return self.same_key_interval;
+        }
+
+        let movement_time = |from: f64| {
+            let difficulty = (1.0 + from / self.key_width).log2();
+            self.finger_speeds.get(&key2.finger) * difficulty
+        };
+        let resting_distance = || {
+            layout
+                .keyboard
+                .finger_resting_positions
+                .get(&key2.hand, &key2.finger)
+                .distance(&key2.position)
+        };
+
+        if key1.hand != key2.hand {
+            self.alternating_interval + self.alternating_overlap * movement_time(resting_distance())
+        } else if key1.finger == key2.finger {
+            self.same_finger_interval + movement_time(key1.position.distance(&key2.position))
+        } else {
+            self.same_hand_interval + movement_time(resting_distance())
+        }
+    }
+}
+
+impl BigramMetric for TypingTime {
+    fn name(&self) -> &str {
+        "Typing Time"
+    }
+
+    fn is_additive(&self) -> bool {
+        true
+    }
+
+    #[inline(always)]
+    fn individual_cost(
+        &self,
+        k1: &LayerKey,
+        k2: &LayerKey,
+        weight: f64,
+        _total_weight: f64,
+        layout: &Layout,
+    ) -> Option<f64> {
+        Some(weight * self.interval(k1, k2, layout))
+    }
+
+    fn total_cost(
+        &self,
+        bigrams: &[((&LayerKey, &LayerKey), f64)],
+        total_weight: Option<f64>,
+        layout: &Layout,
+        _n_worst: usize,
+    ) -> (f64, Option<String>) {
+        let total_weight = total_weight.unwrap_or_else(|| bigrams.iter().map(|(_, w)| w).sum());
+
+        // the time of each bigram is attributed to the finger hitting its second key
+        let mut finger_times: HandFingerMap<f64> = HandFingerMap::with_default(0.0);
+        bigrams.iter().for_each(|((k1, k2), weight)| {
+            *finger_times.get_mut(&k2.key.hand, &k2.key.finger) +=
+                weight * self.interval(k1, k2, layout);
+        });
+
+        let total_time: f64 = finger_times.iter().sum();
+        if total_time == 0.0 {
+            return (0.0, None);
+        }
+
+        let mean_interval = total_time / total_weight;
+        let wpm = 60000.0 / (mean_interval * self.chars_per_word);
+
+        finger_times
+            .iter_mut()
+            .for_each(|t| *t *= 100.0 / total_time);
+
+        let message = format!(
+            "Estimated speed: {:.1} WPM ({:.0} ms per keystroke); Busy time per finger (%): {:4.1} {:4.1} {:4.1} {:4.1} | {:>4.1} - {:<4.1} | {:4.1} {:4.1} {:4.1} {:4.1}",
+            wpm,
+            mean_interval,
+            finger_times.get(&Hand::Left, &Finger::Pinky),
+            finger_times.get(&Hand::Left, &Finger::Ring),
+            finger_times.get(&Hand::Left, &Finger::Middle),
+            finger_times.get(&Hand::Left, &Finger::Index),
+            finger_times.get(&Hand::Left, &Finger::Thumb),
+            finger_times.get(&Hand::Right, &Finger::Thumb),
+            finger_times.get(&Hand::Right, &Finger::Index),
+            finger_times.get(&Hand::Right, &Finger::Middle),
+            finger_times.get(&Hand::Right, &Finger::Ring),
+            finger_times.get(&Hand::Right, &Finger::Pinky),
+        );
+
+        (total_time, Some(message))
+    }
+}
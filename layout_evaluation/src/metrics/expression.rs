@@ -0,0 +1,820 @@
+//! The `expression` module provides a small expression language for computing the cost of
+//! individual ngrams in scripted metrics (see
+//! [`ScriptedBigram`](super::bigram_metrics::scripted_bigram::ScriptedBigram),
+//! [`ScriptedTrigram`](super::trigram_metrics::scripted_trigram::ScriptedTrigram), and
+//! [`ScriptedQuadgram`](super::quadgram_metrics::scripted_quadgram::ScriptedQuadgram)).
+//!
+//! An expression has access to the properties of each key of the ngram as `k1.<field>`,
+//! `k2.<field>`, ... with the fields
+//! - `hand` (`"Left"` or `"Right"`),
+//! - `finger` (`"Thumb"`, `"Index"`, `"Middle"`, `"Ring"`, or `"Pinky"`),
+//! - `column` and `row` (the key's matrix position),
+//! - `x` and `y` (the key's position),
+//! - `cost` (the key's cost),
+//! - `layer`, and
+//! - `is_modifier` (boolean).
+//!
+//! Supported are number, string, and boolean (`true`, `false`) literals, the arithmetic operators
+//! `+ - * /`, comparisons `== != < <= > >=`, the logical operators `&& || !`, conditionals
+//! `condition ? a : b`, and the functions `abs(a)`, `sqrt(a)`, `min(a, b)`, and `max(a, b)`.
+//! Booleans are converted to `1` (`true`) and `0` (`false`) when used as numbers. A division by
+//! zero results in `0` (as does the square root of a negative number), such that the cost of an
+//! ngram is always finite.
+//!
+//! Expressions are parsed and type checked once, such that evaluating them for an ngram can not fail.
+
+use keyboard_layout::{
+    key::{Finger, Hand},
+    layout::LayerKey,
+};
+
+use std::{fmt, str::FromStr};
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum ExpressionError {
+    #[error("Syntax error at position {0}: {1}")]
+    Syntax(usize, String),
+    #[error("Unknown variable '{0}'")]
+    UnknownVariable(String),
+    #[error("Unknown function '{0}'")]
+    UnknownFunction(String),
+    #[error("Function '{0}' expects {1} argument(s)")]
+    WrongArgumentCount(String, usize),
+    #[error("Type error: {0}")]
+    Type(String),
+    #[error("The expression refers to key k{0}, but the ngrams only have {1} keys")]
+    KeyOutOfRange(usize, usize),
+}
+
+/// The type of a (sub-)expression
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Type {
+    Number,
+    Bool,
+    Text,
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Number => write!(f, "number"),
+            Type::Bool => write!(f, "boolean"),
+            Type::Text => write!(f, "string"),
+        }
+    }
+}
+
+/// The value of a (sub-)expression
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Value<'a> {
+    Number(f64),
+    Bool(bool),
+    Text(&'a str),
+}
+
+impl<'a> Value<'a> {
+    #[inline(always)]
+    fn number(self) -> f64 {
+        match self {
+            Value::Number(n) => n,
+            Value::Bool(b) => b as u8 as f64,
+            Value::Text(_) => 0.0,
+        }
+    }
+
+    #[inline(always)]
+    fn bool(self) -> bool {
+        match self {
+            Value::Bool(b) => b,
+            Value::Number(n) => n != 0.0,
+            Value::Text(s) => !s.is_empty(),
+        }
+    }
+}
+
+/// A property of a key that is accessible in expressions
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum KeyField {
+    Hand,
+    Finger,
+    Column,
+    Row,
+    X,
+    Y,
+    Cost,
+    Layer,
+    IsModifier,
+}
+
+impl KeyField {
+    fn from_name(name: &str) -> Option<Self> {
+        let field = match name {
+            "hand" => Self::Hand,
+            "finger" => Self::Finger,
+            "column" => Self::Column,
+            "row" => Self::Row,
+            "x" => Self::X,
+            "y" => Self::Y,
+            "cost" => Self::Cost,
+            "layer" => Self::Layer,
+            "is_modifier" => Self::IsModifier,
+            _ => return None,
+        };
+
+        Some(field)
+    }
+
+    fn value_type(&self) -> Type {
+        match self {
+            Self::Hand | Self::Finger => Type::Text,
+            Self::IsModifier => Type::Bool,
+            _ => Type::Number,
+        }
+    }
+
+    #[inline(always)]
+    fn value(&self, key: &LayerKey) -> Value<'static> {
+        match self {
+            Self::Hand => Value::Text(match key.key.hand {
+                Hand::Left => "Left",
+                Hand::Right => "Right",
+            }),
+            Self::Finger => Value::Text(match key.key.finger {
+                Finger::Thumb => "Thumb",
+                Finger::Index => "Index",
+                Finger::Middle => "Middle",
+                Finger::Ring => "Ring",
+                Finger::Pinky => "Pinky",
+            }),
+            Self::Column => Value::Number(key.key.matrix_position.0 as f64),
+            Self::Row => Value::Number(key.key.matrix_position.1 as f64),
+            Self::X => Value::Number(key.key.position.0),
+            Self::Y => Value::Number(key.key.position.1),
+            Self::Cost => Value::Number(key.key.cost),
+            Self::Layer => Value::Number(key.layer as f64),
+            Self::IsModifier => Value::Bool(key.is_modifier.is_some()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum UnaryOp {
+    Neg,
+    Not,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Function {
+    Abs,
+    Sqrt,
+    Min,
+    Max,
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<Self> {
+        let function = match name {
+            "abs" => Self::Abs,
+            "sqrt" => Self::Sqrt,
+            "min" => Self::Min,
+            "max" => Self::Max,
+            _ => return None,
+        };
+
+        Some(function)
+    }
+
+    fn n_args(&self) -> usize {
+        match self {
+            Self::Abs | Self::Sqrt => 1,
+            Self::Min | Self::Max => 2,
+        }
+    }
+}
+
+/// Node of the syntax tree of an expression
+#[derive(Clone, Debug)]
+enum Node {
+    Number(f64),
+    Bool(bool),
+    Text(String),
+    Key(usize, KeyField),
+    Unary(UnaryOp, Box<Node>),
+    Binary(BinaryOp, Box<Node>, Box<Node>),
+    Conditional(Box<Node>, Box<Node>, Box<Node>),
+    Call(Function, Vec<Node>),
+}
+
+impl Node {
+    /// Determine the type of the node, checking that all operands have suitable types.
+    fn check(&self) -> Result<Type, ExpressionError> {
+        let expect = |node: &Node, allowed: &[Type], context: &str| {
+            let t = node.check()?;
+            if allowed.contains(&t) {
+                Ok(t)
+            } else {
+                Err(ExpressionError::Type(format!(
+                    "{} can not be applied to a {}",
+                    context, t
+                )))
+            }
+        };
+        let numeric = [Type::Number, Type::Bool];
+
+        match self {
+            Node::Number(_) => Ok(Type::Number),
+            Node::Bool(_) => Ok(Type::Bool),
+            Node::Text(_) => Ok(Type::Text),
+            Node::Key(_, field) => Ok(field.value_type()),
+            Node::Unary(UnaryOp::Neg, a) => expect(a, &numeric, "'-'").map(|_| Type::Number),
+            Node::Unary(UnaryOp::Not, a) => expect(a, &numeric, "'!'").map(|_| Type::Bool),
+            Node::Binary(op, a, b) => match op {
+                BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div => {
+                    expect(a, &numeric, "An arithmetic operator")?;
+                    expect(b, &numeric, "An arithmetic operator")?;
+                    Ok(Type::Number)
+                }
+                BinaryOp::And | BinaryOp::Or => {
+                    expect(a, &numeric, "A logical operator")?;
+                    expect(b, &numeric, "A logical operator")?;
+                    Ok(Type::Bool)
+                }
+                BinaryOp::Eq | BinaryOp::Ne => {
+                    let (ta, tb) = (a.check()?, b.check()?);
+                    if (ta == Type::Text) != (tb == Type::Text) {
+                        return Err(ExpressionError::Type(format!(
+                            "A {} can not be compared to a {}",
+                            ta, tb
+                        )));
+                    }
+                    Ok(Type::Bool)
+                }
+                _ => {
+                    expect(a, &numeric, "A comparison")?;
+                    expect(b, &numeric, "A comparison")?;
+                    Ok(Type::Bool)
+                }
+            },
+            Node::Conditional(condition, a, b) => {
+                expect(condition, &numeric, "A condition")?;
+                let (ta, tb) = (a.check()?, b.check()?);
+                match (ta, tb) {
+                    (ta, tb) if ta == tb => Ok(ta),
+                    (Type::Text, _) | (_, Type::Text) => Err(ExpressionError::Type(format!(
+                        "The branches of a conditional have different types ({} and {})",
+                        ta, tb
+                    ))),
+                    _ => Ok(Type::Number),
+                }
+            }
+            Node::Call(_, args) => {
+                for arg in args {
+                    expect(arg, &numeric, "A function")?;
+                }
+                Ok(Type::Number)
+            }
+        }
+    }
+
+    /// The highest key index referred to in the node
+    fn max_key_index(&self) -> usize {
+        match self {
+            Node::Key(i, _) => *i,
+            Node::Unary(_, a) => a.max_key_index(),
+            Node::Binary(_, a, b) => a.max_key_index().max(b.max_key_index()),
+            Node::Conditional(c, a, b) => c
+                .max_key_index()
+                .max(a.max_key_index())
+                .max(b.max_key_index()),
+            Node::Call(_, args) => args.iter().map(|a| a.max_key_index()).max().unwrap_or(0),
+            _ => 0,
+        }
+    }
+
+    fn eval<'a>(&'a self, keys: &[&LayerKey]) -> Value<'a> {
+        match self {
+            Node::Number(n) => Value::Number(*n),
+            Node::Bool(b) => Value::Bool(*b),
+            Node::Text(s) => Value::Text(s),
+            Node::Key(i, field) => field.value(keys[*i - 1]),
+            Node::Unary(UnaryOp::Neg, a) => Value::Number(-a.eval(keys).number()),
+            Node::Unary(UnaryOp::Not, a) => Value::Bool(!a.eval(keys).bool()),
+            Node::Binary(BinaryOp::And, a, b) => {
+                Value::Bool(a.eval(keys).bool() && b.eval(keys).bool())
+            }
+            Node::Binary(BinaryOp::Or, a, b) => {
+                Value::Bool(a.eval(keys).bool() || b.eval(keys).bool())
+            }
+            Node::Binary(op, a, b) => {
+                let (a, b) = (a.eval(keys), b.eval(keys));
+                match op {
+                    BinaryOp::Add => Value::Number(a.number() + b.number()),
+                    BinaryOp::Sub => Value::Number(a.number() - b.number()),
+                    BinaryOp::Mul => Value::Number(a.number() * b.number()),
+                    BinaryOp::Div if b.number() == 0.0 => Value::Number(0.0),
+                    BinaryOp::Div => Value::Number(a.number() / b.number()),
+                    BinaryOp::Eq | BinaryOp::Ne => {
+                        let equal = match (a, b) {
+                            (Value::Text(a), Value::Text(b)) => a == b,
+                            (a, b) => a.number() == b.number(),
+                        };
+                        Value::Bool(equal == (*op == BinaryOp::Eq))
+                    }
+                    BinaryOp::Lt => Value::Bool(a.number() < b.number()),
+                    BinaryOp::Le => Value::Bool(a.number() <= b.number()),
+                    BinaryOp::Gt => Value::Bool(a.number() > b.number()),
+                    BinaryOp::Ge => Value::Bool(a.number() >= b.number()),
+                    BinaryOp::And | BinaryOp::Or => unreachable!(),
+                }
+            }
+            Node::Conditional(condition, a, b) => {
+                if condition.eval(keys).bool() {
+                    a.eval(keys)
+                } else {
+                    b.eval(keys)
+                }
+            }
+            Node::Call(function, args) => {
+                let a = args[0].eval(keys).number();
+                Value::Number(match function {
+                    Function::Abs => a.abs(),
+                    Function::Sqrt => a.max(0.0).sqrt(),
+                    Function::Min => a.min(args[1].eval(keys).number()),
+                    Function::Max => a.max(args[1].eval(keys).number()),
+                })
+            }
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum Token {
+    Number(f64),
+    Text(String),
+    Ident(String),
+    Op(&'static str),
+}
+
+/// Split an expression into tokens (with their positions)
+fn tokenize(s: &str) -> Result<Vec<(usize, Token)>, ExpressionError> {
+    const OPS: [&str; 19] = [
+        "==", "!=", "<=", ">=", "&&", "||", "<", ">", "+", "-", "*", "/", "!", "?", ":", "(", ")",
+        ",", ".",
+    ];
+
+    let chars: Vec<char> = s.chars().collect();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+    while pos < chars.len() {
+        let c = chars[pos];
+        if c.is_whitespace() {
+            pos += 1;
+        } else if c.is_ascii_digit() {
+            let start = pos;
+            while pos < chars.len() && (chars[pos].is_ascii_digit() || chars[pos] == '.') {
+                pos += 1;
+            }
+            let number: String = chars[start..pos].iter().collect();
+            let number = number.parse().map_err(|_| {
+                ExpressionError::Syntax(start, format!("Invalid number '{}'", number))
+            })?;
+            tokens.push((start, Token::Number(number)));
+        } else if c.is_alphabetic() || c == '_' {
+            let start = pos;
+            while pos < chars.len() && (chars[pos].is_alphanumeric() || chars[pos] == '_') {
+                pos += 1;
+            }
+            tokens.push((start, Token::Ident(chars[start..pos].iter().collect())));
+        } else if c == '"' || c == '\'' {
+            let start = pos;
+            pos += 1;
+            while pos < chars.len() && chars[pos] != c {
+                pos += 1;
+            }
+            if pos == chars.len() {
+                return Err(ExpressionError::Syntax(
+                    start,
+                    "Unterminated string".to_string(),
+                ));
+            }
+            tokens.push((start, Token::Text(chars[start + 1..pos].iter().collect())));
+            pos += 1;
+        } else {
+            let rest: String = chars[pos..chars.len().min(pos + 2)].iter().collect();
+            let op = OPS.iter().find(|op| rest.starts_with(*op)).ok_or_else(|| {
+                ExpressionError::Syntax(pos, format!("Unexpected character '{}'", c))
+            })?;
+            tokens.push((pos, Token::Op(op)));
+            pos += op.len();
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// A recursive descent parser for expressions
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    len: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, t)| t)
+    }
+
+    fn error(&self, msg: &str) -> ExpressionError {
+        let pos = self
+            .tokens
+            .get(self.pos)
+            .map(|(p, _)| *p)
+            .unwrap_or(self.len);
+        ExpressionError::Syntax(pos, msg.to_string())
+    }
+
+    fn accept(&mut self, op: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Op(o)) if *o == op) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, op: &str) -> Result<(), ExpressionError> {
+        if self.accept(op) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("Expected '{}'", op)))
+        }
+    }
+
+    fn conditional(&mut self) -> Result<Node, ExpressionError> {
+        let condition = self.binary(0)?;
+        if !self.accept("?") {
+            return Ok(condition);
+        }
+        let a = self.conditional()?;
+        self.expect(":")?;
+        let b = self.conditional()?;
+
+        Ok(Node::Conditional(
+            Box::new(condition),
+            Box::new(a),
+            Box::new(b),
+        ))
+    }
+
+    /// Parse binary operations with operators of given precedence level (and higher).
+    fn binary(&mut self, level: usize) -> Result<Node, ExpressionError> {
+        const LEVELS: [&[(&str, BinaryOp)]; 5] = [
+            &[("||", BinaryOp::Or)],
+            &[("&&", BinaryOp::And)],
+            &[
+                ("==", BinaryOp::Eq),
+                ("!=", BinaryOp::Ne),
+                ("<=", BinaryOp::Le),
+                (">=", BinaryOp::Ge),
+                ("<", BinaryOp::Lt),
+                (">", BinaryOp::Gt),
+            ],
+            &[("+", BinaryOp::Add), ("-", BinaryOp::Sub)],
+            &[("*", BinaryOp::Mul), ("/", BinaryOp::Div)],
+        ];
+
+        if level == LEVELS.len() {
+            return self.unary();
+        }
+
+        let mut node = self.binary(level + 1)?;
+        'outer: loop {
+            for (op_name, op) in LEVELS[level] {
+                if self.accept(op_name) {
+                    let rhs = self.binary(level + 1)?;
+                    node = Node::Binary(*op, Box::new(node), Box::new(rhs));
+                    continue 'outer;
+                }
+            }
+            return Ok(node);
+        }
+    }
+
+    fn unary(&mut self) -> Result<Node, ExpressionError> {
+        if self.accept("-") {
+            Ok(Node::Unary(UnaryOp::Neg, Box::new(self.unary()?)))
+        } else if self.accept("!") {
+            Ok(Node::Unary(UnaryOp::Not, Box::new(self.unary()?)))
+        } else {
+            self.primary()
+        }
+    }
+
+    fn primary(&mut self) -> Result<Node, ExpressionError> {
+        let token = self
+            .peek()
+            .cloned()
+            .ok_or_else(|| self.error("Unexpected end of expression"))?;
+        self.pos += 1;
+
+        match token {
+            Token::Number(n) => Ok(Node::Number(n)),
+            Token::Text(s) => Ok(Node::Text(s)),
+            Token::Op("(") => {
+                let node = self.conditional()?;
+                self.expect(")")?;
+                Ok(node)
+            }
+            Token::Ident(name) if name == "true" => Ok(Node::Bool(true)),
+            Token::Ident(name) if name == "false" => Ok(Node::Bool(false)),
+            Token::Ident(name) if self.accept("(") => {
+                let function = Function::from_name(&name)
+                    .ok_or_else(|| ExpressionError::UnknownFunction(name.clone()))?;
+                let mut args = vec![self.conditional()?];
+                while self.accept(",") {
+                    args.push(self.conditional()?);
+                }
+                self.expect(")")?;
+                if args.len() != function.n_args() {
+                    return Err(ExpressionError::WrongArgumentCount(name, function.n_args()));
+                }
+                Ok(Node::Call(function, args))
+            }
+            Token::Ident(name) => {
+                self.pos -= 1;
+                let variable = self.variable()?;
+                let key_index = name
+                    .strip_prefix('k')
+                    .and_then(|i| i.parse::<usize>().ok())
+                    .filter(|i| *i > 0);
+                let field = variable
+                    .strip_prefix(&format!("{}.", name))
+                    .and_then(KeyField::from_name);
+                match (key_index, field) {
+                    (Some(i), Some(field)) => Ok(Node::Key(i, field)),
+                    _ => Err(ExpressionError::UnknownVariable(variable)),
+                }
+            }
+            _ => {
+                self.pos -= 1;
+                Err(self.error("Unexpected token"))
+            }
+        }
+    }
+
+    /// Parse a (dotted) variable name.
+    fn variable(&mut self) -> Result<String, ExpressionError> {
+        let mut parts = Vec::new();
+        loop {
+            match self.peek().cloned() {
+                Some(Token::Ident(part)) => parts.push(part),
+                _ => return Err(self.error("Expected a variable name")),
+            }
+            self.pos += 1;
+            if !self.accept(".") {
+                return Ok(parts.join("."));
+            }
+        }
+    }
+}
+
+/// A parsed and type checked expression computing a number from the keys of an ngram
+#[derive(Clone, Debug)]
+pub struct Expression {
+    root: Node,
+    source: String,
+}
+
+impl Expression {
+    /// The highest key index (`k1`, `k2`, ...) the expression refers to
+    pub fn n_keys(&self) -> usize {
+        self.root.max_key_index()
+    }
+
+    /// Make sure that the expression only refers to keys available in ngrams with `n` keys.
+    pub fn check_n_keys(&self, n: usize) -> Result<(), ExpressionError> {
+        match self.n_keys() {
+            m if m > n => Err(ExpressionError::KeyOutOfRange(m, n)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Evaluate the expression for the keys of an ngram. The keys need to contain all keys
+    /// referred to in the expression (see [`Self::check_n_keys`]).
+    #[inline(always)]
+    pub fn eval(&self, keys: &[&LayerKey]) -> f64 {
+        self.root.eval(keys).number()
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+impl FromStr for Expression {
+    type Err = ExpressionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tokens = tokenize(s)?;
+        let mut parser = Parser {
+            tokens,
+            pos: 0,
+            len: s.chars().count(),
+        };
+        let root = parser.conditional()?;
+        if parser.peek().is_some() {
+            return Err(parser.error("Unexpected token"));
+        }
+
+        if root.check()? == Type::Text {
+            return Err(ExpressionError::Type(
+                "The expression needs to evaluate to a number or boolean".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            root,
+            source: s.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use keyboard_layout::{
+        key::{Key, MatrixPosition, Position},
+        layout::{LayerModifierType, LayerModifiers},
+    };
+
+    fn layerkey(hand: Hand, finger: Finger, column: u8, row: u8, cost: f64) -> LayerKey {
+        let key = Key {
+            hand,
+            finger,
+            matrix_position: MatrixPosition(column, row),
+            position: Position(column as f64, row as f64),
+            cost,
+            ..Default::default()
+        };
+        LayerKey::new(
+            0,
+            key,
+            'a',
+            LayerModifiers::Hold(Vec::new()),
+            false,
+            LayerModifierType::None,
+        )
+    }
+
+    fn eval(expression: &str) -> f64 {
+        let k1 = layerkey(Hand::Left, Finger::Index, 4, 2, 1.0);
+        let k2 = layerkey(Hand::Right, Finger::Ring, 8, 1, 3.0);
+        let expression: Expression = expression.parse().unwrap();
+        expression.check_n_keys(2).unwrap();
+        expression.eval(&[&k1, &k2])
+    }
+
+    fn error(expression: &str) -> ExpressionError {
+        expression.parse::<Expression>().unwrap_err()
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(eval("1 + 2 * 3"), 7.0);
+        assert_eq!(eval("(1 + 2) * 3"), 9.0);
+        assert_eq!(eval("10 - 4 - 3"), 3.0);
+        assert_eq!(eval("12 / 3 / 2"), 2.0);
+        assert_eq!(eval("-2 * 3 + 1"), -5.0);
+        assert_eq!(eval("1 + 2 < 4 && 2 * 2 == 4"), 1.0);
+        assert_eq!(eval("true || false && false"), 1.0);
+        assert_eq!(eval("!false && false"), 0.0);
+        assert_eq!(eval("1 < 2 == true"), 1.0);
+    }
+
+    #[test]
+    fn keys_and_functions() {
+        assert_eq!(eval("k2.column - k1.column"), 4.0);
+        assert_eq!(eval("abs(k2.row - k1.row)"), 1.0);
+        assert_eq!(eval("k1.cost + k2.cost"), 4.0);
+        assert_eq!(eval("min(k1.x, k2.x) + max(k1.y, k2.y)"), 6.0);
+        assert_eq!(eval("sqrt(16)"), 4.0);
+        assert_eq!(eval("k1.hand != k2.hand"), 1.0);
+        assert_eq!(eval("k2.finger == 'Ring' && !k1.is_modifier"), 1.0);
+    }
+
+    #[test]
+    fn conditionals() {
+        assert_eq!(eval("k1.hand == k2.hand ? 1 : 2"), 2.0);
+        assert_eq!(eval("k1.hand == \"Left\" ? 1 : 2"), 1.0);
+        // right associative
+        assert_eq!(eval("false ? 1 : false ? 2 : 3"), 3.0);
+        assert_eq!(eval("true ? false ? 1 : 2 : 3"), 2.0);
+        // lower precedence than all operators
+        assert_eq!(eval("1 + 1 == 2 ? 10 : 20 + 5"), 10.0);
+        // booleans and numbers are mixed as numbers
+        assert_eq!(eval("true ? k1.is_modifier : 5"), 0.0);
+    }
+
+    #[test]
+    fn division_by_zero() {
+        assert_eq!(eval("1 / 0"), 0.0);
+        assert_eq!(eval("k1.cost / (k2.row - 1)"), 0.0);
+        assert_eq!(eval("0 / 0 + 2"), 2.0);
+        assert_eq!(eval("sqrt(-4)"), 0.0);
+    }
+
+    #[test]
+    fn type_errors() {
+        assert!(matches!(error("k1.hand + 1"), ExpressionError::Type(_)));
+        assert!(matches!(
+            error("k1.finger < 'Ring'"),
+            ExpressionError::Type(_)
+        ));
+        assert!(matches!(error("k1.hand == 1"), ExpressionError::Type(_)));
+        assert!(matches!(error("!k1.hand"), ExpressionError::Type(_)));
+        assert!(matches!(error("abs(k1.hand)"), ExpressionError::Type(_)));
+        assert!(matches!(error("k1.hand ? 1 : 2"), ExpressionError::Type(_)));
+        assert!(matches!(error("true ? 'a' : 2"), ExpressionError::Type(_)));
+        assert!(matches!(error("k1.hand"), ExpressionError::Type(_)));
+    }
+
+    #[test]
+    fn unknown_names() {
+        assert_eq!(
+            error("k1.speed"),
+            ExpressionError::UnknownVariable("k1.speed".to_string())
+        );
+        assert_eq!(
+            error("k1.hand.x"),
+            ExpressionError::UnknownVariable("k1.hand.x".to_string())
+        );
+        assert_eq!(
+            error("k0.cost"),
+            ExpressionError::UnknownVariable("k0.cost".to_string())
+        );
+        assert_eq!(
+            error("key.cost"),
+            ExpressionError::UnknownVariable("key.cost".to_string())
+        );
+        assert_eq!(
+            error("cost"),
+            ExpressionError::UnknownVariable("cost".to_string())
+        );
+        assert_eq!(
+            error("pow(2, 3)"),
+            ExpressionError::UnknownFunction("pow".to_string())
+        );
+        assert_eq!(
+            error("min(1)"),
+            ExpressionError::WrongArgumentCount("min".to_string(), 2)
+        );
+    }
+
+    #[test]
+    fn key_indices() {
+        let expression: Expression = "k1.cost + k3.cost * k2.cost".parse().unwrap();
+        assert_eq!(expression.n_keys(), 3);
+        assert!(expression.check_n_keys(3).is_ok());
+        assert_eq!(
+            expression.check_n_keys(2),
+            Err(ExpressionError::KeyOutOfRange(3, 2))
+        );
+
+        let expression: Expression = "true ? 1 : k4.row".parse().unwrap();
+        assert_eq!(
+            expression.check_n_keys(2),
+            Err(ExpressionError::KeyOutOfRange(4, 2))
+        );
+    }
+
+    #[test]
+    fn syntax_errors() {
+        assert!(matches!(error("1 +"), ExpressionError::Syntax(3, _)));
+        assert!(matches!(error("(1 + 2"), ExpressionError::Syntax(6, _)));
+        assert!(matches!(error("1 2"), ExpressionError::Syntax(2, _)));
+        assert!(matches!(error("1 # 2"), ExpressionError::Syntax(2, _)));
+        assert!(matches!(error("'Left"), ExpressionError::Syntax(0, _)));
+        assert!(matches!(error("1.2.3"), ExpressionError::Syntax(0, _)));
+        assert!(matches!(error("true ? 1"), ExpressionError::Syntax(8, _)));
+    }
+}
@@ -5,6 +5,8 @@ use std::fmt;
 
 pub mod kla_home_key_words;
 pub mod kla_same_finger_words;
+pub mod paired_symbols;
+pub mod reference_layout_distance;
 pub mod shortcut_keys;
 pub mod similar_letter_groups;
 pub mod similar_letters;
@@ -0,0 +1,205 @@
+//! The layout metric [`PairedSymbols`] checks the placement of ordered pairs of symbols that are
+//! typically typed together, e.g. brackets and quotes in programming ("()", "[]", "{}", "<>").
+//! The pairs' positioning is rated the following way:
+//! - `mirrored_cost` if they are on symmetrical keys of both hands (same finger) and the same layer
+//! - `adjacent_cost` if they are next to each other in the same row and layer, with the opening
+//!   symbol on the left
+//! - `reversed_adjacent_cost` if they are next to each other in the same row and layer, but with
+//!   the opening symbol on the right
+//! - `same_layer_cost` plus a distance-dependent cost if they are on the same layer otherwise
+//! - `different_layer_cost` plus a distance-dependent cost if none of the criteria apply
+//!
+//! A pair of identical symbols (e.g. `""`) is typed by repeating the same key, so it is always
+//! considered well placed (without cost).
+//!
+//! Two symbols are on the same layer if they belong to the same layer and are generated with the
+//! same kind of modifiers (e.g. not a symbol of a hold layer and a chord). This also applies to
+//! upper layers, e.g. two symbols of a symbol layer may be reached with modifiers of different
+//! hands.
+
+use super::LayoutMetric;
+
+use keyboard_layout::layout::{LayerKey, Layout};
+
+use serde::Deserialize;
+use std::mem;
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct Parameters {
+    /// Ordered pairs of symbols (opening, closing)
+    pub symbol_pairs: Vec<(char, char)>,
+    pub mirrored_cost: f64,
+    pub adjacent_cost: f64,
+    pub reversed_adjacent_cost: f64,
+    pub same_layer_cost: f64,
+    pub different_layer_cost: f64,
+    /// Cost per key width of distance between the keys (if they are neither mirrored nor adjacent)
+    pub distance_cost: f64,
+    /// Width of a key in units of the keyboard's key positions (e.g. 50 for the keyboards of this repository)
+    pub key_width: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct PairedSymbols {
+    symbol_pairs: Vec<(char, char)>,
+    mirrored_cost: f64,
+    adjacent_cost: f64,
+    reversed_adjacent_cost: f64,
+    same_layer_cost: f64,
+    different_layer_cost: f64,
+    distance_cost: f64,
+    key_width: f64,
+}
+
+impl PairedSymbols {
+    pub fn new(params: &Parameters) -> Self {
+        Self {
+            symbol_pairs: params.symbol_pairs.to_vec(),
+            mirrored_cost: params.mirrored_cost,
+            adjacent_cost: params.adjacent_cost,
+            reversed_adjacent_cost: params.reversed_adjacent_cost,
+            same_layer_cost: params.same_layer_cost,
+            different_layer_cost: params.different_layer_cost,
+            distance_cost: params.distance_cost,
+            key_width: params.key_width,
+        }
+    }
+
+    /// Cost of the placement of the pair and whether it is considered well placed
+    /// (mirrored or adjacent in the right order).
+    fn pair_cost(&self, layerkey1: &LayerKey, layerkey2: &LayerKey) -> (f64, bool) {
+        let key1 = &layerkey1.key;
+        let key2 = &layerkey2.key;
+
+        let on_same_layer = layerkey1.layer == layerkey2.layer
+            && mem::discriminant(&layerkey1.modifiers) == mem::discriminant(&layerkey2.modifiers);
+        let is_mirrored = key1.hand != key2.hand && key1.symmetry_index == key2.symmetry_index;
+        let in_same_row = key1.matrix_position.1 == key2.matrix_position.1;
+
+        if on_same_layer && is_mirrored {
+            (self.mirrored_cost, true)
+        } else if on_same_layer
+            && in_same_row
+            && key1.matrix_position.0 + 1 == key2.matrix_position.0
+        {
+            (self.adjacent_cost, true)
+        } else if on_same_layer
+            && in_same_row
+            && key2.matrix_position.0 + 1 == key1.matrix_position.0
+        {
+            (self.reversed_adjacent_cost, false)
+        } else {
+            let distance_cost =
+                self.distance_cost * key1.position.distance(&key2.position) / self.key_width;
+            if on_same_layer {
+                (self.same_layer_cost + distance_cost, false)
+            } else {
+                (self.different_layer_cost + distance_cost, false)
+            }
+        }
+    }
+}
+
+impl LayoutMetric for PairedSymbols {
+    fn name(&self) -> &str {
+        "Paired Symbols"
+    }
+
+    fn total_cost(&self, layout: &Layout) -> (f64, Option<String>) {
+        let mut cost = 0.0;
+        let mut bad_pairs: Vec<String> = Vec::new();
+        let mut missing_pairs: Vec<String> = Vec::new();
+
+        for (c1, c2) in &self.symbol_pairs {
+            let (layerkey1, layerkey2) = match (
+                layout.get_layerkey_for_symbol(c1),
+                layout.get_layerkey_for_symbol(c2),
+            ) {
+                (Some(layerkey1), Some(layerkey2)) => (layerkey1, layerkey2),
+                _ => {
+                    missing_pairs.push(format!("{}{}", c1, c2));
+                    continue;
+                }
+            };
+
+            if c1 == c2 {
+                continue;
+            }
+
+            let (cost_to_add, is_well_placed) = self.pair_cost(layerkey1, layerkey2);
+            if !is_well_placed {
+                bad_pairs.push(format!("{}{}", c1, c2));
+            }
+            cost += cost_to_add;
+
+            log::trace!(
+                "{} {:?} - {} {:?} - Cost: {}",
+                c1,
+                layerkey1.key.matrix_position,
+                c2,
+                layerkey2.key.matrix_position,
+                cost_to_add
+            );
+        }
+
+        let mut msgs = Vec::new();
+        if !bad_pairs.is_empty() {
+            msgs.push(format!("Poorly placed pairs: {}", bad_pairs.join(", ")));
+        }
+        if !missing_pairs.is_empty() {
+            msgs.push(format!("Pairs not in layout: {}", missing_pairs.join(", ")));
+        }
+
+        let message = if !msgs.is_empty() {
+            Some(msgs.join(";  "))
+        } else {
+            None
+        };
+
+        (cost, message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{layout, STANDARD_CONFIG};
+
+    fn cost(pair: (char, char)) -> (f64, Option<String>) {
+        let metric = PairedSymbols::new(&Parameters {
+            symbol_pairs: vec![pair],
+            mirrored_cost: 0.1,
+            adjacent_cost: 0.2,
+            reversed_adjacent_cost: 0.5,
+            same_layer_cost: 1.0,
+            different_layer_cost: 2.0,
+            distance_cost: 0.0,
+            key_width: 50.0,
+        });
+        metric.total_cost(&layout(STANDARD_CONFIG))
+    }
+
+    #[test]
+    fn pair_placements() {
+        // "{" and ")" lie on mirrored keys of the home row (layer 3)
+        assert_eq!(cost(('{', ')')), (0.1, None));
+        assert_eq!(cost(('[', ']')), (0.2, None));
+        assert_eq!(
+            cost((']', '[')),
+            (0.5, Some("Poorly placed pairs: ][".to_string()))
+        );
+        assert_eq!(
+            cost(('(', '{')).1,
+            Some("Poorly placed pairs: ({".to_string())
+        );
+    }
+
+    #[test]
+    fn identical_symbols_are_well_placed() {
+        assert_eq!(cost(('"', '"')), (0.0, None));
+        assert_eq!(
+            cost(('☺', '☺')),
+            (0.0, Some("Pairs not in layout: ☺☺".to_string()))
+        );
+    }
+}
@@ -0,0 +1,207 @@
+//! The layout metric [`ReferenceLayoutDistance`] measures how much a layout differs from a
+//! reference layout (e.g. the one its users are migrating from) and thereby how hard it is to learn.
+//! The reference layout string is interpreted by the evaluator's layout generator.
+//!
+//! Each base-layer symbol that lies on a different key than in the reference layout incurs a cost.
+//! An additional cost applies if the symbol moved to another finger of the same hand or to the
+//! other hand. The cost of each symbol can be weighted by its frequency (unigram weight), such
+//! that moving common symbols costs more than moving rare ones. Further costs can be configured
+//! for moving shortcut letters and for moving symbols of given (symbol) layers.
+
+use super::LayoutMetric;
+use crate::evaluation::Evaluator;
+
+use ahash::{AHashMap, AHashSet};
+use anyhow::{anyhow, Result};
+use keyboard_layout::{
+    keyboard::KeyIndex,
+    layout::{LayerKey, Layout},
+};
+
+use serde::Deserialize;
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct Parameters {
+    /// The reference layout (as layout string of the base layer's non-fixed keys)
+    pub reference_layout: String,
+    /// Cost for each moved base-layer symbol
+    pub moved_symbol_cost: f64,
+    /// Additional cost for each base-layer symbol that moved to another finger of the same hand
+    pub finger_change_cost: f64,
+    /// Additional cost for each base-layer symbol that moved to the other hand
+    pub hand_change_cost: f64,
+    /// Interpolates between counting moved symbols (0.0) and weighting them by their relative
+    /// frequency (1.0)
+    pub frequency_weighting: f64,
+    /// Symbols used in shortcuts that shall stay in place
+    pub shortcut_chars: String,
+    /// Additional cost for each moved shortcut symbol
+    pub shortcut_cost: f64,
+    /// Layers (counting from zero) whose symbols shall stay in place
+    pub symbol_layers: Vec<u8>,
+    /// Cost for each moved symbol of the `symbol_layers`
+    pub symbol_layer_cost: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct ReferenceLayoutDistance {
+    /// Key indices of all (non-modifier) symbols in the reference layout together with their layer
+    reference_keys: Vec<(char, u8, KeyIndex)>,
+    /// Weight factor of each symbol
+    symbol_weights: AHashMap<char, f64>,
+    moved_symbol_cost: f64,
+    finger_change_cost: f64,
+    hand_change_cost: f64,
+    shortcut_chars: AHashSet<char>,
+    shortcut_cost: f64,
+    symbol_layers: AHashSet<u8>,
+    symbol_layer_cost: f64,
+}
+
+impl ReferenceLayoutDistance {
+    pub fn new(params: &Parameters, evaluator: &Evaluator) -> Result<Self> {
+        let layout_generator = evaluator
+            .layout_generator()
+            .ok_or_else(|| anyhow!("The evaluator has no layout generator"))?;
+        let reference = layout_generator
+            .generate(&params.reference_layout)
+            .map_err(|e| anyhow!("Invalid reference layout: {}", e))?;
+
+        let reference_keys: Vec<(char, u8, KeyIndex)> = reference
+            .layerkeys
+            .iter()
+            .filter(|k| k.is_modifier.is_none())
+            .map(|k| (k.symbol, k.layer, k.key.index))
+            .collect();
+
+        // the frequency of each symbol relative to the mean frequency of the reference's symbols
+        let unigrams = &evaluator.ngram_mapper().unigrams().grams;
+        let frequency = |c: &char| unigrams.get(c).cloned().unwrap_or(0.0);
+        let mean_frequency = reference_keys
+            .iter()
+            .map(|(c, _, _)| frequency(c))
+            .sum::<f64>()
+            / reference_keys.len().max(1) as f64;
+        let symbol_weights = reference_keys
+            .iter()
+            .map(|(c, _, _)| {
+                let relative_frequency = if mean_frequency > 0.0 {
+                    frequency(c) / mean_frequency
+                } else {
+                    1.0
+                };
+                let weight = (1.0 - params.frequency_weighting)
+                    + params.frequency_weighting * relative_frequency;
+
+                (*c, weight)
+            })
+            .collect();
+
+        Ok(Self {
+            reference_keys,
+            symbol_weights,
+            moved_symbol_cost: params.moved_symbol_cost,
+            finger_change_cost: params.finger_change_cost,
+            hand_change_cost: params.hand_change_cost,
+            shortcut_chars: params.shortcut_chars.chars().collect(),
+            shortcut_cost: params.shortcut_cost,
+            symbol_layers: params.symbol_layers.iter().cloned().collect(),
+            symbol_layer_cost: params.symbol_layer_cost,
+        })
+    }
+
+    /// Cost of a symbol that moved from the reference key to the given layout key.
+    fn moved_cost(
+        &self,
+        c: &char,
+        layer: u8,
+        reference_key: KeyIndex,
+        k: &LayerKey,
+        layout: &Layout,
+    ) -> f64 {
+        let weight = self.symbol_weights.get(c).cloned().unwrap_or(1.0);
+        let reference_key = &layout.keyboard.keys[reference_key as usize];
+
+        let mut cost = 0.0;
+        if layer == 0 {
+            cost += weight * self.moved_symbol_cost;
+            if reference_key.hand != k.key.hand {
+                cost += weight * self.hand_change_cost;
+            } else if reference_key.finger != k.key.finger {
+                cost += weight * self.finger_change_cost;
+            }
+        }
+        if self.symbol_layers.contains(&layer) {
+            cost += weight * self.symbol_layer_cost;
+        }
+        if self.shortcut_chars.contains(c) {
+            cost += self.shortcut_cost;
+        }
+
+        cost
+    }
+}
+
+impl LayoutMetric for ReferenceLayoutDistance {
+    fn name(&self) -> &str {
+        "Reference Layout Distance"
+    }
+
+    fn total_cost(&self, layout: &Layout) -> (f64, Option<String>) {
+        let mut cost = 0.0;
+        let mut moved_symbols = Vec::new();
+        let mut n_hand_changes = 0;
+        let mut n_finger_changes = 0;
+        let mut n_moved_layer_symbols = 0;
+
+        self.reference_keys
+            .iter()
+            .for_each(|(c, layer, reference_key)| {
+                // symbols may occur multiple times (e.g. digits), so check the reference key first
+                let is_in_place = layout
+                    .get_layerkey_indices_for_key(*reference_key)
+                    .iter()
+                    .any(|idx| layout.get_layerkey(idx).symbol == *c);
+                if is_in_place {
+                    return;
+                }
+                let k = match layout.get_layerkey_for_symbol(c) {
+                    Some(k) => k,
+                    None => return,
+                };
+
+                cost += self.moved_cost(c, *layer, *reference_key, k, layout);
+
+                if *layer == 0 {
+                    moved_symbols.push(layout.symbol_output(*c));
+                    let reference_key = &layout.keyboard.keys[*reference_key as usize];
+                    if reference_key.hand != k.key.hand {
+                        n_hand_changes += 1;
+                    } else if reference_key.finger != k.key.finger {
+                        n_finger_changes += 1;
+                    }
+                }
+                if self.symbol_layers.contains(layer) {
+                    n_moved_layer_symbols += 1;
+                }
+            });
+
+        let mut msgs = vec![format!(
+            "{} moved symbols ({} to other hand, {} to other finger)",
+            moved_symbols.len(),
+            n_hand_changes,
+            n_finger_changes,
+        )];
+        if !moved_symbols.is_empty() {
+            msgs.push(format!("Moved: {}", moved_symbols.join("")));
+        }
+        if !self.symbol_layers.is_empty() {
+            msgs.push(format!(
+                "{} moved symbol layer symbols",
+                n_moved_layer_symbols
+            ));
+        }
+
+        (cost, Some(msgs.join(";  ")))
+    }
+}
@@ -39,7 +39,7 @@ fn costs<T: PartialEq>(data: &[T]) -> f64 {
     if data.is_empty() {
         return 0.0;
     }
-    let mut cost = 0.0;
+    let mut cost: f64 = 0.0;
     let mut n = 0.0;
     for (i, d1) in data.iter().enumerate() {
         for d2 in data.iter().skip(i + 1) {
@@ -50,7 +50,7 @@ fn costs<T: PartialEq>(data: &[T]) -> f64 {
         }
     }
 
-    ((cost / n) as f64).ln_1p()
+    (cost / n).ln_1p()
 }
 
 impl LayoutMetric for SimilarLetterGroups {
@@ -0,0 +1,67 @@
+//! The quadgram metric [`ScriptedQuadgram`] computes the cost of each quadgram with an expression
+//! given in the evaluation config (see the [`expression`](crate::metrics::expression) module).
+//! This allows trying new cost ideas without implementing a new metric.
+//! The keys of the quadgram are available as `k1`, `k2`, `k3`, and `k4` and the resulting cost is
+//! multiplied with the quadgram's weight.
+//!
+//! *Note:* The metric can be configured multiple times (with `metric: scripted_quadgram` in the
+//! config entry).
+
+use super::QuadgramMetric;
+use crate::metrics::expression::Expression;
+
+use keyboard_layout::layout::{LayerKey, Layout};
+
+use anyhow::Result;
+use serde::Deserialize;
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct Parameters {
+    /// Name of the metric
+    pub name: String,
+    /// Expression for the cost of a quadgram
+    pub expression: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct ScriptedQuadgram {
+    name: String,
+    expression: Expression,
+}
+
+impl ScriptedQuadgram {
+    pub fn new(params: &Parameters) -> Result<Self> {
+        let expression: Expression = params.expression.parse()?;
+        expression.check_n_keys(4)?;
+
+        Ok(Self {
+            name: params.name.clone(),
+            expression,
+        })
+    }
+}
+
+impl QuadgramMetric for ScriptedQuadgram {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn is_additive(&self) -> bool {
+        true
+    }
+
+    #[inline(always)]
+    #[allow(clippy::too_many_arguments)]
+    fn individual_cost(
+        &self,
+        k1: &LayerKey,
+        k2: &LayerKey,
+        k3: &LayerKey,
+        k4: &LayerKey,
+        weight: f64,
+        _total_weight: f64,
+        _layout: &Layout,
+    ) -> Option<f64> {
+        Some(weight * self.expression.eval(&[k1, k2, k3, k4]))
+    }
+}
@@ -0,0 +1,297 @@
+//! The `registry` module provides the [`MetricRegistry`] that maps the names of metrics (as used
+//! in the evaluation config) to constructors of the corresponding metric objects.
+//!
+//! All metrics of this crate are registered in [`MetricRegistry::default`]. Other crates can
+//! register their own metrics with [`MetricRegistry::register`] and use them for evaluations
+//! without modifying this crate.
+
+use super::{
+    bigram_metrics::*, layout_metrics::*, quadgram_metrics::*, skipgram_metrics::*,
+    trigram_metrics::*, unigram_metrics::*,
+};
+use crate::evaluation::Evaluator;
+
+use anyhow::Result;
+use serde::de::DeserializeOwned;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum RegistryError {
+    #[error("Unknown metric '{0}' in evaluation config. Available metrics: {1}")]
+    UnknownMetric(String, String),
+    #[error("Invalid parameters for metric '{0}': {1}")]
+    InvalidParameters(String, serde_yaml::Error),
+    #[error("Could not construct metric '{0}': {1}")]
+    ConstructionFailed(String, String),
+    #[error("Metric '{0}' is registered more than once")]
+    DuplicateMetric(String),
+}
+
+/// A metric object of any of the supported metric types
+pub enum Metric {
+    Layout(Box<dyn LayoutMetric>),
+    Unigram(Box<dyn UnigramMetric>),
+    Bigram(Box<dyn BigramMetric>),
+    Trigram(Box<dyn TrigramMetric>),
+    Quadgram(Box<dyn QuadgramMetric>),
+    Skipgram(Box<dyn SkipgramMetric>),
+}
+
+impl Metric {
+    /// The name of the metric (as used in the evaluation results).
+    pub fn name(&self) -> &str {
+        match self {
+            Metric::Layout(m) => m.name(),
+            Metric::Unigram(m) => m.name(),
+            Metric::Bigram(m) => m.name(),
+            Metric::Trigram(m) => m.name(),
+            Metric::Quadgram(m) => m.name(),
+            Metric::Skipgram(m) => m.name(),
+        }
+    }
+}
+
+/// Constructor of a metric from its (untyped) parameters and the evaluator containing all metrics
+/// that have been added so far
+type MetricConstructor =
+    Box<dyn Fn(&serde_yaml::Value, &Evaluator) -> Result<Metric> + Send + Sync>;
+
+/// Registry of all metrics that can be configured in the evaluation config
+pub struct MetricRegistry {
+    /// Names and constructors of the metrics in the order in which they are added to an evaluator
+    constructors: Vec<(String, MetricConstructor)>,
+}
+
+impl MetricRegistry {
+    /// Generate an empty registry without any metric.
+    pub fn empty() -> Self {
+        Self {
+            constructors: Vec::new(),
+        }
+    }
+
+    /// Register a metric under given name. The metric's parameters (`params` in the evaluation
+    /// config) are deserialized to `P`. Metrics are added to an evaluator in the order of their
+    /// registration, so the `Evaluator` passed to the constructor contains all metrics registered
+    /// before.
+    pub fn register<P, F>(&mut self, name: &str, constructor: F) -> Result<()>
+    where
+        P: DeserializeOwned,
+        F: Fn(&P, &Evaluator) -> Metric + Send + Sync + 'static,
+    {
+        self.register_fallible(name, move |p: &P, evaluator| Ok(constructor(p, evaluator)))
+    }
+
+    /// Register a metric under given name whose construction may fail (e.g. due to invalid
+    /// parameters that can not be detected during deserialization).
+    pub fn register_fallible<P, F>(&mut self, name: &str, constructor: F) -> Result<()>
+    where
+        P: DeserializeOwned,
+        F: Fn(&P, &Evaluator) -> Result<Metric> + Send + Sync + 'static,
+    {
+        if self.contains(name) {
+            return Err(RegistryError::DuplicateMetric(name.to_string()).into());
+        }
+
+        let metric_name = name.to_string();
+        self.constructors.push((
+            name.to_string(),
+            Box::new(move |params, evaluator| {
+                // an empty `params` entry stands for a metric without parameters
+                let params = match params {
+                    serde_yaml::Value::Null => serde_yaml::Value::Mapping(Default::default()),
+                    params => params.clone(),
+                };
+                // deserializing from the YAML text (instead of the `Value`) keeps the lenient
+                // handling of the configs' placeholder entries (e.g. `null: null`)
+                let params: P = serde_yaml::to_string(&params)
+                    .and_then(|s| serde_yaml::from_str(&s))
+                    .map_err(|e| RegistryError::InvalidParameters(metric_name.clone(), e))?;
+                constructor(&params, evaluator).map_err(|e| {
+                    RegistryError::ConstructionFailed(metric_name.clone(), e.to_string()).into()
+                })
+            }),
+        ));
+
+        Ok(())
+    }
+
+    /// Check if a metric with given name is registered.
+    pub fn contains(&self, name: &str) -> bool {
+        self.constructors.iter().any(|(n, _)| n == name)
+    }
+
+    /// Names of all registered metrics (in the order of their registration).
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.constructors.iter().map(|(n, _)| n.as_str())
+    }
+
+    /// Iterate over all registered metrics' names and constructors.
+    pub(crate) fn constructors(&self) -> impl Iterator<Item = &(String, MetricConstructor)> {
+        self.constructors.iter()
+    }
+}
+
+impl Default for MetricRegistry {
+    /// Generate a registry containing all metrics of this crate.
+    fn default() -> Self {
+        let mut registry = Self::empty();
+
+        macro_rules! register_metric {
+            ($variant:ident, $metric_name:ident, $metric_struct:ident) => {
+                registry
+                    .register(
+                        stringify!($metric_name),
+                        |p: &$metric_name::Parameters, _| {
+                            Metric::$variant(Box::new($metric_name::$metric_struct::new(p)))
+                        },
+                    )
+                    .unwrap();
+            };
+            ($variant:ident, $metric_name:ident, $metric_struct:ident, "fallible") => {
+                registry
+                    .register_fallible(
+                        stringify!($metric_name),
+                        |p: &$metric_name::Parameters, _| {
+                            Ok(Metric::$variant(Box::new(
+                                $metric_name::$metric_struct::new(p)?,
+                            )))
+                        },
+                    )
+                    .unwrap();
+            };
+            ($variant:ident, $metric_name:ident, $metric_struct:ident, "from_evaluator") => {
+                registry
+                    .register_fallible(
+                        stringify!($metric_name),
+                        |p: &$metric_name::Parameters, evaluator: &Evaluator| {
+                            Ok(Metric::$variant(Box::new(
+                                $metric_name::$metric_struct::new(p, evaluator)?,
+                            )))
+                        },
+                    )
+                    .unwrap();
+            };
+            ($variant:ident, $metric_name:ident, $metric_struct:ident, "add_bigram_metrics") => {
+                registry
+                    .register(
+                        stringify!($metric_name),
+                        |p: &$metric_name::Parameters, evaluator: &Evaluator| {
+                            Metric::$variant(Box::new($metric_name::$metric_struct::new(
+                                evaluator.bigram_metrics().to_vec(),
+                                p,
+                            )))
+                        },
+                    )
+                    .unwrap();
+            };
+        }
+
+        // layout metrics
+        register_metric!(Layout, shortcut_keys, ShortcutKeys);
+        register_metric!(Layout, similar_letters, SimilarLetters);
+        register_metric!(Layout, similar_letter_groups, SimilarLetterGroups);
+        register_metric!(Layout, paired_symbols, PairedSymbols);
+        register_metric!(
+            Layout,
+            reference_layout_distance,
+            ReferenceLayoutDistance,
+            "from_evaluator"
+        );
+
+        // unigram metrics
+        register_metric!(Unigram, finger_balance, FingerBalance);
+        register_metric!(Unigram, hand_disbalance, HandDisbalance);
+        register_metric!(Unigram, row_loads, RowLoads);
+        register_metric!(Unigram, modifier_usage, ModifierUsage);
+        register_metric!(Unigram, key_costs, KeyCost);
+
+        // bigram metrics
+        register_metric!(Bigram, finger_repeats, FingerRepeats);
+        register_metric!(Bigram, manual_bigram_penalty, ManualBigramPenalty);
+        register_metric!(Bigram, movement_pattern, MovementPattern);
+        register_metric!(
+            Bigram,
+            no_handswitch_after_unbalancing_key,
+            NoHandSwitchAfterUnbalancingKey
+        );
+        register_metric!(Bigram, symmetric_handswitches, SymmetricHandswitches);
+        register_metric!(Bigram, scripted_bigram, ScriptedBigram, "fallible");
+
+        // trigram_metrics
+        register_metric!(Trigram, no_handswitch_in_trigram, NoHandswitchInTrigram);
+        register_metric!(Trigram, trigram_finger_repeats, TrigramFingerRepeats);
+        register_metric!(Trigram, trigram_rolls, TrigramRolls);
+        register_metric!(Trigram, scripted_trigram, ScriptedTrigram, "fallible");
+        register_metric!(Trigram, irregularity, Irregularity, "add_bigram_metrics");
+        register_metric!(
+            Trigram,
+            secondary_bigrams,
+            SecondaryBigrams,
+            "add_bigram_metrics"
+        );
+
+        // bigram metrics that are registered after the metrics above such that they are not
+        // part of the irregularity and secondary bigrams
+        register_metric!(Bigram, typing_time, TypingTime, "fallible");
+        register_metric!(Bigram, scissors, Scissors, "fallible");
+        register_metric!(Bigram, hold_tap_misfires, HoldTapMisfires);
+
+        // quadgram metrics
+        register_metric!(Quadgram, scripted_quadgram, ScriptedQuadgram, "fallible");
+
+        // skipgram metrics
+        register_metric!(Skipgram, same_finger_skipgrams, SameFingerSkipgrams);
+
+        register_metric!(Layout, kla_same_finger_words, KLASameFingerWords);
+        register_metric!(Layout, kla_home_key_words, KLAHomeKeyWords);
+
+        register_metric!(Bigram, kla_distance, KLADistance);
+        register_metric!(Bigram, kla_finger_usage, KLAFingerUsage);
+        register_metric!(Bigram, kla_same_finger, KLASameFinger);
+        register_metric!(Bigram, kla_same_hand, KLASameHand);
+
+        register_metric!(Trigram, oxey_combined_trigram, OxeyCombinedTrigram);
+
+        register_metric!(Bigram, oxey_sfbs, OxeySfbs);
+        register_metric!(Bigram, oxey_lsbs, OxeyLsbs);
+        register_metric!(Trigram, oxey_dsfbs, OxeyDsfbs);
+        register_metric!(Trigram, oxey_inward_rolls, OxeyInwardRolls);
+        register_metric!(Trigram, oxey_outward_rolls, OxeyOutwardRolls);
+        register_metric!(Trigram, oxey_onehands, OxeyOnehands);
+        register_metric!(Trigram, oxey_alternates, OxeyAlternates);
+        register_metric!(Trigram, oxey_alternates_sfs, OxeyAlternatesSfs);
+        register_metric!(Trigram, oxey_redirects, OxeyRedirects);
+        register_metric!(Trigram, oxey_bad_redirects, OxeyBadRedirects);
+
+        registry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_bigram_metrics_are_not_wrapped() {
+        let registry = MetricRegistry::default();
+        let names: Vec<&str> = registry.names().collect();
+        let position = |name: &str| names.iter().position(|n| *n == name).unwrap();
+
+        // irregularity and secondary bigrams wrap all bigram metrics registered before them
+        for name in ["typing_time", "scissors", "hold_tap_misfires"] {
+            assert!(position(name) > position("irregularity"), "{}", name);
+            assert!(position(name) > position("secondary_bigrams"), "{}", name);
+        }
+    }
+
+    #[test]
+    fn scripted_bigrams_are_wrapped() {
+        let registry = MetricRegistry::default();
+        let names: Vec<&str> = registry.names().collect();
+        let position = |name: &str| names.iter().position(|n| *n == name).unwrap();
+
+        assert!(position("scripted_bigram") < position("irregularity"));
+        assert!(position("scripted_bigram") < position("secondary_bigrams"));
+    }
+}
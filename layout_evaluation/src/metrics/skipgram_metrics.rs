@@ -0,0 +1,156 @@
+//! The `metrics` module provides a trait for skipgram metrics.
+use crate::ngram_mapper::LayerKeySkipgram;
+
+use keyboard_layout::layout::{LayerKey, Layout};
+
+use ordered_float::OrderedFloat;
+use priority_queue::DoublePriorityQueue;
+use std::fmt;
+
+pub mod same_finger_skipgrams;
+
+/// SkipgramMetric is a trait for metrics that iterates over weighted skipgrams, i.e. pairs of keys with
+/// a given number ("skip distance") of other keys in between.
+pub trait SkipgramMetric: Send + Sync + SkipgramMetricClone + fmt::Debug {
+    /// Return the name of the metric.
+    fn name(&self) -> &str;
+
+    /// Compute the cost of one skipgram with given skip distance (if that is possible, otherwise,
+    /// return `None`).
+    #[inline(always)]
+    fn individual_cost(
+        &self,
+        _key1: &LayerKey,
+        _key2: &LayerKey,
+        _skip: usize,
+        _weight: f64,
+        _total_weight: f64,
+        _layout: &Layout,
+    ) -> Option<f64> {
+        None
+    }
+
+    /// Whether the total cost is the sum of the individual costs of all skipgrams and each individual cost
+    /// is proportional to the skipgram's weight (independently of all other skipgrams). Only such metrics
+    /// can be evaluated incrementally for small changes of a layout (see `Evaluator::evaluate_layout_delta`).
+    fn is_additive(&self) -> bool {
+        false
+    }
+
+    /// Compute the total cost for the metric. The optional message lists the `n_worst` skipgrams with the
+    /// highest costs (no message is generated if `n_worst` is zero).
+    fn total_cost(
+        &self,
+        skipgrams: &[(LayerKeySkipgram, f64)],
+        // total_weight is optional for performance reasons (it can be computed from skipgrams)
+        total_weight: Option<f64>,
+        layout: &Layout,
+        n_worst: usize,
+    ) -> (f64, Option<String>) {
+        let total_weight = total_weight.unwrap_or_else(|| skipgrams.iter().map(|(_, w)| w).sum());
+        let cost_iter = skipgrams
+            .iter()
+            .enumerate()
+            .filter_map(|(i, (skipgram, weight))| {
+                let cost_option = self.individual_cost(
+                    skipgram.0,
+                    skipgram.1,
+                    skipgram.2,
+                    *weight,
+                    total_weight,
+                    layout,
+                );
+
+                cost_option.map(|cost| (i, skipgram, cost))
+            });
+
+        let (total_cost, msg) = if n_worst > 0 {
+            let (total_cost, worst, worst_nonfixed) = cost_iter.fold(
+                (0.0, DoublePriorityQueue::new(), DoublePriorityQueue::new()),
+                |(mut total_cost, mut worst, mut worst_nonfixed), (i, skipgram, cost)| {
+                    total_cost += cost;
+
+                    if !skipgram.0.is_fixed && !skipgram.1.is_fixed {
+                        worst_nonfixed.push(i, OrderedFloat(cost.abs()));
+                    }
+                    worst.push(i, OrderedFloat(cost.abs()));
+
+                    if worst.len() > n_worst {
+                        worst.pop_min();
+                    }
+                    if worst_nonfixed.len() > n_worst {
+                        worst_nonfixed.pop_min();
+                    }
+
+                    (total_cost, worst, worst_nonfixed)
+                },
+            );
+
+            let gen_msgs = |q: DoublePriorityQueue<usize, OrderedFloat<f64>>| {
+                let worst_msgs: Vec<String> = q
+                    .into_sorted_iter()
+                    .rev()
+                    .filter(|(_, cost)| cost.into_inner() > 0.0)
+                    .map(|(i, cost)| {
+                        let (gram, _) = skipgrams[i];
+                        // skipped keys are indicated by underscores
+                        format!(
+                            "{}{}{} ({:>5.2}%)",
+                            layout.layerkey_label(gram.0),
+                            "_".repeat(gram.2),
+                            layout.layerkey_label(gram.1),
+                            100.0 * cost.into_inner() / total_cost,
+                        )
+                    })
+                    .collect();
+
+                worst_msgs
+            };
+
+            let mut msgs = Vec::new();
+
+            let worst_msgs = gen_msgs(worst);
+            if !worst_msgs.is_empty() {
+                msgs.push(format!("Worst: {}", worst_msgs.join(", ")))
+            }
+
+            let worst_nonfixed_msgs = gen_msgs(worst_nonfixed);
+            if !worst_nonfixed_msgs.is_empty() {
+                msgs.push(format!(
+                    "Worst non-fixed: {}",
+                    worst_nonfixed_msgs.join(", ")
+                ))
+            }
+
+            let msg = Some(msgs.join(";  "));
+
+            (total_cost, msg)
+        } else {
+            let total_cost: f64 = cost_iter.map(|(_, _, c)| c).sum();
+
+            (total_cost, None)
+        };
+
+        (total_cost, msg)
+    }
+}
+
+impl Clone for Box<dyn SkipgramMetric> {
+    fn clone(&self) -> Box<dyn SkipgramMetric> {
+        self.clone_box()
+    }
+}
+
+/// Helper trait for realizing clonability for `Box<dyn SkipgramMetric>`.
+pub trait SkipgramMetricClone {
+    fn clone_box(&self) -> Box<dyn SkipgramMetric>;
+}
+
+impl<T> SkipgramMetricClone for T
+where
+    T: 'static + SkipgramMetric + Clone,
+{
+    fn clone_box(&self) -> Box<dyn SkipgramMetric> {
+        Box::new(self.clone())
+    }
+}
@@ -0,0 +1,89 @@
+//! The skipgram metric [`SameFingerSkipgrams`] incurrs a cost for skipgrams that use the same finger
+//! for different keys. The cost decays with the skip distance (the number of keys in between) by a
+//! configurable factor per distance and may be multiplied with finger-individual factors.
+//!
+//! *Note:* In contrast to the trigram metric `oxey_dsfbs`, the skipgrams are collected directly
+//! from the corpus. Therefore, they are not affected by the reduction of the trigrams to the most
+//! common ones and may have larger skip distances than one.
+
+use super::SkipgramMetric;
+
+use ahash::AHashMap;
+use keyboard_layout::{
+    key::{Finger, FingerMap},
+    layout::{LayerKey, Layout},
+};
+
+use serde::Deserialize;
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct Parameters {
+    /// Factor for each skip distance, starting with a distance of one (skipgrams with larger
+    /// distances than listed incur no cost)
+    pub skip_factors: Vec<f64>,
+    pub finger_factors: AHashMap<Finger, f64>,
+    pub exclude_thumbs: bool,
+    pub exclude_modifiers: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct SameFingerSkipgrams {
+    skip_factors: Vec<f64>,
+    finger_factors: FingerMap<f64>,
+    exclude_thumbs: bool,
+    exclude_modifiers: bool,
+}
+
+impl SameFingerSkipgrams {
+    pub fn new(params: &Parameters) -> Self {
+        Self {
+            skip_factors: params.skip_factors.clone(),
+            finger_factors: FingerMap::with_hashmap(&params.finger_factors, 1.0),
+            exclude_thumbs: params.exclude_thumbs,
+            exclude_modifiers: params.exclude_modifiers,
+        }
+    }
+}
+
+impl SkipgramMetric for SameFingerSkipgrams {
+    fn name(&self) -> &str {
+        "Same Finger Skipgrams"
+    }
+
+    fn is_additive(&self) -> bool {
+        true
+    }
+
+    #[inline(always)]
+    fn individual_cost(
+        &self,
+        k1: &LayerKey,
+        k2: &LayerKey,
+        skip: usize,
+        weight: f64,
+        _total_weight: f64,
+        _layout: &Layout,
+    ) -> Option<f64> {
+        let skip_factor = match skip.checked_sub(1).and_then(|i| self.skip_factors.get(i)) {
+            Some(f) => *f,
+            None => return Some(0.0),
+        };
+
+        if self.exclude_modifiers && (k1.is_modifier.is_some() || k2.is_modifier.is_some()) {
+            return Some(0.0);
+        }
+
+        // no same-key skipgrams
+        if k1 == k2 || k1.key.hand != k2.key.hand || k1.key.finger != k2.key.finger {
+            return Some(0.0);
+        }
+
+        if self.exclude_thumbs && k1.key.finger == Finger::Thumb {
+            return Some(0.0);
+        }
+
+        let finger_factor = self.finger_factors.get(&k1.key.finger);
+
+        Some(weight * skip_factor * finger_factor)
+    }
+}
@@ -0,0 +1,197 @@
+//! The `tabulated` module provides wrappers around "key-based" bigram and trigram metrics
+//! (see [`BigramMetric::is_key_based`] and [`TrigramMetric::is_key_based`]). Their costs are
+//! precomputed for all pairs (triples) of keys of a keyboard once, such that evaluating a bigram
+//! (trigram) becomes a table lookup.
+//!
+//! The table is generated upon the first evaluation and is only valid for the keyboard of that
+//! layout. Ngrams of layouts with other keyboards, ngrams involving modifiers, and ngrams with
+//! symbols from higher layers are evaluated with the wrapped metric directly.
+//!
+//! A trigram table of a keyboard with 72 keys holds about 370k entries (3 MB). The benchmark
+//! `benches/tabulation.rs` compares the lookups with evaluating the metrics directly.
+
+use super::{bigram_metrics::BigramMetric, trigram_metrics::TrigramMetric};
+
+use keyboard_layout::{
+    keyboard::Keyboard,
+    layout::{LayerKey, LayerModifierType, LayerModifiers, Layout},
+};
+
+use once_cell::sync::OnceCell;
+use std::sync::Arc;
+
+/// Costs (per unit weight) of all combinations of `N` keys of a keyboard
+#[derive(Debug)]
+struct KeyCostTable<const N: usize> {
+    keyboard: Arc<Keyboard>,
+    n_keys: usize,
+    costs: Vec<f64>,
+}
+
+impl<const N: usize> KeyCostTable<N> {
+    /// Compute the table for the keyboard of given layout.
+    fn new(layout: &Layout, cost: impl Fn([&LayerKey; N], &Layout) -> f64) -> Self {
+        let keyboard = layout.keyboard.clone();
+        let n_keys = keyboard.keys.len();
+
+        // "neutral" LayerKeys of the base layer without any symbol
+        let layerkeys: Vec<LayerKey> = keyboard
+            .keys
+            .iter()
+            .map(|key| {
+                LayerKey::new(
+                    0,
+                    key.clone(),
+                    '\0',
+                    LayerModifiers::Hold(Vec::new()),
+                    false,
+                    LayerModifierType::None,
+                )
+            })
+            .collect();
+
+        // the last key varies fastest (row-major order, see `index`)
+        let costs = (0..n_keys.pow(N as u32))
+            .map(|mut i| {
+                let mut keys = [&layerkeys[0]; N];
+                for key in keys.iter_mut().rev() {
+                    *key = &layerkeys[i % n_keys];
+                    i /= n_keys;
+                }
+                cost(keys, layout)
+            })
+            .collect();
+
+        Self {
+            keyboard,
+            n_keys,
+            costs,
+        }
+    }
+
+    /// Look up the cost of the ngram if it is covered by the table.
+    #[inline(always)]
+    fn get(&self, keys: [&LayerKey; N], layout: &Layout) -> Option<f64> {
+        if !Arc::ptr_eq(&self.keyboard, &layout.keyboard)
+            || keys.iter().any(|k| k.layer != 0 || k.is_modifier.is_some())
+        {
+            return None;
+        }
+
+        let index = keys
+            .iter()
+            .fold(0, |index, k| index * self.n_keys + k.key.index as usize);
+        Some(self.costs[index])
+    }
+}
+
+/// A key-based bigram metric whose costs are looked up in a precomputed table
+#[derive(Clone, Debug)]
+pub struct TabulatedBigramMetric {
+    metric: Box<dyn BigramMetric>,
+    table: Arc<OnceCell<KeyCostTable<2>>>,
+}
+
+impl TabulatedBigramMetric {
+    pub fn new(metric: Box<dyn BigramMetric>) -> Self {
+        Self {
+            metric,
+            table: Arc::new(OnceCell::new()),
+        }
+    }
+}
+
+impl BigramMetric for TabulatedBigramMetric {
+    fn name(&self) -> &str {
+        self.metric.name()
+    }
+
+    #[inline(always)]
+    fn individual_cost(
+        &self,
+        k1: &LayerKey,
+        k2: &LayerKey,
+        weight: f64,
+        total_weight: f64,
+        layout: &Layout,
+    ) -> Option<f64> {
+        let table = self.table.get_or_init(|| {
+            KeyCostTable::new(layout, |[k1, k2], layout| {
+                self.metric
+                    .individual_cost(k1, k2, 1.0, 1.0, layout)
+                    .unwrap_or(0.0)
+            })
+        });
+
+        match table.get([k1, k2], layout) {
+            Some(cost) => Some(weight * cost),
+            None => self
+                .metric
+                .individual_cost(k1, k2, weight, total_weight, layout),
+        }
+    }
+
+    fn is_additive(&self) -> bool {
+        self.metric.is_additive()
+    }
+
+    fn is_key_based(&self) -> bool {
+        true
+    }
+}
+
+/// A key-based trigram metric whose costs are looked up in a precomputed table
+#[derive(Clone, Debug)]
+pub struct TabulatedTrigramMetric {
+    metric: Box<dyn TrigramMetric>,
+    table: Arc<OnceCell<KeyCostTable<3>>>,
+}
+
+impl TabulatedTrigramMetric {
+    pub fn new(metric: Box<dyn TrigramMetric>) -> Self {
+        Self {
+            metric,
+            table: Arc::new(OnceCell::new()),
+        }
+    }
+}
+
+impl TrigramMetric for TabulatedTrigramMetric {
+    fn name(&self) -> &str {
+        self.metric.name()
+    }
+
+    #[inline(always)]
+    fn individual_cost(
+        &self,
+        k1: &LayerKey,
+        k2: &LayerKey,
+        k3: &LayerKey,
+        weight: f64,
+        total_weight: f64,
+        layout: &Layout,
+    ) -> Option<f64> {
+        let table = self.table.get_or_init(|| {
+            KeyCostTable::new(layout, |[k1, k2, k3], layout| {
+                self.metric
+                    .individual_cost(k1, k2, k3, 1.0, 1.0, layout)
+                    .unwrap_or(0.0)
+            })
+        });
+
+        match table.get([k1, k2, k3], layout) {
+            Some(cost) => Some(weight * cost),
+            None => self
+                .metric
+                .individual_cost(k1, k2, k3, weight, total_weight, layout),
+        }
+    }
+
+    fn is_additive(&self) -> bool {
+        self.metric.is_additive()
+    }
+
+    fn is_key_based(&self) -> bool {
+        true
+    }
+}
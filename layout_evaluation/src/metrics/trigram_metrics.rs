@@ -3,7 +3,7 @@ use keyboard_layout::layout::{LayerKey, Layout};
 
 use ordered_float::OrderedFloat;
 use priority_queue::DoublePriorityQueue;
-use std::{env, fmt};
+use std::fmt;
 
 pub mod irregularity;
 pub mod no_handswitch_in_trigram;
@@ -16,6 +16,7 @@ pub mod oxey_inward_rolls;
 pub mod oxey_onehands;
 pub mod oxey_outward_rolls;
 pub mod oxey_redirects;
+pub mod scripted_trigram;
 pub mod secondary_bigrams;
 pub mod trigram_finger_repeats;
 pub mod trigram_rolls;
@@ -39,23 +40,32 @@ pub trait TrigramMetric: Send + Sync + TrigramMetricClone + fmt::Debug {
         None
     }
 
-    /// Compute the total cost for the metric.
+    /// Whether the total cost is the sum of the individual costs of all trigrams and each individual cost
+    /// is proportional to the trigram's weight (independently of all other trigrams). Only such metrics
+    /// can be evaluated incrementally for small changes of a layout (see `Evaluator::evaluate_layout_delta`).
+    fn is_additive(&self) -> bool {
+        false
+    }
+
+    /// Whether the cost of a trigram of non-modifier keys from the base layer only depends on the keys
+    /// (not on the symbols or the total weight) and is proportional to the trigram's weight. The costs
+    /// of such metrics are precomputed for all combinations of keys by the `Evaluator`, such that
+    /// evaluating a trigram is a table lookup (see the `tabulated` module). This requires the default
+    /// implementation of `total_cost`.
+    fn is_key_based(&self) -> bool {
+        false
+    }
+
+    /// Compute the total cost for the metric. The optional message lists the `n_worst` trigrams with the
+    /// highest costs (no message is generated if `n_worst` is zero).
     fn total_cost(
         &self,
         trigrams: &[((&LayerKey, &LayerKey, &LayerKey), f64)],
         // total_weight is optional for performance reasons (it can be computed from trigrams)
         total_weight: Option<f64>,
         layout: &Layout,
+        n_worst: usize,
     ) -> (f64, Option<String>) {
-        let show_worst: bool = env::var("SHOW_WORST")
-            .ok()
-            .and_then(|s| s.parse().ok())
-            .unwrap_or(true);
-        let n_worst: usize = env::var("N_WORST")
-            .ok()
-            .and_then(|s| s.parse().ok())
-            .unwrap_or(3);
-
         let total_weight = total_weight.unwrap_or_else(|| trigrams.iter().map(|(_, w)| w).sum());
         let cost_iter = trigrams
             .iter()
@@ -73,7 +83,7 @@ pub trait TrigramMetric: Send + Sync + TrigramMetricClone + fmt::Debug {
                 cost_option.map(|cost| (i, trigram, cost))
             });
 
-        let (total_cost, msg) = if show_worst {
+        let (total_cost, msg) = if n_worst > 0 {
             let (total_cost, worst, worst_nonfixed) = cost_iter.fold(
                 (0.0, DoublePriorityQueue::new(), DoublePriorityQueue::new()),
                 |(mut total_cost, mut worst, mut worst_nonfixed), (i, trigram, cost)| {
@@ -104,9 +114,9 @@ pub trait TrigramMetric: Send + Sync + TrigramMetricClone + fmt::Debug {
                         let (gram, _) = trigrams[i];
                         format!(
                             "{}{}{} ({:>5.2}%)",
-                            gram.0,
-                            gram.1,
-                            gram.2,
+                            layout.layerkey_label(gram.0),
+                            layout.layerkey_label(gram.1),
+                            layout.layerkey_label(gram.2),
                             100.0 * cost.into_inner() / total_cost,
                         )
                     })
@@ -13,7 +13,6 @@ use keyboard_layout::layout::{LayerKey, Layout};
 use ordered_float::OrderedFloat;
 use priority_queue::DoublePriorityQueue;
 use serde::Deserialize;
-use std::env;
 
 #[derive(Clone, Deserialize, Debug)]
 pub struct Parameters {}
@@ -73,16 +72,8 @@ impl TrigramMetric for Irregularity {
         trigrams: &[((&LayerKey, &LayerKey, &LayerKey), f64)],
         total_weight: Option<f64>,
         layout: &Layout,
+        n_worst: usize,
     ) -> (f64, Option<String>) {
-        let show_worst: bool = env::var("SHOW_WORST")
-            .ok()
-            .and_then(|s| s.parse().ok())
-            .unwrap_or(true);
-        let n_worst: usize = env::var("N_WORST")
-            .ok()
-            .and_then(|s| s.parse().ok())
-            .unwrap_or(3);
-
         // NOTE: ArneBab's solution does not involve all bigram metrics (the asymmetric bigrams metric is missing)
 
         let total_weight = total_weight.unwrap_or_else(|| trigrams.iter().map(|(_, w)| w).sum());
@@ -102,7 +93,7 @@ impl TrigramMetric for Irregularity {
                 cost_option.map(|cost| (i, trigram, cost))
             });
 
-        let (total_cost, msg) = if show_worst {
+        let (total_cost, msg) = if n_worst > 0 {
             let (total_cost, worst, worst_nonfixed) = cost_iter.fold(
                 (0.0, DoublePriorityQueue::new(), DoublePriorityQueue::new()),
                 |(mut total_cost, mut worst, mut worst_nonfixed), (i, trigram, cost)| {
@@ -133,9 +124,9 @@ impl TrigramMetric for Irregularity {
                         let (gram, _) = trigrams[i];
                         format!(
                             "{}{}{} ({:>5.2}%)",
-                            gram.0,
-                            gram.1,
-                            gram.2,
+                            layout.layerkey_label(gram.0),
+                            layout.layerkey_label(gram.1),
+                            layout.layerkey_label(gram.2),
                             100.0 * cost.into_inner() / total_cost,
                         )
                     })
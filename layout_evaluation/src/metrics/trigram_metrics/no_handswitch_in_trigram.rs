@@ -54,6 +54,14 @@ impl TrigramMetric for NoHandswitchInTrigram {
         "No Handswitch in Trigram"
     }
 
+    fn is_additive(&self) -> bool {
+        true
+    }
+
+    fn is_key_based(&self) -> bool {
+        true
+    }
+
     #[inline(always)]
     fn individual_cost(
         &self,
@@ -37,6 +37,10 @@ impl TrigramMetric for OxeyAlternates {
         "Alternates"
     }
 
+    fn is_additive(&self) -> bool {
+        true
+    }
+
     #[inline(always)]
     fn individual_cost(
         &self,
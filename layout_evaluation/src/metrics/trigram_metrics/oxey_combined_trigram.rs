@@ -185,6 +185,7 @@ impl TrigramMetric for OxeyCombinedTrigram {
         // total_weight is optional for performance reasons (it can be computed from trigrams)
         _total_weight: Option<f64>,
         _layout: &Layout,
+        _n_worst: usize,
     ) -> (f64, Option<String>) {
         let mut counts = TrigramTypeCounts::default();
 
@@ -46,6 +46,10 @@ impl TrigramMetric for OxeyRedirects {
         "Redirects"
     }
 
+    fn is_additive(&self) -> bool {
+        true
+    }
+
     #[inline(always)]
     fn individual_cost(
         &self,
@@ -48,6 +48,12 @@ impl TrigramMetric for SecondaryBigrams {
         "Secondary Bigrams"
     }
 
+    fn is_additive(&self) -> bool {
+        self.bigram_metrics
+            .iter()
+            .all(|(_, _, metric)| metric.is_additive())
+    }
+
     #[inline(always)]
     fn individual_cost(
         &self,
@@ -37,6 +37,14 @@ impl TrigramMetric for TrigramFingerRepeats {
         "Trigram Finger Repeats"
     }
 
+    fn is_additive(&self) -> bool {
+        true
+    }
+
+    fn is_key_based(&self) -> bool {
+        true
+    }
+
     #[inline(always)]
     fn individual_cost(
         &self,
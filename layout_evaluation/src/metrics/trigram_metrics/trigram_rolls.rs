@@ -39,6 +39,14 @@ impl TrigramMetric for TrigramRolls {
         "Trigram Rolls"
     }
 
+    fn is_additive(&self) -> bool {
+        true
+    }
+
+    fn is_key_based(&self) -> bool {
+        true
+    }
+
     #[inline(always)]
     fn individual_cost(
         &self,
@@ -76,17 +84,17 @@ impl TrigramMetric for TrigramRolls {
         }
 
         // only allow rolls with keys that are directly next to each others
-        let inward1 = (k1.key.hand == Hand::Left && pos1.0 == pos2.0 - 1)
+        let inward1 = (k1.key.hand == Hand::Left && pos1.0 + 1 == pos2.0)
             || (k1.key.hand == Hand::Right && pos1.0 == pos2.0 + 1);
 
-        let inward2 = (k2.key.hand == Hand::Left && pos2.0 == pos3.0 - 1)
+        let inward2 = (k2.key.hand == Hand::Left && pos2.0 + 1 == pos3.0)
             || (k2.key.hand == Hand::Right && pos2.0 == pos3.0 + 1);
 
         let outward1 = (k1.key.hand == Hand::Left && pos1.0 == pos2.0 + 1)
-            || (k1.key.hand == Hand::Right && pos1.0 == pos2.0 - 1);
+            || (k1.key.hand == Hand::Right && pos1.0 + 1 == pos2.0);
 
         let outward2 = (k2.key.hand == Hand::Left && pos2.0 == pos3.0 + 1)
-            || (k2.key.hand == Hand::Right && pos2.0 == pos3.0 - 1);
+            || (k2.key.hand == Hand::Right && pos2.0 + 1 == pos3.0);
 
         // both bigrams need to have the same direction
         let mut cost = if inward1 && inward2 {
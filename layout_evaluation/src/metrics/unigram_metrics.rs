@@ -3,7 +3,7 @@ use keyboard_layout::layout::{LayerKey, Layout};
 use ordered_float::OrderedFloat;
 use priority_queue::DoublePriorityQueue;
 
-use std::{env, fmt};
+use std::fmt;
 
 pub mod finger_balance;
 pub mod hand_disbalance;
@@ -28,23 +28,23 @@ pub trait UnigramMetric: Send + Sync + UnigramMetricClone + fmt::Debug {
         None
     }
 
-    /// Compute the total cost for the metric.
+    /// Whether the total cost is the sum of the individual costs of all unigrams and each individual cost
+    /// is proportional to the unigram's weight (independently of all other unigrams). Only such metrics
+    /// can be evaluated incrementally for small changes of a layout (see `Evaluator::evaluate_layout_delta`).
+    fn is_additive(&self) -> bool {
+        false
+    }
+
+    /// Compute the total cost for the metric. The optional message lists the `n_worst` unigrams with the
+    /// highest costs (no message is generated if `n_worst` is zero).
     fn total_cost(
         &self,
         unigrams: &[(&LayerKey, f64)],
         // total_weight is optional for performance reasons (it can be computed from unigrams)
         total_weight: Option<f64>,
         layout: &Layout,
+        n_worst: usize,
     ) -> (f64, Option<String>) {
-        let show_worst: bool = env::var("SHOW_WORST")
-            .ok()
-            .and_then(|s| s.parse().ok())
-            .unwrap_or(true);
-        let n_worst: usize = env::var("N_WORST")
-            .ok()
-            .and_then(|s| s.parse().ok())
-            .unwrap_or(3);
-
         let total_weight = total_weight.unwrap_or_else(|| unigrams.iter().map(|(_, w)| w).sum());
         let cost_iter = unigrams
             .iter()
@@ -55,7 +55,7 @@ pub trait UnigramMetric: Send + Sync + UnigramMetricClone + fmt::Debug {
                 cost_option.map(|cost| (i, unigram, cost))
             });
 
-        let (total_cost, msg) = if show_worst {
+        let (total_cost, msg) = if n_worst > 0 {
             let (total_cost, worst) = cost_iter.fold(
                 (0.0, DoublePriorityQueue::new()),
                 |(mut total_cost, mut worst), (i, _, cost)| {
@@ -79,7 +79,7 @@ pub trait UnigramMetric: Send + Sync + UnigramMetricClone + fmt::Debug {
                     let (gram, _) = unigrams[i];
                     format!(
                         "{} ({:>5.2}%)",
-                        gram,
+                        layout.layerkey_label(gram),
                         100.0 * cost.into_inner() / total_cost,
                     )
                 })
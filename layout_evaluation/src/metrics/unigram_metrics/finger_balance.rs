@@ -51,6 +51,7 @@ impl UnigramMetric for FingerBalance {
         unigrams: &[(&LayerKey, f64)],
         _total_weight: Option<f64>,
         _layout: &Layout,
+        _n_worst: usize,
     ) -> (f64, Option<String>) {
         let mut finger_loads: HandFingerMap<f64> = HandFingerMap::with_default(0.0);
 
@@ -32,6 +32,7 @@ impl UnigramMetric for HandDisbalance {
         unigrams: &[(&LayerKey, f64)],
         _total_weight: Option<f64>,
         _layout: &Layout,
+        _n_worst: usize,
     ) -> (f64, Option<String>) {
         let mut hand_loads: HandMap<f64> = HandMap::default();
         let mut total_weight = 0.0;
@@ -1,6 +1,9 @@
 //! The unigram metric [`KeyCost`] multiplies each unigram's weight with the key cost
 //! of the corresponding key (as configured for the [`Keyboard`]) and the associated
 //! layer cost (as configured for the [`Layout`]).
+//!
+//! *Note:* Chorded symbols are split into their constituent keys by the ngram mapper, so
+//! the costs of all keys of a chord add up.
 
 use super::UnigramMetric;
 
@@ -25,6 +28,10 @@ impl UnigramMetric for KeyCost {
         "Key Costs"
     }
 
+    fn is_additive(&self) -> bool {
+        true
+    }
+
     #[inline(always)]
     fn individual_cost(
         &self,
@@ -35,6 +35,10 @@ impl UnigramMetric for ModifierUsage {
         "Modifier Usage"
     }
 
+    fn is_additive(&self) -> bool {
+        true
+    }
+
     #[inline(always)]
     fn individual_cost(
         &self,
@@ -56,6 +60,8 @@ impl UnigramMetric for ModifierUsage {
             LayerModifiers::Hold(v) => self.hold_cost * v.len() as f64,
             LayerModifiers::OneShot(v) => self.one_shot_cost * v.len() as f64,
             LayerModifiers::LongPress => self.long_press_cost,
+            // the other keys of a chord and dead keys are no modifiers
+            LayerModifiers::Chord(_) | LayerModifiers::DeadKey(_) => 0.0,
         };
 
         Some(weight * (key_cost + modifier_costs))
@@ -35,6 +35,7 @@ impl UnigramMetric for RowLoads {
         unigrams: &[(&LayerKey, f64)],
         _total_weight: Option<f64>,
         _layout: &Layout,
+        _n_worst: usize,
     ) -> (f64, Option<String>) {
         let mut row_load: AHashMap<u8, f64> = AHashMap::default();
         let mut total_weight = 0.0;
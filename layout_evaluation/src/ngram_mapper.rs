@@ -30,14 +30,27 @@
 //! of the involved base-keys and modifiers. Keys from the latter parts of the trigram will always be after
 //! former ones and modifers always come before their base key. The number of generated trigrams from a single
 //! trigram can be large (tens of trigrams) if multiple symbols of the trigram are accessed using multiple modifiers.
+//!
+//! Quadgrams are expanded in the same way as trigrams.
+//!
+//! If a symbol can be generated by several keys (e.g. space on both thumbs), the key with the lowest cost
+//! is used by default. Optionally, the keys of each bigram, trigram, and quadgram are chosen depending on
+//! their neighbors in the ngram (see [`on_demand_ngram_mapper::KeyChoiceConfig`]), and the ngram's weight
+//! is attributed to the chosen keys.
+//!
+//! Skipgrams (the first and last symbol of ngrams with other symbols in between) are expanded in the
+//! same way as bigrams.
 
 pub mod bigram_mapper;
 pub mod common;
+pub mod quadgram_mapper;
 pub mod trigram_mapper;
 pub mod unigram_mapper;
 
 pub mod on_demand_ngram_mapper;
 
+use crate::ngrams::{Bigrams, Quadgrams, Skipgrams, Trigrams, Unigrams};
+
 use keyboard_layout::layout::{LayerKey, Layout};
 
 use std::fmt;
@@ -75,11 +88,101 @@ pub struct MappedTrigrams<'s> {
     pub weight_found: f64,
 }
 
+/// A quadgram in terms of a [`Layout`]'s [`LayerKey`]s
+pub type LayerKeyQuadgram<'s> = (&'s LayerKey, &'s LayerKey, &'s LayerKey, &'s LayerKey);
+
+/// Quadgrams in terms of a [`Layout`]'s [`LayerKey`]s and statistics about ngrams that
+/// can not be generated by the layout.
+pub struct MappedQuadgrams<'s> {
+    /// Quadgrams in terms of [`LayerKey`]s
+    pub grams: Vec<(LayerKeyQuadgram<'s>, f64)>,
+    /// Total weight (frequencies) of quadgrams that can not be generated by the layout
+    pub weight_not_found: f64,
+    /// Total weight (frequencies) of quadgrams that can be generated by the layout
+    pub weight_found: f64,
+}
+
+/// A skipgram in terms of a [`Layout`]'s [`LayerKey`]s together with its skip distance
+/// (the number of symbols in between)
+pub type LayerKeySkipgram<'s> = (&'s LayerKey, &'s LayerKey, usize);
+
+/// Skipgrams (of all available skip distances) in terms of a [`Layout`]'s [`LayerKey`]s and
+/// statistics about ngrams that can not be generated by the layout.
+pub struct MappedSkipgrams<'s> {
+    /// Skipgrams in terms of [`LayerKey`]s
+    pub grams: Vec<(LayerKeySkipgram<'s>, f64)>,
+    /// Total weight (frequencies) of skipgrams that can not be generated by the layout
+    pub weight_not_found: f64,
+    /// Total weight (frequencies) of skipgrams that can be generated by the layout
+    pub weight_found: f64,
+}
+
 /// Provides ngrams in terms of a [`Layout`]'s [`LayerKey`]s.
 pub trait NgramMapper: Send + Sync + NgramMapperClone + fmt::Debug {
     fn map_unigrams<'s>(&self, layout: &'s Layout) -> MappedUnigrams<'s>;
     fn map_bigrams<'s>(&self, layout: &'s Layout) -> MappedBigrams<'s>;
     fn map_trigrams<'s>(&self, layout: &'s Layout) -> MappedTrigrams<'s>;
+    fn map_quadgrams<'s>(&self, layout: &'s Layout) -> MappedQuadgrams<'s>;
+    fn map_skipgrams<'s>(&self, layout: &'s Layout) -> MappedSkipgrams<'s>;
+
+    /// The char-based unigrams that are mapped.
+    fn unigrams(&self) -> &Unigrams;
+    /// The char-based bigrams that are mapped.
+    fn bigrams(&self) -> &Bigrams;
+    /// The char-based trigrams that are mapped.
+    fn trigrams(&self) -> &Trigrams;
+    /// The char-based quadgrams that are mapped.
+    fn quadgrams(&self) -> &Quadgrams;
+    /// The char-based skipgrams (one entry per skip distance) that are mapped.
+    fn skipgrams(&self) -> &[Skipgrams];
+
+    /// Map a single char-based unigram, appending the resulting unigrams to `grams` (without aggregating
+    /// identical ones). Returns `false` if the unigram can not be generated by the layout.
+    fn map_single_unigram<'s>(
+        &self,
+        unigram: &char,
+        weight: f64,
+        layout: &'s Layout,
+        grams: &mut Vec<(&'s LayerKey, f64)>,
+    ) -> bool;
+    /// Map a single char-based bigram, appending the resulting bigrams to `grams` (without aggregating
+    /// identical ones). Returns `false` if the bigram can not be generated by the layout.
+    fn map_single_bigram<'s>(
+        &self,
+        bigram: &(char, char),
+        weight: f64,
+        layout: &'s Layout,
+        grams: &mut Vec<((&'s LayerKey, &'s LayerKey), f64)>,
+    ) -> bool;
+    /// Map a single char-based trigram, appending the resulting trigrams to `grams` (without aggregating
+    /// identical ones). Returns `false` if the trigram can not be generated by the layout.
+    fn map_single_trigram<'s>(
+        &self,
+        trigram: &(char, char, char),
+        weight: f64,
+        layout: &'s Layout,
+        grams: &mut Vec<((&'s LayerKey, &'s LayerKey, &'s LayerKey), f64)>,
+    ) -> bool;
+    /// Map a single char-based quadgram, appending the resulting quadgrams to `grams` (without aggregating
+    /// identical ones). Returns `false` if the quadgram can not be generated by the layout.
+    fn map_single_quadgram<'s>(
+        &self,
+        quadgram: &(char, char, char, char),
+        weight: f64,
+        layout: &'s Layout,
+        grams: &mut Vec<(LayerKeyQuadgram<'s>, f64)>,
+    ) -> bool;
+    /// Map a single char-based skipgram (with given skip distance), appending the resulting skipgrams
+    /// to `grams` (without aggregating identical ones). Returns `false` if the skipgram can not be
+    /// generated by the layout.
+    fn map_single_skipgram<'s>(
+        &self,
+        skipgram: &(char, char),
+        skip: usize,
+        weight: f64,
+        layout: &'s Layout,
+        grams: &mut Vec<(LayerKeySkipgram<'s>, f64)>,
+    ) -> bool;
 }
 
 // in order to implement clone for Box<dyn LayoutMetric>, the following trick is necessary
@@ -4,9 +4,12 @@
 //! Note: In contrast to ArneBab's algorithm, here all trigrams will be used
 //! for secondary bigrams. Not only those that lead to same-hand bigrams.
 
-use super::{common::*, on_demand_ngram_mapper::SplitModifiersConfig};
+use super::{
+    common::*,
+    on_demand_ngram_mapper::{KeyChoiceConfig, SplitModifiersConfig},
+};
 
-use crate::ngrams::Bigrams;
+use crate::ngrams::interned::SymbolId;
 
 use keyboard_layout::layout::{LayerKey, LayerKeyIndex, LayerModifiers, Layout};
 
@@ -18,78 +21,129 @@ use ahash::AHashMap;
 type BigramIndices = AHashMap<(LayerKeyIndex, LayerKeyIndex), f64>;
 type BigramIndicesVec = Vec<((LayerKeyIndex, LayerKeyIndex), f64)>;
 
-/// Turns the [`Bigrams`]'s characters into their indices, returning a [`BigramIndicesVec`].
+/// Turns the interned bigrams into their [`LayerKeyIndex`]s (given for each symbol in `symbol_keys`
+/// or chosen among alternatives, see [`choose_alternative_keys`]), returning a [`BigramIndicesVec`].
 fn map_bigrams(
-    bigrams: &Bigrams,
+    bigrams: &[((SymbolId, SymbolId), f64)],
+    symbol_keys: &[Option<LayerKeyIndex>],
     layout: &Layout,
-    exclude_line_breaks: bool,
+    key_choice: &KeyChoiceConfig,
 ) -> (BigramIndicesVec, f64) {
     let mut not_found_weight = 0.0;
-    let mut bigrams_vec: BigramIndicesVec = Vec::with_capacity(bigrams.grams.len());
-
-    bigrams_vec.extend(
-        bigrams
-            .grams
-            .iter()
-            //.filter(|((c1, c2), _weight)| !c1.is_whitespace() && !c2.is_whitespace())
-            .filter_map(|((c1, c2), weight)| {
-                // Exclude bigrams that contain a line break, followed by a non-line-break character
-                if exclude_line_breaks && *c1 == '\n' && *c2 != '\n' {
-                    return None;
-                }
-
-                let idx1 = match layout.get_layerkey_index_for_symbol(c1) {
-                    Some(idx) => idx,
-                    None => {
-                        not_found_weight += *weight;
-                        return None;
-                    }
-                };
-                let idx2 = match layout.get_layerkey_index_for_symbol(c2) {
-                    Some(idx) => idx,
-                    None => {
-                        not_found_weight += *weight;
-                        return None;
-                    }
-                };
-
-                Some(((idx1, idx2), *weight))
-            }),
-    );
+    let mut bigrams_vec: BigramIndicesVec = Vec::with_capacity(bigrams.len());
+
+    bigrams_vec.extend(bigrams.iter().filter_map(|((s1, s2), weight)| {
+        match (symbol_keys[*s1 as usize], symbol_keys[*s2 as usize]) {
+            (Some(idx1), Some(idx2)) => {
+                let mut keys = [idx1, idx2];
+                choose_alternative_keys(&mut keys, layout, key_choice);
+                Some(((keys[0], keys[1]), *weight))
+            }
+            _ => {
+                not_found_weight += *weight;
+                None
+            }
+        }
+    }));
 
     (bigrams_vec, not_found_weight)
 }
 
+/// Exclude bigrams that contain a line break, followed by a non-line-break character
+#[inline(always)]
+pub fn is_excluded((c1, c2): &(char, char), exclude_line_breaks: bool) -> bool {
+    exclude_line_breaks && *c1 == '\n' && *c2 != '\n'
+}
+
+/// Turns a bigram's characters into their indices (if both can be generated by the layout).
+#[inline(always)]
+fn map_bigram(
+    (c1, c2): &(char, char),
+    layout: &Layout,
+    key_choice: &KeyChoiceConfig,
+) -> Option<(LayerKeyIndex, LayerKeyIndex)> {
+    let mut keys = [
+        layout.get_layerkey_index_for_symbol(c1)?,
+        layout.get_layerkey_index_for_symbol(c2)?,
+    ];
+    choose_alternative_keys(&mut keys, layout, key_choice);
+
+    Some((keys[0], keys[1]))
+}
+
+/// Resolves &[`LayerKey`] references for a [`LayerKeyIndex`]-based bigram unless it contains
+/// repeating identical modifiers.
+#[inline(always)]
+fn filtered_layerkeys<'s>(
+    (idx1, idx2): &(LayerKeyIndex, LayerKeyIndex),
+    w: f64,
+    layout: &'s Layout,
+) -> Option<((&'s LayerKey, &'s LayerKey), f64)> {
+    let k1 = layout.get_layerkey(idx1);
+
+    // If the same modifier appears consecutively, it is usually "hold" instead of repeatedly pressed
+    // --> remove
+    match k1.is_modifier.is_hold() && idx1 == idx2 {
+        false => Some((
+            (
+                k1,                        // LayerKey 1
+                layout.get_layerkey(idx2), // LayerKey 2
+            ),
+            w,
+        )),
+        true => None,
+    }
+}
+
 /// Generates [`LayerKey`]-based [Bigrams] from char-based unigrams. Optionally resolves modifiers
 /// for higher-layer symbols of the layout.
 #[derive(Clone, Debug)]
 pub struct OnDemandBigramMapper {
     split_modifiers: SplitModifiersConfig,
+    key_choice: KeyChoiceConfig,
 }
 
 impl OnDemandBigramMapper {
-    pub fn new(split_modifiers: SplitModifiersConfig) -> Self {
-        Self { split_modifiers }
+    pub fn new(split_modifiers: SplitModifiersConfig, key_choice: KeyChoiceConfig) -> Self {
+        Self {
+            split_modifiers,
+            key_choice,
+        }
     }
 
-    /// For a given [`Layout`] generate [`LayerKeyIndex`]-based unigrams, optionally resolving modifiers for higer-layer symbols.
+    /// For a given [`Layout`] generate [`LayerKeyIndex`]-based bigrams from interned ones, optionally
+    /// resolving modifiers for higer-layer symbols. `symbol_keys` holds the [`LayerKeyIndex`] for each symbol id.
     pub fn layerkey_indices(
         &self,
-        bigrams: &Bigrams,
+        bigrams: &[((SymbolId, SymbolId), f64)],
+        symbol_keys: &[Option<LayerKeyIndex>],
         layout: &Layout,
-        exclude_line_breaks: bool,
-    ) -> (BigramIndices, f64) {
+    ) -> (BigramIndicesVec, f64) {
         let (mut bigram_keys_vec, not_found_weight) =
-            map_bigrams(bigrams, layout, exclude_line_breaks);
+            map_bigrams(bigrams, symbol_keys, layout, &self.key_choice);
+
+        if layout.has_dead_keys() {
+            bigram_keys_vec = self.process_dead_keys(bigram_keys_vec, layout);
+        }
 
         if layout.has_one_shot_layers() {
             bigram_keys_vec = self.process_one_shot_modifiers(bigram_keys_vec, layout);
         }
 
-        let bigram_keys = if self.split_modifiers.enabled && layout.has_hold_layers() {
+        // Each symbol corresponds to a different LayerKey, so bigrams only need to be aggregated
+        // if they were split or processed otherwise.
+        let bigram_keys = if has_held_keys(layout, self.split_modifiers.enabled) {
             self.process_hold_modifiers(bigram_keys_vec, layout)
+                .into_iter()
+                .collect()
+        } else if layout.has_dead_keys() || layout.has_one_shot_layers() {
+            let mut bigram_w_map = BigramIndices::with_capacity(bigram_keys_vec.len());
+            bigram_keys_vec
+                .into_iter()
+                .for_each(|(bigram, w)| bigram_w_map.insert_or_add_weight(bigram, w));
+            bigram_w_map.into_iter().collect()
         } else {
-            bigram_keys_vec.into_iter().collect()
+            bigram_keys_vec
         };
 
         // bigram_keys
@@ -105,89 +159,154 @@ impl OnDemandBigramMapper {
     /// Resolves &[`LayerKey`] references for [`LayerKeyIndex`] and filters bigrams that contain
     /// repeating identical modifiers.
     pub fn get_filtered_layerkeys<'s>(
-        bigrams: &BigramIndices,
+        bigrams: &[((LayerKeyIndex, LayerKeyIndex), f64)],
         layout: &'s Layout,
     ) -> Vec<((&'s LayerKey, &'s LayerKey), f64)> {
         let mut layerkeys = Vec::with_capacity(bigrams.len());
 
-        layerkeys.extend(bigrams.iter().filter_map(|((idx1, idx2), w)| {
-            let k1 = layout.get_layerkey(idx1);
-
-            // If the same modifier appears consecutively, it is usually "hold" instead of repeatedly pressed
-            // --> remove
-            match k1.is_modifier.is_hold() && idx1 == idx2 {
-                false => Some((
-                    (
-                        k1,                        // LayerKey 1
-                        layout.get_layerkey(idx2), // LayerKey 2
-                    ),
-                    *w,
-                )),
-                true => None,
-            }
-        }));
+        layerkeys.extend(
+            bigrams
+                .iter()
+                .filter_map(|(bigram, w)| filtered_layerkeys(bigram, *w, layout)),
+        );
 
         layerkeys
     }
 
+    /// Map a single char-based bigram to [`LayerKey`]-based bigrams (in the same way as
+    /// [`Self::layerkey_indices`] and [`Self::get_filtered_layerkeys`] do), appending them to `layerkeys`.
+    /// Identical resulting bigrams are not aggregated.
+    ///
+    /// Returns `false` if the bigram can not be generated by the layout.
+    pub fn map_single_bigram<'s>(
+        &self,
+        bigram: &(char, char),
+        weight: f64,
+        layout: &'s Layout,
+        exclude_line_breaks: bool,
+        layerkeys: &mut Vec<((&'s LayerKey, &'s LayerKey), f64)>,
+    ) -> bool {
+        if is_excluded(bigram, exclude_line_breaks) {
+            return true;
+        }
+
+        let indices = match map_bigram(bigram, layout, &self.key_choice) {
+            Some(indices) => indices,
+            None => return false,
+        };
+
+        let mut resolved = ResolvingNgramVec::new(layerkeys, |bigram, w| {
+            filtered_layerkeys(&bigram, w, layout)
+        });
+        let split_hold_modifiers = has_held_keys(layout, self.split_modifiers.enabled);
+
+        if layout.has_dead_keys() || layout.has_one_shot_layers() {
+            let mut bigrams = vec![(indices, weight)];
+            if layout.has_dead_keys() {
+                bigrams = self.process_dead_keys(bigrams, layout);
+            }
+            if layout.has_one_shot_layers() {
+                bigrams = self.process_one_shot_modifiers(bigrams, layout);
+            }
+
+            bigrams
+                .into_iter()
+                .for_each(|(bigram, w)| match split_hold_modifiers {
+                    true => self.split_hold_modifiers(bigram, w, layout, &mut resolved),
+                    false => resolved.insert_or_add_weight(bigram, w),
+                });
+        } else if split_hold_modifiers {
+            self.split_hold_modifiers(indices, weight, layout, &mut resolved);
+        } else {
+            resolved.insert_or_add_weight(indices, weight);
+        }
+
+        true
+    }
+
     /// Map all bigrams to base-layer bigrams, potentially generating multiple bigrams
     /// with modifiers for those with higer-layer keys.
     ///
     /// Each bigram of higher-layer symbols will transform into a series of bigrams with permutations of
     /// the involved base-keys and modifers. However, the base-key will always be after its modifier.
+    /// Chorded symbols are split in the same way with the chord's main key as base-key and the
+    /// other keys of the chord as modifiers (always, independent of whether hold modifiers shall be split).
     fn process_hold_modifiers(&self, bigrams: BigramIndicesVec, layout: &Layout) -> BigramIndices {
         let mut bigram_w_map = AHashMap::with_capacity(bigrams.len() / 3);
 
-        bigrams.into_iter().for_each(|((k1, k2), w)| {
-            let (base1, mods1) = layout.resolve_modifiers(&k1);
-            let (base2, mods2) = layout.resolve_modifiers(&k2);
-
-            let (key1, mods1) = match mods1 {
-                LayerModifiers::Hold(mods) => (base1, mods),
-                _ => (k1, Vec::new()),
-            };
+        bigrams.into_iter().for_each(|(bigram, w)| {
+            self.split_hold_modifiers(bigram, w, layout, &mut bigram_w_map);
+        });
 
-            let (key2, mods2) = match mods2 {
-                LayerModifiers::Hold(mods) => (base2, mods),
-                _ => (k2, Vec::new()),
-            };
+        bigram_w_map
+    }
 
-            bigram_w_map.insert_or_add_weight((key1, key2), w);
-            // log::trace!("{:>3}{:<3} -> {:>3}{:<3}", layout.get_layerkey(&k1).symbol, layout.get_layerkey(&k2).symbol, layout.get_layerkey(&base1).symbol, layout.get_layerkey(&base2).symbol);
+    /// Split a single bigram into base-layer bigrams (see [`Self::process_hold_modifiers`]).
+    #[inline(always)]
+    fn split_hold_modifiers<M: NgramMap<(LayerKeyIndex, LayerKeyIndex)>>(
+        &self,
+        (k1, k2): (LayerKeyIndex, LayerKeyIndex),
+        w: f64,
+        layout: &Layout,
+        bigram_w_map: &mut M,
+    ) {
+        let (key1, mods1) = resolve_held_keys(k1, layout, self.split_modifiers.enabled);
+        let (key2, mods2) = resolve_held_keys(k2, layout, self.split_modifiers.enabled);
 
-            mods1.iter().for_each(|mod1| {
-                // mix mods of k1 with base of k2
-                bigram_w_map.insert_or_add_weight((*mod1, key2), w);
-                // log::trace!("{:>3}{:<3} -> {:>3}{:<3}", layout.get_layerkey(&k1).symbol, layout.get_layerkey(&k2).symbol, layout.get_layerkey(&mod1).symbol, layout.get_layerkey(&base2).symbol);
+        bigram_w_map.insert_or_add_weight((key1, key2), w);
+        // log::trace!("{:>3}{:<3} -> {:>3}{:<3}", layout.get_layerkey(&k1).symbol, layout.get_layerkey(&k2).symbol, layout.get_layerkey(&base1).symbol, layout.get_layerkey(&base2).symbol);
 
-                // mix mods of k1 and k2
-                mods2.iter().for_each(|mod2| {
-                    bigram_w_map.insert_or_add_weight((*mod1, *mod2), w);
-                    // log::trace!("{:>3}{:<3} -> {:>3}{:<3}", layout.get_layerkey(&k1).symbol, layout.get_layerkey(&k2).symbol, layout.get_layerkey(&mod1).symbol, layout.get_layerkey(&mod2).symbol);
-                });
-            });
+        mods1.iter().for_each(|mod1| {
+            // mix mods of k1 with base of k2
+            bigram_w_map.insert_or_add_weight((*mod1, key2), w);
+            // log::trace!("{:>3}{:<3} -> {:>3}{:<3}", layout.get_layerkey(&k1).symbol, layout.get_layerkey(&k2).symbol, layout.get_layerkey(&mod1).symbol, layout.get_layerkey(&base2).symbol);
 
+            // mix mods of k1 and k2
             mods2.iter().for_each(|mod2| {
-                // mix mods of k2 with base of k1
-                bigram_w_map.insert_or_add_weight((key1, *mod2), w);
-                // log::trace!("{:>3}{:<3} -> {:>3}{:<3}", layout.get_layerkey(&k1).symbol, layout.get_layerkey(&k2).symbol, layout.get_layerkey(&base1).symbol, layout.get_layerkey(&mod2).symbol);
+                bigram_w_map.insert_or_add_weight((*mod1, *mod2), w);
+                // log::trace!("{:>3}{:<3} -> {:>3}{:<3}", layout.get_layerkey(&k1).symbol, layout.get_layerkey(&k2).symbol, layout.get_layerkey(&mod1).symbol, layout.get_layerkey(&mod2).symbol);
             });
+        });
 
-            // same key mods
-            TakeTwoLayerKey::new(key1, &mods1, w, self.split_modifiers.same_key_mod_factor)
-                .for_each(|(e, w)| {
-                    bigram_w_map.insert_or_add_weight(e, w);
-                    // log::trace!("{:>3}{:<3} -> {:>3}{:<3}", layout.get_layerkey(&k1).symbol, layout.get_layerkey(&k2).symbol, layout.get_layerkey(&e.0).symbol, layout.get_layerkey(&e.1).symbol);
-                });
+        mods2.iter().for_each(|mod2| {
+            // mix mods of k2 with base of k1
+            bigram_w_map.insert_or_add_weight((key1, *mod2), w);
+            // log::trace!("{:>3}{:<3} -> {:>3}{:<3}", layout.get_layerkey(&k1).symbol, layout.get_layerkey(&k2).symbol, layout.get_layerkey(&base1).symbol, layout.get_layerkey(&mod2).symbol);
+        });
 
-            TakeTwoLayerKey::new(key2, &mods2, w, self.split_modifiers.same_key_mod_factor)
-                .for_each(|(e, w)| {
-                    bigram_w_map.insert_or_add_weight(e, w);
-                    // log::trace!("{:>3}{:<3} -> {:>3}{:<3}", layout.get_layerkey(&k1).symbol, layout.get_layerkey(&k2).symbol, layout.get_layerkey(&e.0).symbol, layout.get_layerkey(&e.1).symbol);
-                });
+        // same key mods
+        TakeTwoLayerKey::new(key1, &mods1, w, self.split_modifiers.same_key_mod_factor).for_each(
+            |(e, w)| {
+                bigram_w_map.insert_or_add_weight(e, w);
+                // log::trace!("{:>3}{:<3} -> {:>3}{:<3}", layout.get_layerkey(&k1).symbol, layout.get_layerkey(&k2).symbol, layout.get_layerkey(&e.0).symbol, layout.get_layerkey(&e.1).symbol);
+            },
+        );
+
+        TakeTwoLayerKey::new(key2, &mods2, w, self.split_modifiers.same_key_mod_factor).for_each(
+            |(e, w)| {
+                bigram_w_map.insert_or_add_weight(e, w);
+                // log::trace!("{:>3}{:<3} -> {:>3}{:<3}", layout.get_layerkey(&k1).symbol, layout.get_layerkey(&k2).symbol, layout.get_layerkey(&e.0).symbol, layout.get_layerkey(&e.1).symbol);
+            },
+        );
+    }
+
+    /// Replace symbols that are generated with dead keys by the key sequence they are typed with
+    /// and generate bigrams of consecutive keys from the result.
+    fn process_dead_keys(&self, bigrams: BigramIndicesVec, layout: &Layout) -> BigramIndicesVec {
+        let mut processed_bigrams = Vec::with_capacity(bigrams.len());
+        let mut keys = Vec::new();
+
+        bigrams.into_iter().for_each(|((k1, k2), w)| {
+            keys.clear();
+            push_dead_key_sequence(k1, layout, &mut keys);
+            push_dead_key_sequence(k2, layout, &mut keys);
+
+            keys.iter().zip(keys.iter().skip(1)).for_each(|(lk1, lk2)| {
+                processed_bigrams.push(((*lk1, *lk2), w));
+            });
         });
 
-        bigram_w_map
+        processed_bigrams
     }
 
     fn process_one_shot_modifiers(
@@ -1,9 +1,138 @@
 /// The `common` module provides utility functions for resolving modifiers in ngrams.
-use keyboard_layout::layout::LayerKeyIndex;
+use super::on_demand_ngram_mapper::KeyChoiceConfig;
+
+use keyboard_layout::layout::{LayerKeyIndex, LayerModifiers, Layout};
 
 use ahash::AHashMap;
+use ordered_float::OrderedFloat;
 use std::{cmp::Eq, hash::Hash, slice};
 
+/// Whether ngrams need to be split because some symbols require keys to be held simultaneously
+/// (hold modifiers, if they shall be split, and chords).
+#[inline(always)]
+pub fn has_held_keys(layout: &Layout, split_hold_modifiers: bool) -> bool {
+    (split_hold_modifiers && layout.has_hold_layers()) || layout.has_chords()
+}
+
+/// Resolves a [`LayerKeyIndex`] into the key to press and the keys that need to be held
+/// simultaneously: its hold modifiers (if they shall be split) or the other keys of a chord.
+#[inline(always)]
+pub fn resolve_held_keys(
+    k: LayerKeyIndex,
+    layout: &Layout,
+    split_hold_modifiers: bool,
+) -> (LayerKeyIndex, Vec<LayerKeyIndex>) {
+    let (base, mods) = layout.resolve_modifiers(&k);
+
+    match mods {
+        LayerModifiers::Hold(mods) if split_hold_modifiers => (base, mods),
+        LayerModifiers::Chord(keys) => (base, keys),
+        _ => (k, Vec::new()),
+    }
+}
+
+/// Chooses for each symbol of an ngram among all [`LayerKeyIndex`]s generating it (see
+/// [`Layout::get_alternative_layerkey_indices`]), such that the sum of the keys' costs and the costs
+/// of the transitions between consecutive keys is minimal. The keys are replaced in place.
+///
+/// The given keys are preferred if several combinations have the same cost.
+pub fn choose_alternative_keys(
+    keys: &mut [LayerKeyIndex],
+    layout: &Layout,
+    config: &KeyChoiceConfig,
+) {
+    if !config.enabled
+        || !layout.has_alternative_layerkeys()
+        || keys
+            .iter()
+            .all(|k| layout.get_alternative_layerkey_indices(k).is_empty())
+    {
+        return;
+    }
+
+    // the variants for each symbol, starting with the given key
+    let variants: Vec<Vec<LayerKeyIndex>> = keys
+        .iter()
+        .map(|k| {
+            let mut variants = vec![*k];
+            variants.extend(
+                layout
+                    .get_alternative_layerkey_indices(k)
+                    .iter()
+                    .filter(|idx| *idx != k),
+            );
+            variants
+        })
+        .collect();
+
+    let key_cost = |k: &LayerKeyIndex| {
+        let lk = layout.get_layerkey(k);
+        let modifier_cost: f64 = lk
+            .modifiers
+            .layerkey_indices()
+            .iter()
+            .map(|idx| layout.get_layerkey(idx).key.cost)
+            .sum();
+
+        config.key_cost_factor * (lk.key.cost + modifier_cost)
+    };
+    let transition_cost = |k1: &LayerKeyIndex, k2: &LayerKeyIndex| {
+        let key1 = &layout.get_layerkey(k1).key;
+        let key2 = &layout.get_layerkey(k2).key;
+        if key1.hand != key2.hand {
+            0.0
+        } else if key1.finger == key2.finger && key1.index != key2.index {
+            config.same_hand_cost + config.same_finger_cost
+        } else {
+            config.same_hand_cost
+        }
+    };
+
+    // lowest cost of the ngram up to each variant (together with the best variant before it)
+    let mut costs: Vec<Vec<(f64, usize)>> = Vec::with_capacity(variants.len());
+    for (i, vars) in variants.iter().enumerate() {
+        let var_costs = vars
+            .iter()
+            .map(|k| match i.checked_sub(1) {
+                None => (key_cost(k), 0),
+                Some(prev) => variants[prev]
+                    .iter()
+                    .zip(costs[prev].iter())
+                    .map(|(prev_k, (prev_cost, _))| prev_cost + transition_cost(prev_k, k))
+                    .enumerate()
+                    .min_by_key(|(_, c)| OrderedFloat(*c))
+                    .map(|(j, c)| (c + key_cost(k), j))
+                    .unwrap(), // there is at least one variant
+            })
+            .collect();
+        costs.push(var_costs);
+    }
+
+    // trace back the best combination
+    let mut best = costs
+        .last()
+        .unwrap() // there is at least one key
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, (c, _))| OrderedFloat(*c))
+        .map(|(j, _)| j)
+        .unwrap();
+    for i in (0..keys.len()).rev() {
+        keys[i] = variants[i][best];
+        best = costs[i][best].1;
+    }
+}
+
+/// Appends the sequence of keys that is typed for a [`LayerKeyIndex`] to `keys`. This is the
+/// whole sequence for symbols generated with dead keys and the [`LayerKeyIndex`] itself otherwise.
+#[inline(always)]
+pub fn push_dead_key_sequence(k: LayerKeyIndex, layout: &Layout, keys: &mut Vec<LayerKeyIndex>) {
+    match &layout.get_layerkey(&k).modifiers {
+        LayerModifiers::DeadKey(sequence) => keys.extend(sequence),
+        _ => keys.push(k),
+    }
+}
+
 /// Iterator over unigrams of the base-layer key and each modifier.
 #[derive(Clone, Debug)]
 pub struct TakeOneLayerKey<'a> {
@@ -438,3 +567,195 @@ impl<Ngram: Eq + Hash> NgramMap<Ngram> for AHashMap<Ngram, f64> {
         *self.entry(k).or_insert(0.0) += w;
     }
 }
+
+/// For a Vec, identical ngrams are not aggregated. This is useful if the ngrams resulting from
+/// a single char-based ngram shall be kept apart from all others.
+impl<Ngram: Eq + Hash> NgramMap<Ngram> for Vec<(Ngram, f64)> {
+    #[inline(always)]
+    fn insert_or_add_weight(&mut self, k: Ngram, w: f64) {
+        self.push((k, w));
+    }
+}
+
+/// Pushes each inserted ngram to a Vec after resolving it with a given function (omitting it if
+/// the function returns `None`). Identical ngrams are not aggregated.
+pub struct ResolvingNgramVec<'a, T, F> {
+    grams: &'a mut Vec<T>,
+    resolve: F,
+}
+
+impl<'a, T, F> ResolvingNgramVec<'a, T, F> {
+    pub fn new(grams: &'a mut Vec<T>, resolve: F) -> Self {
+        Self { grams, resolve }
+    }
+}
+
+impl<'a, Ngram, T, F> NgramMap<Ngram> for ResolvingNgramVec<'a, T, F>
+where
+    Ngram: Eq + Hash,
+    F: FnMut(Ngram, f64) -> Option<T>,
+{
+    #[inline(always)]
+    fn insert_or_add_weight(&mut self, k: Ngram, w: f64) {
+        if let Some(gram) = (self.resolve)(k, w) {
+            self.grams.push(gram);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ngram_mapper::{
+            on_demand_ngram_mapper::{
+                NgramMapperConfig, OnDemandNgramMapper, SplitModifiersConfig,
+            },
+            NgramMapper,
+        },
+        ngrams::{Bigrams, Quadgrams, Skipgrams, Trigrams, Unigrams},
+        test_utils::{layout_from_config, CRKBD_CONFIG, TEXT},
+    };
+
+    use keyboard_layout::{config::LayoutConfig, key::Hand};
+
+    const KEY_CHOICE: KeyChoiceConfig = KeyChoiceConfig {
+        enabled: true,
+        key_cost_factor: 1.0,
+        same_hand_cost: 10.0,
+        same_finger_cost: 10.0,
+    };
+
+    /// A layout with space on the thumbs of both hands.
+    fn layout() -> Layout {
+        let mut layout_config = LayoutConfig::from_yaml(CRKBD_CONFIG).unwrap();
+        let thumb_keys = layout_config.base_layout.keys.last_mut().unwrap();
+        thumb_keys[2] = vec![" ".to_string()];
+        layout_from_config(layout_config)
+    }
+
+    fn choose(symbols: &str, layout: &Layout) -> Vec<Hand> {
+        let mut keys: Vec<LayerKeyIndex> = symbols
+            .chars()
+            .map(|c| layout.get_layerkey_index_for_symbol(&c).unwrap())
+            .collect();
+        choose_alternative_keys(&mut keys, layout, &KEY_CHOICE);
+        keys.iter()
+            .map(|k| layout.get_layerkey(k).key.hand)
+            .collect()
+    }
+
+    #[test]
+    fn key_choice_picks_thumb_of_other_hand() {
+        let layout = layout();
+        let space = layout.get_layerkey_index_for_symbol(&' ').unwrap();
+        let space_hands: Vec<Hand> = layout
+            .get_alternative_layerkey_indices(&space)
+            .iter()
+            .map(|k| layout.get_layerkey(k).key.hand)
+            .collect();
+        assert!(space_hands.contains(&Hand::Left) && space_hands.contains(&Hand::Right));
+
+        assert_eq!(choose("a ", &layout), vec![Hand::Left, Hand::Right]);
+        assert_eq!(choose(" a", &layout), vec![Hand::Right, Hand::Left]);
+        assert_eq!(choose("n ", &layout), vec![Hand::Right, Hand::Left]);
+        assert_eq!(
+            choose("e a", &layout),
+            vec![Hand::Left, Hand::Right, Hand::Left]
+        );
+        assert_eq!(
+            choose("t n", &layout),
+            vec![Hand::Right, Hand::Left, Hand::Right]
+        );
+
+        // disabled key choice keeps the keys
+        let mut keys = vec![
+            layout.get_layerkey_index_for_symbol(&'n').unwrap(),
+            layout.get_layerkey_index_for_symbol(&' ').unwrap(),
+        ];
+        let expected = keys.clone();
+        let disabled = KeyChoiceConfig {
+            enabled: false,
+            ..KEY_CHOICE
+        };
+        choose_alternative_keys(&mut keys, &layout, &disabled);
+        assert_eq!(keys, expected);
+    }
+
+    fn mapper(key_choice: KeyChoiceConfig, split_modifiers: bool) -> OnDemandNgramMapper {
+        OnDemandNgramMapper::with_ngrams(
+            Unigrams::from_text(TEXT).unwrap(),
+            Bigrams::from_text(TEXT).unwrap(),
+            Trigrams::from_text(TEXT).unwrap(),
+            Quadgrams::from_text(TEXT).unwrap(),
+            vec![Skipgrams::from_text(TEXT, 2).unwrap()],
+            NgramMapperConfig {
+                split_modifiers: SplitModifiersConfig {
+                    enabled: split_modifiers,
+                    same_key_mod_factor: 0.5,
+                },
+                exclude_line_breaks: true,
+                key_choice,
+            },
+        )
+    }
+
+    #[test]
+    fn key_choice_keeps_weights() {
+        let layout = layout();
+
+        for split_modifiers in [true, false] {
+            let with_choice = mapper(KEY_CHOICE, split_modifiers);
+            let without_choice = mapper(KeyChoiceConfig::default(), split_modifiers);
+
+            let bigrams = with_choice.map_bigrams(&layout);
+            assert!(bigrams.grams.iter().any(|((k1, k2), _)| k1.symbol == 'n'
+                && k2.symbol == ' '
+                && k2.key.hand == Hand::Left));
+
+            macro_rules! assert_same_weights {
+                ($map:ident) => {
+                    let mapped = without_choice.$map(&layout);
+                    let mapped_with_choice = with_choice.$map(&layout);
+                    assert_eq!(mapped_with_choice.weight_found, mapped.weight_found);
+                    assert_eq!(mapped_with_choice.weight_not_found, mapped.weight_not_found);
+
+                    // choosing keys on higher layers may add ngrams with modifiers
+                    if !split_modifiers {
+                        let total: f64 = mapped.grams.iter().map(|(_, w)| w).sum();
+                        let total_with_choice: f64 =
+                            mapped_with_choice.grams.iter().map(|(_, w)| w).sum();
+                        assert!((total_with_choice - total).abs() <= 1e-9 * total);
+                    }
+                };
+            }
+
+            assert_same_weights!(map_bigrams);
+            assert_same_weights!(map_trigrams);
+            assert_same_weights!(map_quadgrams);
+            assert_same_weights!(map_skipgrams);
+        }
+    }
+
+    #[test]
+    fn skipgrams_use_keys_without_choice() {
+        let layout = layout();
+        let key_indices = |mapper: &OnDemandNgramMapper| {
+            let mut keys: Vec<_> = mapper
+                .map_skipgrams(&layout)
+                .grams
+                .iter()
+                .map(|((k1, k2, skip), _)| {
+                    ((k1.key.index, k1.layer), (k2.key.index, k2.layer), *skip)
+                })
+                .collect();
+            keys.sort_unstable();
+            keys
+        };
+
+        assert_eq!(
+            key_indices(&mapper(KEY_CHOICE, true)),
+            key_indices(&mapper(KeyChoiceConfig::default(), true))
+        );
+    }
+}
@@ -1,13 +1,17 @@
 //! This module provides an implementation of the [`NgramMapper`] trait.
 
-use super::bigram_mapper::OnDemandBigramMapper;
-use super::trigram_mapper::OnDemandTrigramMapper;
+use super::bigram_mapper::{self, OnDemandBigramMapper};
+use super::quadgram_mapper::{self, OnDemandQuadgramMapper};
+use super::trigram_mapper::{self, OnDemandTrigramMapper};
 use super::unigram_mapper::OnDemandUnigramMapper;
-use super::{MappedBigrams, MappedTrigrams, MappedUnigrams, NgramMapper};
+use super::{
+    LayerKeyQuadgram, LayerKeySkipgram, MappedBigrams, MappedQuadgrams, MappedSkipgrams,
+    MappedTrigrams, MappedUnigrams, NgramMapper,
+};
 
-use crate::ngrams::{Bigrams, Trigrams, Unigrams};
+use crate::ngrams::{interned::InternedNgrams, Bigrams, Quadgrams, Skipgrams, Trigrams, Unigrams};
 
-use keyboard_layout::layout::Layout;
+use keyboard_layout::layout::{LayerKey, Layout};
 
 use serde::Deserialize;
 
@@ -20,6 +24,23 @@ pub struct SplitModifiersConfig {
     pub same_key_mod_factor: f64,
 }
 
+/// Configuration parameters for choosing among several keys that generate the same symbol
+/// (e.g. space on both thumbs or a symbol on two layers) depending on the neighboring keys in
+/// each bigram, trigram, and quadgram. The combination of keys with the lowest cost is chosen.
+/// Skipgrams always use the key with the lowest cost, because their symbols are not typed
+/// consecutively.
+#[derive(Clone, Default, Deserialize, Debug)]
+pub struct KeyChoiceConfig {
+    /// Whether to choose keys per ngram (otherwise, the key with the lowest cost is always used).
+    pub enabled: bool,
+    /// Factor for the cost of each key (including the costs of required modifiers).
+    pub key_cost_factor: f64,
+    /// Cost for consecutive keys on the same hand (i.e. to prefer hand alternation).
+    pub same_hand_cost: f64,
+    /// Additional cost for consecutive (different) keys on the same finger.
+    pub same_finger_cost: f64,
+}
+
 /// Configuration parameters for the [`OnDemandNgramMapper`].
 #[derive(Clone, Deserialize, Debug)]
 pub struct NgramMapperConfig {
@@ -27,6 +48,9 @@ pub struct NgramMapperConfig {
     pub split_modifiers: SplitModifiersConfig,
     /// Exclude ngrams that contain a line break, followed by a non-line-break character
     pub exclude_line_breaks: bool,
+    /// Parameters for choosing among several keys generating the same symbol.
+    #[serde(default)]
+    pub key_choice: KeyChoiceConfig,
 }
 
 /// Implements the [`NgramMapper`] trait for generating ngrams in terms of [`LayerKey`]s for a given [`Layout`].
@@ -35,27 +59,79 @@ pub struct OnDemandNgramMapper {
     unigrams: Unigrams,
     bigrams: Bigrams,
     trigrams: Trigrams,
+    quadgrams: Quadgrams,
+    skipgrams: Vec<Skipgrams>,
+    /// The ngrams in terms of symbol ids (without those excluded by the config)
+    interned: InternedNgrams,
+    /// The total weights of the char-based unigrams, bigrams, trigrams, and quadgrams
+    total_weights: (f64, f64, f64, f64),
+    /// The total weight of the char-based skipgrams (of all skip distances)
+    total_skipgram_weight: f64,
     unigram_mapper: OnDemandUnigramMapper,
     bigram_mapper: OnDemandBigramMapper,
+    /// Maps skipgrams like bigrams, but without choosing keys
+    skipgram_mapper: OnDemandBigramMapper,
     trigram_mapper: OnDemandTrigramMapper,
+    quadgram_mapper: OnDemandQuadgramMapper,
     config: NgramMapperConfig,
 }
 
 impl OnDemandNgramMapper {
-    /// Generate a [`OnDemandNgramMapper`] with given char-based ngrams.
+    /// Generate a [`OnDemandNgramMapper`] with given char-based ngrams. The quadgrams and
+    /// skipgrams may be empty if no quadgram or skipgram metrics are evaluated.
     pub fn with_ngrams(
         unigrams: Unigrams,
         bigrams: Bigrams,
         trigrams: Trigrams,
+        quadgrams: Quadgrams,
+        skipgrams: Vec<Skipgrams>,
         config: NgramMapperConfig,
     ) -> Self {
+        let exclude_line_breaks = config.exclude_line_breaks;
+        let interned = InternedNgrams::new(
+            &unigrams,
+            &bigrams,
+            &trigrams,
+            &quadgrams,
+            &skipgrams,
+            |bigram| !bigram_mapper::is_excluded(bigram, exclude_line_breaks),
+            |trigram| !trigram_mapper::is_excluded(trigram, exclude_line_breaks),
+            |quadgram| !quadgram_mapper::is_excluded(quadgram, exclude_line_breaks),
+        );
+        let total_weights = (
+            unigrams.total_weight(),
+            bigrams.total_weight(),
+            trigrams.total_weight(),
+            quadgrams.total_weight(),
+        );
+        let total_skipgram_weight = skipgrams.iter().map(|s| s.total_weight()).sum();
+
         Self {
             unigrams,
             bigrams,
             trigrams,
+            quadgrams,
+            skipgrams,
+            interned,
+            total_weights,
+            total_skipgram_weight,
             unigram_mapper: OnDemandUnigramMapper::new(config.split_modifiers.clone()),
-            bigram_mapper: OnDemandBigramMapper::new(config.split_modifiers.clone()),
-            trigram_mapper: OnDemandTrigramMapper::new(config.split_modifiers.clone()),
+            bigram_mapper: OnDemandBigramMapper::new(
+                config.split_modifiers.clone(),
+                config.key_choice.clone(),
+            ),
+            skipgram_mapper: OnDemandBigramMapper::new(
+                config.split_modifiers.clone(),
+                KeyChoiceConfig::default(),
+            ),
+            trigram_mapper: OnDemandTrigramMapper::new(
+                config.split_modifiers.clone(),
+                config.key_choice.clone(),
+            ),
+            quadgram_mapper: OnDemandQuadgramMapper::new(
+                config.split_modifiers.clone(),
+                config.key_choice.clone(),
+            ),
             config,
         }
     }
@@ -63,10 +139,12 @@ impl OnDemandNgramMapper {
 
 impl NgramMapper for OnDemandNgramMapper {
     fn map_unigrams<'s>(&self, layout: &'s Layout) -> MappedUnigrams<'s> {
-        // map char-based unigrams to LayerKeyIndex
+        // map interned unigrams to LayerKeyIndex
+        let symbol_keys = self.interned.symbols.layerkey_indices(layout);
         let (key_indices, weight_not_found) =
-            self.unigram_mapper.layerkey_indices(&self.unigrams, layout);
-        let weight_found = self.unigrams.total_weight() - weight_not_found;
+            self.unigram_mapper
+                .layerkey_indices(&self.interned.unigrams, &symbol_keys, layout);
+        let weight_found = self.total_weights.0 - weight_not_found;
         // map LayerKeyIndex to &LayerKey
         let grams = OnDemandUnigramMapper::get_layerkeys(&key_indices, layout);
 
@@ -78,13 +156,12 @@ impl NgramMapper for OnDemandNgramMapper {
     }
 
     fn map_bigrams<'s>(&self, layout: &'s Layout) -> MappedBigrams<'s> {
-        // map char-based bigrams to LayerKeyIndex
-        let (key_indices, weight_not_found) = self.bigram_mapper.layerkey_indices(
-            &self.bigrams,
-            layout,
-            self.config.exclude_line_breaks,
-        );
-        let weight_found = self.bigrams.total_weight() - weight_not_found;
+        // map interned bigrams to LayerKeyIndex
+        let symbol_keys = self.interned.symbols.layerkey_indices(layout);
+        let (key_indices, weight_not_found) =
+            self.bigram_mapper
+                .layerkey_indices(&self.interned.bigrams, &symbol_keys, layout);
+        let weight_found = self.total_weights.1 - weight_not_found;
         // map LayerKeyIndex to &LayerKey
         let grams = OnDemandBigramMapper::get_filtered_layerkeys(&key_indices, layout);
 
@@ -96,13 +173,12 @@ impl NgramMapper for OnDemandNgramMapper {
     }
 
     fn map_trigrams<'s>(&self, layout: &'s Layout) -> MappedTrigrams<'s> {
-        // map char-based trigrams to LayerKeyIndex
-        let (key_indices, weight_not_found) = self.trigram_mapper.layerkey_indices(
-            &self.trigrams,
-            layout,
-            self.config.exclude_line_breaks,
-        );
-        let weight_found = self.trigrams.total_weight() - weight_not_found;
+        // map interned trigrams to LayerKeyIndex
+        let symbol_keys = self.interned.symbols.layerkey_indices(layout);
+        let (key_indices, weight_not_found) =
+            self.trigram_mapper
+                .layerkey_indices(&self.interned.trigrams, &symbol_keys, layout);
+        let weight_found = self.total_weights.2 - weight_not_found;
         // map LayerKeyIndex to &LayerKey
         let grams = OnDemandTrigramMapper::get_filtered_layerkeys(&key_indices, layout);
 
@@ -112,4 +188,147 @@ impl NgramMapper for OnDemandNgramMapper {
             weight_found,
         }
     }
+
+    fn map_quadgrams<'s>(&self, layout: &'s Layout) -> MappedQuadgrams<'s> {
+        // map interned quadgrams to LayerKeyIndex
+        let symbol_keys = self.interned.symbols.layerkey_indices(layout);
+        let (key_indices, weight_not_found) =
+            self.quadgram_mapper
+                .layerkey_indices(&self.interned.quadgrams, &symbol_keys, layout);
+        let weight_found = self.total_weights.3 - weight_not_found;
+        // map LayerKeyIndex to &LayerKey
+        let grams = OnDemandQuadgramMapper::get_filtered_layerkeys(&key_indices, layout);
+
+        MappedQuadgrams {
+            grams,
+            weight_not_found,
+            weight_found,
+        }
+    }
+
+    fn map_skipgrams<'s>(&self, layout: &'s Layout) -> MappedSkipgrams<'s> {
+        let symbol_keys = self.interned.symbols.layerkey_indices(layout);
+        let mut grams = Vec::new();
+        let mut weight_not_found = 0.0;
+        for (skip, interned_skipgrams) in self.interned.skipgrams.iter() {
+            // map interned skipgrams to LayerKeyIndex (resolving modifiers like for bigrams)
+            let (key_indices, skip_weight_not_found) =
+                self.skipgram_mapper
+                    .layerkey_indices(interned_skipgrams, &symbol_keys, layout);
+            weight_not_found += skip_weight_not_found;
+            // map LayerKeyIndex to &LayerKey
+            grams.extend(
+                OnDemandBigramMapper::get_filtered_layerkeys(&key_indices, layout)
+                    .into_iter()
+                    .map(|((k1, k2), w)| ((k1, k2, *skip), w)),
+            );
+        }
+        let weight_found = self.total_skipgram_weight - weight_not_found;
+
+        MappedSkipgrams {
+            grams,
+            weight_not_found,
+            weight_found,
+        }
+    }
+
+    fn unigrams(&self) -> &Unigrams {
+        &self.unigrams
+    }
+
+    fn bigrams(&self) -> &Bigrams {
+        &self.bigrams
+    }
+
+    fn trigrams(&self) -> &Trigrams {
+        &self.trigrams
+    }
+
+    fn quadgrams(&self) -> &Quadgrams {
+        &self.quadgrams
+    }
+
+    fn skipgrams(&self) -> &[Skipgrams] {
+        &self.skipgrams
+    }
+
+    fn map_single_unigram<'s>(
+        &self,
+        unigram: &char,
+        weight: f64,
+        layout: &'s Layout,
+        grams: &mut Vec<(&'s LayerKey, f64)>,
+    ) -> bool {
+        self.unigram_mapper
+            .map_single_unigram(unigram, weight, layout, grams)
+    }
+
+    fn map_single_bigram<'s>(
+        &self,
+        bigram: &(char, char),
+        weight: f64,
+        layout: &'s Layout,
+        grams: &mut Vec<((&'s LayerKey, &'s LayerKey), f64)>,
+    ) -> bool {
+        self.bigram_mapper.map_single_bigram(
+            bigram,
+            weight,
+            layout,
+            self.config.exclude_line_breaks,
+            grams,
+        )
+    }
+
+    fn map_single_trigram<'s>(
+        &self,
+        trigram: &(char, char, char),
+        weight: f64,
+        layout: &'s Layout,
+        grams: &mut Vec<((&'s LayerKey, &'s LayerKey, &'s LayerKey), f64)>,
+    ) -> bool {
+        self.trigram_mapper.map_single_trigram(
+            trigram,
+            weight,
+            layout,
+            self.config.exclude_line_breaks,
+            grams,
+        )
+    }
+
+    fn map_single_quadgram<'s>(
+        &self,
+        quadgram: &(char, char, char, char),
+        weight: f64,
+        layout: &'s Layout,
+        grams: &mut Vec<(LayerKeyQuadgram<'s>, f64)>,
+    ) -> bool {
+        self.quadgram_mapper.map_single_quadgram(
+            quadgram,
+            weight,
+            layout,
+            self.config.exclude_line_breaks,
+            grams,
+        )
+    }
+
+    fn map_single_skipgram<'s>(
+        &self,
+        skipgram: &(char, char),
+        skip: usize,
+        weight: f64,
+        layout: &'s Layout,
+        grams: &mut Vec<(LayerKeySkipgram<'s>, f64)>,
+    ) -> bool {
+        let mut bigrams = Vec::new();
+        let found = self.skipgram_mapper.map_single_bigram(
+            skipgram,
+            weight,
+            layout,
+            self.config.exclude_line_breaks,
+            &mut bigrams,
+        );
+        grams.extend(bigrams.into_iter().map(|((k1, k2), w)| ((k1, k2, skip), w)));
+
+        found
+    }
 }
@@ -0,0 +1,397 @@
+//! This module provides an implementation of quadgram mapping functionalities
+//! used by the [`OnDemandNgramMapper`].
+
+use super::{
+    common::*,
+    on_demand_ngram_mapper::{KeyChoiceConfig, SplitModifiersConfig},
+    LayerKeyQuadgram,
+};
+
+use crate::ngrams::interned::SymbolQuadgram;
+
+use ahash::AHashMap;
+use keyboard_layout::layout::{LayerKeyIndex, LayerModifiers, Layout};
+
+type QuadgramKeyIndices = (LayerKeyIndex, LayerKeyIndex, LayerKeyIndex, LayerKeyIndex);
+
+// Before passing the resulting LayerKey-based ngrams as a result, smaller LayerKeyIndex-based
+// ones are used because they are smaller than a reference (u16 vs usize) and yield better
+// hashing performance.
+pub type QuadgramIndices = AHashMap<QuadgramKeyIndices, f64>;
+type QuadgramIndicesVec = Vec<(QuadgramKeyIndices, f64)>;
+
+/// Turns the interned quadgrams into their [`LayerKeyIndex`]s (given for each symbol in `symbol_keys`
+/// or chosen among alternatives, see [`choose_alternative_keys`]), returning a [`QuadgramIndicesVec`].
+fn map_quadgrams(
+    quadgrams: &[(SymbolQuadgram, f64)],
+    symbol_keys: &[Option<LayerKeyIndex>],
+    layout: &Layout,
+    key_choice: &KeyChoiceConfig,
+) -> (QuadgramIndicesVec, f64) {
+    let mut not_found_weight = 0.0;
+    let mut quadgrams_vec = Vec::with_capacity(quadgrams.len());
+
+    quadgrams_vec.extend(quadgrams.iter().filter_map(|((s1, s2, s3, s4), weight)| {
+        match (
+            symbol_keys[*s1 as usize],
+            symbol_keys[*s2 as usize],
+            symbol_keys[*s3 as usize],
+            symbol_keys[*s4 as usize],
+        ) {
+            (Some(idx1), Some(idx2), Some(idx3), Some(idx4)) => {
+                let mut keys = [idx1, idx2, idx3, idx4];
+                choose_alternative_keys(&mut keys, layout, key_choice);
+                Some(((keys[0], keys[1], keys[2], keys[3]), *weight))
+            }
+            _ => {
+                not_found_weight += *weight;
+                None
+            }
+        }
+    }));
+
+    (quadgrams_vec, not_found_weight)
+}
+
+/// Exclude quadgrams that contain a line break, followed by a non-line-break character
+#[inline(always)]
+pub fn is_excluded((c1, c2, c3, c4): &(char, char, char, char), exclude_line_breaks: bool) -> bool {
+    exclude_line_breaks
+        && ((*c1 == '\n' && *c2 != '\n')
+            || (*c2 == '\n' && *c3 != '\n')
+            || (*c3 == '\n' && *c4 != '\n'))
+}
+
+/// Turns a quadgram's characters into their indices (if all of them can be generated by the layout).
+#[inline(always)]
+fn map_quadgram(
+    (c1, c2, c3, c4): &(char, char, char, char),
+    layout: &Layout,
+    key_choice: &KeyChoiceConfig,
+) -> Option<QuadgramKeyIndices> {
+    let mut keys = [
+        layout.get_layerkey_index_for_symbol(c1)?,
+        layout.get_layerkey_index_for_symbol(c2)?,
+        layout.get_layerkey_index_for_symbol(c3)?,
+        layout.get_layerkey_index_for_symbol(c4)?,
+    ];
+    choose_alternative_keys(&mut keys, layout, key_choice);
+
+    Some((keys[0], keys[1], keys[2], keys[3]))
+}
+
+/// Resolves &[`LayerKey`] references for a [`LayerKeyIndex`]-based quadgram unless it contains
+/// repeating identical modifiers.
+#[inline(always)]
+fn filtered_layerkeys<'s>(
+    (idx1, idx2, idx3, idx4): &QuadgramKeyIndices,
+    w: f64,
+    layout: &'s Layout,
+) -> Option<(LayerKeyQuadgram<'s>, f64)> {
+    let k2 = layout.get_layerkey(idx2);
+    let k3 = layout.get_layerkey(idx3);
+
+    // If the same modifier appears consecutively, it is usually "hold" instead of repeatedly pressed
+    // --> remove
+    match (k2.is_modifier.is_hold() && (idx1 == idx2 || idx2 == idx3))
+        || (k3.is_modifier.is_hold() && idx3 == idx4)
+    {
+        false => Some((
+            (
+                layout.get_layerkey(idx1), // LayerKey 1
+                k2,                        // LayerKey 2
+                k3,                        // LayerKey 3
+                layout.get_layerkey(idx4), // LayerKey 4
+            ),
+            w,
+        )),
+        true => None,
+    }
+}
+
+/// Generates [`LayerKey`]-based quadgrams from char-based quadgrams. Optionally resolves modifiers
+/// for higher-layer symbols of the layout.
+#[derive(Clone, Debug)]
+pub struct OnDemandQuadgramMapper {
+    split_modifiers: SplitModifiersConfig,
+    key_choice: KeyChoiceConfig,
+}
+
+impl OnDemandQuadgramMapper {
+    pub fn new(split_modifiers: SplitModifiersConfig, key_choice: KeyChoiceConfig) -> Self {
+        Self {
+            split_modifiers,
+            key_choice,
+        }
+    }
+
+    /// For a given [`Layout`] generate [`LayerKeyIndex`]-based quadgrams from interned ones, optionally
+    /// resolving modifiers for higer-layer symbols. `symbol_keys` holds the [`LayerKeyIndex`] for each symbol id.
+    pub fn layerkey_indices(
+        &self,
+        quadgrams: &[(SymbolQuadgram, f64)],
+        symbol_keys: &[Option<LayerKeyIndex>],
+        layout: &Layout,
+    ) -> (QuadgramIndicesVec, f64) {
+        let (mut quadgram_keys_vec, not_found_weight) =
+            map_quadgrams(quadgrams, symbol_keys, layout, &self.key_choice);
+
+        if layout.has_dead_keys() {
+            quadgram_keys_vec = self.process_dead_keys(quadgram_keys_vec, layout);
+        }
+
+        if layout.has_one_shot_layers() {
+            quadgram_keys_vec = self.process_one_shot_modifiers(quadgram_keys_vec, layout);
+        }
+
+        // Each symbol corresponds to a different LayerKey, so quadgrams only need to be aggregated
+        // if they were split or processed otherwise.
+        let quadgram_keys = if has_held_keys(layout, self.split_modifiers.enabled) {
+            self.process_hold_modifiers(quadgram_keys_vec, layout)
+                .into_iter()
+                .collect()
+        } else if layout.has_dead_keys() || layout.has_one_shot_layers() {
+            let mut quadgram_w_map = QuadgramIndices::with_capacity(quadgram_keys_vec.len());
+            quadgram_keys_vec
+                .into_iter()
+                .for_each(|(quadgram, w)| quadgram_w_map.insert_or_add_weight(quadgram, w));
+            quadgram_w_map.into_iter().collect()
+        } else {
+            quadgram_keys_vec
+        };
+
+        (quadgram_keys, not_found_weight)
+    }
+
+    /// Resolve &[`LayerKey`] references for [`LayerKeyIndex`] and filters quadgrams that contain
+    /// repeating identical modifiers.
+    pub fn get_filtered_layerkeys<'s>(
+        quadgrams: &[(QuadgramKeyIndices, f64)],
+        layout: &'s Layout,
+    ) -> Vec<(LayerKeyQuadgram<'s>, f64)> {
+        let mut layerkeys = Vec::with_capacity(quadgrams.len());
+
+        layerkeys.extend(
+            quadgrams
+                .iter()
+                .filter_map(|(quadgram, w)| filtered_layerkeys(quadgram, *w, layout)),
+        );
+
+        layerkeys
+    }
+
+    /// Map a single char-based quadgram to [`LayerKey`]-based quadgrams (in the same way as
+    /// [`Self::layerkey_indices`] and [`Self::get_filtered_layerkeys`] do), appending them to `layerkeys`.
+    /// Identical resulting quadgrams are not aggregated.
+    ///
+    /// Returns `false` if the quadgram can not be generated by the layout.
+    pub fn map_single_quadgram<'s>(
+        &self,
+        quadgram: &(char, char, char, char),
+        weight: f64,
+        layout: &'s Layout,
+        exclude_line_breaks: bool,
+        layerkeys: &mut Vec<(LayerKeyQuadgram<'s>, f64)>,
+    ) -> bool {
+        if is_excluded(quadgram, exclude_line_breaks) {
+            return true;
+        }
+
+        let indices = match map_quadgram(quadgram, layout, &self.key_choice) {
+            Some(indices) => indices,
+            None => return false,
+        };
+
+        let mut resolved = ResolvingNgramVec::new(layerkeys, |quadgram, w| {
+            filtered_layerkeys(&quadgram, w, layout)
+        });
+        let split_hold_modifiers = has_held_keys(layout, self.split_modifiers.enabled);
+
+        if layout.has_dead_keys() || layout.has_one_shot_layers() {
+            let mut quadgrams = vec![(indices, weight)];
+            if layout.has_dead_keys() {
+                quadgrams = self.process_dead_keys(quadgrams, layout);
+            }
+            if layout.has_one_shot_layers() {
+                quadgrams = self.process_one_shot_modifiers(quadgrams, layout);
+            }
+
+            quadgrams
+                .into_iter()
+                .for_each(|(quadgram, w)| match split_hold_modifiers {
+                    true => self.split_hold_modifiers(quadgram, w, layout, &mut resolved),
+                    false => resolved.insert_or_add_weight(quadgram, w),
+                });
+        } else if split_hold_modifiers {
+            self.split_hold_modifiers(indices, weight, layout, &mut resolved);
+        } else {
+            resolved.insert_or_add_weight(indices, weight);
+        }
+
+        true
+    }
+
+    /// Map all quadgrams to base-layer quadgrams, potentially generating multiple quadgrams
+    /// with modifiers for those with higer-layer keys.
+    ///
+    /// This works in the same way as for trigrams: Each quadgram of higher-layer symbols transforms into a
+    /// series of quadgrams with permutations of the involved base-keys and modifiers. Keys from the latter
+    /// parts of the quadgram always come after former ones and modifers always come before their base key.
+    /// Quadgrams consisting of four keys of a single symbol (requiring at least three modifiers) are not
+    /// generated.
+    fn process_hold_modifiers(
+        &self,
+        quadgrams: QuadgramIndicesVec,
+        layout: &Layout,
+    ) -> QuadgramIndices {
+        let mut quadgram_w_map = AHashMap::with_capacity(quadgrams.len() / 3);
+        quadgrams.into_iter().for_each(|(quadgram, w)| {
+            self.split_hold_modifiers(quadgram, w, layout, &mut quadgram_w_map);
+        });
+
+        quadgram_w_map
+    }
+
+    /// Split a single quadgram into base-layer quadgrams (see [`Self::process_hold_modifiers`]).
+    #[inline(always)]
+    fn split_hold_modifiers<M: NgramMap<QuadgramKeyIndices>>(
+        &self,
+        (k1, k2, k3, k4): QuadgramKeyIndices,
+        w: f64,
+        layout: &Layout,
+        quadgram_w_map: &mut M,
+    ) {
+        let same_key_mod_factor = self.split_modifiers.same_key_mod_factor;
+        let resolved = [
+            resolve_held_keys(k1, layout, self.split_modifiers.enabled),
+            resolve_held_keys(k2, layout, self.split_modifiers.enabled),
+            resolve_held_keys(k3, layout, self.split_modifiers.enabled),
+            resolve_held_keys(k4, layout, self.split_modifiers.enabled),
+        ];
+
+        let take_one: Vec<TakeOneLayerKey> = resolved
+            .iter()
+            .map(|(key, mods)| TakeOneLayerKey::new(*key, mods, w))
+            .collect();
+        let take_two: Vec<TakeTwoLayerKey> = resolved
+            .iter()
+            .map(|(key, mods)| TakeTwoLayerKey::new(*key, mods, w, same_key_mod_factor))
+            .collect();
+        let take_three: Vec<TakeThreeLayerKey> = resolved
+            .iter()
+            .map(|(key, mods)| TakeThreeLayerKey::new(*key, mods, w, same_key_mod_factor))
+            .collect();
+
+        // one of each symbol
+        take_one[0].clone().for_each(|(e1, _)| {
+            take_one[1].clone().for_each(|(e2, _)| {
+                take_one[2].clone().for_each(|(e3, _)| {
+                    take_one[3].clone().for_each(|(e4, _)| {
+                        quadgram_w_map.insert_or_add_weight((e1, e2, e3, e4), w);
+                    });
+                });
+            });
+        });
+
+        // two of one symbol and one of each of its neighbors (three consecutive symbols)
+        for s in 0..2 {
+            take_two[s].clone().for_each(|((e1, e2), w1)| {
+                take_one[s + 1].clone().for_each(|(e3, _)| {
+                    take_one[s + 2].clone().for_each(|(e4, _)| {
+                        quadgram_w_map.insert_or_add_weight((e1, e2, e3, e4), w1);
+                    });
+                });
+            });
+
+            take_one[s].clone().for_each(|(e1, _)| {
+                take_two[s + 1].clone().for_each(|((e2, e3), w1)| {
+                    take_one[s + 2].clone().for_each(|(e4, _)| {
+                        quadgram_w_map.insert_or_add_weight((e1, e2, e3, e4), w1);
+                    });
+                });
+            });
+
+            take_one[s].clone().for_each(|(e1, _)| {
+                take_one[s + 1].clone().for_each(|(e2, _)| {
+                    take_two[s + 2].clone().for_each(|((e3, e4), w1)| {
+                        quadgram_w_map.insert_or_add_weight((e1, e2, e3, e4), w1);
+                    });
+                });
+            });
+        }
+
+        // four keys of two consecutive symbols
+        for s in 0..3 {
+            take_three[s].clone().for_each(|((e1, e2, e3), w1)| {
+                take_one[s + 1].clone().for_each(|(e4, _)| {
+                    quadgram_w_map.insert_or_add_weight((e1, e2, e3, e4), w1);
+                });
+            });
+
+            take_two[s].clone().for_each(|((e1, e2), w1)| {
+                take_two[s + 1].clone().for_each(|((e3, e4), w2)| {
+                    quadgram_w_map.insert_or_add_weight((e1, e2, e3, e4), w1 * w2 / w);
+                });
+            });
+
+            take_one[s].clone().for_each(|(e1, _)| {
+                take_three[s + 1].clone().for_each(|((e2, e3, e4), w1)| {
+                    quadgram_w_map.insert_or_add_weight((e1, e2, e3, e4), w1);
+                });
+            });
+        }
+    }
+
+    /// Replace symbols that are generated with dead keys by the key sequence they are typed with
+    /// and generate quadgrams of consecutive keys from the result.
+    fn process_dead_keys(
+        &self,
+        quadgrams: QuadgramIndicesVec,
+        layout: &Layout,
+    ) -> QuadgramIndicesVec {
+        let mut processed_quadgrams = Vec::with_capacity(quadgrams.len());
+        let mut keys = Vec::new();
+
+        quadgrams.into_iter().for_each(|((k1, k2, k3, k4), w)| {
+            keys.clear();
+            push_dead_key_sequence(k1, layout, &mut keys);
+            push_dead_key_sequence(k2, layout, &mut keys);
+            push_dead_key_sequence(k3, layout, &mut keys);
+            push_dead_key_sequence(k4, layout, &mut keys);
+
+            keys.windows(4).for_each(|lks| {
+                processed_quadgrams.push(((lks[0], lks[1], lks[2], lks[3]), w));
+            });
+        });
+
+        processed_quadgrams
+    }
+
+    fn process_one_shot_modifiers(
+        &self,
+        quadgrams: QuadgramIndicesVec,
+        layout: &Layout,
+    ) -> QuadgramIndicesVec {
+        let mut processed_quadgrams = Vec::with_capacity(quadgrams.len());
+
+        quadgrams.into_iter().for_each(|((k1, k2, k3, k4), w)| {
+            let mut keys = Vec::new();
+
+            for k in [k1, k2, k3, k4] {
+                let (base, mods) = layout.resolve_modifiers(&k);
+                if let LayerModifiers::OneShot(mods) = mods {
+                    keys.extend(mods);
+                    keys.push(base);
+                } else {
+                    keys.push(k);
+                };
+            }
+
+            keys.windows(4).for_each(|lks| {
+                processed_quadgrams.push(((lks[0], lks[1], lks[2], lks[3]), w));
+            });
+        });
+
+        processed_quadgrams
+    }
+}
@@ -1,9 +1,12 @@
 //! This module provides an implementation of trigram mapping functionalities
 //! used by the [`OnDemandNgramMapper`].
 
-use super::{common::*, on_demand_ngram_mapper::SplitModifiersConfig};
+use super::{
+    common::*,
+    on_demand_ngram_mapper::{KeyChoiceConfig, SplitModifiersConfig},
+};
 
-use crate::ngrams::Trigrams;
+use crate::ngrams::interned::SymbolId;
 
 use ahash::AHashMap;
 use keyboard_layout::layout::{LayerKey, LayerKeyIndex, LayerModifiers, Layout};
@@ -14,89 +17,135 @@ use keyboard_layout::layout::{LayerKey, LayerKeyIndex, LayerModifiers, Layout};
 pub type TrigramIndices = AHashMap<(LayerKeyIndex, LayerKeyIndex, LayerKeyIndex), f64>;
 type TrigramIndicesVec = Vec<((LayerKeyIndex, LayerKeyIndex, LayerKeyIndex), f64)>;
 
-/// Turns the [`Trigrams`]'s characters into their indices, returning a [`TrigramIndicesVec`].
+/// Turns the interned trigrams into their [`LayerKeyIndex`]s (given for each symbol in `symbol_keys`
+/// or chosen among alternatives, see [`choose_alternative_keys`]), returning a [`TrigramIndicesVec`].
 fn map_trigrams(
-    trigrams: &Trigrams,
+    trigrams: &[((SymbolId, SymbolId, SymbolId), f64)],
+    symbol_keys: &[Option<LayerKeyIndex>],
     layout: &Layout,
-    exclude_line_breaks: bool,
+    key_choice: &KeyChoiceConfig,
 ) -> (TrigramIndicesVec, f64) {
     let mut not_found_weight = 0.0;
-    let mut trigrams_vec = Vec::with_capacity(trigrams.grams.len());
-
-    trigrams_vec.extend(
-        trigrams
-            .grams
-            .iter()
-            //.filter(|((c1, c2, c3), _weight)| {
-            //    !c1.is_whitespace() && !c2.is_whitespace() && !c3.is_whitespace()
-            //})
-            .filter_map(|((c1, c2, c3), weight)| {
-                // Exclude trigrams that contain a line break, followed by a non-line-break character
-                if exclude_line_breaks
-                    && ((*c1 == '\n' && *c2 != '\n') || (*c2 == '\n' && *c3 != '\n'))
-                {
-                    return None;
-                }
-
-                let idx1 = match layout.get_layerkey_index_for_symbol(c1) {
-                    Some(idx) => idx,
-                    None => {
-                        not_found_weight += *weight;
-                        return None;
-                    }
-                };
-                let idx2 = match layout.get_layerkey_index_for_symbol(c2) {
-                    Some(idx) => idx,
-                    None => {
-                        not_found_weight += *weight;
-                        return None;
-                    }
-                };
-                let idx3 = match layout.get_layerkey_index_for_symbol(c3) {
-                    Some(idx) => idx,
-                    None => {
-                        not_found_weight += *weight;
-                        return None;
-                    }
-                };
-
-                Some(((idx1, idx2, idx3), *weight))
-            }),
-    );
+    let mut trigrams_vec = Vec::with_capacity(trigrams.len());
+
+    trigrams_vec.extend(trigrams.iter().filter_map(|((s1, s2, s3), weight)| {
+        match (
+            symbol_keys[*s1 as usize],
+            symbol_keys[*s2 as usize],
+            symbol_keys[*s3 as usize],
+        ) {
+            (Some(idx1), Some(idx2), Some(idx3)) => {
+                let mut keys = [idx1, idx2, idx3];
+                choose_alternative_keys(&mut keys, layout, key_choice);
+                Some(((keys[0], keys[1], keys[2]), *weight))
+            }
+            _ => {
+                not_found_weight += *weight;
+                None
+            }
+        }
+    }));
 
     (trigrams_vec, not_found_weight)
 }
 
+/// Exclude trigrams that contain a line break, followed by a non-line-break character
+#[inline(always)]
+pub fn is_excluded((c1, c2, c3): &(char, char, char), exclude_line_breaks: bool) -> bool {
+    exclude_line_breaks && ((*c1 == '\n' && *c2 != '\n') || (*c2 == '\n' && *c3 != '\n'))
+}
+
+/// Turns a trigram's characters into their indices (if all of them can be generated by the layout).
+#[inline(always)]
+fn map_trigram(
+    (c1, c2, c3): &(char, char, char),
+    layout: &Layout,
+    key_choice: &KeyChoiceConfig,
+) -> Option<(LayerKeyIndex, LayerKeyIndex, LayerKeyIndex)> {
+    let mut keys = [
+        layout.get_layerkey_index_for_symbol(c1)?,
+        layout.get_layerkey_index_for_symbol(c2)?,
+        layout.get_layerkey_index_for_symbol(c3)?,
+    ];
+    choose_alternative_keys(&mut keys, layout, key_choice);
+
+    Some((keys[0], keys[1], keys[2]))
+}
+
+/// Resolves &[`LayerKey`] references for a [`LayerKeyIndex`]-based trigram unless it contains
+/// repeating identical modifiers.
+#[inline(always)]
+fn filtered_layerkeys<'s>(
+    (idx1, idx2, idx3): &(LayerKeyIndex, LayerKeyIndex, LayerKeyIndex),
+    w: f64,
+    layout: &'s Layout,
+) -> Option<((&'s LayerKey, &'s LayerKey, &'s LayerKey), f64)> {
+    let k2 = layout.get_layerkey(idx2);
+
+    // If the same modifier appears consecutively, it is usually "hold" instead of repeatedly pressed
+    // --> remove
+    match k2.is_modifier.is_hold() && (idx1 == idx2 || idx2 == idx3) {
+        false => Some((
+            (
+                layout.get_layerkey(idx1), // LayerKey 1
+                k2,                        // LayerKey 2
+                layout.get_layerkey(idx3), // LayerKey 3
+            ),
+            w,
+        )),
+        true => None,
+    }
+}
+
 /// Generates [`LayerKey`]-based trigrams from char-based unigrams. Optionally resolves modifiers
 /// for higher-layer symbols of the layout.
 #[derive(Clone, Debug)]
 pub struct OnDemandTrigramMapper {
     split_modifiers: SplitModifiersConfig,
+    key_choice: KeyChoiceConfig,
 }
 
 impl OnDemandTrigramMapper {
-    pub fn new(split_modifiers: SplitModifiersConfig) -> Self {
-        Self { split_modifiers }
+    pub fn new(split_modifiers: SplitModifiersConfig, key_choice: KeyChoiceConfig) -> Self {
+        Self {
+            split_modifiers,
+            key_choice,
+        }
     }
 
-    /// For a given [`Layout`] generate [`LayerKeyIndex`]-based unigrams, optionally resolving modifiers for higer-layer symbols.
+    /// For a given [`Layout`] generate [`LayerKeyIndex`]-based trigrams from interned ones, optionally
+    /// resolving modifiers for higer-layer symbols. `symbol_keys` holds the [`LayerKeyIndex`] for each symbol id.
     pub fn layerkey_indices(
         &self,
-        trigrams: &Trigrams,
+        trigrams: &[((SymbolId, SymbolId, SymbolId), f64)],
+        symbol_keys: &[Option<LayerKeyIndex>],
         layout: &Layout,
-        exclude_line_breaks: bool,
-    ) -> (TrigramIndices, f64) {
+    ) -> (TrigramIndicesVec, f64) {
         let (mut trigram_keys_vec, not_found_weight) =
-            map_trigrams(trigrams, layout, exclude_line_breaks);
+            map_trigrams(trigrams, symbol_keys, layout, &self.key_choice);
+
+        if layout.has_dead_keys() {
+            trigram_keys_vec = self.process_dead_keys(trigram_keys_vec, layout);
+        }
 
         if layout.has_one_shot_layers() {
             trigram_keys_vec = self.process_one_shot_modifiers(trigram_keys_vec, layout);
         }
 
-        let trigram_keys = if self.split_modifiers.enabled && layout.has_hold_layers() {
+        // Each symbol corresponds to a different LayerKey, so trigrams only need to be aggregated
+        // if they were split or processed otherwise.
+        let trigram_keys = if has_held_keys(layout, self.split_modifiers.enabled) {
             self.process_hold_modifiers(trigram_keys_vec, layout)
+                .into_iter()
+                .collect()
+        } else if layout.has_dead_keys() || layout.has_one_shot_layers() {
+            let mut trigram_w_map = TrigramIndices::with_capacity(trigram_keys_vec.len());
+            trigram_keys_vec
+                .into_iter()
+                .for_each(|(trigram, w)| trigram_w_map.insert_or_add_weight(trigram, w));
+            trigram_w_map.into_iter().collect()
         } else {
-            trigram_keys_vec.into_iter().collect()
+            trigram_keys_vec
         };
 
         (trigram_keys, not_found_weight)
@@ -105,32 +154,71 @@ impl OnDemandTrigramMapper {
     /// Resolve &[`LayerKey`] references for [`LayerKeyIndex`] and filters trigrams that contain
     /// repeating identical modifiers.
     pub fn get_filtered_layerkeys<'s>(
-        trigrams: &TrigramIndices,
+        trigrams: &[((LayerKeyIndex, LayerKeyIndex, LayerKeyIndex), f64)],
         layout: &'s Layout,
     ) -> Vec<((&'s LayerKey, &'s LayerKey, &'s LayerKey), f64)> {
         let mut layerkeys = Vec::with_capacity(trigrams.len());
 
-        layerkeys.extend(trigrams.iter().filter_map(|((idx1, idx2, idx3), w)| {
-            let k2 = layout.get_layerkey(idx2);
-
-            // If the same modifier appears consecutively, it is usually "hold" instead of repeatedly pressed
-            // --> remove
-            match k2.is_modifier.is_hold() && (idx1 == idx2 || idx2 == idx3) {
-                false => Some((
-                    (
-                        layout.get_layerkey(idx1), // LayerKey 1
-                        k2,                        // LayerKey 2
-                        layout.get_layerkey(idx3), // LayerKey 3
-                    ),
-                    *w,
-                )),
-                true => None,
-            }
-        }));
+        layerkeys.extend(
+            trigrams
+                .iter()
+                .filter_map(|(trigram, w)| filtered_layerkeys(trigram, *w, layout)),
+        );
 
         layerkeys
     }
 
+    /// Map a single char-based trigram to [`LayerKey`]-based trigrams (in the same way as
+    /// [`Self::layerkey_indices`] and [`Self::get_filtered_layerkeys`] do), appending them to `layerkeys`.
+    /// Identical resulting trigrams are not aggregated.
+    ///
+    /// Returns `false` if the trigram can not be generated by the layout.
+    pub fn map_single_trigram<'s>(
+        &self,
+        trigram: &(char, char, char),
+        weight: f64,
+        layout: &'s Layout,
+        exclude_line_breaks: bool,
+        layerkeys: &mut Vec<((&'s LayerKey, &'s LayerKey, &'s LayerKey), f64)>,
+    ) -> bool {
+        if is_excluded(trigram, exclude_line_breaks) {
+            return true;
+        }
+
+        let indices = match map_trigram(trigram, layout, &self.key_choice) {
+            Some(indices) => indices,
+            None => return false,
+        };
+
+        let mut resolved = ResolvingNgramVec::new(layerkeys, |trigram, w| {
+            filtered_layerkeys(&trigram, w, layout)
+        });
+        let split_hold_modifiers = has_held_keys(layout, self.split_modifiers.enabled);
+
+        if layout.has_dead_keys() || layout.has_one_shot_layers() {
+            let mut trigrams = vec![(indices, weight)];
+            if layout.has_dead_keys() {
+                trigrams = self.process_dead_keys(trigrams, layout);
+            }
+            if layout.has_one_shot_layers() {
+                trigrams = self.process_one_shot_modifiers(trigrams, layout);
+            }
+
+            trigrams
+                .into_iter()
+                .for_each(|(trigram, w)| match split_hold_modifiers {
+                    true => self.split_hold_modifiers(trigram, w, layout, &mut resolved),
+                    false => resolved.insert_or_add_weight(trigram, w),
+                });
+        } else if split_hold_modifiers {
+            self.split_hold_modifiers(indices, weight, layout, &mut resolved);
+        } else {
+            resolved.insert_or_add_weight(indices, weight);
+        }
+
+        true
+    }
+
     /// Map all trigrams to base-layer trigrams, potentially generating multiple trigrams
     /// with modifiers for those with higer-layer keys.
     ///
@@ -138,7 +226,8 @@ impl OnDemandTrigramMapper {
     /// of the involved base-keys and modifiers. Keys from the latter parts of the trigram will always be after
     /// former ones and modifers always come before their base key. The number of generated trigrams from a single
     /// trigram can be large (tens of trigrams) if multiple symbols of the trigram are accessed using multiple modifiers.
-
+    /// Chorded symbols are split in the same way with the chord's main key as base-key and the
+    /// other keys of the chord as modifiers (always, independent of whether hold modifiers shall be split).
     // this is one of the most intensive functions of the layout evaluation
     fn process_hold_modifiers(
         &self,
@@ -146,134 +235,157 @@ impl OnDemandTrigramMapper {
         layout: &Layout,
     ) -> TrigramIndices {
         let mut trigram_w_map = AHashMap::with_capacity(trigrams.len() / 3);
-        trigrams.into_iter().for_each(|((k1, k2, k3), w)| {
-            let (base1, mods1) = layout.resolve_modifiers(&k1);
-            let (base2, mods2) = layout.resolve_modifiers(&k2);
-            let (base3, mods3) = layout.resolve_modifiers(&k3);
-
-            let (key1, mods1) = match mods1 {
-                LayerModifiers::Hold(mods) => (base1, mods),
-                _ => (k1, Vec::new()),
-            };
-
-            let (key2, mods2) = match mods2 {
-                LayerModifiers::Hold(mods) => (base2, mods),
-                _ => (k2, Vec::new()),
-            };
-
-            let (key3, mods3) = match mods3 {
-                LayerModifiers::Hold(mods) => (base3, mods),
-                _ => (k3, Vec::new()),
-            };
+        trigrams.into_iter().for_each(|(trigram, w)| {
+            self.split_hold_modifiers(trigram, w, layout, &mut trigram_w_map);
+        });
 
-            let k1_take_one = TakeOneLayerKey::new(key1, &mods1, w);
-            let k2_take_one = TakeOneLayerKey::new(key2, &mods2, w);
-            let k3_take_one = TakeOneLayerKey::new(key3, &mods3, w);
-
-            let k1_take_two =
-                TakeTwoLayerKey::new(key1, &mods1, w, self.split_modifiers.same_key_mod_factor);
-            let k2_take_two =
-                TakeTwoLayerKey::new(key2, &mods2, w, self.split_modifiers.same_key_mod_factor);
-            let k3_take_two =
-                TakeTwoLayerKey::new(key3, &mods3, w, self.split_modifiers.same_key_mod_factor);
-
-            k1_take_one.clone().for_each(|(e1, _)| {
-                k2_take_one.clone().for_each(|(e2, _)| {
-                    k3_take_one.clone().for_each(|(e3, _)| {
-                        // log::trace!(
-                        //     "one each:                    {}{}{}",
-                        //     layout.get_layerkey(&e1).symbol,
-                        //     layout.get_layerkey(&e2).symbol,
-                        //     layout.get_layerkey(&e3).symbol,
-                        // );
-                        trigram_w_map.insert_or_add_weight((e1, e2, e3), w);
-                    });
-                });
-            });
+        trigram_w_map
+    }
 
-            k1_take_two.for_each(|((e1, e2), w1)| {
-                k2_take_one.clone().for_each(|(e3, _)| {
+    /// Split a single trigram into base-layer trigrams (see [`Self::process_hold_modifiers`]).
+    #[inline(always)]
+    fn split_hold_modifiers<M: NgramMap<(LayerKeyIndex, LayerKeyIndex, LayerKeyIndex)>>(
+        &self,
+        (k1, k2, k3): (LayerKeyIndex, LayerKeyIndex, LayerKeyIndex),
+        w: f64,
+        layout: &Layout,
+        trigram_w_map: &mut M,
+    ) {
+        let (key1, mods1) = resolve_held_keys(k1, layout, self.split_modifiers.enabled);
+        let (key2, mods2) = resolve_held_keys(k2, layout, self.split_modifiers.enabled);
+        let (key3, mods3) = resolve_held_keys(k3, layout, self.split_modifiers.enabled);
+
+        let k1_take_one = TakeOneLayerKey::new(key1, &mods1, w);
+        let k2_take_one = TakeOneLayerKey::new(key2, &mods2, w);
+        let k3_take_one = TakeOneLayerKey::new(key3, &mods3, w);
+
+        let k1_take_two =
+            TakeTwoLayerKey::new(key1, &mods1, w, self.split_modifiers.same_key_mod_factor);
+        let k2_take_two =
+            TakeTwoLayerKey::new(key2, &mods2, w, self.split_modifiers.same_key_mod_factor);
+        let k3_take_two =
+            TakeTwoLayerKey::new(key3, &mods3, w, self.split_modifiers.same_key_mod_factor);
+
+        k1_take_one.clone().for_each(|(e1, _)| {
+            k2_take_one.clone().for_each(|(e2, _)| {
+                k3_take_one.clone().for_each(|(e3, _)| {
                     // log::trace!(
-                    //     "two of first, one of second: {}{}{}",
+                    //     "one each:                    {}{}{}",
                     //     layout.get_layerkey(&e1).symbol,
                     //     layout.get_layerkey(&e2).symbol,
                     //     layout.get_layerkey(&e3).symbol,
                     // );
-                    trigram_w_map.insert_or_add_weight((e1, e2, e3), w1);
+                    trigram_w_map.insert_or_add_weight((e1, e2, e3), w);
                 });
             });
+        });
 
-            k1_take_one.for_each(|(e1, _)| {
-                k2_take_two.clone().for_each(|((e2, e3), w1)| {
-                    // log::trace!(
-                    //     "one of first, two of second: {}{}{}",
-                    //     layout.get_layerkey(&e1).symbol,
-                    //     layout.get_layerkey(&e2).symbol,
-                    //     layout.get_layerkey(&e3).symbol,
-                    // );
-                    trigram_w_map.insert_or_add_weight((e1, e2, e3), w1);
-                });
+        k1_take_two.for_each(|((e1, e2), w1)| {
+            k2_take_one.clone().for_each(|(e3, _)| {
+                // log::trace!(
+                //     "two of first, one of second: {}{}{}",
+                //     layout.get_layerkey(&e1).symbol,
+                //     layout.get_layerkey(&e2).symbol,
+                //     layout.get_layerkey(&e3).symbol,
+                // );
+                trigram_w_map.insert_or_add_weight((e1, e2, e3), w1);
             });
+        });
 
-            k2_take_two.for_each(|((e1, e2), w1)| {
-                k3_take_one.clone().for_each(|(e3, _)| {
-                    // log::trace!(
-                    //     "two of second, one of third: {}{}{}",
-                    //     layout.get_layerkey(&e1).symbol,
-                    //     layout.get_layerkey(&e2).symbol,
-                    //     layout.get_layerkey(&e3).symbol,
-                    // );
-                    trigram_w_map.insert_or_add_weight((e1, e2, e3), w1);
-                });
+        k1_take_one.for_each(|(e1, _)| {
+            k2_take_two.clone().for_each(|((e2, e3), w1)| {
+                // log::trace!(
+                //     "one of first, two of second: {}{}{}",
+                //     layout.get_layerkey(&e1).symbol,
+                //     layout.get_layerkey(&e2).symbol,
+                //     layout.get_layerkey(&e3).symbol,
+                // );
+                trigram_w_map.insert_or_add_weight((e1, e2, e3), w1);
             });
+        });
 
-            k2_take_one.for_each(|(e1, _)| {
-                k3_take_two.clone().for_each(|((e2, e3), w1)| {
-                    // log::trace!(
-                    //     "one of second, two of third: {}{}{}",
-                    //     layout.get_layerkey(&e1).symbol,
-                    //     layout.get_layerkey(&e2).symbol,
-                    //     layout.get_layerkey(&e3).symbol,
-                    // );
-                    trigram_w_map.insert_or_add_weight((e1, e2, e3), w1);
-                });
+        k2_take_two.for_each(|((e1, e2), w1)| {
+            k3_take_one.clone().for_each(|(e3, _)| {
+                // log::trace!(
+                //     "two of second, one of third: {}{}{}",
+                //     layout.get_layerkey(&e1).symbol,
+                //     layout.get_layerkey(&e2).symbol,
+                //     layout.get_layerkey(&e3).symbol,
+                // );
+                trigram_w_map.insert_or_add_weight((e1, e2, e3), w1);
             });
+        });
 
-            TakeThreeLayerKey::new(key1, &mods1, w, self.split_modifiers.same_key_mod_factor)
-                .for_each(|(e, w)| {
-                    // log::trace!(
-                    //     "three of first:              {}{}{}",
-                    //     layout.get_layerkey(&e.0).symbol,
-                    //     layout.get_layerkey(&e.1).symbol,
-                    //     layout.get_layerkey(&e.2).symbol,
-                    // );
-                    trigram_w_map.insert_or_add_weight(e, w);
-                });
+        k2_take_one.for_each(|(e1, _)| {
+            k3_take_two.clone().for_each(|((e2, e3), w1)| {
+                // log::trace!(
+                //     "one of second, two of third: {}{}{}",
+                //     layout.get_layerkey(&e1).symbol,
+                //     layout.get_layerkey(&e2).symbol,
+                //     layout.get_layerkey(&e3).symbol,
+                // );
+                trigram_w_map.insert_or_add_weight((e1, e2, e3), w1);
+            });
+        });
 
-            TakeThreeLayerKey::new(key2, &mods2, w, self.split_modifiers.same_key_mod_factor)
-                .for_each(|(e, w)| {
-                    // log::trace!(
-                    //     "three of second:             {}{}{}",
-                    //     layout.get_layerkey(&e.0).symbol,
-                    //     layout.get_layerkey(&e.1).symbol,
-                    //     layout.get_layerkey(&e.2).symbol,
-                    // );
-                    trigram_w_map.insert_or_add_weight(e, w);
-                });
+        TakeThreeLayerKey::new(key1, &mods1, w, self.split_modifiers.same_key_mod_factor).for_each(
+            |(e, w)| {
+                // log::trace!(
+                //     "three of first:              {}{}{}",
+                //     layout.get_layerkey(&e.0).symbol,
+                //     layout.get_layerkey(&e.1).symbol,
+                //     layout.get_layerkey(&e.2).symbol,
+                // );
+                trigram_w_map.insert_or_add_weight(e, w);
+            },
+        );
+
+        TakeThreeLayerKey::new(key2, &mods2, w, self.split_modifiers.same_key_mod_factor).for_each(
+            |(e, w)| {
+                // log::trace!(
+                //     "three of second:             {}{}{}",
+                //     layout.get_layerkey(&e.0).symbol,
+                //     layout.get_layerkey(&e.1).symbol,
+                //     layout.get_layerkey(&e.2).symbol,
+                // );
+                trigram_w_map.insert_or_add_weight(e, w);
+            },
+        );
+
+        TakeThreeLayerKey::new(key3, &mods3, w, self.split_modifiers.same_key_mod_factor).for_each(
+            |(e, w)| {
+                // log::trace!(
+                //     "three of third:              {}{}{}",
+                //     layout.get_layerkey(&e.0).symbol,
+                //     layout.get_layerkey(&e.1).symbol,
+                //     layout.get_layerkey(&e.2).symbol,
+                // );
+                trigram_w_map.insert_or_add_weight(e, w);
+            },
+        );
+    }
 
-            TakeThreeLayerKey::new(key3, &mods3, w, self.split_modifiers.same_key_mod_factor)
-                .for_each(|(e, w)| {
-                    // log::trace!(
-                    //     "three of third:              {}{}{}",
-                    //     layout.get_layerkey(&e.0).symbol,
-                    //     layout.get_layerkey(&e.1).symbol,
-                    //     layout.get_layerkey(&e.2).symbol,
-                    // );
-                    trigram_w_map.insert_or_add_weight(e, w);
+    /// Replace symbols that are generated with dead keys by the key sequence they are typed with
+    /// and generate trigrams of consecutive keys from the result.
+    fn process_dead_keys(&self, trigrams: TrigramIndicesVec, layout: &Layout) -> TrigramIndicesVec {
+        let mut processed_trigrams = Vec::with_capacity(trigrams.len());
+        let mut keys = Vec::new();
+
+        trigrams.into_iter().for_each(|((k1, k2, k3), w)| {
+            keys.clear();
+            push_dead_key_sequence(k1, layout, &mut keys);
+            push_dead_key_sequence(k2, layout, &mut keys);
+            push_dead_key_sequence(k3, layout, &mut keys);
+
+            keys.iter()
+                .zip(keys.iter().skip(1))
+                .zip(keys.iter().skip(2))
+                .for_each(|((lk1, lk2), lk3)| {
+                    processed_trigrams.push(((*lk1, *lk2, *lk3), w));
                 });
         });
 
-        trigram_w_map
+        processed_trigrams
     }
 
     fn process_one_shot_modifiers(
@@ -3,7 +3,7 @@
 
 use super::{common::*, on_demand_ngram_mapper::SplitModifiersConfig};
 
-use crate::ngrams::Unigrams;
+use crate::ngrams::interned::SymbolId;
 
 use ahash::AHashMap;
 use keyboard_layout::layout::{LayerKey, LayerKeyIndex, LayerModifiers, Layout};
@@ -14,26 +14,24 @@ use keyboard_layout::layout::{LayerKey, LayerKeyIndex, LayerModifiers, Layout};
 type UnigramIndices = AHashMap<LayerKeyIndex, f64>;
 type UnigramIndicesVec = Vec<(LayerKeyIndex, f64)>;
 
-/// Turns the [`Unigrams`]'s characters into their indices, returning a [`UnigramIndicesVec`].
-fn map_unigrams(unigrams: &Unigrams, layout: &Layout) -> (UnigramIndicesVec, f64) {
+/// Turns the interned unigrams into their [`LayerKeyIndex`]s (given for each symbol in `symbol_keys`),
+/// returning a [`UnigramIndicesVec`].
+fn map_unigrams(
+    unigrams: &[(SymbolId, f64)],
+    symbol_keys: &[Option<LayerKeyIndex>],
+) -> (UnigramIndicesVec, f64) {
     let mut not_found_weight = 0.0;
-    let mut unigrams_vec = Vec::with_capacity(unigrams.grams.len());
+    let mut unigrams_vec = Vec::with_capacity(unigrams.len());
 
     unigrams_vec.extend(
         unigrams
-            .grams
             .iter()
-            //.filter(|(c, _weight)| !c.is_whitespace())
-            .filter_map(|(c, weight)| {
-                let layerkeyidx = match layout.get_layerkey_index_for_symbol(c) {
-                    Some(idx) => idx,
-                    None => {
-                        not_found_weight += *weight;
-                        return None;
-                    }
-                };
-
-                Some((layerkeyidx, *weight))
+            .filter_map(|(s, weight)| match symbol_keys[*s as usize] {
+                Some(idx) => Some((idx, *weight)),
+                None => {
+                    not_found_weight += *weight;
+                    None
+                }
             }),
     );
 
@@ -52,18 +50,38 @@ impl OnDemandUnigramMapper {
         Self { split_modifiers }
     }
 
-    /// For a given [`Layout`] generate [`LayerKeyIndex`]-based unigrams, optionally resolving modifiers for higer-layer symbols.
-    pub fn layerkey_indices(&self, unigrams: &Unigrams, layout: &Layout) -> (UnigramIndices, f64) {
-        let (mut unigram_keys_vec, not_found_weight) = map_unigrams(unigrams, layout);
+    /// For a given [`Layout`] generate [`LayerKeyIndex`]-based unigrams from interned ones, optionally
+    /// resolving modifiers for higer-layer symbols. `symbol_keys` holds the [`LayerKeyIndex`] for each symbol id.
+    pub fn layerkey_indices(
+        &self,
+        unigrams: &[(SymbolId, f64)],
+        symbol_keys: &[Option<LayerKeyIndex>],
+        layout: &Layout,
+    ) -> (UnigramIndicesVec, f64) {
+        let (mut unigram_keys_vec, not_found_weight) = map_unigrams(unigrams, symbol_keys);
+
+        if layout.has_dead_keys() {
+            unigram_keys_vec = self.process_dead_keys(unigram_keys_vec, layout);
+        }
 
         if layout.has_one_shot_layers() {
             unigram_keys_vec = self.process_one_shot_modifiers(unigram_keys_vec, layout);
         }
 
-        let unigram_keys = if self.split_modifiers.enabled && layout.has_hold_layers() {
-            Self::process_hold_modifiers(unigram_keys_vec, layout)
+        // Each symbol corresponds to a different LayerKey, so unigrams only need to be aggregated
+        // if they were split or processed otherwise.
+        let unigram_keys = if has_held_keys(layout, self.split_modifiers.enabled) {
+            self.process_hold_modifiers(unigram_keys_vec, layout)
+                .into_iter()
+                .collect()
+        } else if layout.has_dead_keys() || layout.has_one_shot_layers() {
+            let mut idx_w_map = UnigramIndices::with_capacity(unigram_keys_vec.len());
+            unigram_keys_vec
+                .into_iter()
+                .for_each(|(k, w)| idx_w_map.insert_or_add_weight(k, w));
+            idx_w_map.into_iter().collect()
         } else {
-            unigram_keys_vec.into_iter().collect()
+            unigram_keys_vec
         };
 
         (unigram_keys, not_found_weight)
@@ -71,7 +89,7 @@ impl OnDemandUnigramMapper {
 
     /// Resolve &[`LayerKey`] references for [`LayerKeyIndex`]
     pub fn get_layerkeys<'s>(
-        unigrams: &UnigramIndices,
+        unigrams: &[(LayerKeyIndex, f64)],
         layout: &'s Layout,
     ) -> Vec<(&'s LayerKey, f64)> {
         unigrams
@@ -80,24 +98,67 @@ impl OnDemandUnigramMapper {
             .collect()
     }
 
+    /// Map a single char-based unigram to [`LayerKey`]-based unigrams (in the same way as
+    /// [`Self::layerkey_indices`] and [`Self::get_layerkeys`] do), appending them to `layerkeys`.
+    /// Identical resulting unigrams are not aggregated.
+    ///
+    /// Returns `false` if the unigram can not be generated by the layout.
+    pub fn map_single_unigram<'s>(
+        &self,
+        unigram: &char,
+        weight: f64,
+        layout: &'s Layout,
+        layerkeys: &mut Vec<(&'s LayerKey, f64)>,
+    ) -> bool {
+        let idx = match layout.get_layerkey_index_for_symbol(unigram) {
+            Some(idx) => idx,
+            None => return false,
+        };
+
+        let mut resolved =
+            ResolvingNgramVec::new(layerkeys, |k, w| Some((layout.get_layerkey(&k), w)));
+        let split_hold_modifiers = has_held_keys(layout, self.split_modifiers.enabled);
+
+        if layout.has_dead_keys() || layout.has_one_shot_layers() {
+            let mut unigrams = vec![(idx, weight)];
+            if layout.has_dead_keys() {
+                unigrams = self.process_dead_keys(unigrams, layout);
+            }
+            if layout.has_one_shot_layers() {
+                unigrams = self.process_one_shot_modifiers(unigrams, layout);
+            }
+
+            unigrams
+                .into_iter()
+                .for_each(|(k, w)| match split_hold_modifiers {
+                    true => self.split_hold_modifiers(k, w, layout, &mut resolved),
+                    false => resolved.insert_or_add_weight(k, w),
+                });
+        } else if split_hold_modifiers {
+            self.split_hold_modifiers(idx, weight, layout, &mut resolved);
+        } else {
+            resolved.insert_or_add_weight(idx, weight);
+        }
+
+        true
+    }
+
     /// Map all unigrams to base-layer unigrams, potentially generating multiple unigrams
     /// with modifiers for those with higer-layer keys.
     ///
     /// Each unigram of a higher-layer symbol will transform into a unigram with the base-layer key and one
     /// for each modifier involved in accessing the higher layer.
-    fn process_hold_modifiers(unigrams: UnigramIndicesVec, layout: &Layout) -> UnigramIndices {
+    /// Chorded symbols are split into unigrams of each of the chord's keys (always, independent of
+    /// whether hold modifiers shall be split).
+    fn process_hold_modifiers(
+        &self,
+        unigrams: UnigramIndicesVec,
+        layout: &Layout,
+    ) -> UnigramIndices {
         let mut idx_w_map = AHashMap::with_capacity(unigrams.len() / 3);
         unigrams.into_iter().for_each(|(k, w)| {
-            let (base, mods) = layout.resolve_modifiers(&k);
-
-            let (key, mods) = match mods {
-                LayerModifiers::Hold(mods) => (base, mods),
-                _ => (k, Vec::new()),
-            };
-
             // Make sure we don't have any duplicate unigrams by adding them up.
-            TakeOneLayerKey::new(key, &mods, w)
-                .for_each(|(idx, w)| idx_w_map.insert_or_add_weight(idx, w));
+            self.split_hold_modifiers(k, w, layout, &mut idx_w_map);
 
             // if base.symbol == ' ' {
             // println!(
@@ -117,6 +178,36 @@ impl OnDemandUnigramMapper {
         idx_w_map
     }
 
+    /// Split a single unigram into base-layer unigrams (see [`Self::process_hold_modifiers`]).
+    #[inline(always)]
+    fn split_hold_modifiers<M: NgramMap<LayerKeyIndex>>(
+        &self,
+        k: LayerKeyIndex,
+        w: f64,
+        layout: &Layout,
+        idx_w_map: &mut M,
+    ) {
+        let (key, mods) = resolve_held_keys(k, layout, self.split_modifiers.enabled);
+
+        TakeOneLayerKey::new(key, &mods, w)
+            .for_each(|(idx, w)| idx_w_map.insert_or_add_weight(idx, w));
+    }
+
+    /// Replace each unigram of a symbol that is generated with dead keys by unigrams of each key
+    /// of its sequence.
+    fn process_dead_keys(&self, unigrams: UnigramIndicesVec, layout: &Layout) -> UnigramIndicesVec {
+        let mut processed_unigrams = Vec::with_capacity(unigrams.len());
+        let mut keys = Vec::new();
+
+        unigrams.into_iter().for_each(|(k, w)| {
+            keys.clear();
+            push_dead_key_sequence(k, layout, &mut keys);
+            processed_unigrams.extend(keys.iter().map(|k| (*k, w)));
+        });
+
+        processed_unigrams
+    }
+
     fn process_one_shot_modifiers(
         &self,
         unigrams: UnigramIndicesVec,
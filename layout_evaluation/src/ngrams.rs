@@ -1,6 +1,11 @@
 //! The `ngrams` module provides structs for reading (and to some extent modifying)
-//! ngram (unigram, bigram, trigram) data that serve as the underlying data for layout
-//! evaluations.
+//! ngram (unigram, bigram, trigram, quadgram, skipgram) data that serve as the underlying data
+//! for layout evaluations.
+//!
+//! For the evaluation of layouts, the char-based ngrams are converted into a more compact
+//! representation (see the [`interned`] module).
+
+pub mod interned;
 
 use crate::ngram_mapper::common::NgramMap;
 
@@ -200,11 +205,16 @@ pub struct Bigrams {
 impl Bigrams {
     /// Collect bigrams from given text.
     pub fn from_text(text: &str) -> Result<Self> {
+        Self::from_text_with_skip(text, 0)
+    }
+
+    /// Collect bigrams of chars with `skip` other chars in between ("skipgrams") from given text.
+    pub fn from_text_with_skip(text: &str, skip: usize) -> Result<Self> {
         let mut grams = AHashMap::default();
         let chars = text.chars().filter(|c| *c != '\r');
         chars
             .clone()
-            .zip(chars.clone().skip(1))
+            .zip(chars.clone().skip(1 + skip))
             //.filter(|(c1, c2)| !c1.is_whitespace() && !c2.is_whitespace())
             .for_each(|c| {
                 grams.insert_or_add_weight(c, 1.0);
@@ -318,6 +328,95 @@ impl Bigrams {
     }
 }
 
+/// The skipgrams that are generated from texts by default have up to this many chars in between.
+pub const DEFAULT_MAX_SKIP: usize = 2;
+
+/// Holds bigrams of chars with a given number of other chars in between ("skipgrams") with
+/// corresponding frequency. In contrast to deriving them from trigrams, skipgrams collected
+/// directly from a text are not affected by reducing the trigrams to the most common ones.
+#[derive(Clone, Debug)]
+pub struct Skipgrams {
+    /// Number of chars between the two chars of each skipgram
+    pub skip: usize,
+    /// The first and last char of each skipgram
+    pub bigrams: Bigrams,
+}
+
+impl Skipgrams {
+    /// Name of the frequency file of skipgrams with given skip distance.
+    pub fn filename(skip: usize) -> String {
+        format!("{}-skipgrams.txt", skip)
+    }
+
+    /// Collect skipgrams with given skip distance from given text.
+    pub fn from_text(text: &str, skip: usize) -> Result<Self> {
+        Ok(Self {
+            skip,
+            bigrams: Bigrams::from_text_with_skip(text, skip)?,
+        })
+    }
+
+    /// Read skipgrams (with given skip distance) and weights from a file containing lines with
+    /// the skipgrams' first and last chars and their weights.
+    pub fn from_file(filename: &str, skip: usize) -> Result<Self> {
+        Ok(Self {
+            skip,
+            bigrams: Bigrams::from_file(filename)?,
+        })
+    }
+
+    /// Read all skipgram files (with consecutive skip distances starting at one) from a directory.
+    pub fn from_dir<T: AsRef<Path>>(dir: T) -> Result<Vec<Self>> {
+        let mut skipgrams = Vec::new();
+        loop {
+            let skip = skipgrams.len() + 1;
+            let p = dir.as_ref().join(Self::filename(skip));
+            if !p.exists() {
+                break;
+            }
+            log::info!("Reading skipgram file: '{:?}'", p);
+            skipgrams.push(Self::from_file(p.to_str().unwrap(), skip)?);
+        }
+
+        Ok(skipgrams)
+    }
+
+    /// Total weight of all combined skipgrams
+    pub fn total_weight(&self) -> f64 {
+        self.bigrams.total_weight()
+    }
+
+    /// Return a reduced set of the skipgrams containing only the most common skipgrams up to a
+    /// given combined fraction.
+    pub fn tops(&self, fraction: f64) -> Self {
+        Self {
+            skip: self.skip,
+            bigrams: self.bigrams.tops(fraction),
+        }
+    }
+
+    // Return a reduced set of skipgrams filtering out those containing a given character
+    pub fn exclude_char(&self, exclude: &char) -> Self {
+        Self {
+            skip: self.skip,
+            bigrams: self.bigrams.exclude_char(exclude),
+        }
+    }
+
+    /// Save frequencies to a file (named by [`Self::filename`]) in given directory
+    pub fn save_frequencies<T: AsRef<Path>>(&self, dir: T) -> Result<(), String> {
+        self.bigrams
+            .save_frequencies(dir.as_ref().join(Self::filename(self.skip)))
+    }
+
+    pub fn increase_common(&self, params: &IncreaseCommonNgramsConfig) -> Self {
+        Self {
+            skip: self.skip,
+            bigrams: self.bigrams.increase_common(params),
+        }
+    }
+}
+
 /// Holds a hashmap of trigrams (three chars) with corresponding frequency (here often called "weight").
 #[derive(Clone, Debug)]
 pub struct Trigrams {
@@ -454,3 +553,134 @@ impl Trigrams {
         Self { grams }
     }
 }
+
+/// Holds a hashmap of quadgrams (four chars) with corresponding frequency (here often called "weight").
+#[derive(Clone, Debug, Default)]
+pub struct Quadgrams {
+    pub grams: AHashMap<(char, char, char, char), f64>,
+}
+
+impl Quadgrams {
+    /// Collect quadgrams from given text.
+    pub fn from_text(text: &str) -> Result<Self> {
+        let mut grams = AHashMap::default();
+        let chars = text.chars().filter(|c| *c != '\r');
+        chars
+            .clone()
+            .zip(chars.clone().skip(1))
+            .zip(chars.clone().skip(2))
+            .zip(chars.clone().skip(3))
+            .for_each(|(((c1, c2), c3), c4)| {
+                grams.insert_or_add_weight((c1, c2, c3, c4), 1.0);
+            });
+
+        Ok(Self { grams })
+    }
+
+    /// Read quadgrams and weights from a string containing lines with quadgrams and their weights.
+    pub fn from_frequencies_str(data: &str) -> Result<Self> {
+        let mut grams = AHashMap::default();
+        for line in data.lines() {
+            let mut parts = line.trim_start().splitn(2, ' ');
+            let weight: f64 = parts.next().unwrap().parse().unwrap();
+            let quadgram = parts.next().unwrap();
+            let quadgram = process_special_characters(quadgram);
+            let c: Vec<char> = quadgram.chars().collect();
+            if c.len() != 4 {
+                log::info!("Len of quadgram {} is unequal four: {:?}", quadgram, c);
+            }
+            grams.insert_or_add_weight((c[0], c[1], c[2], c[3]), weight);
+        }
+
+        Ok(Quadgrams { grams })
+    }
+
+    /// Read quadgrams and weights from a file containing lines with quadgrams and their weights.
+    pub fn from_file(filename: &str) -> Result<Self> {
+        let data = fs::read_to_string(filename)?;
+        Quadgrams::from_frequencies_str(&data)
+    }
+
+    /// Total weight of all combined quadgrams
+    pub fn total_weight(&self) -> f64 {
+        self.grams.values().sum()
+    }
+
+    /// Return a reduced set of the quadgrams containing only the most common quadgrams up to a
+    /// given combined fraction.
+    pub fn tops(&self, fraction: f64) -> Self {
+        let target_weight = fraction * self.total_weight();
+        let mut total_weight = 0.0;
+        let mut sorted_grams: Vec<((char, char, char, char), f64)> =
+            self.grams.clone().into_iter().collect();
+        sorted_grams.sort_by(|(_, w1), (_, w2)| w2.partial_cmp(w1).unwrap());
+        let grams: AHashMap<(char, char, char, char), f64> = sorted_grams
+            .iter()
+            .take_while(|(_c, w)| {
+                let res = total_weight < target_weight;
+                total_weight += *w;
+
+                res
+            })
+            .cloned()
+            .collect();
+
+        log::info!(
+            "Quadgrams: Reducing from originally {} to the top {} ngrams.",
+            self.grams.len(),
+            grams.len()
+        );
+        Self { grams }
+    }
+
+    // Return a reduced set of quadgrams filtering out those containing a given character
+    pub fn exclude_char(&self, exclude: &char) -> Self {
+        let grams: AHashMap<(char, char, char, char), f64> = self
+            .grams
+            .iter()
+            .filter_map(|((c1, c2, c3, c4), w)| {
+                if *c1 == *exclude || *c2 == *exclude || *c3 == *exclude || *c4 == *exclude {
+                    None
+                } else {
+                    Some(((*c1, *c2, *c3, *c4), *w))
+                }
+            })
+            .collect();
+        Self { grams }
+    }
+
+    /// Save frequencies to file
+    pub fn save_frequencies<T: AsRef<Path>>(&self, filename: T) -> Result<(), String> {
+        let p = filename.as_ref();
+        create_dir_all(p.parent().unwrap()).map_err(|e| {
+            format!(
+                "Unable to create directory '{}': {}",
+                p.to_str().unwrap(),
+                e
+            )
+        })?;
+
+        let mut grams: Vec<((char, char, char, char), f64)> =
+            self.grams.iter().map(|(c, w)| (*c, *w)).collect();
+        grams.sort_by(|(_, w1), (_, w2)| w2.partial_cmp(w1).unwrap());
+
+        let file = File::create(&filename)
+            .map_err(|e| format!("Unable to create file '{}': {}", p.to_str().unwrap(), e))?;
+        let mut buf_writer = BufWriter::new(file);
+        grams.iter().for_each(|((c1, c2, c3, c4), w)| {
+            let processed: String = [c1, c2, c3, c4]
+                .iter()
+                .map(|c| process_special_characters_inverse(&c.to_string()))
+                .collect();
+            writeln!(&mut buf_writer, "{} {}", w, processed).unwrap();
+        });
+
+        Ok(())
+    }
+
+    pub fn increase_common(&self, params: &IncreaseCommonNgramsConfig) -> Self {
+        let mut grams = self.grams.clone();
+        increase_common_ngrams(&mut grams, params);
+        Self { grams }
+    }
+}
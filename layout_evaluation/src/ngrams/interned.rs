@@ -0,0 +1,336 @@
+//! The `interned` module provides a compact representation of char-based ngrams for the
+//! evaluation of layouts.
+//!
+//! Each distinct symbol of the ngrams is assigned a dense [`SymbolId`] once and the ngrams are
+//! stored as sorted arrays of id tuples with their weights. For a given layout, mapping the
+//! ngrams to the layout's keys then only requires a lookup of each distinct symbol (see
+//! [`SymbolTable::layerkey_indices`]) and array indexing for each ngram, instead of hashing
+//! every single ngram.
+
+use super::{Bigrams, Quadgrams, Skipgrams, Trigrams, Unigrams};
+
+use keyboard_layout::layout::{LayerKeyIndex, Layout};
+
+use ahash::AHashMap;
+
+/// The index of a symbol in a [`SymbolTable`]
+pub type SymbolId = u32;
+
+/// A bigram (or skipgram) in terms of [`SymbolId`]s
+pub type SymbolBigram = (SymbolId, SymbolId);
+
+/// A quadgram in terms of [`SymbolId`]s
+pub type SymbolQuadgram = (SymbolId, SymbolId, SymbolId, SymbolId);
+
+/// Assigns a dense [`SymbolId`] to each distinct symbol.
+#[derive(Clone, Debug, Default)]
+pub struct SymbolTable {
+    symbols: Vec<char>,
+    ids: AHashMap<char, SymbolId>,
+}
+
+impl SymbolTable {
+    /// Get the id of a symbol, assigning a new one if the symbol is not yet known.
+    pub fn intern(&mut self, c: char) -> SymbolId {
+        let symbols = &mut self.symbols;
+        *self.ids.entry(c).or_insert_with(|| {
+            symbols.push(c);
+            (symbols.len() - 1) as SymbolId
+        })
+    }
+
+    /// Get the id of a symbol (if it is known).
+    pub fn id(&self, c: &char) -> Option<SymbolId> {
+        self.ids.get(c).cloned()
+    }
+
+    /// Get the symbol corresponding to an id.
+    pub fn symbol(&self, id: SymbolId) -> char {
+        self.symbols[id as usize]
+    }
+
+    /// All known symbols (indexed by their ids).
+    pub fn symbols(&self) -> &[char] {
+        &self.symbols
+    }
+
+    /// Number of known symbols.
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    /// Whether no symbol is known.
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    /// For each symbol (indexed by its id), the [`LayerKeyIndex`] of the [`keyboard_layout::layout::LayerKey`]
+    /// that generates it in the given layout (`None` if the layout can not generate the symbol).
+    pub fn layerkey_indices(&self, layout: &Layout) -> Vec<Option<LayerKeyIndex>> {
+        self.symbols
+            .iter()
+            .map(|c| layout.get_layerkey_index_for_symbol(c))
+            .collect()
+    }
+}
+
+/// Unigrams, bigrams, trigrams, quadgrams, and skipgrams in terms of the [`SymbolId`]s of a common [`SymbolTable`]
+#[derive(Clone, Debug)]
+pub struct InternedNgrams {
+    /// The table of all symbols occurring in the ngrams
+    pub symbols: SymbolTable,
+    /// Unigrams (sorted by their ids) and their weights
+    pub unigrams: Vec<(SymbolId, f64)>,
+    /// Bigrams (sorted by their ids) and their weights
+    pub bigrams: Vec<((SymbolId, SymbolId), f64)>,
+    /// Trigrams (sorted by their ids) and their weights
+    pub trigrams: Vec<((SymbolId, SymbolId, SymbolId), f64)>,
+    /// Quadgrams (sorted by their ids) and their weights
+    pub quadgrams: Vec<(SymbolQuadgram, f64)>,
+    /// For each skip distance, skipgrams (sorted by their ids) and their weights
+    pub skipgrams: Vec<(usize, Vec<(SymbolBigram, f64)>)>,
+}
+
+impl InternedNgrams {
+    /// Intern the given char-based ngrams. Only the ngrams for which `keep_*` returns `true` are
+    /// included. Skipgrams are filtered with `keep_bigram`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        unigrams: &Unigrams,
+        bigrams: &Bigrams,
+        trigrams: &Trigrams,
+        quadgrams: &Quadgrams,
+        skipgrams: &[Skipgrams],
+        keep_bigram: impl Fn(&(char, char)) -> bool,
+        keep_trigram: impl Fn(&(char, char, char)) -> bool,
+        keep_quadgram: impl Fn(&(char, char, char, char)) -> bool,
+    ) -> Self {
+        let mut symbols = SymbolTable::default();
+
+        let mut interned_unigrams: Vec<(SymbolId, f64)> = unigrams
+            .grams
+            .iter()
+            .map(|(c, w)| (symbols.intern(*c), *w))
+            .collect();
+        interned_unigrams.sort_unstable_by_key(|(ids, _)| *ids);
+
+        let mut interned_bigrams: Vec<((SymbolId, SymbolId), f64)> = bigrams
+            .grams
+            .iter()
+            .filter(|(bigram, _)| keep_bigram(bigram))
+            .map(|((c1, c2), w)| ((symbols.intern(*c1), symbols.intern(*c2)), *w))
+            .collect();
+        interned_bigrams.sort_unstable_by_key(|(ids, _)| *ids);
+
+        let interned_skipgrams: Vec<(usize, Vec<(SymbolBigram, f64)>)> = skipgrams
+            .iter()
+            .map(|s| {
+                let mut interned: Vec<(SymbolBigram, f64)> = s
+                    .bigrams
+                    .grams
+                    .iter()
+                    .filter(|(bigram, _)| keep_bigram(bigram))
+                    .map(|((c1, c2), w)| ((symbols.intern(*c1), symbols.intern(*c2)), *w))
+                    .collect();
+                interned.sort_unstable_by_key(|(ids, _)| *ids);
+                (s.skip, interned)
+            })
+            .collect();
+
+        let mut interned_trigrams: Vec<((SymbolId, SymbolId, SymbolId), f64)> = trigrams
+            .grams
+            .iter()
+            .filter(|(trigram, _)| keep_trigram(trigram))
+            .map(|((c1, c2, c3), w)| {
+                (
+                    (
+                        symbols.intern(*c1),
+                        symbols.intern(*c2),
+                        symbols.intern(*c3),
+                    ),
+                    *w,
+                )
+            })
+            .collect();
+        interned_trigrams.sort_unstable_by_key(|(ids, _)| *ids);
+
+        let mut interned_quadgrams: Vec<(SymbolQuadgram, f64)> = quadgrams
+            .grams
+            .iter()
+            .filter(|(quadgram, _)| keep_quadgram(quadgram))
+            .map(|((c1, c2, c3, c4), w)| {
+                (
+                    (
+                        symbols.intern(*c1),
+                        symbols.intern(*c2),
+                        symbols.intern(*c3),
+                        symbols.intern(*c4),
+                    ),
+                    *w,
+                )
+            })
+            .collect();
+        interned_quadgrams.sort_unstable_by_key(|(ids, _)| *ids);
+
+        Self {
+            symbols,
+            unigrams: interned_unigrams,
+            bigrams: interned_bigrams,
+            trigrams: interned_trigrams,
+            quadgrams: interned_quadgrams,
+            skipgrams: interned_skipgrams,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_utils::{layout, STANDARD_CONFIG, TEXT};
+
+    use keyboard_layout::layout::LayerKeyIndex;
+
+    use ordered_float::OrderedFloat;
+
+    type MappedNgrams = Vec<(Option<Vec<LayerKeyIndex>>, OrderedFloat<f64>)>;
+
+    /// Map char-based ngrams symbol by symbol (`None` if a symbol is not in the layout).
+    fn map_chars(grams: impl Iterator<Item = (Vec<char>, f64)>, layout: &Layout) -> MappedNgrams {
+        let mut mapped: MappedNgrams = grams
+            .map(|(chars, w)| {
+                let keys = chars
+                    .iter()
+                    .map(|c| layout.get_layerkey_index_for_symbol(c))
+                    .collect();
+                (keys, OrderedFloat(w))
+            })
+            .collect();
+        mapped.sort_unstable();
+        mapped
+    }
+
+    /// Map interned ngrams with the symbols' keys (`None` if a symbol is not in the layout).
+    fn map_ids(
+        grams: impl Iterator<Item = (Vec<SymbolId>, f64)>,
+        symbol_keys: &[Option<LayerKeyIndex>],
+    ) -> MappedNgrams {
+        let mut mapped: MappedNgrams = grams
+            .map(|(ids, w)| {
+                let keys = ids.iter().map(|id| symbol_keys[*id as usize]).collect();
+                (keys, OrderedFloat(w))
+            })
+            .collect();
+        mapped.sort_unstable();
+        mapped
+    }
+
+    #[test]
+    fn interned_ngrams_map_like_chars() {
+        let layout = layout(STANDARD_CONFIG);
+        let unigrams = Unigrams::from_text(TEXT).unwrap();
+        let bigrams = Bigrams::from_text(TEXT).unwrap();
+        let trigrams = Trigrams::from_text(TEXT).unwrap();
+        let quadgrams = Quadgrams::from_text(TEXT).unwrap();
+        let skipgrams = vec![Skipgrams::from_text(TEXT, 2).unwrap()];
+
+        let keep_bigram = |(c1, c2): &(char, char)| !(*c1 == '\n' && *c2 != '\n');
+        let keep_trigram = |(c1, c2, _): &(char, char, char)| keep_bigram(&(*c1, *c2));
+        let keep_quadgram = |(c1, c2, _, _): &(char, char, char, char)| keep_bigram(&(*c1, *c2));
+        let interned = InternedNgrams::new(
+            &unigrams,
+            &bigrams,
+            &trigrams,
+            &quadgrams,
+            &skipgrams,
+            keep_bigram,
+            keep_trigram,
+            keep_quadgram,
+        );
+        let symbol_keys = interned.symbols.layerkey_indices(&layout);
+
+        assert!(interned.symbols.symbols().contains(&'😀'));
+        assert!(symbol_keys.iter().any(|k| k.is_none()));
+
+        assert_eq!(
+            map_ids(
+                interned.unigrams.iter().map(|(id, w)| (vec![*id], *w)),
+                &symbol_keys
+            ),
+            map_chars(unigrams.grams.iter().map(|(c, w)| (vec![*c], *w)), &layout),
+        );
+
+        assert_eq!(
+            map_ids(
+                interned
+                    .bigrams
+                    .iter()
+                    .map(|((id1, id2), w)| (vec![*id1, *id2], *w)),
+                &symbol_keys
+            ),
+            map_chars(
+                bigrams
+                    .grams
+                    .iter()
+                    .filter(|(bigram, _)| keep_bigram(bigram))
+                    .map(|((c1, c2), w)| (vec![*c1, *c2], *w)),
+                &layout
+            ),
+        );
+
+        assert_eq!(
+            map_ids(
+                interned.skipgrams[0]
+                    .1
+                    .iter()
+                    .map(|((id1, id2), w)| (vec![*id1, *id2], *w)),
+                &symbol_keys
+            ),
+            map_chars(
+                skipgrams[0]
+                    .bigrams
+                    .grams
+                    .iter()
+                    .filter(|(bigram, _)| keep_bigram(bigram))
+                    .map(|((c1, c2), w)| (vec![*c1, *c2], *w)),
+                &layout
+            ),
+        );
+
+        assert_eq!(
+            map_ids(
+                interned
+                    .trigrams
+                    .iter()
+                    .map(|((id1, id2, id3), w)| (vec![*id1, *id2, *id3], *w)),
+                &symbol_keys
+            ),
+            map_chars(
+                trigrams
+                    .grams
+                    .iter()
+                    .filter(|(trigram, _)| keep_trigram(trigram))
+                    .map(|((c1, c2, c3), w)| (vec![*c1, *c2, *c3], *w)),
+                &layout
+            ),
+        );
+
+        assert_eq!(
+            map_ids(
+                interned
+                    .quadgrams
+                    .iter()
+                    .map(|((id1, id2, id3, id4), w)| (vec![*id1, *id2, *id3, *id4], *w)),
+                &symbol_keys
+            ),
+            map_chars(
+                quadgrams
+                    .grams
+                    .iter()
+                    .filter(|(quadgram, _)| keep_quadgram(quadgram))
+                    .map(|((c1, c2, c3, c4), w)| (vec![*c1, *c2, *c3, *c4], *w)),
+                &layout
+            ),
+        );
+    }
+}
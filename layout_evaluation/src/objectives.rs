@@ -0,0 +1,114 @@
+//! The `objectives` module provides vector-valued objectives for multi-objective optimizations.
+//!
+//! Instead of collapsing all metrics into a single total cost, the metrics are grouped into
+//! several objectives (e.g. ergonomics and learnability). Each objective is the sum of the
+//! weighted costs of the metrics in its group.
+
+use crate::results::{EvaluationResult, MetricType};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ObjectiveError {
+    #[error("Unknown (or disabled) metric '{0}' in objective '{1}'")]
+    UnknownMetric(String, String),
+    #[error("Objective '{0}' does not contain any evaluated metric")]
+    EmptyObjective(String),
+}
+
+/// A group of metrics whose weighted costs are summed to an objective.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ObjectiveGroup {
+    /// Name of the objective.
+    pub name: String,
+    /// Include all metrics of these types.
+    #[serde(default)]
+    pub metric_types: Vec<MetricType>,
+    /// Include the metrics of these names (as in the evaluation results).
+    #[serde(default)]
+    pub metrics: Vec<String>,
+}
+
+impl ObjectiveGroup {
+    /// Whether the group includes the metric of given type and name.
+    fn contains(&self, metric_type: &MetricType, name: &str) -> bool {
+        self.metric_types.contains(metric_type) || self.metrics.iter().any(|m| m == name)
+    }
+}
+
+/// The objectives of a multi-objective optimization (all to be minimized).
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct Objectives(pub Vec<ObjectiveGroup>);
+
+impl Objectives {
+    /// Names of the objectives.
+    pub fn names(&self) -> Vec<&str> {
+        self.0.iter().map(|group| group.name.as_str()).collect()
+    }
+
+    /// Number of objectives.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Whether there are no objectives.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Check that all metrics named in the objectives are contained in given evaluation result
+    /// and that each objective contains at least one of its metrics.
+    pub fn validate(&self, result: &EvaluationResult) -> Result<()> {
+        let metrics: Vec<(&MetricType, &str)> = result
+            .iter()
+            .flat_map(|metric_results| {
+                metric_results
+                    .metric_costs
+                    .iter()
+                    .map(move |mc| (&metric_results.metric_type, mc.core.name.as_str()))
+            })
+            .collect();
+
+        for group in self.0.iter() {
+            if let Some(name) = group
+                .metrics
+                .iter()
+                .find(|name| !metrics.iter().any(|(_, m)| m == name))
+            {
+                return Err(
+                    ObjectiveError::UnknownMetric(name.to_string(), group.name.clone()).into(),
+                );
+            }
+
+            if !metrics.iter().any(|(t, m)| group.contains(t, m)) {
+                return Err(ObjectiveError::EmptyObjective(group.name.clone()).into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Compute the objective vector, i.e. the sum of the weighted costs of each objective's metrics.
+    pub fn evaluate(&self, result: &EvaluationResult) -> Vec<f64> {
+        self.0
+            .iter()
+            .map(|group| {
+                result
+                    .iter()
+                    .flat_map(|metric_results| {
+                        metric_results
+                            .metric_costs
+                            .iter()
+                            .filter(move |mc| {
+                                group.contains(&metric_results.metric_type, &mc.core.name)
+                            })
+                            .map(|mc| mc.weighted_cost)
+                    })
+                    .sum()
+            })
+            .collect()
+    }
+}
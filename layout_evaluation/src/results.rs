@@ -1,7 +1,11 @@
 //! The `results` module contains structs representing the results of metric evaluations.
 
+use crate::calibration::MetricCalibration;
+
+use keyboard_layout::key::MatrixPosition;
+
 use colored::Colorize;
-use serde::{Deserialize, Serialize};
+use serde::{de, Deserialize, Deserializer, Serialize};
 use std::{fmt, slice};
 
 /// The [`NormalizationType`] specifies how the total cost of a metric evaluation shall be normalized.
@@ -15,6 +19,56 @@ pub enum NormalizationType {
     WeightFound(f64),
     /// Divide the metric result's cost value by the sum of all ngram weights and a given fixed value.
     WeightAll(f64),
+    /// Express the metric result's cost value as z-score relative to the costs of random layouts
+    /// (as recorded in a calibration file). The metric's weight is applied to the z-score of its
+    /// unweighted cost.
+    ZScore(ZScoreNormalization),
+}
+
+/// Parameters of the [`NormalizationType::ZScore`] normalization.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ZScoreNormalization {
+    /// Calibration file containing the metric's cost distribution for random layouts
+    /// (see [`crate::calibration::Calibration`]).
+    pub calibration: String,
+    /// The metric's cost distribution, read from the calibration file when the metric is added
+    /// to an evaluator. It is serialized along with the results, such that deserialized results
+    /// do not depend on the calibration file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub distribution: Option<Box<MetricCalibration>>,
+}
+
+impl ZScoreNormalization {
+    /// The metric's cost distribution. Metric results only hold z-score normalizations with
+    /// distributions (see `deserialize_resolved_normalization`).
+    fn distribution(&self) -> &MetricCalibration {
+        self.distribution.as_ref().unwrap_or_else(|| {
+            panic!(
+                "Z-score normalization without loaded calibration file {}",
+                self.calibration
+            )
+        })
+    }
+}
+
+/// Deserialize the normalization of a metric result, which must not be a z-score normalization
+/// without distribution (results from before distributions were serialized).
+fn deserialize_resolved_normalization<'de, D>(
+    deserializer: D,
+) -> Result<NormalizationType, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let normalization = NormalizationType::deserialize(deserializer)?;
+    match &normalization {
+        NormalizationType::ZScore(z) if z.distribution.is_none() => {
+            Err(de::Error::custom(format!(
+                "z-score normalization without distribution of calibration file {}",
+                z.calibration
+            )))
+        }
+        _ => Ok(normalization),
+    }
 }
 
 /// Specify which data a metric operates on.
@@ -24,6 +78,21 @@ pub enum MetricType {
     Unigram,
     Bigram,
     Trigram,
+    Quadgram,
+    Skipgram,
+}
+
+/// Describes the cost of an individual ngram (in terms of a layout's keys) for a metric.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct NgramCost {
+    /// Labels of the symbols (or modifiers) of the ngram's keys.
+    pub symbols: Vec<String>,
+    /// Matrix positions of the ngram's keys.
+    pub keys: Vec<MatrixPosition>,
+    /// Weight (frequency) of the ngram.
+    pub weight: f64,
+    /// Cost of the ngram (not normalized).
+    pub cost: f64,
 }
 
 /// Describes the result of an individual metric evaluation.
@@ -35,12 +104,32 @@ pub struct MetricResult {
     pub cost: f64,
     /// An optional message that may contain additional details.
     pub message: Option<String>,
+    /// The costs of the individual ngrams, sorted by descending (absolute) cost. Only available if
+    /// requested in the evaluation options and if the metric provides costs for individual ngrams.
+    /// Note that the total cost of non-additive metrics may differ from the sum of these costs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub breakdown: Option<Vec<NgramCost>>,
     /// The weight that shall be used when aggregating all metrics.
     pub weight: f64,
     /// The normalization type to apply.
+    #[serde(deserialize_with = "deserialize_resolved_normalization")]
     pub normalization: NormalizationType,
 }
 
+impl MetricResult {
+    /// The weight and normalization of the metric's cost disregarding any z-score normalization,
+    /// i.e. those that a calibration is based on.
+    pub fn base_weighting(&self) -> (f64, &NormalizationType) {
+        match &self.normalization {
+            NormalizationType::ZScore(z) => {
+                let distribution = z.distribution();
+                (distribution.weight, &distribution.normalization)
+            }
+            normalization => (self.weight, normalization),
+        }
+    }
+}
+
 /// Describes the normalized results of an individual metric evaluation
 /// taking into account the total found/not found ngram weights.
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -110,13 +199,25 @@ impl MetricResults {
         })
     }
 
-    /// Normalize a metric's cost value with given normalization strategy.
-    fn normalize_value(&self, val: f64, normalization_type: &NormalizationType) -> f64 {
+    /// Weight and normalize a metric's cost value with given normalization strategy.
+    fn normalize_value(
+        &self,
+        val: f64,
+        weight: f64,
+        normalization_type: &NormalizationType,
+    ) -> f64 {
         let mut res = match normalization_type {
-            NormalizationType::Fixed(t) => val / t,
-            NormalizationType::WeightFound(t) => val / (t * self.found_weight),
+            NormalizationType::Fixed(t) => weight * val / t,
+            NormalizationType::WeightFound(t) => weight * val / (t * self.found_weight),
             NormalizationType::WeightAll(t) => {
-                val / (t * (self.found_weight + self.not_found_weight))
+                weight * val / (t * (self.found_weight + self.not_found_weight))
+            }
+            NormalizationType::ZScore(z) => {
+                let distribution = z.distribution();
+                let base_cost =
+                    self.normalize_value(val, distribution.weight, &distribution.normalization);
+                // z-score of the unweighted cost (the calibration's weight may be negative)
+                weight * distribution.weight.signum() * distribution.z_score(base_cost)
             }
         };
 
@@ -135,17 +236,24 @@ impl MetricResults {
         normalize: bool,
         weight: bool,
     ) -> f64 {
-        let cost = match weight {
-            true => metric_cost.weight * metric_cost.cost,
-            false => metric_cost.cost,
+        let weight = match weight {
+            true => metric_cost.weight,
+            false => 1.0,
         };
 
         match normalize {
-            true => self.normalize_value(cost, &metric_cost.normalization),
-            false => cost,
+            true => self.normalize_value(metric_cost.cost, weight, &metric_cost.normalization),
+            false => weight * metric_cost.cost,
         }
     }
 
+    /// Compute the weighted and normalized cost of a metric disregarding any z-score normalization
+    /// (see [`MetricResult::base_weighting`]).
+    pub fn base_weighted_cost(&self, metric_cost: &MetricResult) -> f64 {
+        let (weight, normalization) = metric_cost.base_weighting();
+        self.normalize_value(metric_cost.cost, weight, normalization)
+    }
+
     /// Helper function for aggregating all individual metrics' results to a total value.
     fn aggregate_metric_costs(&self, normalize: bool, weight: bool) -> f64 {
         self.metric_costs.iter().fold(0.0, |acc, metric_cost| {
@@ -174,9 +282,7 @@ impl fmt::Display for EvaluationResult {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         self.individual_results
             .iter()
-            .fold(Ok(()), |acc, results| {
-                acc.and_then(|_| writeln!(f, "{}", results))
-            })?;
+            .try_for_each(|results| writeln!(f, "{}", results))?;
 
         writeln!(
             f,
@@ -215,3 +321,56 @@ impl EvaluationResult {
         self.individual_results.iter()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn z_score_result() -> EvaluationResult {
+        let mut results = MetricResults::new(MetricType::Bigram, 100.0, 0.0);
+        results.add_result(MetricResult {
+            name: "Finger Repeats".to_string(),
+            cost: 30.0,
+            message: None,
+            breakdown: None,
+            weight: 2.0,
+            normalization: NormalizationType::ZScore(ZScoreNormalization {
+                calibration: "calibration.yml".to_string(),
+                distribution: Some(Box::new(MetricCalibration {
+                    weight: 1.0,
+                    normalization: NormalizationType::WeightFound(1.0),
+                    mean: 0.2,
+                    std: 0.1,
+                })),
+            }),
+        });
+
+        EvaluationResult::new("layout".to_string(), vec![results])
+    }
+
+    #[test]
+    fn z_score_results_survive_serialization() {
+        let result = z_score_result();
+        // z-score of 30 / 100 = 0.3 is 1.0, weighted with 2.0
+        assert!((result.total_cost() - 2.0).abs() < 1e-9);
+
+        let serialized = serde_yaml::to_string(&result).unwrap();
+        let deserialized: EvaluationResult = serde_yaml::from_str(&serialized).unwrap();
+        assert_eq!(deserialized.total_cost(), result.total_cost());
+    }
+
+    #[test]
+    fn z_score_results_without_distribution_are_rejected() {
+        let mut result = z_score_result();
+        if let NormalizationType::ZScore(z) = &mut result.individual_results[0].metric_costs[0]
+            .core
+            .normalization
+        {
+            z.distribution = None;
+        }
+
+        let serialized = serde_yaml::to_string(&result).unwrap();
+        let err = serde_yaml::from_str::<EvaluationResult>(&serialized).unwrap_err();
+        assert!(err.to_string().contains("calibration.yml"));
+    }
+}
@@ -0,0 +1,380 @@
+//! The `sensitivity` module analyses how robust the ranking of a set of layouts is with respect
+//! to the weights of the metrics.
+//!
+//! The weighted cost of each metric is proportional to its weight (for all normalization types).
+//! Therefore, the total cost of a layout for any perturbed weights can be computed from the
+//! metrics' costs of a single evaluation (see [`MetricCostTable`]).
+
+use crate::results::EvaluationResult;
+
+use colored::Colorize;
+use serde::Serialize;
+use std::fmt;
+
+/// The normalized but unweighted costs of all metrics for a set of layouts.
+#[derive(Debug, Clone, Serialize)]
+pub struct MetricCostTable {
+    /// The evaluated layouts.
+    pub layouts: Vec<String>,
+    /// Names of the metrics.
+    pub metrics: Vec<String>,
+    /// Weights of the metrics (as configured).
+    pub weights: Vec<f64>,
+    /// The normalized but unweighted cost of each metric (inner Vec) for each layout (outer Vec).
+    pub costs: Vec<Vec<f64>>,
+}
+
+impl MetricCostTable {
+    /// Collect the metrics' costs from the evaluation results of the given layouts (evaluated by
+    /// the same evaluator).
+    pub fn new(layouts: Vec<String>, results: &[EvaluationResult]) -> Self {
+        let (metrics, weights) = results
+            .first()
+            .map(|result| {
+                result
+                    .iter()
+                    .flat_map(|metric_results| metric_results.metric_costs.iter())
+                    .map(|mc| (mc.core.name.clone(), mc.core.weight))
+                    .unzip()
+            })
+            .unwrap_or_default();
+
+        let costs = results
+            .iter()
+            .map(|result| {
+                result
+                    .iter()
+                    .flat_map(|metric_results| metric_results.metric_costs.iter())
+                    .map(|mc| mc.unweighted_cost)
+                    .collect()
+            })
+            .collect();
+
+        Self {
+            layouts,
+            metrics,
+            weights,
+            costs,
+        }
+    }
+
+    /// The total cost of each layout if the metrics' weights are scaled by given factors.
+    pub fn total_costs(&self, weight_factors: &[f64]) -> Vec<f64> {
+        self.costs
+            .iter()
+            .map(|costs| {
+                costs
+                    .iter()
+                    .zip(self.weights.iter().zip(weight_factors.iter()))
+                    .map(|(cost, (weight, factor))| cost * weight * factor)
+                    .sum()
+            })
+            .collect()
+    }
+}
+
+/// The rank (starting at zero) of each layout with respect to given costs.
+fn compute_ranks(costs: &[f64]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..costs.len()).collect();
+    order.sort_by(|i, j| costs[*i].partial_cmp(&costs[*j]).unwrap());
+
+    let mut ranks = vec![0; costs.len()];
+    order
+        .iter()
+        .enumerate()
+        .for_each(|(rank, i)| ranks[*i] = rank);
+
+    ranks
+}
+
+/// A change of a single metric's weight that reverses the order of two layouts.
+#[derive(Debug, Clone, Serialize)]
+pub struct RankFlip {
+    /// Index of the layout that is better with the configured weights.
+    pub better: usize,
+    /// Index of the layout that is worse with the configured weights.
+    pub worse: usize,
+    /// Name of the metric.
+    pub metric: String,
+    /// The configured weight of the metric.
+    pub weight: f64,
+    /// The weight of the metric at which the order of the layouts is reversed.
+    pub flip_weight: f64,
+}
+
+impl RankFlip {
+    /// The factor by which the metric's weight needs to be scaled to reverse the order.
+    pub fn factor(&self) -> f64 {
+        self.flip_weight / self.weight
+    }
+}
+
+/// Results of the weight sensitivity analysis of a set of layouts.
+#[derive(Debug, Clone, Serialize)]
+pub struct WeightSensitivity {
+    /// The analysed layouts.
+    pub layouts: Vec<String>,
+    /// Total cost of each layout with the configured weights.
+    pub costs: Vec<f64>,
+    /// Rank (starting at zero) of each layout with the configured weights.
+    pub ranks: Vec<usize>,
+    /// Number of weight perturbations.
+    pub samples: usize,
+    /// How often each layout (outer Vec) achieved each rank (inner Vec) among the perturbations.
+    pub rank_counts: Vec<Vec<usize>>,
+    /// Fraction of the perturbations in which a layout (outer Vec) has lower costs than another one (inner Vec).
+    pub win_probabilities: Vec<Vec<f64>>,
+    /// For each pair of consecutive layouts among the top layouts, the changes of individual metric
+    /// weights that reverse their order (sorted by the required relative change).
+    pub rank_flips: Vec<RankFlip>,
+}
+
+impl WeightSensitivity {
+    /// Rank the layouts for each of the given weight factors (scaling each metric's weight) and
+    /// compute the weight changes that reverse the order of consecutive layouts among the `n_top`
+    /// best layouts.
+    pub fn new(table: &MetricCostTable, weight_factors: &[Vec<f64>], n_top: usize) -> Self {
+        let n_layouts = table.layouts.len();
+
+        let costs = table.total_costs(&vec![1.0; table.metrics.len()]);
+        let ranks = compute_ranks(&costs);
+
+        let mut rank_counts = vec![vec![0; n_layouts]; n_layouts];
+        let mut wins = vec![vec![0; n_layouts]; n_layouts];
+        for factors in weight_factors {
+            let perturbed_costs = table.total_costs(factors);
+            for (i, rank) in compute_ranks(&perturbed_costs).into_iter().enumerate() {
+                rank_counts[i][rank] += 1;
+            }
+            for (i, cost_i) in perturbed_costs.iter().enumerate() {
+                for (j, cost_j) in perturbed_costs.iter().enumerate() {
+                    if cost_i < cost_j {
+                        wins[i][j] += 1;
+                    }
+                }
+            }
+        }
+
+        let samples = weight_factors.len();
+        let win_probabilities = wins
+            .iter()
+            .map(|w| w.iter().map(|w| *w as f64 / samples as f64).collect())
+            .collect();
+
+        let mut order: Vec<usize> = (0..n_layouts).collect();
+        order.sort_by_key(|i| ranks[*i]);
+        let rank_flips = order
+            .iter()
+            .take(n_top)
+            .zip(order.iter().skip(1).take(n_top.saturating_sub(1)))
+            .flat_map(|(better, worse)| find_rank_flips(table, &costs, *better, *worse))
+            .collect();
+
+        Self {
+            layouts: table.layouts.clone(),
+            costs,
+            ranks,
+            samples,
+            rank_counts,
+            win_probabilities,
+            rank_flips,
+        }
+    }
+}
+
+/// The changes of individual metric weights that reverse the order of two layouts (without changing
+/// the sign of the weight).
+fn find_rank_flips(
+    table: &MetricCostTable,
+    costs: &[f64],
+    better: usize,
+    worse: usize,
+) -> Vec<RankFlip> {
+    let cost_difference = costs[worse] - costs[better];
+
+    let mut flips: Vec<RankFlip> = table
+        .metrics
+        .iter()
+        .enumerate()
+        .filter_map(|(m, metric)| {
+            let weight = table.weights[m];
+            let metric_difference = table.costs[worse][m] - table.costs[better][m];
+            if metric_difference == 0.0 || weight == 0.0 {
+                return None;
+            }
+
+            let flip_weight = weight - cost_difference / metric_difference;
+            (flip_weight.signum() == weight.signum()).then(|| RankFlip {
+                better,
+                worse,
+                metric: metric.clone(),
+                weight,
+                flip_weight,
+            })
+        })
+        .collect();
+
+    flips.sort_by(|f1, f2| {
+        let change1 = f1.factor().ln().abs();
+        let change2 = f2.factor().ln().abs();
+        change1.partial_cmp(&change2).unwrap()
+    });
+
+    flips
+}
+
+impl fmt::Display for WeightSensitivity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut order: Vec<usize> = (0..self.layouts.len()).collect();
+        order.sort_by_key(|i| self.ranks[*i]);
+
+        writeln!(
+            f,
+            "{}",
+            format!(
+                "Rank distributions ({} weight perturbations):",
+                self.samples
+            )
+            .bold()
+        )?;
+        for i in order.iter() {
+            let distribution: Vec<String> = self.rank_counts[*i]
+                .iter()
+                .enumerate()
+                .filter(|(_, count)| **count > 0)
+                .map(|(rank, count)| {
+                    format!(
+                        "{}: {:.1}%",
+                        rank + 1,
+                        100.0 * *count as f64 / self.samples as f64
+                    )
+                })
+                .collect();
+            writeln!(
+                f,
+                "  {:>3}. {} {:>8.2} | {}",
+                self.ranks[*i] + 1,
+                self.layouts[*i],
+                self.costs[*i],
+                distribution.join(", ")
+            )?;
+        }
+
+        writeln!(
+            f,
+            "{}",
+            "Win probabilities (row has lower costs than column):".bold()
+        )?;
+        let header: Vec<String> = order
+            .iter()
+            .map(|i| format!("{:>6}", self.ranks[*i] + 1))
+            .collect();
+        writeln!(f, "       {}", header.join(""))?;
+        for i in order.iter() {
+            let probabilities: Vec<String> = order
+                .iter()
+                .map(|j| match i == j {
+                    true => format!("{:>6}", "-"),
+                    false => format!("{:>5.0}%", 100.0 * self.win_probabilities[*i][*j]),
+                })
+                .collect();
+            writeln!(f, "  {:>3}. {}", self.ranks[*i] + 1, probabilities.join(""))?;
+        }
+
+        writeln!(f, "{}", "Weight changes reversing the order:".bold())?;
+        let mut pair = None;
+        for flip in self.rank_flips.iter() {
+            if pair != Some((flip.better, flip.worse)) {
+                pair = Some((flip.better, flip.worse));
+                writeln!(
+                    f,
+                    "  {}. {} vs. {}. {} ({:+.2}):",
+                    self.ranks[flip.better] + 1,
+                    self.layouts[flip.better],
+                    self.ranks[flip.worse] + 1,
+                    self.layouts[flip.worse],
+                    self.costs[flip.worse] - self.costs[flip.better],
+                )?;
+            }
+            writeln!(
+                f,
+                "    {:<35} ×{:<6.2} ({} → {:.2})",
+                flip.metric,
+                flip.factor(),
+                flip.weight,
+                flip.flip_weight
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Layout "B" is better than "A" with the configured weights (costs 9 vs. 12).
+    fn table() -> MetricCostTable {
+        MetricCostTable {
+            layouts: vec!["A".to_string(), "B".to_string()],
+            metrics: vec!["m0".to_string(), "m1".to_string(), "m2".to_string()],
+            weights: vec![1.0, 3.0, 1.0],
+            costs: vec![vec![1.0, 3.0, 2.0], vec![5.0, 1.0, 1.0]],
+        }
+    }
+
+    #[test]
+    fn flip_weights_reverse_the_order() {
+        let table = table();
+        let sensitivity = WeightSensitivity::new(&table, &[vec![1.0; 3]], 2);
+        assert_eq!(sensitivity.costs, vec![12.0, 9.0]);
+        assert_eq!(sensitivity.ranks, vec![1, 0]);
+
+        // m2 would need a negative weight
+        let flips: Vec<(&str, f64)> = sensitivity
+            .rank_flips
+            .iter()
+            .map(|flip| (flip.metric.as_str(), flip.flip_weight))
+            .collect();
+        assert_eq!(flips, vec![("m0", 1.75), ("m1", 1.5)]);
+
+        for flip in sensitivity.rank_flips.iter() {
+            assert_eq!((flip.better, flip.worse), (1, 0));
+            let m = table
+                .metrics
+                .iter()
+                .position(|m| *m == flip.metric)
+                .unwrap();
+            let costs_with_factor = |factor: f64| {
+                let mut factors = vec![1.0; 3];
+                factors[m] = factor;
+                table.total_costs(&factors)
+            };
+
+            // equal costs at the flip weight, reversed order beyond it
+            let costs = costs_with_factor(flip.factor());
+            assert!((costs[0] - costs[1]).abs() < 1e-9);
+            let beyond = if flip.factor() > 1.0 { 1.01 } else { 0.99 };
+            let costs = costs_with_factor(flip.factor() * beyond);
+            assert!(costs[0] < costs[1]);
+        }
+    }
+
+    #[test]
+    fn rank_counts_and_win_probabilities() {
+        let weight_factors = vec![
+            vec![1.0, 1.0, 1.0], // B wins
+            vec![2.0, 1.0, 1.0], // A wins (13 vs. 14)
+            vec![1.0, 1.0, 2.0], // B wins (10 vs. 14)
+        ];
+        let sensitivity = WeightSensitivity::new(&table(), &weight_factors, 2);
+
+        assert_eq!(sensitivity.samples, 3);
+        assert_eq!(sensitivity.rank_counts, vec![vec![1, 2], vec![2, 1]]);
+        assert_eq!(
+            sensitivity.win_probabilities,
+            vec![vec![0.0, 1.0 / 3.0], vec![2.0 / 3.0, 0.0]]
+        );
+    }
+}
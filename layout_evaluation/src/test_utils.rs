@@ -0,0 +1,33 @@
+//! Fixtures shared by the unit tests.
+
+use keyboard_layout::{
+    config::LayoutConfig, keyboard::Keyboard, layout::Layout, layout_generator::LayoutGenerator,
+    neo_layout_generator::NeoLayoutGenerator,
+};
+
+use std::sync::Arc;
+
+pub const STANDARD_CONFIG: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/../config/keyboard/standard.yml"
+);
+pub const CRKBD_CONFIG: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../config/keyboard/crkbd.yml");
+
+pub const LAYOUT: &str = "xvlcwkhgfqyßuiaeosnrtdüöäpzbm,.j";
+
+/// Contains higher-layer symbols, line breaks, and symbols that are not in the layout.
+pub const TEXT: &str = "Über den Wolken muss die Freiheit\nwohl grenzenlos sein. Alle Ängste, \
+    alle Sorgen, sagt man,\n\nblieben darunter verborgen – und dann… 😀 (»Reinhard Mey«) ✓";
+
+/// Generate [`LAYOUT`] for the given layout config.
+pub fn layout_from_config(layout_config: LayoutConfig) -> Layout {
+    let keyboard = Arc::new(Keyboard::from_yaml_object(layout_config.keyboard));
+    NeoLayoutGenerator::from_object(layout_config.base_layout, keyboard)
+        .generate(LAYOUT)
+        .unwrap()
+}
+
+/// Generate [`LAYOUT`] for the layout config file at `path`.
+pub fn layout(path: &str) -> Layout {
+    layout_from_config(LayoutConfig::from_yaml(path).unwrap())
+}
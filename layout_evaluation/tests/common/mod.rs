@@ -0,0 +1,49 @@
+//! Fixtures shared by the integration tests.
+
+// not every test uses every fixture
+#![allow(dead_code)]
+
+use keyboard_layout::{
+    config::LayoutConfig, keyboard::Keyboard, neo_layout_generator::NeoLayoutGenerator,
+};
+use layout_evaluation::{
+    config::EvaluationParameters,
+    ngram_mapper::on_demand_ngram_mapper::OnDemandNgramMapper,
+    ngrams::{Bigrams, Quadgrams, Trigrams, Unigrams},
+};
+
+use std::sync::Arc;
+
+pub const NGRAMS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../ngrams/deu_web_1m");
+pub const LAYOUT_CONFIG: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/../config/keyboard/standard.yml"
+);
+pub const EVALUATION_PARAMETERS: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/../config/evaluation/default.yml"
+);
+
+pub const LAYOUT: &str = "xvlcwkhgfqyßuiaeosnrtdüöäpzbm,.j";
+
+pub fn layout_generator() -> NeoLayoutGenerator {
+    let layout_config = LayoutConfig::from_yaml(LAYOUT_CONFIG).unwrap();
+    let keyboard = Arc::new(Keyboard::from_yaml_object(layout_config.keyboard));
+    NeoLayoutGenerator::from_object(layout_config.base_layout, keyboard)
+}
+
+pub fn eval_params() -> EvaluationParameters {
+    EvaluationParameters::from_yaml(EVALUATION_PARAMETERS).unwrap()
+}
+
+/// An ngram mapper for the uni-, bi-, and trigrams of the German corpus.
+pub fn ngram_mapper(eval_params: &EvaluationParameters) -> OnDemandNgramMapper {
+    OnDemandNgramMapper::with_ngrams(
+        Unigrams::from_file(&format!("{}/1-grams.txt", NGRAMS)).unwrap(),
+        Bigrams::from_file(&format!("{}/2-grams.txt", NGRAMS)).unwrap(),
+        Trigrams::from_file(&format!("{}/3-grams.txt", NGRAMS)).unwrap(),
+        Quadgrams::default(),
+        Vec::new(),
+        eval_params.ngram_mapper.clone(),
+    )
+}
@@ -0,0 +1,105 @@
+mod common;
+
+use common::LAYOUT;
+use keyboard_layout::{
+    layout_generator::LayoutGenerator, neo_layout_generator::NeoLayoutGenerator,
+};
+use layout_evaluation::{
+    evaluation::{EvaluationOptions, Evaluator},
+    results::EvaluationResult,
+};
+
+fn setup() -> (NeoLayoutGenerator, Evaluator) {
+    let layout_generator = common::layout_generator();
+    let eval_params = common::eval_params();
+
+    let evaluator = Evaluator::default(Box::new(common::ngram_mapper(&eval_params)))
+        .with_layout_generator(Box::new(layout_generator.clone()))
+        .default_metrics(&eval_params.metrics)
+        .unwrap();
+
+    (layout_generator, evaluator)
+}
+
+fn assert_same_costs(delta: &EvaluationResult, full: &EvaluationResult) {
+    for (delta_results, full_results) in delta.iter().zip(full.iter()) {
+        assert_eq!(delta_results.metric_type, full_results.metric_type);
+        for (d, f) in delta_results
+            .metric_costs
+            .iter()
+            .zip(full_results.metric_costs.iter())
+        {
+            assert_eq!(d.core.name, f.core.name);
+            assert!(
+                (d.weighted_cost - f.weighted_cost).abs() <= 1e-9 * f.weighted_cost.abs().max(1.0),
+                "{}: delta {} != full {}",
+                f.core.name,
+                d.weighted_cost,
+                f.weighted_cost
+            );
+        }
+    }
+
+    let (delta_cost, full_cost) = (delta.total_cost(), full.total_cost());
+    assert!(
+        (delta_cost - full_cost).abs() <= 1e-9 * full_cost.abs(),
+        "total: delta {} != full {}",
+        delta_cost,
+        full_cost
+    );
+}
+
+#[test]
+fn delta_evaluation_matches_full_evaluation() {
+    let (layout_generator, evaluator) = setup();
+    let options = EvaluationOptions::default();
+
+    let layout = layout_generator.generate(LAYOUT).unwrap();
+    let mut state = evaluator.delta_evaluation_state(&layout);
+
+    let mut layout_chars: Vec<char> = LAYOUT.chars().collect();
+    // swaps within the letters, with punctuation, of a key with itself, and back again
+    let swaps = [
+        (0, 1),
+        (13, 14),
+        (5, 30),
+        (2, 2),
+        (20, 31),
+        (13, 14),
+        (8, 27),
+    ];
+    for (i, j) in swaps {
+        layout_chars.swap(i, j);
+        let layout_str: String = layout_chars.iter().collect();
+        let layout = layout_generator.generate(&layout_str).unwrap();
+
+        let changed_keys = state.layout().differing_keys(&layout);
+        let delta = evaluator.evaluate_layout_delta(&mut state, &layout, &changed_keys);
+        let full = evaluator.evaluate_layout(&layout, &options);
+
+        assert_same_costs(&delta, &full);
+    }
+}
+
+#[test]
+fn delta_evaluation_matches_full_evaluation_after_several_swaps() {
+    let (layout_generator, evaluator) = setup();
+    let options = EvaluationOptions::default();
+
+    let layout = layout_generator.generate(LAYOUT).unwrap();
+    let mut state = evaluator.delta_evaluation_state(&layout);
+
+    // several keys change at once, as with multiple swaps per step in the optimizers
+    let mut layout_chars: Vec<char> = LAYOUT.chars().collect();
+    layout_chars.swap(0, 10);
+    layout_chars.swap(10, 20);
+    layout_chars.swap(3, 29);
+    let layout_str: String = layout_chars.iter().collect();
+    let layout = layout_generator.generate(&layout_str).unwrap();
+
+    let changed_keys = state.layout().differing_keys(&layout);
+    let delta = evaluator.evaluate_layout_delta(&mut state, &layout, &changed_keys);
+    let full = evaluator.evaluate_layout(&layout, &options);
+
+    assert_same_costs(&delta, &full);
+}
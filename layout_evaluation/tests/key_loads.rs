@@ -0,0 +1,45 @@
+mod common;
+
+use common::LAYOUT;
+use keyboard_layout::layout_generator::LayoutGenerator;
+use layout_evaluation::{
+    evaluation::Evaluator,
+    ngram_mapper::on_demand_ngram_mapper::{
+        KeyChoiceConfig, NgramMapperConfig, OnDemandNgramMapper, SplitModifiersConfig,
+    },
+    ngrams::{Bigrams, Quadgrams, Trigrams, Unigrams},
+};
+
+fn key_loads(unigrams: Unigrams) -> Vec<f64> {
+    let layout = common::layout_generator().generate(LAYOUT).unwrap();
+
+    let ngram_provider = OnDemandNgramMapper::with_ngrams(
+        unigrams,
+        Bigrams::from_text("").unwrap(),
+        Trigrams::from_text("").unwrap(),
+        Quadgrams::default(),
+        Vec::new(),
+        NgramMapperConfig {
+            split_modifiers: SplitModifiersConfig {
+                enabled: true,
+                same_key_mod_factor: 1.0,
+            },
+            exclude_line_breaks: false,
+            key_choice: KeyChoiceConfig::default(),
+        },
+    );
+
+    Evaluator::default(Box::new(ngram_provider)).key_loads(&layout)
+}
+
+#[test]
+fn key_loads_sum_to_one() {
+    let loads = key_loads(Unigrams::from_text("hallo welt").unwrap());
+    assert!((loads.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+}
+
+#[test]
+fn key_loads_without_unigram_weight_are_zero() {
+    let loads = key_loads(Unigrams::from_frequencies_str("0 a\n0 e").unwrap());
+    assert!(loads.iter().all(|l| *l == 0.0));
+}
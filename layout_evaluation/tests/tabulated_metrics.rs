@@ -0,0 +1,92 @@
+mod common;
+
+use common::LAYOUT;
+use keyboard_layout::{layout::Layout, layout_generator::LayoutGenerator};
+use layout_evaluation::{
+    config::EvaluationParameters,
+    metrics::{
+        bigram_metrics::{finger_repeats, movement_pattern, BigramMetric},
+        tabulated::{TabulatedBigramMetric, TabulatedTrigramMetric},
+        trigram_metrics::{
+            no_handswitch_in_trigram, trigram_finger_repeats, trigram_rolls, TrigramMetric,
+        },
+    },
+    ngram_mapper::{on_demand_ngram_mapper::OnDemandNgramMapper, NgramMapper},
+};
+
+use serde::de::DeserializeOwned;
+
+fn setup() -> (Layout, EvaluationParameters, OnDemandNgramMapper) {
+    let layout = common::layout_generator().generate(LAYOUT).unwrap();
+    let eval_params = common::eval_params();
+    let ngram_mapper = common::ngram_mapper(&eval_params);
+
+    (layout, eval_params, ngram_mapper)
+}
+
+fn params<P: DeserializeOwned>(eval_params: &EvaluationParameters, name: &str) -> P {
+    let params = &eval_params.metrics.0[name].params;
+    serde_yaml::from_str(&serde_yaml::to_string(params).unwrap()).unwrap()
+}
+
+fn assert_same_cost(name: &str, tabulated: f64, direct: f64) {
+    assert!(direct != 0.0, "{}: no costs", name);
+    assert!(
+        (tabulated - direct).abs() <= 1e-9 * direct.abs(),
+        "{}: tabulated {} != direct {}",
+        name,
+        tabulated,
+        direct
+    );
+}
+
+#[test]
+fn tabulated_bigram_metrics_match_direct_evaluation() {
+    let (layout, eval_params, ngram_mapper) = setup();
+    let bigrams = ngram_mapper.map_bigrams(&layout);
+
+    let metrics: Vec<Box<dyn BigramMetric>> = vec![
+        Box::new(finger_repeats::FingerRepeats::new(&params(
+            &eval_params,
+            "finger_repeats",
+        ))),
+        Box::new(movement_pattern::MovementPattern::new(&params(
+            &eval_params,
+            "movement_pattern",
+        ))),
+    ];
+
+    for metric in metrics {
+        let tabulated = TabulatedBigramMetric::new(metric.clone());
+        let (direct_cost, _) = metric.total_cost(&bigrams.grams, None, &layout, 0);
+        let (tabulated_cost, _) = tabulated.total_cost(&bigrams.grams, None, &layout, 0);
+        assert_same_cost(metric.name(), tabulated_cost, direct_cost);
+    }
+}
+
+#[test]
+fn tabulated_trigram_metrics_match_direct_evaluation() {
+    let (layout, eval_params, ngram_mapper) = setup();
+    let trigrams = ngram_mapper.map_trigrams(&layout);
+
+    let metrics: Vec<Box<dyn TrigramMetric>> = vec![
+        Box::new(no_handswitch_in_trigram::NoHandswitchInTrigram::new(
+            &params(&eval_params, "no_handswitch_in_trigram"),
+        )),
+        Box::new(trigram_finger_repeats::TrigramFingerRepeats::new(&params(
+            &eval_params,
+            "trigram_finger_repeats",
+        ))),
+        Box::new(trigram_rolls::TrigramRolls::new(&params(
+            &eval_params,
+            "trigram_rolls",
+        ))),
+    ];
+
+    for metric in metrics {
+        let tabulated = TabulatedTrigramMetric::new(metric.clone());
+        let (direct_cost, _) = metric.total_cost(&trigrams.grams, None, &layout, 0);
+        let (tabulated_cost, _) = tabulated.total_cost(&trigrams.grams, None, &layout, 0);
+        assert_same_cost(metric.name(), tabulated_cost, direct_cost);
+    }
+}
@@ -1,3 +1,5 @@
+use keyboard_layout::macro_keys::{MacroKeys, MacroKeysError};
+
 use rand::{seq::SliceRandom, thread_rng};
 
 #[derive(Clone, Debug)]
@@ -6,10 +8,16 @@ pub struct LayoutPermutator {
     perm_indices: Vec<usize>,
     fixed_keys: Vec<char>,
     fixed_indices: Vec<usize>,
+    macro_keys: MacroKeys,
 }
 
 impl LayoutPermutator {
-    pub fn new(layout: &str, fixed: &str) -> Self {
+    /// Both the `layout` and the `fixed` symbols are layout strings, i.e. macros are given in
+    /// braces (see [`MacroKeys::parse_layout_str`]).
+    pub fn new(layout: &str, fixed: &str, macro_keys: &MacroKeys) -> Result<Self, MacroKeysError> {
+        let layout = macro_keys.parse_layout_str(layout)?;
+        let fixed = macro_keys.parse_layout_str(fixed)?;
+
         let mut perm_keys = Vec::new();
         let mut perm_indices = Vec::new();
         let mut fixed_keys = Vec::new();
@@ -24,14 +32,16 @@ impl LayoutPermutator {
                 perm_indices.push(i);
             }
         }
-        Self {
+        Ok(Self {
             perm_keys,
             perm_indices,
             fixed_keys,
             fixed_indices,
-        }
+            macro_keys: macro_keys.clone(),
+        })
     }
 
+    /// Generate the layout string of a permutation
     pub fn generate_string(&self, permutation: &[usize]) -> String {
         let mut res: Vec<char> = vec!['-'; self.fixed_keys.len() + self.perm_keys.len()];
 
@@ -45,7 +55,8 @@ impl LayoutPermutator {
             .zip(self.perm_keys.iter())
             .for_each(|(i, c)| res[*i] = *c);
 
-        res.iter().collect()
+        self.macro_keys
+            .format_layout_str(&res.iter().collect::<String>())
     }
 
     pub fn generate_random(&self) -> Vec<usize> {
@@ -83,7 +94,7 @@ impl LayoutPermutator {
         sw_to.shuffle(rng);
 
         // Perform nr_switches switches
-        for (from, to) in sw_from.into_iter().zip(sw_to.into_iter()) {
+        for (from, to) in sw_from.into_iter().zip(sw_to) {
             indices[*to] = permutation[*from];
         }
 
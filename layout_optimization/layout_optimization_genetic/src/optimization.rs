@@ -1,5 +1,8 @@
 use keyboard_layout::{layout::Layout, layout_generator::LayoutGenerator};
-use layout_evaluation::{cache::Cache, evaluation::Evaluator};
+use layout_evaluation::{
+    cache::Cache,
+    evaluation::{EvaluationOptions, Evaluator},
+};
 
 use layout_optimization_common::LayoutPermutator;
 
@@ -68,9 +71,14 @@ impl FitnessFunction<Genotype, usize> for FitnessCalc {
         // Get & return the evaluation-result
         match &self.result_cache {
             Some(result_cache) => result_cache.get_or_insert_with(&layout_str, || {
-                self.evaluator.evaluate_layout(&l).optimization_score()
+                self.evaluator
+                    .evaluate_layout(&l, &EvaluationOptions::default())
+                    .optimization_score()
             }),
-            None => self.evaluator.evaluate_layout(&l).optimization_score(),
+            None => self
+                .evaluator
+                .evaluate_layout(&l, &EvaluationOptions::default())
+                .optimization_score(),
         }
     }
 
@@ -225,12 +233,13 @@ pub fn init_optimization(
     params: &Parameters,
     evaluator: &Evaluator,
     layout_str: &str,
-    layout_generator: &Box<dyn LayoutGenerator>,
+    layout_generator: &dyn LayoutGenerator,
     fixed_characters: &str,
     start_with_layout: bool,
     cache_results: bool,
 ) -> (MySimulator, LayoutPermutator) {
-    let pm = LayoutPermutator::new(layout_str, fixed_characters);
+    let pm = LayoutPermutator::new(layout_str, fixed_characters, layout_generator.macro_keys())
+        .unwrap_or_else(|e| panic!("{}", e));
     let initial_population: Population<Genotype> = if start_with_layout {
         build_population()
             .with_genome_builder(FromGivenLayoutBuilder::with_permutable_layout(&pm))
@@ -254,7 +263,7 @@ pub fn init_optimization(
             .with_evaluation(FitnessCalc {
                 evaluator: Arc::new(evaluator.clone()),
                 permutator: pm.clone(),
-                layout_generator: layout_generator.clone(),
+                layout_generator: layout_generator.clone_box(),
                 result_cache,
             })
             .with_selection(MaximizeSelector::new(
@@ -279,7 +288,7 @@ pub fn optimize(
     params: &Parameters,
     evaluator: &Evaluator,
     layout_str: &str,
-    layout_generator: &Box<dyn LayoutGenerator>,
+    layout_generator: &dyn LayoutGenerator,
     fixed_characters: &str,
     start_with_layout: bool,
     cache_results: bool,
@@ -308,7 +317,8 @@ pub fn optimize(
                         let layout_str = pm.generate_string(&best_solution.solution.genome);
                         let layout = layout_generator.generate(&layout_str).unwrap();
 
-                        let evaluation_result = evaluator.evaluate_layout(&layout);
+                        let evaluation_result =
+                            evaluator.evaluate_layout(&layout, &EvaluationOptions::default());
                         println!(
                             "{}: {} (score: {})\n{}",
                             format!("New best in generation {}:", step.iteration)
@@ -0,0 +1,561 @@
+use keyboard_layout::layout_generator::LayoutGenerator;
+use layout_evaluation::{
+    cache::Cache,
+    evaluation::{EvaluationOptions, Evaluator},
+    objectives::{ObjectiveGroup, Objectives},
+    results::MetricType,
+};
+
+use layout_optimization_common::LayoutPermutator;
+
+use anyhow::Result;
+use colored::Colorize;
+use rand::{rngs::ThreadRng, thread_rng, Rng};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{cmp::Ordering, fmt, fs::File};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Parameters {
+    /// Size of the population.
+    pub population_size: usize,
+    /// Number of generations to evaluate.
+    pub generation_limit: u64,
+    /// Probability of generating a child by crossover of two parents (instead of copying one).
+    pub crossover_rate: f64,
+    /// Probability of mutating a child by swapping key pairs.
+    pub mutation_rate: f64,
+    /// In each mutation, swap this many key pairs.
+    pub key_switches: usize,
+    /// Maximal number of layouts in the Pareto archive.
+    pub archive_size: usize,
+    /// The objectives to minimize.
+    pub objectives: Objectives,
+}
+
+impl Default for Parameters {
+    fn default() -> Self {
+        Parameters {
+            population_size: 100,
+            generation_limit: 500,
+            crossover_rate: 0.9,
+            mutation_rate: 0.5,
+            key_switches: 1,
+            archive_size: 100,
+            objectives: Objectives(vec![
+                ObjectiveGroup {
+                    name: "Loads".to_string(),
+                    metric_types: vec![MetricType::Unigram],
+                    metrics: Vec::new(),
+                },
+                ObjectiveGroup {
+                    name: "Flow".to_string(),
+                    metric_types: vec![MetricType::Bigram, MetricType::Trigram],
+                    metrics: Vec::new(),
+                },
+            ]),
+        }
+    }
+}
+
+impl Parameters {
+    pub fn from_yaml(filename: &str) -> Result<Self> {
+        let f = File::open(filename)?;
+        Ok(serde_yaml::from_reader(f)?)
+    }
+}
+
+// The genotype
+type Genotype = Vec<usize>;
+
+/// Whether the objective vector `a` dominates `b`, i.e. is not worse in any and better in at least
+/// one objective.
+fn dominates(a: &[f64], b: &[f64]) -> bool {
+    a.iter().zip(b.iter()).all(|(a, b)| a <= b) && a.iter().zip(b.iter()).any(|(a, b)| a < b)
+}
+
+/// Sort the objective vectors into fronts of mutually non-dominated vectors. The first front
+/// contains the vectors not dominated by any other, the second those only dominated by the
+/// first front, and so on.
+fn non_dominated_fronts(objectives: &[&[f64]]) -> Vec<Vec<usize>> {
+    let n = objectives.len();
+    let mut dominated_by_count = vec![0; n];
+    let mut dominates_list: Vec<Vec<usize>> = vec![Vec::new(); n];
+    for i in 0..n {
+        for j in (i + 1)..n {
+            if dominates(objectives[i], objectives[j]) {
+                dominates_list[i].push(j);
+                dominated_by_count[j] += 1;
+            } else if dominates(objectives[j], objectives[i]) {
+                dominates_list[j].push(i);
+                dominated_by_count[i] += 1;
+            }
+        }
+    }
+
+    let mut fronts = Vec::new();
+    let mut front: Vec<usize> = (0..n).filter(|i| dominated_by_count[*i] == 0).collect();
+    while !front.is_empty() {
+        let mut next_front = Vec::new();
+        for i in front.iter() {
+            for j in dominates_list[*i].iter() {
+                dominated_by_count[*j] -= 1;
+                if dominated_by_count[*j] == 0 {
+                    next_front.push(*j);
+                }
+            }
+        }
+        fronts.push(front);
+        front = next_front;
+    }
+
+    fronts
+}
+
+/// The crowding distance of each vector of a front, i.e. the (normalized) size of the cuboid
+/// spanned by its neighbors in the front. Extreme vectors have infinite distance.
+fn crowding_distances(objectives: &[&[f64]], front: &[usize]) -> Vec<f64> {
+    let mut distances = vec![0.0; front.len()];
+    let n_objectives = objectives.first().map(|o| o.len()).unwrap_or(0);
+
+    let objective_values = (0..n_objectives).map(|m| {
+        front
+            .iter()
+            .map(|i| objectives[*i][m])
+            .collect::<Vec<f64>>()
+    });
+    for values in objective_values {
+        let mut order: Vec<usize> = (0..front.len()).collect();
+        order.sort_by(|i, j| values[*i].partial_cmp(&values[*j]).unwrap());
+
+        let (first, last) = match (order.first(), order.last()) {
+            (Some(first), Some(last)) => (*first, *last),
+            _ => continue,
+        };
+        distances[first] = f64::INFINITY;
+        distances[last] = f64::INFINITY;
+
+        let range = values[last] - values[first];
+        if range <= 0.0 {
+            continue;
+        }
+        for k in 1..order.len().saturating_sub(1) {
+            distances[order[k]] += (values[order[k + 1]] - values[order[k - 1]]) / range;
+        }
+    }
+
+    distances
+}
+
+#[derive(Clone, Debug)]
+struct Individual {
+    genome: Genotype,
+    objectives: Vec<f64>,
+    /// Index of the non-dominated front (zero for the best front).
+    rank: usize,
+    crowding_distance: f64,
+}
+
+/// Select the best `n` individuals by their fronts and (within the last selected front) their
+/// crowding distances.
+fn select(individuals: Vec<Individual>, n: usize) -> Vec<Individual> {
+    let objectives: Vec<&[f64]> = individuals
+        .iter()
+        .map(|i| i.objectives.as_slice())
+        .collect();
+    let fronts = non_dominated_fronts(&objectives);
+
+    let mut ranked: Vec<(usize, usize, f64)> = Vec::with_capacity(individuals.len());
+    for (rank, front) in fronts.iter().enumerate() {
+        let distances = crowding_distances(&objectives, front);
+        ranked.extend(front.iter().zip(distances).map(|(i, d)| (*i, rank, d)));
+    }
+    ranked.sort_by(|(_, rank1, d1), (_, rank2, d2)| {
+        rank1
+            .cmp(rank2)
+            .then_with(|| d2.partial_cmp(d1).unwrap_or(Ordering::Equal))
+    });
+
+    ranked
+        .into_iter()
+        .take(n)
+        .map(|(i, rank, crowding_distance)| Individual {
+            rank,
+            crowding_distance,
+            ..individuals[i].clone()
+        })
+        .collect()
+}
+
+/// Select a parent by a binary tournament (preferring better fronts and larger crowding distances).
+fn tournament<'a>(population: &'a [Individual], rng: &mut ThreadRng) -> &'a Individual {
+    let a = &population[rng.gen_range(0..population.len())];
+    let b = &population[rng.gen_range(0..population.len())];
+    if a.rank < b.rank || (a.rank == b.rank && a.crowding_distance > b.crowding_distance) {
+        a
+    } else {
+        b
+    }
+}
+
+/// Order crossover: take a random slice from the first parent and fill the remaining positions
+/// with the other values in the order of the second parent.
+fn order_crossover(p1: &[usize], p2: &[usize], rng: &mut ThreadRng) -> Genotype {
+    let len = p1.len();
+    let (mut start, mut end) = (rng.gen_range(0..=len), rng.gen_range(0..=len));
+    if start > end {
+        std::mem::swap(&mut start, &mut end);
+    }
+
+    let max_value = p1.iter().max().cloned().unwrap_or(0);
+    let mut used = vec![false; max_value + 1];
+    p1[start..end].iter().for_each(|v| used[*v] = true);
+
+    let mut remaining = (0..len).map(|k| p2[(end + k) % len]).filter(|v| !used[*v]);
+    let mut child = p1.to_vec();
+    for k in 0..(len - (end - start)) {
+        child[(end + k) % len] = remaining.next().unwrap();
+    }
+
+    child
+}
+
+/// A layout of the Pareto archive and its objective vector.
+#[derive(Clone, Debug, Serialize)]
+pub struct ParetoLayout {
+    pub layout: String,
+    pub objectives: Vec<f64>,
+}
+
+/// An archive of the non-dominated layouts found so far. If it exceeds its maximal size, the layouts
+/// in the most crowded regions of the front are removed.
+#[derive(Clone, Debug, Serialize)]
+pub struct ParetoArchive {
+    /// Names of the objectives.
+    pub objectives: Vec<String>,
+    /// The non-dominated layouts.
+    pub layouts: Vec<ParetoLayout>,
+    #[serde(skip)]
+    max_size: usize,
+}
+
+impl ParetoArchive {
+    pub fn new(objectives: &Objectives, max_size: usize) -> Self {
+        Self {
+            objectives: objectives.names().iter().map(|n| n.to_string()).collect(),
+            layouts: Vec::new(),
+            max_size,
+        }
+    }
+
+    /// Insert a layout if it is not dominated by any layout in the archive (removing the layouts it dominates).
+    pub fn insert(&mut self, layout: &str, objectives: &[f64]) -> bool {
+        if self.layouts.iter().any(|l| {
+            l.layout == layout || l.objectives == objectives || dominates(&l.objectives, objectives)
+        }) {
+            return false;
+        }
+
+        self.layouts
+            .retain(|l| !dominates(objectives, &l.objectives));
+        self.layouts.push(ParetoLayout {
+            layout: layout.to_string(),
+            objectives: objectives.to_vec(),
+        });
+
+        while self.layouts.len() > self.max_size {
+            let objectives: Vec<&[f64]> = self
+                .layouts
+                .iter()
+                .map(|l| l.objectives.as_slice())
+                .collect();
+            let front: Vec<usize> = (0..self.layouts.len()).collect();
+            let distances = crowding_distances(&objectives, &front);
+            let most_crowded = distances
+                .iter()
+                .enumerate()
+                .min_by(|(_, d1), (_, d2)| d1.partial_cmp(d2).unwrap())
+                .map(|(i, _)| i)
+                .unwrap();
+            self.layouts.remove(most_crowded);
+        }
+
+        true
+    }
+
+    /// The lowest value of each objective in the archive.
+    pub fn best_objectives(&self) -> Vec<f64> {
+        (0..self.objectives.len())
+            .map(|m| {
+                self.layouts
+                    .iter()
+                    .map(|l| l.objectives[m])
+                    .fold(f64::INFINITY, f64::min)
+            })
+            .collect()
+    }
+
+    /// Sort the layouts by their objective values.
+    pub fn sort(&mut self) {
+        self.layouts
+            .sort_by(|l1, l2| l1.objectives.partial_cmp(&l2.objectives).unwrap());
+    }
+}
+
+impl fmt::Display for ParetoArchive {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let header: Vec<String> = self
+            .objectives
+            .iter()
+            .map(|name| format!("{:>14}", name))
+            .collect();
+        let width = self
+            .layouts
+            .iter()
+            .map(|l| l.layout.chars().count())
+            .max()
+            .unwrap_or(0);
+        writeln!(
+            f,
+            "{} {}",
+            format!("{:<width$}", "Layout", width = width).bold(),
+            header.join("").bold()
+        )?;
+        for l in self.layouts.iter() {
+            let objectives: Vec<String> = l
+                .objectives
+                .iter()
+                .map(|o| format!("{:>14.2}", o))
+                .collect();
+            writeln!(
+                f,
+                "{:<width$} {}",
+                l.layout,
+                objectives.join(""),
+                width = width
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Performs one multi-objective optimization run, then returns the archive of non-dominated layouts.
+pub fn optimize(
+    params: &Parameters,
+    evaluator: &Evaluator,
+    layout_str: &str,
+    layout_generator: &dyn LayoutGenerator,
+    fixed_characters: &str,
+    start_with_layout: bool,
+    cache_results: bool,
+) -> Result<ParetoArchive> {
+    let pm = LayoutPermutator::new(layout_str, fixed_characters, layout_generator.macro_keys())?;
+    let result_cache: Option<Cache<Vec<f64>>> = match cache_results {
+        true => Some(Cache::new()),
+        false => None,
+    };
+
+    let evaluate = |genome: &Genotype| -> Vec<f64> {
+        let layout_str = pm.generate_string(genome);
+        let evaluate_layout_str = || {
+            let l = layout_generator.generate(&layout_str).unwrap();
+            let result = evaluator.evaluate_layout(&l, &EvaluationOptions::default());
+            params.objectives.evaluate(&result)
+        };
+        match &result_cache {
+            Some(result_cache) => result_cache.get_or_insert_with(&layout_str, evaluate_layout_str),
+            None => evaluate_layout_str(),
+        }
+    };
+    let evaluate_all = |genomes: Vec<Genotype>| -> Vec<Individual> {
+        genomes
+            .into_par_iter()
+            .map(|genome| Individual {
+                objectives: evaluate(&genome),
+                genome,
+                rank: 0,
+                crowding_distance: 0.0,
+            })
+            .collect()
+    };
+
+    // make sure that the objectives refer to evaluated metrics
+    let initial_indices = pm.get_permutable_indices();
+    let initial_layout = layout_generator.generate(&pm.generate_string(&initial_indices))?;
+    params
+        .objectives
+        .validate(&evaluator.evaluate_layout(&initial_layout, &EvaluationOptions::default()))?;
+
+    let mut archive = ParetoArchive::new(&params.objectives, params.archive_size);
+    let mut rng = thread_rng();
+
+    let initial_genomes: Vec<Genotype> = (0..params.population_size)
+        .map(|i| match start_with_layout && i == 0 {
+            true => initial_indices.clone(),
+            false => pm.generate_random(),
+        })
+        .collect();
+    let mut population = evaluate_all(initial_genomes);
+    population
+        .iter()
+        .for_each(|i| archive_insert(&mut archive, &pm, i));
+    population = select(population, params.population_size);
+
+    log::info!(
+        "{} Starting optimization with: {:?}",
+        "NSGA:".yellow().bold(),
+        params,
+    );
+    for generation in 0..params.generation_limit {
+        let children: Vec<Genotype> = (0..params.population_size)
+            .map(|_| {
+                let p1 = tournament(&population, &mut rng);
+                let p2 = tournament(&population, &mut rng);
+                let mut child = match rng.gen_bool(params.crossover_rate) {
+                    true => order_crossover(&p1.genome, &p2.genome, &mut rng),
+                    false => p1.genome.clone(),
+                };
+                if rng.gen_bool(params.mutation_rate) {
+                    child = pm.perform_n_swaps(&child, params.key_switches);
+                }
+                child
+            })
+            .collect();
+
+        let children = evaluate_all(children);
+        children
+            .iter()
+            .for_each(|i| archive_insert(&mut archive, &pm, i));
+
+        population.extend(children);
+        population = select(population, params.population_size);
+
+        let best: Vec<String> = archive
+            .best_objectives()
+            .iter()
+            .map(|o| format!("{:.1}", o))
+            .collect();
+        log::info!(
+            "{} {} {:>4}, {} {:>3}, {} {}",
+            "NSGA:".yellow().bold(),
+            "generation:".bold(),
+            generation,
+            "archive:".bold(),
+            archive.layouts.len(),
+            "best objectives:".bold(),
+            best.join(", "),
+        );
+    }
+
+    archive.sort();
+
+    Ok(archive)
+}
+
+/// Insert an individual into the archive (with its layout string).
+fn archive_insert(archive: &mut ParetoArchive, pm: &LayoutPermutator, individual: &Individual) {
+    archive.insert(
+        &pm.generate_string(&individual.genome),
+        &individual.objectives,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn archive(max_size: usize) -> ParetoArchive {
+        let group = |name: &str| ObjectiveGroup {
+            name: name.to_string(),
+            metric_types: Vec::new(),
+            metrics: Vec::new(),
+        };
+        ParetoArchive::new(&Objectives(vec![group("a"), group("b")]), max_size)
+    }
+
+    fn layouts(archive: &ParetoArchive) -> Vec<&str> {
+        archive.layouts.iter().map(|l| l.layout.as_str()).collect()
+    }
+
+    #[test]
+    fn fronts_by_domination() {
+        let points: Vec<Vec<f64>> = vec![
+            vec![3.0, 3.0],
+            vec![1.0, 4.0],
+            vec![4.0, 4.0],
+            vec![2.0, 2.0],
+            vec![2.0, 5.0],
+            vec![4.0, 1.0],
+        ];
+        let objectives: Vec<&[f64]> = points.iter().map(|p| p.as_slice()).collect();
+
+        let mut fronts = non_dominated_fronts(&objectives);
+        fronts.iter_mut().for_each(|front| front.sort_unstable());
+        assert_eq!(fronts, vec![vec![1, 3, 5], vec![0, 4], vec![2]]);
+    }
+
+    #[test]
+    fn equal_vectors_share_a_front() {
+        let points: Vec<Vec<f64>> = vec![vec![1.0, 2.0], vec![1.0, 2.0], vec![1.0, 3.0]];
+        let objectives: Vec<&[f64]> = points.iter().map(|p| p.as_slice()).collect();
+
+        assert_eq!(non_dominated_fronts(&objectives), vec![vec![0, 1], vec![2]]);
+    }
+
+    #[test]
+    fn crowding_distances_of_front() {
+        let points: Vec<Vec<f64>> = vec![
+            vec![3.0, 1.0],
+            vec![0.0, 4.0],
+            vec![1.0, 2.0],
+            vec![4.0, 0.0],
+        ];
+        let objectives: Vec<&[f64]> = points.iter().map(|p| p.as_slice()).collect();
+
+        let distances = crowding_distances(&objectives, &[0, 1, 2, 3]);
+        assert_eq!(distances[1], f64::INFINITY);
+        assert_eq!(distances[3], f64::INFINITY);
+        // (4 - 1) / 4 + (2 - 0) / 4
+        assert!((distances[0] - 1.25).abs() < 1e-12);
+        // (3 - 0) / 4 + (4 - 1) / 4
+        assert!((distances[2] - 1.5).abs() < 1e-12);
+
+        // only the front's vectors are considered
+        let distances = crowding_distances(&objectives, &[2]);
+        assert_eq!(distances, vec![f64::INFINITY]);
+    }
+
+    #[test]
+    fn archive_keeps_non_dominated_layouts() {
+        let mut archive = archive(10);
+        assert!(archive.insert("a", &[2.0, 2.0]));
+        assert!(archive.insert("b", &[1.0, 3.0]));
+
+        // dominated, duplicate layout, and duplicate objectives
+        assert!(!archive.insert("c", &[2.0, 3.0]));
+        assert!(!archive.insert("a", &[0.0, 0.0]));
+        assert!(!archive.insert("d", &[1.0, 3.0]));
+        assert_eq!(layouts(&archive), vec!["a", "b"]);
+
+        // dominates "a", but not "b"
+        assert!(archive.insert("e", &[1.5, 2.0]));
+        assert_eq!(layouts(&archive), vec!["b", "e"]);
+        assert_eq!(archive.best_objectives(), vec![1.0, 2.0]);
+    }
+
+    #[test]
+    fn archive_prunes_most_crowded_layout() {
+        let mut archive = archive(3);
+        assert!(archive.insert("a", &[0.0, 4.0]));
+        assert!(archive.insert("b", &[1.0, 2.0]));
+        assert!(archive.insert("c", &[3.0, 1.0]));
+        assert!(archive.insert("d", &[4.0, 0.0]));
+
+        // "c" has a smaller crowding distance (1.25) than "b" (1.5), the extremes are kept
+        assert_eq!(layouts(&archive), vec!["a", "b", "d"]);
+
+        archive.sort();
+        assert_eq!(layouts(&archive), vec!["a", "b", "d"]);
+    }
+}
@@ -1,5 +1,8 @@
 use keyboard_layout::{layout::Layout, layout_generator::LayoutGenerator};
-use layout_evaluation::{cache::Cache, evaluation::Evaluator};
+use layout_evaluation::{
+    cache::Cache,
+    evaluation::{DeltaEvaluationState, EvaluationOptions, Evaluator},
+};
 
 use layout_optimization_common::LayoutPermutator;
 
@@ -7,7 +10,10 @@ use anyhow::Result;
 use colored::Colorize;
 use rand_xoshiro::{rand_core::SeedableRng, Xoshiro256PlusPlus};
 use serde::Deserialize;
-use std::{fs::File, sync::Arc};
+use std::{
+    fs::File,
+    sync::{Arc, Mutex},
+};
 
 use argmin::{
     core::{
@@ -72,6 +78,9 @@ pub struct AnnealingStruct {
     layout_generator: Box<dyn LayoutGenerator>,
     key_switches: usize,
     result_cache: Option<Cache<f64>>,
+    /// Cached per-ngram costs of the most recently evaluated layout. As consecutive candidates differ
+    /// in only a few keys, only the ngrams involving those need to be reevaluated.
+    delta_state: Mutex<Option<DeltaEvaluationState>>,
 }
 
 impl CostFunction for AnnealingStruct {
@@ -82,7 +91,13 @@ impl CostFunction for AnnealingStruct {
     fn cost(&self, param: &Self::Param) -> Result<Self::Output, Error> {
         let evaluate_layout_str = |layout_str: &str| -> f64 {
             let l = self.layout_generator.generate(layout_str).unwrap();
-            self.evaluator.evaluate_layout(&l).total_cost()
+            let mut delta_state = self.delta_state.lock().unwrap();
+            let state =
+                delta_state.get_or_insert_with(|| self.evaluator.delta_evaluation_state(&l));
+            let changed_keys = state.layout().differing_keys(&l);
+            self.evaluator
+                .evaluate_layout_delta(state, &l, &changed_keys)
+                .total_cost()
         };
 
         let layout_string = self.permutator.generate_string(param);
@@ -228,7 +243,7 @@ fn get_cost_sd(
     initial_indices: &[usize],
     evaluator: Arc<Evaluator>,
     permutator: &LayoutPermutator,
-    layout_generator: &Box<dyn LayoutGenerator>,
+    layout_generator: &dyn LayoutGenerator,
     key_pair_switches: usize,
 ) -> f64 {
     const USED_NEIGHBORS: u16 = 100;
@@ -242,7 +257,7 @@ fn get_cost_sd(
         let layout = layout_generator
             .generate(&permutator.generate_string(&current_indices))
             .unwrap();
-        let evaluation_result = evaluator.evaluate_layout(&layout);
+        let evaluation_result = evaluator.evaluate_layout(&layout, &EvaluationOptions::default());
         costs.push(evaluation_result.total_cost());
         current_indices = permutator.perform_n_swaps(&current_indices, key_pair_switches);
     }
@@ -264,14 +279,15 @@ pub fn optimize(
     params: &Parameters,
     layout_str: &str,
     fixed_characters: &str,
-    layout_generator: &Box<dyn LayoutGenerator>,
+    layout_generator: &dyn LayoutGenerator,
     start_with_layout: bool,
     evaluator: &Evaluator,
     log_everything: bool,
     result_cache: Option<Cache<f64>>,
     custom_observer: Option<CustomObserver>,
 ) -> (String, Layout) {
-    let pm = LayoutPermutator::new(layout_str, fixed_characters);
+    let pm = LayoutPermutator::new(layout_str, fixed_characters, layout_generator.macro_keys())
+        .unwrap_or_else(|e| panic!("{}", e));
     // Get initial Layout.
     let initial_indices = match start_with_layout {
         true => pm.get_permutable_indices(),
@@ -314,9 +330,10 @@ pub fn optimize(
     let problem = AnnealingStruct {
         evaluator: Arc::new(evaluator.clone()),
         permutator: pm.clone(),
-        layout_generator: layout_generator.clone(),
+        layout_generator: layout_generator.clone_box(),
         key_switches: params.key_switches,
         result_cache,
+        delta_state: Mutex::new(None),
     };
 
     // Create new SA solver with some parameters (see docs for details)
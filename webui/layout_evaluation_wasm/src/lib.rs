@@ -9,15 +9,15 @@ use wasm_bindgen::prelude::*;
 
 use keyboard_layout::{
     config::LayoutConfig, keyboard::Keyboard, layout::Layout, layout_generator::LayoutGenerator,
-    neo_layout_generator::NeoLayoutGenerator,
+    neo_layout_generator::NeoLayoutGenerator, svg,
 };
 
 use layout_evaluation::{
     cache::Cache,
     config::EvaluationParameters,
-    evaluation::Evaluator,
+    evaluation::{EvaluationOptions, Evaluator},
     ngram_mapper::on_demand_ngram_mapper::OnDemandNgramMapper,
-    ngrams::{Bigrams, Trigrams, Unigrams},
+    ngrams::{Bigrams, Quadgrams, Skipgrams, Trigrams, Unigrams, DEFAULT_MAX_SKIP},
     results::EvaluationResult,
 };
 
@@ -89,6 +89,15 @@ impl LayoutPlotter {
             .map_err(|e| format!("Could not plot the layout: {:?}", e))?;
         Ok(layout.plot_layer(layer))
     }
+
+    pub fn svg(&self, layout_str: &str) -> Result<String, JsValue> {
+        let layout_str: String = layout_str.chars().filter(|c| !c.is_whitespace()).collect();
+        let layout = self
+            .layout_generator
+            .generate_unchecked(&layout_str)
+            .map_err(|e| format!("Could not plot the layout: {:?}", e))?;
+        Ok(svg::layout_svg(&layout, None))
+    }
 }
 
 #[wasm_bindgen]
@@ -121,8 +130,14 @@ impl NgramProvider {
             trigrams = trigrams.increase_common(&ngrams_config.increase_common_ngrams);
         }
 
-        let ngram_provider =
-            OnDemandNgramMapper::with_ngrams(unigrams, bigrams, trigrams, eval_params.ngram_mapper);
+        let ngram_provider = OnDemandNgramMapper::with_ngrams(
+            unigrams,
+            bigrams,
+            trigrams,
+            Quadgrams::default(),
+            Vec::new(),
+            eval_params.ngram_mapper,
+        );
 
         Ok(NgramProvider { ngram_provider })
     }
@@ -134,6 +149,12 @@ impl NgramProvider {
             .map_err(|e| format!("Could not generate bigrams from text: {:?}", e))?;
         let mut trigrams = Trigrams::from_text(text)
             .map_err(|e| format!("Could not generate trigrams from text: {:?}", e))?;
+        let mut quadgrams = Quadgrams::from_text(text)
+            .map_err(|e| format!("Could not generate quadgrams from text: {:?}", e))?;
+        let mut skipgrams = (1..=DEFAULT_MAX_SKIP)
+            .map(|skip| Skipgrams::from_text(text, skip))
+            .collect::<Result<Vec<Skipgrams>, _>>()
+            .map_err(|e| format!("Could not generate skipgrams from text: {:?}", e))?;
 
         let eval_params: EvaluationParameters = serde_yaml::from_str(eval_params_str)
             .map_err(|e| format!("Could not read evaluation parameters: {:?}", e))?;
@@ -143,10 +164,21 @@ impl NgramProvider {
             unigrams = unigrams.increase_common(&ngrams_config.increase_common_ngrams);
             bigrams = bigrams.increase_common(&ngrams_config.increase_common_ngrams);
             trigrams = trigrams.increase_common(&ngrams_config.increase_common_ngrams);
+            quadgrams = quadgrams.increase_common(&ngrams_config.increase_common_ngrams);
+            skipgrams = skipgrams
+                .iter()
+                .map(|s| s.increase_common(&ngrams_config.increase_common_ngrams))
+                .collect();
         }
 
-        let ngram_provider =
-            OnDemandNgramMapper::with_ngrams(unigrams, bigrams, trigrams, eval_params.ngram_mapper);
+        let ngram_provider = OnDemandNgramMapper::with_ngrams(
+            unigrams,
+            bigrams,
+            trigrams,
+            quadgrams,
+            skipgrams,
+            eval_params.ngram_mapper,
+        );
 
         Ok(NgramProvider { ngram_provider })
     }
@@ -173,12 +205,18 @@ impl LayoutEvaluator {
         let keyboard = Arc::new(Keyboard::from_yaml_object(layout_cfg.keyboard));
 
         let layout_generator = NeoLayoutGenerator::from_object(layout_cfg.base_layout, keyboard);
+        // the ngrams are not tokenized for macros
+        if !layout_generator.macro_keys().is_empty() {
+            return Err("Layouts with macro keys are not supported".into());
+        }
 
         let eval_params: EvaluationParameters = serde_yaml::from_str(eval_params_str)
             .map_err(|e| format!("Could not read evaluation parameters: {:?}", e))?;
 
         let evaluator = Evaluator::default(Box::new(ngram_provider.ngram_provider.clone()))
-            .default_metrics(&eval_params.metrics);
+            .with_layout_generator(Box::new(layout_generator.clone()))
+            .default_metrics(&eval_params.metrics)
+            .map_err(|e| format!("Could not initialize metrics: {}", e))?;
 
         Ok(LayoutEvaluator {
             layout_generator,
@@ -192,7 +230,9 @@ impl LayoutEvaluator {
             .layout_generator
             .generate(&layout_str)
             .map_err(|e| format!("Could not generate layout: {:?}", e))?;
-        let res = self.evaluator.evaluate_layout(&layout);
+        let res = self
+            .evaluator
+            .evaluate_layout(&layout, &EvaluationOptions::summary());
         let printed = Some(format!("{}", res));
         let plot = Some(layout.plot());
         let layout_str = Some(layout_str);
@@ -213,6 +253,26 @@ impl LayoutEvaluator {
         Ok(layout.plot_layer(layer))
     }
 
+    /// Render the layout as SVG image with keys colored by their unigram load ("unigram_load")
+    /// or the cost attributed to them by the metric with given name.
+    pub fn svg(&self, layout_str: &str, heatmap: Option<String>) -> Result<String, JsValue> {
+        let layout_str: String = layout_str.chars().filter(|c| !c.is_whitespace()).collect();
+        let layout = self
+            .layout_generator
+            .generate(&layout_str)
+            .map_err(|e| format!("Could not plot the layout: {:?}", e))?;
+        let key_values = match heatmap.as_deref() {
+            None => None,
+            Some("unigram_load") => Some(self.evaluator.key_loads(&layout)),
+            Some(metric_name) => Some(
+                self.evaluator
+                    .key_costs(&layout, metric_name)
+                    .ok_or_else(|| format!("Unknown metric: {}", metric_name))?,
+            ),
+        };
+        Ok(svg::layout_svg(&layout, key_values.as_deref()))
+    }
+
     pub fn permutable_keys(&self) -> JsValue {
         let permutable_keys = self.layout_generator.permutable_keys();
         JsValue::from_serde(&permutable_keys).unwrap()
@@ -253,7 +313,7 @@ impl LayoutOptimizer {
             &parameters,
             &layout_evaluator.evaluator,
             &layout_str,
-            &layout_generator,
+            layout_generator.as_ref(),
             fixed_characters,
             start_with_layout,
             true,
@@ -296,7 +356,9 @@ impl LayoutOptimizer {
                     .permutator
                     .generate_string(&self.all_time_best.as_ref().unwrap().1);
                 let layout = self.layout_generator.generate(&layout_str).unwrap();
-                let res = self.evaluator.evaluate_layout(&layout);
+                let res = self
+                    .evaluator
+                    .evaluate_layout(&layout, &EvaluationOptions::summary());
                 let printed = Some(format!("{}", res));
                 let plot = Some(layout.plot());
 
@@ -386,7 +448,12 @@ pub fn sa_optimize(
     let _ = update_callback.call2(&this, &zero, &init_temp);
 
     let observer = SaObserver {
-        permutator: LayoutPermutator::new(layout_str, fixed_characters),
+        permutator: LayoutPermutator::new(
+            layout_str,
+            fixed_characters,
+            layout_evaluator.layout_generator.macro_keys(),
+        )
+        .unwrap(),
         last_update_call: Instant::now(),
         update_callback: update_callback.clone(),
         new_best_callback,
@@ -401,7 +468,7 @@ pub fn sa_optimize(
         &parameters,
         &layout_str,
         fixed_characters,
-        &layout_generator,
+        layout_generator.as_ref(),
         start_with_layout,
         &layout_evaluator.evaluator,
         /* log_everything: */ false,
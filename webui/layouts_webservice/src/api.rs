@@ -2,7 +2,10 @@ use super::Options;
 
 use keyboard_layout::layout_generator::LayoutGenerator;
 use keyboard_layout::neo_layout_generator::NeoLayoutGenerator;
-use layout_evaluation::{evaluation::Evaluator, results::EvaluationResult};
+use layout_evaluation::{
+    evaluation::{EvaluationOptions, Evaluator},
+    results::EvaluationResult,
+};
 
 use ahash::AHashMap;
 use rocket::{
@@ -120,7 +123,7 @@ async fn post(
     let result = match result {
         None => {
             println!("Evaluating new layout: {}", layout_str);
-            let evaluation_result = evaluator.evaluate_layout(&l);
+            let evaluation_result = evaluator.evaluate_layout(&l, &EvaluationOptions::summary());
 
             let result = LayoutEvaluationDB {
                 id: None,
@@ -202,14 +205,17 @@ async fn get(
         eprintln!("Error while fetching layout from db: {:?}", e);
         Status::InternalServerError
     })
-    .map(|e| {
+    .and_then(|e| {
         let mut res: LayoutEvaluation = e.clone().into();
         let l = layout_generator
                 .generate(&e.layout).unwrap();
         res.plot = Some(l.plot());
-        res.details = Some(serde_json::from_str(&e.details_json).unwrap());
+        res.details = Some(serde_json::from_str(&e.details_json).map_err(|err| {
+            eprintln!("Error while reading stored evaluation of layout {} (re-evaluation required?): {:?}", e.layout, err);
+            Status::InternalServerError
+        })?);
         res.printed = Some(e.printed);
-        Json(res)
+        Ok(Json(res))
     })
 }
 
@@ -240,7 +246,7 @@ async fn reeval(
             .get(&result.layout_config)
             .ok_or(Status::BadRequest)?;
         let layout = layout_generator.generate(&result.layout).unwrap();
-        let evaluation_result = evaluator.evaluate_layout(&layout);
+        let evaluation_result = evaluator.evaluate_layout(&layout, &EvaluationOptions::summary());
         let total_cost = evaluation_result.total_cost();
         let details_json = Some(serde_json::to_string(&evaluation_result).unwrap());
         let printed = format!("{}", evaluation_result);
@@ -2,13 +2,14 @@
 extern crate rocket;
 
 use keyboard_layout::{
-    config::LayoutConfig, keyboard::Keyboard, neo_layout_generator::NeoLayoutGenerator,
+    config::LayoutConfig, keyboard::Keyboard, layout_generator::LayoutGenerator,
+    neo_layout_generator::NeoLayoutGenerator,
 };
 use layout_evaluation::{
     config::EvaluationParameters,
     evaluation::Evaluator,
     ngram_mapper::on_demand_ngram_mapper::OnDemandNgramMapper,
-    ngrams::{Bigrams, Trigrams, Unigrams},
+    ngrams::{Bigrams, Quadgrams, Trigrams, Unigrams},
 };
 
 use ahash::AHashMap;
@@ -89,6 +90,13 @@ fn rocket() -> _ {
 
         let keyboard = Arc::new(Keyboard::from_yaml_object(layout_config.keyboard));
         let layout_generator = NeoLayoutGenerator::from_object(layout_config.base_layout, keyboard);
+        // the ngram files are character-based and can not be mapped to macros reliably
+        if !layout_generator.macro_keys().is_empty() {
+            panic!(
+                "Layout config '{}' contains macro keys, which are not supported with ngram files",
+                config_id
+            );
+        }
         layout_generators.insert(config_id.to_owned(), layout_generator);
     }
 
@@ -109,11 +117,31 @@ fn rocket() -> _ {
     let trigrams = Trigrams::from_file(p.to_str().unwrap())
         .unwrap_or_else(|_| panic!("Could not read 3-gramme file from '{:?}'.", &p));
     let ngram_mapper_config = eval_params.ngram_mapper.clone();
-    let ngram_mapper =
-        OnDemandNgramMapper::with_ngrams(unigrams, bigrams, trigrams, ngram_mapper_config);
+    let ngram_mapper = OnDemandNgramMapper::with_ngrams(
+        unigrams,
+        bigrams,
+        trigrams,
+        Quadgrams::default(),
+        Vec::new(),
+        ngram_mapper_config,
+    );
+
+    // the evaluator is shared by all layout configs and therefore has no layout generator to
+    // generate a reference layout with
+    if let Some((name, _)) = eval_params.metrics.0.iter().find(|(name, params)| {
+        params.enabled
+            && params.metric.as_deref().unwrap_or(name.as_str()) == "reference_layout_distance"
+    }) {
+        panic!(
+            "Metric '{}' (reference_layout_distance) is not supported by the webservice. \
+            Disable it in '{}'.",
+            name, &options.eval_parameters
+        );
+    }
 
-    let evaluator =
-        Evaluator::default(Box::new(ngram_mapper)).default_metrics(&eval_params.metrics);
+    let evaluator = Evaluator::default(Box::new(ngram_mapper))
+        .default_metrics(&eval_params.metrics)
+        .expect("Could not initialize metrics");
 
     rocket
         .manage(evaluator)